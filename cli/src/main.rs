@@ -2,9 +2,11 @@
 // Command-line interface for ssengine
 
 use clap::{Parser, Subcommand};
-use ssengine_core::{Workbook, new_workbook};
-use ssengine_io::{read_xlsx, write_xlsx};
-use ssengine_sdk::run_server;
+use ssengine_core::{Workbook, CellValue, CellAddress, CellRange, CellOrRange, CellState, WorkbookDiff, column_index_to_letter, new_workbook};
+use ssengine_core::date::{DateSystem, serial_to_date};
+use ssengine_core::evaluator::TraceNode;
+use ssengine_io::{read_csv, read_xlsx, write_xlsx};
+use ssengine_sdk::{run_server, ServerConfig};
 use std::path::{Path, PathBuf};
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 
@@ -38,6 +40,14 @@ enum Commands {
         /// Optional workbook to load
         #[arg(short, long)]
         workbook: Option<PathBuf>,
+        
+        /// Bearer token required on routes that modify the workbook or touch files
+        #[arg(long)]
+        token: Option<String>,
+        
+        /// Directory that import and export paths are confined to
+        #[arg(long, default_value = ".")]
+        sandbox: PathBuf,
     },
     
     /// Convert a workbook between formats
@@ -50,6 +60,51 @@ enum Commands {
         #[arg(short, long)]
         output: PathBuf,
     },
+    
+    /// Print the contents of a sheet as a text table
+    Print {
+        /// Input file (.xlsx or .csv)
+        #[arg(short, long)]
+        input: PathBuf,
+        
+        /// Sheet to print (defaults to the active sheet)
+        #[arg(short, long)]
+        sheet: Option<String>,
+        
+        /// Range to print, e.g. A1:D10 (defaults to the used range)
+        #[arg(short, long)]
+        range: Option<String>,
+    },
+    
+    /// Evaluate a formula and show the value of every sub-expression
+    Trace {
+        /// Formula to evaluate, e.g. "=1+2*3"
+        formula: String,
+        
+        /// Workbook the formula's references resolve against (.xlsx or .csv)
+        #[arg(short, long)]
+        input: Option<PathBuf>,
+        
+        /// Sheet the formula is evaluated on (defaults to the active sheet)
+        #[arg(short, long)]
+        sheet: Option<String>,
+    },
+    
+    /// Report formulas that look like modeling mistakes
+    Lint {
+        /// Input file (.xlsx or .csv)
+        #[arg(short, long)]
+        input: PathBuf,
+    },
+    
+    /// Show the sheets and cells that differ between two workbooks
+    Diff {
+        /// Original workbook (.xlsx or .csv)
+        old: PathBuf,
+        
+        /// Changed workbook (.xlsx or .csv)
+        new: PathBuf,
+    },
 }
 
 #[tokio::main]
@@ -73,7 +128,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             println!("Workbook created successfully.");
         },
         
-        Commands::Serve { port, address, workbook } => {
+        Commands::Serve { port, address, workbook, token, sandbox } => {
             println!("Starting ssengine API server on {}:{}", address, port);
             
             // Create workbook API
@@ -84,14 +139,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             } else {
                 println!("Creating empty workbook");
                 ssengine_sdk::WorkbookApi::new()
-            };
+            }.with_base_dir(sandbox);
             
             // Parse the IP address
             let addr = address.parse::<IpAddr>().unwrap_or(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)));
             let socket = SocketAddr::new(addr, port);
             
             // Run the server
-            run_server(api, socket).await;
+            let config = ServerConfig { auth_token: token };
+            run_server(api, socket, config).await;
         },
         
         Commands::Convert { input, output } => {
@@ -117,7 +173,235 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             
             println!("Conversion completed successfully.");
         },
+        
+        Commands::Print { input, sheet, range } => {
+            let wb = read_workbook(&input)?;
+            print!("{}", render_sheet(&wb, sheet.as_deref(), range.as_deref())?);
+        },
+        
+        Commands::Trace { formula, input, sheet } => {
+            // Without an input file, evaluate against an empty sheet
+            let wb = match input {
+                Some(input) => read_workbook(&input)?,
+                None => {
+                    let mut wb = new_workbook();
+                    wb.add_sheet("Sheet1".to_string())?;
+                    wb
+                },
+            };
+            
+            let sheet_name = match sheet {
+                Some(name) => name,
+                None => wb.active_sheet_name()
+                    .or_else(|| wb.sheet_names().into_iter().next())
+                    .cloned()
+                    .ok_or("Workbook has no sheets")?,
+            };
+            
+            let trace = wb.trace_formula(&sheet_name, &formula)?;
+            print!("{}", render_trace(&trace, wb.date_system()));
+        },
+        
+        Commands::Lint { input } => {
+            let wb = read_workbook(&input)?;
+            let warnings = wb.lint();
+            for warning in &warnings {
+                println!("{}!{}: {}", warning.sheet, warning.address.to_a1(), warning.message);
+            }
+            println!("{} warning{}", warnings.len(), if warnings.len() == 1 { "" } else { "s" });
+        },
+        
+        Commands::Diff { old, new } => {
+            let (old, new) = (read_workbook(&old)?, read_workbook(&new)?);
+            print!("{}", render_diff(&old.diff(&new), new.date_system()));
+        },
     }
     
     Ok(())
 }
+
+// Render a sheet (or a range of it) as an aligned text table
+fn render_sheet(wb: &Workbook, sheet: Option<&str>, range: Option<&str>) -> Result<String, Box<dyn std::error::Error>> {
+    let sheet_name = match sheet {
+        Some(name) => name.to_string(),
+        None => wb.active_sheet_name()
+            .or_else(|| wb.sheet_names().into_iter().next())
+            .cloned()
+            .ok_or("Workbook has no sheets")?,
+    };
+    
+    // Effective values of the range to print, with its top-left corner
+    let (start, values) = match range {
+        Some(range) => {
+            let sheet = wb.get_sheet(&sheet_name)
+                .ok_or_else(|| format!("Sheet '{}' does not exist", sheet_name))?;
+            let CellRange { start, end } = CellOrRange::from_a1(range)?.to_range();
+            let values: Vec<Vec<CellValue>> = (start.row..=end.row)
+                .map(|row| (start.col..=end.col)
+                    .map(|col| sheet.get_cell(row, col).map(|cell| cell.effective_value().clone()).unwrap_or(CellValue::Blank))
+                    .collect())
+                .collect();
+            (start, values)
+        },
+        None => (CellAddress::new(0, 0), wb.sheet_snapshot(&sheet_name)?),
+    };
+    let columns = match values.first() {
+        Some(first) => first.len() as u32,
+        None => return Ok(String::new()),
+    };
+    
+    // Build the table: header row of column letters, then one row per sheet row
+    let mut table = vec![std::iter::once(String::new())
+        .chain((start.col..start.col + columns).map(column_index_to_letter))
+        .collect::<Vec<_>>()];
+    for (row, row_values) in (start.row..).zip(&values) {
+        let mut line = vec![(row + 1).to_string()];
+        line.extend(row_values.iter().map(|value| format_value(value, wb.date_system())));
+        table.push(line);
+    }
+    
+    // Pad every column to its widest entry
+    let widths: Vec<usize> = (0..table[0].len())
+        .map(|i| table.iter().map(|line| line[i].chars().count()).max().unwrap_or(0))
+        .collect();
+    
+    let mut output = String::new();
+    for line in table {
+        let cells: Vec<String> = line.iter().zip(&widths)
+            .map(|(text, width)| format!("{:<width$}", text, width = width))
+            .collect();
+        output.push_str(cells.join(" | ").trim_end());
+        output.push('\n');
+    }
+    
+    Ok(output)
+}
+
+// Load an .xlsx or .csv file
+fn read_workbook(path: &Path) -> Result<Workbook, Box<dyn std::error::Error>> {
+    let ext = path.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+    match ext.as_str() {
+        "xlsx" => Ok(read_xlsx(path)?),
+        "csv" => Ok(read_csv(path, None)?),
+        _ => Err(format!("Unsupported input format: {}", ext).into()),
+    }
+}
+
+// Render a workbook diff: sheets added (+) and removed (-), then one line per
+// changed cell, and a count of differences
+fn render_diff(diff: &WorkbookDiff, date_system: DateSystem) -> String {
+    let mut output = String::new();
+    for sheet in &diff.added_sheets {
+        output.push_str(&format!("+ sheet {}\n", sheet));
+    }
+    for sheet in &diff.removed_sheets {
+        output.push_str(&format!("- sheet {}\n", sheet));
+    }
+    
+    // A formula cell shows as its formula and value: =A1*2 (10)
+    let state = |state: &CellState| match &state.formula {
+        Some(formula) => format!("{} ({})", formula, format_value(&state.value, date_system)),
+        None => format_value(&state.value, date_system),
+    };
+    let mut cells = 0;
+    for sheet in &diff.sheets {
+        for change in &sheet.changes {
+            let reference = format!("{}!{}", sheet.sheet, change.address.to_a1());
+            let line = match (&change.before, &change.after) {
+                (None, Some(after)) => format!("+ {}: {}", reference, state(after)),
+                (Some(before), None) => format!("- {}: {}", reference, state(before)),
+                (Some(before), Some(after)) => format!("~ {}: {} -> {}", reference, state(before), state(after)),
+                (None, None) => continue,
+            };
+            output.push_str(&line);
+            output.push('\n');
+            cells += 1;
+        }
+    }
+    
+    let total = cells + diff.added_sheets.len() + diff.removed_sheets.len();
+    output.push_str(&format!("{} difference{}\n", total, if total == 1 { "" } else { "s" }));
+    output
+}
+
+// Render a formula trace as an indented tree, one sub-expression per line
+fn render_trace(trace: &TraceNode, date_system: DateSystem) -> String {
+    let mut output = String::new();
+    let mut pending = vec![(trace, 0)];
+    while let Some((node, depth)) = pending.pop() {
+        output.push_str(&format!("{}{} => {}\n", "  ".repeat(depth), node.expression, format_value(&node.value, date_system)));
+        pending.extend(node.children.iter().rev().map(|child| (child, depth + 1)));
+    }
+    output
+}
+
+// Text shown for a cell's effective value
+fn format_value(value: &CellValue, date_system: DateSystem) -> String {
+    match value {
+        CellValue::Date(serial) => match serial_to_date(*serial, date_system) {
+            Some(date) => date.format("%Y-%m-%d").to_string(),
+            None => value.to_string(),
+        },
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn small_workbook() -> Workbook {
+        let mut wb = new_workbook();
+        wb.add_sheet("Data".to_string()).unwrap();
+        wb.set_cell_value("Data", 0, 0, "Item").unwrap();
+        wb.set_cell_value("Data", 0, 1, "Price").unwrap();
+        wb.set_cell_value("Data", 1, 0, "Apple").unwrap();
+        wb.set_cell_value("Data", 1, 1, 1.5).unwrap();
+        wb.set_cell_value("Data", 2, 0, "Banana").unwrap();
+        wb.set_cell_value("Data", 2, 1, "=B2*2").unwrap();
+        wb
+    }
+    
+    #[test]
+    fn print_renders_the_used_range_as_a_table() {
+        let output = render_sheet(&small_workbook(), None, None).unwrap();
+        assert_eq!(output, concat!(
+            "  | A      | B\n",
+            "1 | Item   | Price\n",
+            "2 | Apple  | 1.5\n",
+            "3 | Banana | 3\n",
+        ));
+    }
+    
+    #[test]
+    fn print_renders_a_range() {
+        let output = render_sheet(&small_workbook(), Some("Data"), Some("B2:C3")).unwrap();
+        assert_eq!(output, "  | B   | C\n2 | 1.5 |\n3 | 3   |\n");
+        assert!(render_sheet(&small_workbook(), Some("Missing"), None).is_err());
+    }
+    
+    #[test]
+    fn trace_renders_an_indented_tree() {
+        let mut wb = new_workbook();
+        wb.add_sheet("Sheet1".to_string()).unwrap();
+        let trace = wb.trace_formula("Sheet1", "=1+2*3").unwrap();
+        assert_eq!(render_trace(&trace, wb.date_system()), "1+2*3 => 7\n  1 => 1\n  2*3 => 6\n    2 => 2\n    3 => 3\n");
+    }
+    
+    #[test]
+    fn diff_renders_one_line_per_difference() {
+        let old = small_workbook();
+        let mut new = small_workbook();
+        new.set_cell_value("Data", 1, 1, 2.0).unwrap();
+        new.add_sheet("Notes".to_string()).unwrap();
+        
+        let output = render_diff(&old.diff(&new), new.date_system());
+        assert_eq!(output, concat!(
+            "+ sheet Notes\n",
+            "~ Data!B2: 1.5 -> 2\n",
+            "~ Data!B3: =B2*2 (3) -> =B2*2 (4)\n",
+            "3 differences\n",
+        ));
+        assert_eq!(render_diff(&old.diff(&old), old.date_system()), "0 differences\n");
+    }
+}
@@ -1,12 +1,16 @@
 // ssengine-core/src/ast.rs
 // Abstract Syntax Tree for formula parsing
 
-use crate::model::{CellAddress, RowId, ColumnId};
+use std::fmt;
+
+use crate::model::{CellAddress, CellRange, RowId, ColumnId, MAX_ROWS, MAX_COLUMNS, column_index_to_letter};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum AstNode {
     Literal(Literal),
     Reference(Reference),
+    // A column of an Excel table, resolved against the workbook's tables on evaluation
+    TableReference(TableReference),
     BinaryOp {
         op: BinaryOperator,
         left: Box<AstNode>,
@@ -20,6 +24,8 @@ pub enum AstNode {
         name: String,
         args: Vec<AstNode>,
     },
+    // Several areas taken together, (A1:A2,C1:C2)
+    Union(Vec<AstNode>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -36,6 +42,92 @@ pub enum Reference {
     Range { start: CellAddress, end: CellAddress },
     SheetCell { sheet: String, address: CellAddress },
     SheetRange { sheet: String, start: CellAddress, end: CellAddress },
+    // Whole columns (A:C), optionally on another sheet
+    ColumnRange { sheet: Option<String>, start: ColumnId, end: ColumnId },
+    // Whole rows (1:3), optionally on another sheet
+    RowRange { sheet: Option<String>, start: RowId, end: RowId },
+    // The array a cell holds, as if spilled from it (A1#), optionally on another sheet
+    Spill { sheet: Option<String>, anchor: CellAddress },
+}
+
+// A structured reference: Sales[Amount] is the Amount column of table Sales,
+// Sales[@Amount] its value in the formula's row. Without a table name ([@Amount])
+// it refers to the table the formula sits in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableReference {
+    pub table: Option<String>,
+    pub column: String,
+    pub this_row: bool,
+}
+
+impl Reference {
+    // The sheet the reference names explicitly, if any
+    pub fn sheet(&self) -> Option<&str> {
+        match self {
+            Reference::Cell(_) | Reference::Range { .. } => None,
+            Reference::SheetCell { sheet, .. } | Reference::SheetRange { sheet, .. } => Some(sheet),
+            Reference::ColumnRange { sheet, .. } | Reference::RowRange { sheet, .. } | Reference::Spill { sheet, .. } => sheet.as_deref(),
+        }
+    }
+    
+    // The rectangle the reference covers. Whole columns and rows span the full sheet;
+    // a spill covers its anchor cell, which holds the whole array.
+    pub fn bounds(&self) -> CellRange {
+        match self {
+            Reference::Cell(address) | Reference::SheetCell { address, .. } | Reference::Spill { anchor: address, .. } => {
+                CellRange::new(address.clone(), address.clone())
+            },
+            Reference::Range { start, end } | Reference::SheetRange { start, end, .. } => CellRange::new(start.clone(), end.clone()),
+            Reference::ColumnRange { start, end, .. } => {
+                CellRange::new(CellAddress::new(0, *start), CellAddress::new(MAX_ROWS - 1, *end))
+            },
+            Reference::RowRange { start, end, .. } => {
+                CellRange::new(CellAddress::new(*start, 0), CellAddress::new(*end, MAX_COLUMNS - 1))
+            },
+        }
+    }
+    
+    // Whether the reference covers whole columns or rows
+    pub fn is_whole_line(&self) -> bool {
+        matches!(self, Reference::ColumnRange { .. } | Reference::RowRange { .. })
+    }
+
+    // The same reference shifted by a number of rows and columns. The caller keeps
+    // the result inside the sheet; whole columns and rows are returned unchanged.
+    pub fn offset(&self, rows: i64, cols: i64) -> Reference {
+        let shift = |address: &CellAddress| {
+            CellAddress::new((address.row as i64 + rows) as RowId, (address.col as i64 + cols) as ColumnId)
+        };
+        match self {
+            Reference::Cell(address) => Reference::Cell(shift(address)),
+            Reference::Range { start, end } => Reference::Range { start: shift(start), end: shift(end) },
+            Reference::SheetCell { sheet, address } => Reference::SheetCell { sheet: sheet.clone(), address: shift(address) },
+            Reference::SheetRange { sheet, start, end } => {
+                Reference::SheetRange { sheet: sheet.clone(), start: shift(start), end: shift(end) }
+            },
+            Reference::Spill { sheet, anchor } => Reference::Spill { sheet: sheet.clone(), anchor: shift(anchor) },
+            Reference::ColumnRange { .. } | Reference::RowRange { .. } => self.clone(),
+        }
+    }
+    
+    // The reference in R1C1 notation relative to `origin`: R[-1]C is the cell above,
+    // RC[2] two to the right. Whole columns and rows are relative too (C[-1], R[2]:R[3]).
+    pub fn to_r1c1(&self, origin: &CellAddress) -> String {
+        let offset = |axis: char, delta: i64| if delta == 0 { axis.to_string() } else { format!("{}[{}]", axis, delta) };
+        let row = |row: RowId| offset('R', row as i64 - origin.row as i64);
+        let col = |col: ColumnId| offset('C', col as i64 - origin.col as i64);
+        let cell = |address: &CellAddress| format!("{}{}", row(address.row), col(address.col));
+        
+        let prefix = self.sheet().map(|sheet| format!("{}!", quote_sheet_name(sheet))).unwrap_or_default();
+        let body = match self {
+            Reference::Cell(address) | Reference::SheetCell { address, .. } => cell(address),
+            Reference::Range { start, end } | Reference::SheetRange { start, end, .. } => format!("{}:{}", cell(start), cell(end)),
+            Reference::ColumnRange { start, end, .. } => format!("{}:{}", col(*start), col(*end)),
+            Reference::RowRange { start, end, .. } => format!("{}:{}", row(*start), row(*end)),
+            Reference::Spill { anchor, .. } => format!("{}#", cell(anchor)),
+        };
+        prefix + &body
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -52,6 +144,7 @@ pub enum BinaryOperator {
     GreaterThan,
     GreaterThanOrEqual,
     Concat,
+    Intersect, // A1:B2 B2:C3: the cells both references cover
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -59,4 +152,229 @@ pub enum UnaryOperator {
     Positive,
     Negative,
     Percent,
+    ImplicitIntersection, // @A1:A10: the one value in the formula's row or column
+}
+
+impl BinaryOperator {
+    // Excel operator symbol
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Power => "^",
+            BinaryOperator::Equal => "=",
+            BinaryOperator::NotEqual => "<>",
+            BinaryOperator::LessThan => "<",
+            BinaryOperator::LessThanOrEqual => "<=",
+            BinaryOperator::GreaterThan => ">",
+            BinaryOperator::GreaterThanOrEqual => ">=",
+            BinaryOperator::Concat => "&",
+            BinaryOperator::Intersect => " ",
+        }
+    }
+    
+    // Binding strength, higher binds tighter (matches the grammar hierarchy)
+    pub fn precedence(&self) -> u8 {
+        match self {
+            BinaryOperator::Equal
+            | BinaryOperator::NotEqual
+            | BinaryOperator::LessThan
+            | BinaryOperator::LessThanOrEqual
+            | BinaryOperator::GreaterThan
+            | BinaryOperator::GreaterThanOrEqual => 1,
+            BinaryOperator::Concat => 2,
+            BinaryOperator::Add | BinaryOperator::Subtract => 3,
+            BinaryOperator::Multiply | BinaryOperator::Divide => 4,
+            BinaryOperator::Power => 5,
+            BinaryOperator::Intersect => 7,
+        }
+    }
+}
+
+// Precedence of unary operators and atoms (literals, references, calls, unions).
+// Intersection sits between them: -A1:B2 B2:C3 negates the intersection.
+const UNARY_PRECEDENCE: u8 = 6;
+const ATOM_PRECEDENCE: u8 = 8;
+
+impl AstNode {
+    fn precedence(&self) -> u8 {
+        match self {
+            AstNode::BinaryOp { op, .. } => op.precedence(),
+            AstNode::UnaryOp { .. } => UNARY_PRECEDENCE,
+            _ => ATOM_PRECEDENCE,
+        }
+    }
+    
+    // Formula text (without the leading '=') with references in R1C1 notation
+    // relative to `origin`, so copies of a formula along a row or column read the same
+    pub fn to_r1c1(&self, origin: &CellAddress) -> String {
+        FormulaText { node: self, reference: &|reference| reference.to_r1c1(origin) }.to_string()
+    }
+}
+
+// An AST written as formula text, with references written by `reference`
+struct FormulaText<'a> {
+    node: &'a AstNode,
+    reference: &'a dyn Fn(&Reference) -> String,
+}
+
+impl<'a> FormulaText<'a> {
+    fn child(&self, node: &'a AstNode) -> FormulaText<'a> {
+        FormulaText { node, reference: self.reference }
+    }
+    
+    // Write a child node, parenthesizing it when it binds looser than `min_precedence`
+    fn fmt_operand(&self, f: &mut fmt::Formatter<'_>, node: &'a AstNode, min_precedence: u8) -> fmt::Result {
+        if node.precedence() < min_precedence {
+            write!(f, "({})", self.child(node))
+        } else {
+            write!(f, "{}", self.child(node))
+        }
+    }
+}
+
+// Canonical formula text without the leading '='. All binary operators are
+// left-associative, so a right operand of equal precedence is parenthesized.
+impl fmt::Display for AstNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", FormulaText { node: self, reference: &|reference| reference.to_string() })
+    }
+}
+
+impl fmt::Display for FormulaText<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.node {
+            AstNode::Literal(literal) => write!(f, "{}", literal),
+            AstNode::Reference(reference) => write!(f, "{}", (self.reference)(reference)),
+            AstNode::TableReference(reference) => write!(f, "{}", reference),
+            AstNode::BinaryOp { op, left, right } => {
+                let precedence = op.precedence();
+                self.fmt_operand(f, left, precedence)?;
+                write!(f, "{}", op.symbol())?;
+                self.fmt_operand(f, right, precedence + 1)
+            },
+            AstNode::UnaryOp { op, operand } => match op {
+                UnaryOperator::Positive => {
+                    write!(f, "+")?;
+                    self.fmt_operand(f, operand, UNARY_PRECEDENCE)
+                },
+                UnaryOperator::Negative => {
+                    write!(f, "-")?;
+                    self.fmt_operand(f, operand, UNARY_PRECEDENCE)
+                },
+                UnaryOperator::Percent => {
+                    // Percents chain without parentheses (50%%, @A1%); other operands need them
+                    match operand.as_ref() {
+                        AstNode::UnaryOp { op: UnaryOperator::Percent | UnaryOperator::ImplicitIntersection, .. } => write!(f, "{}", self.child(operand))?,
+                        _ => self.fmt_operand(f, operand, ATOM_PRECEDENCE)?,
+                    }
+                    write!(f, "%")
+                },
+                UnaryOperator::ImplicitIntersection => {
+                    write!(f, "@")?;
+                    self.fmt_operand(f, operand, ATOM_PRECEDENCE)
+                },
+            },
+            AstNode::FunctionCall { name, args } => {
+                write!(f, "{}(", name)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", self.child(arg))?;
+                }
+                write!(f, ")")
+            },
+            AstNode::Union(areas) => {
+                write!(f, "(")?;
+                for (i, area) in areas.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", self.child(area))?;
+                }
+                write!(f, ")")
+            },
+        }
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::Number(n) => write!(f, "{}", n),
+            Literal::Text(s) => write!(f, "\"{}\"", s.replace('"', "\"\"")),
+            Literal::Boolean(b) => write!(f, "{}", if *b { "TRUE" } else { "FALSE" }),
+            Literal::Error(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reference::Cell(address) => write!(f, "{}", address.to_a1()),
+            Reference::Range { start, end } => write!(f, "{}:{}", start.to_a1(), end.to_a1()),
+            Reference::SheetCell { sheet, address } => {
+                write!(f, "{}!{}", quote_sheet_name(sheet), address.to_a1())
+            },
+            Reference::SheetRange { sheet, start, end } => {
+                write!(f, "{}!{}:{}", quote_sheet_name(sheet), start.to_a1(), end.to_a1())
+            },
+            Reference::ColumnRange { sheet, start, end } => {
+                if let Some(sheet) = sheet {
+                    write!(f, "{}!", quote_sheet_name(sheet))?;
+                }
+                write!(f, "{}:{}", column_index_to_letter(*start), column_index_to_letter(*end))
+            },
+            Reference::RowRange { sheet, start, end } => {
+                if let Some(sheet) = sheet {
+                    write!(f, "{}!", quote_sheet_name(sheet))?;
+                }
+                write!(f, "{}:{}", start + 1, end + 1)
+            },
+            Reference::Spill { sheet, anchor } => {
+                if let Some(sheet) = sheet {
+                    write!(f, "{}!", quote_sheet_name(sheet))?;
+                }
+                write!(f, "{}#", anchor.to_a1())
+            },
+        }
+    }
+}
+
+impl fmt::Display for TableReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(table) = &self.table {
+            write!(f, "{}", table)?;
+        }
+        write!(f, "[{}{}]", if self.this_row { "@" } else { "" }, self.column)
+    }
+}
+
+// Sheet names that are not plain identifiers must be quoted in formulas, with
+// any quote inside doubled: Bob's Data is written 'Bob''s Data'
+pub(crate) fn quote_sheet_name(sheet: &str) -> String {
+    let mut chars = sheet.chars();
+    let is_identifier = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    
+    if is_identifier {
+        sheet.to_string()
+    } else {
+        format!("'{}'", sheet.replace('\'', "''"))
+    }
+}
+
+// The sheet name a (possibly quoted) sheet name in a formula stands for. None for
+// a quoted name with an undoubled quote inside, or an empty one.
+pub(crate) fn unquote_sheet_name(text: &str) -> Option<String> {
+    match text.strip_prefix('\'').and_then(|inner| inner.strip_suffix('\'')) {
+        Some(inner) if !inner.is_empty() && !inner.replace("''", "").contains('\'') => Some(inner.replace("''", "'")),
+        Some(_) => None,
+        None if !text.is_empty() && !text.contains('\'') => Some(text.to_string()),
+        None => None,
+    }
 }
@@ -0,0 +1,99 @@
+// ssengine-core/src/date.rs
+// Conversion between date serial numbers and calendar dates
+
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime, Timelike};
+
+// Date system of a workbook, i.e. which day the serial numbers count from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DateSystem {
+    #[default]
+    Excel1900, // Serial 1 is 1900-01-01 (Windows default)
+    Excel1904, // Serial 0 is 1904-01-01 (older Mac workbooks)
+}
+
+// Difference between the 1900 and 1904 serial numbers of the same date
+pub const DATE_1904_OFFSET: f64 = 1462.0;
+
+// Day 0 of the 1900 serial numbering. Using 1899-12-30 rather than 1900-01-00
+// absorbs Excel's fictitious 1900-02-29 (serial 60) for every later date.
+fn epoch() -> NaiveDate {
+    NaiveDate::from_ymd_opt(1899, 12, 30).unwrap()
+}
+
+// Convert a calendar date to its serial number
+pub fn date_to_serial(date: NaiveDate, system: DateSystem) -> f64 {
+    let days = (date - epoch()).num_days();
+
+    // Before 1900-03-01 Excel's numbering is one lower (no leap day yet)
+    let serial = if days < 61 {
+        (days - 1) as f64
+    } else {
+        days as f64
+    };
+
+    match system {
+        DateSystem::Excel1900 => serial,
+        DateSystem::Excel1904 => serial - DATE_1904_OFFSET,
+    }
+}
+
+// Convert a date and time to a serial number (time is the fractional part)
+pub fn datetime_to_serial(datetime: NaiveDateTime, system: DateSystem) -> f64 {
+    let seconds = datetime.time().num_seconds_from_midnight() as f64;
+    date_to_serial(datetime.date(), system) + seconds / 86_400.0
+}
+
+// Convert a serial number to a calendar date, ignoring the time of day.
+// Returns None for negative or out-of-range serials.
+pub fn serial_to_date(serial: f64, system: DateSystem) -> Option<NaiveDate> {
+    if !serial.is_finite() || serial < 0.0 {
+        return None;
+    }
+
+    let serial = match system {
+        DateSystem::Excel1900 => serial,
+        DateSystem::Excel1904 => serial + DATE_1904_OFFSET,
+    };
+
+    let serial = serial.floor() as i64;
+    // Serial 60 is the non-existent 1900-02-29; treat it as the 28th
+    let days = if serial < 60 { serial + 1 } else { serial };
+    epoch().checked_add_signed(Duration::try_days(days)?)
+}
+
+// Add (or subtract) whole months, clamping the day to the end of the month
+pub fn add_months(date: NaiveDate, months: i64) -> Option<NaiveDate> {
+    let magnitude = Months::new(u32::try_from(months.unsigned_abs()).ok()?);
+    if months >= 0 {
+        date.checked_add_months(magnitude)
+    } else {
+        date.checked_sub_months(magnitude)
+    }
+}
+
+// Last day of the month containing `date`
+pub fn end_of_month(date: NaiveDate) -> Option<NaiveDate> {
+    add_months(date.with_day(1)?, 1)?.pred_opt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+    
+    #[test]
+    fn the_same_date_has_a_serial_in_each_system() {
+        let new_year = date(2024, 1, 1);
+        assert_eq!(date_to_serial(new_year, DateSystem::Excel1900), 45292.0);
+        assert_eq!(date_to_serial(new_year, DateSystem::Excel1904), 43830.0);
+        assert_eq!(serial_to_date(45292.0, DateSystem::Excel1900), Some(new_year));
+        assert_eq!(serial_to_date(43830.0, DateSystem::Excel1904), Some(new_year));
+        
+        // The epochs
+        assert_eq!(serial_to_date(1.0, DateSystem::Excel1900), Some(date(1900, 1, 1)));
+        assert_eq!(serial_to_date(0.0, DateSystem::Excel1904), Some(date(1904, 1, 1)));
+    }
+}
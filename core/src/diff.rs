@@ -0,0 +1,162 @@
+// ssengine-core/src/diff.rs
+// Differences between two workbooks, cell by cell
+
+use std::collections::BTreeSet;
+
+use crate::model::{Cell, CellAddress, CellValue, Sheet, Workbook};
+
+// What a cell holds, as compared by a diff: its formula and effective value
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellState {
+    pub formula: Option<String>,
+    pub value: CellValue, // The calculated value of a formula cell
+}
+
+// One cell that differs. A cell that was added has no `before`, one that was
+// removed no `after`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellChange {
+    pub address: CellAddress,
+    pub before: Option<CellState>,
+    pub after: Option<CellState>,
+}
+
+// The cells that differ on a sheet both workbooks have, by row then column
+#[derive(Debug, Clone, PartialEq)]
+pub struct SheetDiff {
+    pub sheet: String,
+    pub changes: Vec<CellChange>,
+}
+
+// Everything that differs from one workbook to another. Styles, notes and other
+// cell properties are not compared.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct WorkbookDiff {
+    pub added_sheets: Vec<String>,
+    pub removed_sheets: Vec<String>,
+    pub sheets: Vec<SheetDiff>, // Only sheets with differing cells
+}
+
+impl CellChange {
+    pub fn is_added(&self) -> bool {
+        self.before.is_none()
+    }
+
+    pub fn is_removed(&self) -> bool {
+        self.after.is_none()
+    }
+}
+
+impl WorkbookDiff {
+    // Whether the workbooks hold the same sheets and cells
+    pub fn is_empty(&self) -> bool {
+        self.added_sheets.is_empty() && self.removed_sheets.is_empty() && self.sheets.is_empty()
+    }
+}
+
+// Compare two workbooks, sheets in the order of the workbook they appear in
+pub(crate) fn diff_workbooks(old: &Workbook, new: &Workbook) -> WorkbookDiff {
+    let mut diff = WorkbookDiff::default();
+
+    for name in old.sheet_names() {
+        match (old.get_sheet(name), new.get_sheet(name)) {
+            (Some(before), Some(after)) => {
+                let changes = diff_sheets(before, after);
+                if !changes.is_empty() {
+                    diff.sheets.push(SheetDiff { sheet: name.clone(), changes });
+                }
+            },
+            _ => diff.removed_sheets.push(name.clone()),
+        }
+    }
+    diff.added_sheets = new.sheet_names().into_iter()
+        .filter(|name| old.get_sheet(name).is_none())
+        .cloned()
+        .collect();
+
+    diff
+}
+
+// The cells that differ between two versions of a sheet
+fn diff_sheets(before: &Sheet, after: &Sheet) -> Vec<CellChange> {
+    let positions: BTreeSet<_> = before.get_all_cells().chain(after.get_all_cells())
+        .map(|(&position, _)| position)
+        .collect();
+
+    positions.into_iter()
+        .filter_map(|(row, col)| {
+            let old = before.get_cell(row, col).and_then(cell_state);
+            let new = after.get_cell(row, col).and_then(cell_state);
+            (old != new).then(|| CellChange { address: CellAddress::new(row, col), before: old, after: new })
+        })
+        .collect()
+}
+
+// A cell's formula and value. Cells holding neither (e.g. only a style) count as
+// absent, so formatting a blank cell is not reported as adding it.
+fn cell_state(cell: &Cell) -> Option<CellState> {
+    let value = cell.effective_value();
+    if cell.formula.is_none() && *value == CellValue::Blank {
+        return None;
+    }
+    Some(CellState { formula: cell.formula.clone(), value: value.clone() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model() -> Workbook {
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Sheet1".to_string()).unwrap();
+        workbook.set_cell_value("Sheet1", 0, 0, 100.0).unwrap();
+        workbook.set_cell_value("Sheet1", 1, 0, "=A1*2").unwrap();
+        workbook.set_cell_value("Sheet1", 0, 1, "draft").unwrap();
+        workbook
+    }
+
+    #[test]
+    fn diff_reports_a_changed_cell_and_an_added_sheet() {
+        let old = model();
+        let mut new = model();
+        assert!(old.diff(&new).is_empty());
+
+        new.set_cell_value("Sheet1", 0, 1, "final").unwrap();
+        new.add_sheet("Notes".to_string()).unwrap();
+        let diff = old.diff(&new);
+        assert_eq!(diff.added_sheets, vec!["Notes".to_string()]);
+        assert!(diff.removed_sheets.is_empty());
+        assert_eq!(diff.sheets, vec![SheetDiff {
+            sheet: "Sheet1".to_string(),
+            changes: vec![CellChange {
+                address: CellAddress::new(0, 1),
+                before: Some(CellState { formula: None, value: CellValue::Text("draft".to_string()) }),
+                after: Some(CellState { formula: None, value: CellValue::Text("final".to_string()) }),
+            }],
+        }]);
+
+        // The other way round the sheet was removed
+        let reverse = new.diff(&old);
+        assert_eq!(reverse.removed_sheets, vec!["Notes".to_string()]);
+        assert!(reverse.added_sheets.is_empty());
+    }
+
+    #[test]
+    fn diff_compares_formulas_and_their_values() {
+        let old = model();
+        let mut new = model();
+        new.set_cell_value("Sheet1", 0, 0, 150.0).unwrap();
+        new.set_cell_value("Sheet1", 2, 0, "=A2+1").unwrap();
+        new.delete_cell("Sheet1", 0, 1).unwrap();
+
+        let changes = &old.diff(&new).sheets[0].changes;
+        let addresses: Vec<String> = changes.iter().map(|change| change.address.to_a1()).collect();
+        assert_eq!(addresses, vec!["A1", "B1", "A2", "A3"]);
+        assert!(changes[1].is_removed());
+        assert!(changes[3].is_added());
+        // Same formula, new value
+        assert_eq!(changes[2].before.as_ref().unwrap().value, CellValue::Number(200.0));
+        assert_eq!(changes[2].after.as_ref().unwrap().value, CellValue::Number(300.0));
+        assert_eq!(changes[2].after.as_ref().unwrap().formula.as_deref(), Some("=A1*2"));
+    }
+}
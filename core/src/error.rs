@@ -18,15 +18,63 @@ pub enum EngineError {
     #[error("Unknown function: {0}")]
     UnknownFunction(String),
     
+    // A formula that parses but can't run as written, e.g. a call with too few
+    // arguments. The position is the 1-based character where the call starts.
+    #[error("Invalid formula at position {position}: {message}")]
+    InvalidFormula { position: usize, message: String },
+    
+    #[error("Cell error: {0}")]
+    CellValueError(CellError),
+    
+    #[error("I/O error: {0}")]
+    IoError(String),
+    
     #[error("Internal error: {0}")]
     Internal(String),
 
     #[error("Not implemented: {0}")]
     NotImplemented(String),
+
+    // An error raised while recalculating a cell, with the cell's address (e.g. G10)
+    #[error("Error in {sheet}!{cell}: {source}")]
+    CellEvaluation { sheet: String, cell: String, source: Box<EngineError> },
+}
+
+impl EngineError {
+    // The cell error a formula shows for this error, or None for internal
+    // problems that should abort evaluation instead
+    pub fn as_cell_error(&self) -> Option<CellError> {
+        match self {
+            EngineError::CellValueError(e) => Some(e.clone()),
+            EngineError::EvaluationError(_) => Some(CellError::InvalidValue),
+            EngineError::CircularReference(_) | EngineError::InvalidReference(_) => Some(CellError::InvalidReference),
+            EngineError::UnknownFunction(_) => Some(CellError::NameNotFound),
+            EngineError::ParseError(_) | EngineError::InvalidFormula { .. } | EngineError::IoError(_) | EngineError::Internal(_) | EngineError::NotImplemented(_) => None,
+            EngineError::CellEvaluation { source, .. } => source.as_cell_error(),
+        }
+    }
+    
+    // This error as raised by the given cell. An error that already names its cell
+    // is returned unchanged.
+    pub fn in_cell(self, sheet: &str, cell: String) -> EngineError {
+        match self {
+            EngineError::CellEvaluation { .. } => self,
+            source => EngineError::CellEvaluation { sheet: sheet.to_string(), cell, source: Box::new(source) },
+        }
+    }
+}
+
+impl From<CellError> for EngineError {
+    fn from(error: CellError) -> Self {
+        EngineError::CellValueError(error)
+    }
 }
 
 #[derive(Error, Debug, Clone, PartialEq)]
 pub enum CellError {
+    #[error("#NULL!")]
+    Null, // Intersection of references that share no cell
+    
     #[error("#DIV/0!")]
     DivisionByZero,
     
@@ -3,8 +3,8 @@
 
 use crate::ast::{AstNode, Literal, Reference, BinaryOperator, UnaryOperator};
 use crate::error::{EngineError, CellError};
-use crate::model::{Workbook, Sheet, CellAddress, CellValue};
-use crate::functions::FunctionRegistry;
+use crate::model::{Workbook, CellAddress, CellRange, CellValue};
+use crate::functions::{flatten_args, FunctionRegistry};
 
 // Evaluation context for resolving cell references and tracking state
 pub struct EvaluationContext<'a> {
@@ -14,6 +14,17 @@ pub struct EvaluationContext<'a> {
     
     // Track cells being evaluated to detect circular references
     evaluating_cells: Vec<(String, CellAddress)>,
+    
+    // When tracing, the finished sub-expressions of each node still being evaluated
+    trace: Option<Vec<Vec<TraceNode>>>,
+}
+
+// A sub-expression of a traced formula with the value it evaluated to
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceNode {
+    pub expression: String,
+    pub value: CellValue,
+    pub children: Vec<TraceNode>,
 }
 
 impl<'a> EvaluationContext<'a> {
@@ -23,6 +34,7 @@ impl<'a> EvaluationContext<'a> {
             current_sheet: sheet,
             current_cell: cell,
             evaluating_cells: Vec::new(),
+            trace: None,
         }
     }
     
@@ -41,20 +53,91 @@ impl<'a> EvaluationContext<'a> {
         self.evaluating_cells.pop();
     }
 
-    // Resolve a reference and get its value
+    // Resolve a reference and get its value. Ranges resolve to a 2-D array of their
+    // cells, except that a single-cell range is just that cell's value.
     pub fn resolve_reference(&mut self, r: &Reference) -> Result<CellValue, EngineError> {
         match r {
             Reference::Cell(addr) => self.resolve_cell_value(self.current_sheet, addr),
             Reference::SheetCell { sheet, address } => self.resolve_cell_value(sheet, address),
-            Reference::Range { start, end } => self.resolve_cell_value(self.current_sheet, start),
-            Reference::SheetRange { sheet, start, end } => self.resolve_cell_value(sheet, start),
+            Reference::Range { start, end } if start == end => self.resolve_cell_value(self.current_sheet, start),
+            Reference::SheetRange { sheet, start, end } if start == end => self.resolve_cell_value(sheet, start),
+            Reference::Range { start, end } => {
+                let sheet = self.current_sheet;
+                self.resolve_range(sheet, &CellRange::new(start.clone(), end.clone()))
+            },
+            Reference::SheetRange { sheet, start, end } => self.resolve_range(sheet, &CellRange::new(start.clone(), end.clone())),
+            Reference::ColumnRange { .. } | Reference::RowRange { .. } => self.resolve_line_range(r),
+            Reference::Spill { sheet, anchor } => {
+                let sheet = sheet.as_deref().unwrap_or(self.current_sheet);
+                // Only a cell holding an array has a spill range
+                match self.resolve_cell_value(sheet, anchor)? {
+                    CellValue::Array(rows) => Ok(CellValue::Array(rows)),
+                    CellValue::Error(e) => Ok(CellValue::Error(e)),
+                    _ => Ok(CellValue::Error(CellError::InvalidReference)),
+                }
+            },
+        }
+    }
+
+    // Resolve a rectangular range into a 2-D array of values (row-major)
+    pub fn resolve_range(&mut self, sheet: &str, range: &CellRange) -> Result<CellValue, EngineError> {
+        if self.workbook.get_sheet(sheet).is_none() {
+            return Ok(CellValue::Error(CellError::InvalidReference));
+        }
+        let range = range.normalized();
+        let mut rows = Vec::new();
+        for row in range.start.row..=range.end.row {
+            let mut values = Vec::new();
+            for col in range.start.col..=range.end.col {
+                values.push(self.resolve_cell_value(sheet, &CellAddress::new(row, col))?);
+            }
+            rows.push(values);
+        }
+        Ok(CellValue::Array(rows))
+    }
+
+    // Resolve only the populated cells of a range, as a single row of values. For
+    // functions that ignore blanks this matches `resolve_range` without visiting
+    // every empty coordinate.
+    pub fn resolve_range_sparse(&mut self, sheet: &str, range: &CellRange) -> Result<CellValue, EngineError> {
+        let addresses: Vec<CellAddress> = match self.workbook.get_sheet(sheet) {
+            Some(s) => s.get_range_cells(range).map(|(address, _)| address).collect(),
+            None => return Ok(CellValue::Error(CellError::InvalidReference)),
+        };
+        
+        let mut values = Vec::with_capacity(addresses.len());
+        for address in &addresses {
+            values.push(self.resolve_cell_value(sheet, address)?);
         }
+        Ok(CellValue::Array(vec![values]))
+    }
+
+    // Resolve whole columns or rows into a 2-D array. The open dimension is clipped to
+    // the sheet's used bounds so A:A doesn't visit a million empty rows.
+    pub fn resolve_line_range(&mut self, r: &Reference) -> Result<CellValue, EngineError> {
+        let sheet = r.sheet().unwrap_or(self.current_sheet);
+        let bounds = r.bounds();
+        let (used_row, used_col) = match self.workbook.get_sheet(sheet) {
+            Some(s) => s.used_bounds().unwrap_or((0, 0)),
+            None => return Ok(CellValue::Error(CellError::InvalidReference)),
+        };
+        
+        let end = match r {
+            Reference::ColumnRange { .. } => CellAddress::new(used_row.max(bounds.start.row), bounds.end.col),
+            Reference::RowRange { .. } => CellAddress::new(bounds.end.row, used_col.max(bounds.start.col)),
+            _ => bounds.end.clone(),
+        };
+        self.resolve_range(sheet, &CellRange::new(bounds.start, end))
     }
 
     fn resolve_cell_value(&mut self, sheet: &str, addr: &CellAddress) -> Result<CellValue, EngineError> {
         if self.is_circular(sheet, addr) {
             return Err(EngineError::CircularReference(format!("Circular reference detected at {}!{}", sheet, addr.to_a1())));
         }
+        // A reference to a sheet that doesn't exist is a broken reference, not a failure
+        if self.workbook.get_sheet(sheet).is_none() {
+            return Ok(CellValue::Error(CellError::InvalidReference));
+        }
         self.push_cell(sheet, addr.clone());
         let res = self.workbook.get_cell_value(sheet, addr.row, addr.col);
         self.pop_cell();
@@ -70,27 +153,90 @@ pub struct Evaluator {
 impl Evaluator {
     /// Create a new Evaluator with default functions registered
     pub fn new() -> Self {
-        let mut eval = Evaluator { function_registry: FunctionRegistry::new() };
-        eval.function_registry.register_defaults();
-        eval
+        Evaluator { function_registry: FunctionRegistry::new() }
     }
 
     /// Evaluate a formula string by parsing to AST and evaluating
     pub fn evaluate_formula(&self, workbook: &Workbook, sheet: &str, cell_addr: &CellAddress, formula: &str) -> Result<CellValue, EngineError> {
+        let ast = crate::parser::parse_formula(formula)?;
+        self.evaluate_ast(workbook, sheet, cell_addr, &ast)
+    }
+
+    /// Evaluate an already parsed formula in the given cell
+    pub fn evaluate_ast(&self, workbook: &Workbook, sheet: &str, cell_addr: &CellAddress, ast: &AstNode) -> Result<CellValue, EngineError> {
+        let mut ctx = EvaluationContext::new(workbook, sheet, cell_addr.clone());
+        self.evaluate(ast, &mut ctx)
+    }
+
+    /// Evaluate a formula string, recording the value of every sub-expression.
+    /// Returns the root of the trace; its value is the formula's result.
+    pub fn evaluate_traced(&self, workbook: &Workbook, sheet: &str, cell_addr: &CellAddress, formula: &str) -> Result<TraceNode, EngineError> {
         let ast = crate::parser::parse_formula(formula)?;
         let mut ctx = EvaluationContext::new(workbook, sheet, cell_addr.clone());
-        self.evaluate(&ast, &mut ctx)
+        ctx.trace = Some(vec![Vec::new()]);
+        self.evaluate(&ast, &mut ctx)?;
+        
+        ctx.trace
+            .and_then(|mut levels| levels.pop())
+            .and_then(|mut roots| roots.pop())
+            .ok_or_else(|| EngineError::Internal("Formula evaluation left no trace".into()))
+    }
+
+    /// The functions formulas can call
+    pub fn function_registry(&self) -> &FunctionRegistry {
+        &self.function_registry
+    }
+
+    /// Call a registered function with already-evaluated arguments. Errors the
+    /// function reports as cell errors are returned as error values.
+    pub fn call_function(&self, name: &str, args: &[CellValue]) -> Result<CellValue, EngineError> {
+        match self.function_registry.call(name, args) {
+            Err(EngineError::CellValueError(e)) => Ok(CellValue::Error(e)),
+            other => other.map(CellValue::finite_or_error),
+        }
+    }
+
+    /// Whether a formula calls a volatile function (RAND, NOW, ...) anywhere in its tree
+    pub fn is_volatile(&self, node: &AstNode) -> bool {
+        match node {
+            AstNode::Literal(_) | AstNode::Reference(_) | AstNode::TableReference(_) => false,
+            AstNode::BinaryOp{left,right,..} => self.is_volatile(left) || self.is_volatile(right),
+            AstNode::UnaryOp{operand,..} => self.is_volatile(operand),
+            AstNode::Union(areas) => areas.iter().any(|area| self.is_volatile(area)),
+            AstNode::FunctionCall{name,args} => {
+                self.function_registry.is_volatile(name) || args.iter().any(|arg| self.is_volatile(arg))
+            },
+        }
     }
 
     /// Evaluate an AST node
     pub fn evaluate(&self, node: &AstNode, context: &mut EvaluationContext) -> Result<CellValue, EngineError> {
-        match node {
+        if let Some(levels) = context.trace.as_mut() {
+            levels.push(Vec::new());
+        }
+        
+        let result = match node {
             AstNode::Literal(lit) => self.evaluate_literal(lit),
             AstNode::Reference(r) => context.resolve_reference(r),
+            AstNode::TableReference(r) => match context.workbook.resolve_table_reference(context.current_sheet, &context.current_cell, r) {
+                Ok(reference) => context.resolve_reference(&reference),
+                Err(e) => Ok(CellValue::Error(e)),
+            },
             AstNode::BinaryOp{op,left,right} => self.evaluate_binary_op(op, left, right, context),
             AstNode::UnaryOp{op,operand} => self.evaluate_unary_op(op, operand, context),
             AstNode::FunctionCall{name,args} => self.evaluate_function(name, args, context),
+            AstNode::Union(areas) => self.evaluate_union(areas, context),
+        };
+        
+        // Record this node under its parent, with the sub-expressions evaluated meanwhile
+        if let Some(levels) = context.trace.as_mut() {
+            let children = levels.pop().unwrap_or_default();
+            if let (Ok(value), Some(parent)) = (&result, levels.last_mut()) {
+                parent.push(TraceNode { expression: node.to_string(), value: value.clone(), children });
+            }
         }
+        
+        result
     }
 
     fn evaluate_literal(&self, lit: &Literal) -> Result<CellValue, EngineError> {
@@ -103,9 +249,27 @@ impl Evaluator {
     }
 
     fn evaluate_binary_op(&self, op: &BinaryOperator, l: &AstNode, r: &AstNode, ctx: &mut EvaluationContext) -> Result<CellValue, EngineError> {
+        // Intersection works on the references themselves, not their values
+        if *op == BinaryOperator::Intersect {
+            return match self.intersect_references(l, r, ctx)? {
+                Ok(reference) => ctx.resolve_reference(&reference),
+                Err(e) => Ok(CellValue::Error(e)),
+            };
+        }
+        
         let lv = self.evaluate(l, ctx)?;
         let rv = self.evaluate(r, ctx)?;
-        match op {
+        
+        // Dates calculate as serial numbers. Shifting a date by a number keeps it a date;
+        // anything else (including date - date) yields a plain number.
+        let keeps_date = match op {
+            BinaryOperator::Add => lv.is_date() != rv.is_date(),
+            BinaryOperator::Subtract => lv.is_date() && !rv.is_date(),
+            _ => false,
+        };
+        let (lv, rv) = (lv.date_as_number(), rv.date_as_number());
+        
+        let result = match op {
             BinaryOperator::Add => self.add(&lv, &rv),
             BinaryOperator::Subtract => self.subtract(&lv, &rv),
             BinaryOperator::Multiply => self.multiply(&lv, &rv),
@@ -118,112 +282,245 @@ impl Evaluator {
             BinaryOperator::GreaterThan => self.greater_than(&lv, &rv),
             BinaryOperator::GreaterThanOrEqual => self.greater_than_or_equal(&lv, &rv),
             BinaryOperator::Concat => self.concatenate(&lv, &rv),
+            BinaryOperator::Intersect => unreachable!("intersection is handled above"),
+        }?;
+        
+        Ok(match result {
+            CellValue::Number(n) if keeps_date => CellValue::Date(n),
+            other => other,
+        })
+    }
+
+    // The reference covering the cells two references share. #VALUE! when an operand
+    // isn't a reference or they are on different sheets, #NULL! when they are disjoint.
+    fn intersect_references(&self, l: &AstNode, r: &AstNode, ctx: &mut EvaluationContext) -> Result<Result<Reference, CellError>, EngineError> {
+        let mut operand = |node: &AstNode| -> Result<Result<Reference, CellError>, EngineError> {
+            match node {
+                AstNode::Reference(reference) => Ok(Ok(reference.clone())),
+                AstNode::BinaryOp { op: BinaryOperator::Intersect, left, right } => self.intersect_references(left, right, ctx),
+                _ => Ok(Err(CellError::InvalidValue)),
+            }
+        };
+        let left = match operand(l)? {
+            Ok(reference) => reference,
+            Err(e) => return Ok(Err(e)),
+        };
+        let right = match operand(r)? {
+            Ok(reference) => reference,
+            Err(e) => return Ok(Err(e)),
+        };
+        
+        // A reference without a sheet is on the formula's own sheet
+        if left.sheet().unwrap_or(ctx.current_sheet) != right.sheet().unwrap_or(ctx.current_sheet) {
+            return Ok(Err(CellError::InvalidValue));
+        }
+        let sheet = left.sheet().or(right.sheet()).map(str::to_string);
+        let Some(overlap) = left.bounds().intersection(&right.bounds()) else {
+            return Ok(Err(CellError::Null));
+        };
+        
+        let (start, end) = (overlap.start, overlap.end);
+        Ok(Ok(match (sheet, start == end) {
+            (None, true) => Reference::Cell(start),
+            (None, false) => Reference::Range { start, end },
+            (Some(sheet), true) => Reference::SheetCell { sheet, address: start },
+            (Some(sheet), false) => Reference::SheetRange { sheet, start, end },
+        }))
+    }
+    
+    // A union of areas, as one row of the values of every area in turn, so
+    // aggregates such as SUM((A1:A2,C1:C2)) see every cell
+    fn evaluate_union(&self, areas: &[AstNode], ctx: &mut EvaluationContext) -> Result<CellValue, EngineError> {
+        let mut values = Vec::new();
+        for area in areas {
+            values.push(self.evaluate(area, ctx)?);
         }
+        Ok(CellValue::Array(vec![flatten_args(&values)]))
     }
 
     fn evaluate_unary_op(&self, op: &UnaryOperator, node: &AstNode, ctx: &mut EvaluationContext) -> Result<CellValue, EngineError> {
-        let v = self.evaluate(node, ctx)?;
+        if *op == UnaryOperator::ImplicitIntersection {
+            return self.implicit_intersection(node, ctx);
+        }
+        
+        let v = self.evaluate(node, ctx)?.date_as_number();
         match op {
-            UnaryOperator::Positive => Ok(v),
             UnaryOperator::Negative => self.negate(&v),
             UnaryOperator::Percent => self.percent(&v),
+            _ => Ok(v),
+        }
+    }
+
+    // Implicit intersection (@). A range gives its cell in the formula's row (for a
+    // column) or column (for a row); a cell holding an array gives the array's value
+    // there, as if the array had spilled from the cell. Other arrays give their first
+    // value. #VALUE! when the formula's row or column misses the range.
+    fn implicit_intersection(&self, node: &AstNode, ctx: &mut EvaluationContext) -> Result<CellValue, EngineError> {
+        let current = ctx.current_cell.clone();
+        
+        if let AstNode::Reference(reference) = node {
+            let bounds = reference.bounds();
+            let row = intersect_line(bounds.start.row, bounds.end.row, current.row);
+            let col = intersect_line(bounds.start.col, bounds.end.col, current.col);
+            let (Some(row), Some(col)) = (row, col) else {
+                return Ok(CellValue::Error(CellError::InvalidValue));
+            };
+            
+            let address = CellAddress::new(row, col);
+            let cell = match reference.sheet() {
+                Some(sheet) => Reference::SheetCell { sheet: sheet.to_string(), address: address.clone() },
+                None => Reference::Cell(address.clone()),
+            };
+            return match ctx.resolve_reference(&cell)? {
+                CellValue::Array(rows) => Ok(intersect_array(&rows, &address, &current)),
+                value => Ok(value),
+            };
+        }
+        
+        match self.evaluate(node, ctx)? {
+            CellValue::Array(rows) => Ok(rows.first()
+                .and_then(|row| row.first())
+                .cloned()
+                .unwrap_or(CellValue::Error(CellError::InvalidValue))),
+            value => Ok(value),
         }
     }
 
     fn evaluate_function(&self, name: &str, args: &[AstNode], ctx: &mut EvaluationContext) -> Result<CellValue, EngineError> {
+        // Contextual functions evaluate (or inspect) their arguments themselves
+        let result = if let Some(implementation) = self.function_registry.get_contextual(name) {
+            implementation(self, args, ctx)
+        } else {
+            let mut vals = if self.function_registry.skips_blanks(name) {
+                self.evaluate_sparse_args(args, ctx)?
+            } else {
+                self.evaluate_args(args, ctx)?
+            };
+            // A single cell reference is passed as a one-cell range, so functions that
+            // skip text in references skip it there too
+            if self.function_registry.reads_numbers(name) {
+                for (val, arg) in vals.iter_mut().zip(args) {
+                    if matches!(arg, AstNode::Reference(_)) && !matches!(val, CellValue::Array(_)) {
+                        *val = CellValue::Array(vec![vec![val.clone()]]);
+                    }
+                }
+            }
+            self.function_registry.call(name, &vals)
+        };
+        
+        // Errors reported as cell errors become the formula's value rather than failing
+        // it, and a NaN or infinite result is #NUM!
+        match result {
+            Err(EngineError::CellValueError(e)) => Ok(CellValue::Error(e)),
+            other => other.map(CellValue::finite_or_error),
+        }
+    }
+
+    /// Evaluate function arguments to values
+    pub fn evaluate_args(&self, args: &[AstNode], ctx: &mut EvaluationContext) -> Result<Vec<CellValue>, EngineError> {
         let mut vals = Vec::new();
-        for a in args { vals.push(self.evaluate(a, ctx)?); }
-        self.function_registry.call(name, &vals)
+        for a in args {
+            // Range arguments are passed to functions as 2-D arrays, even a single cell
+            let v = match a {
+                AstNode::Reference(Reference::Range { start, end }) => {
+                    let sheet = ctx.current_sheet;
+                    ctx.resolve_range(sheet, &CellRange::new(start.clone(), end.clone()))?
+                },
+                AstNode::Reference(Reference::SheetRange { sheet, start, end }) => {
+                    ctx.resolve_range(sheet, &CellRange::new(start.clone(), end.clone()))?
+                },
+                _ => self.evaluate(a, ctx)?,
+            };
+            vals.push(v);
+        }
+        Ok(vals)
     }
 
-    // Operator helper methods
-    fn add(&self, left: &CellValue, right: &CellValue) -> Result<CellValue, EngineError> {
-        match (left, right) {
-            (CellValue::Number(a), CellValue::Number(b)) => Ok(CellValue::Number(a + b)),
-            (CellValue::Blank, CellValue::Number(b)) => Ok(CellValue::Number(*b)),
-            (CellValue::Number(a), CellValue::Blank) => Ok(CellValue::Number(*a)),
-            (CellValue::Error(e), _) | (_, CellValue::Error(e)) => Ok(CellValue::Error(e.clone())),
-            _ => Ok(CellValue::Error(CellError::InvalidValue)),
+    // Evaluate arguments for a function that ignores blanks: range arguments become
+    // a single row holding only their populated cells
+    fn evaluate_sparse_args(&self, args: &[AstNode], ctx: &mut EvaluationContext) -> Result<Vec<CellValue>, EngineError> {
+        let mut vals = Vec::new();
+        for a in args {
+            let v = match a {
+                AstNode::Reference(r) if !matches!(r, Reference::Cell(_) | Reference::SheetCell { .. } | Reference::Spill { .. }) => {
+                    let sheet = r.sheet().unwrap_or(ctx.current_sheet);
+                    ctx.resolve_range_sparse(sheet, &r.bounds())?
+                },
+                _ => self.evaluate(a, ctx)?,
+            };
+            vals.push(v);
         }
+        Ok(vals)
     }
 
-    fn subtract(&self, left: &CellValue, right: &CellValue) -> Result<CellValue, EngineError> {
-        match (left, right) {
-            (CellValue::Number(a), CellValue::Number(b)) => Ok(CellValue::Number(a - b)),
-            (CellValue::Blank, CellValue::Number(b)) => Ok(CellValue::Number(-b)),
-            (CellValue::Number(a), CellValue::Blank) => Ok(CellValue::Number(*a)),
-            (CellValue::Error(e), _) | (_, CellValue::Error(e)) => Ok(CellValue::Error(e.clone())),
+    // Operator helper methods. Blanks are coerced by the rules documented on
+    // `CellValue::arithmetic_operand` and `CellValue::comparison_operand`.
+    
+    // Apply an arithmetic operator: an error operand (the left one first) is the
+    // result, and an operand that isn't a number or blank is #VALUE!
+    fn arithmetic(&self, left: &CellValue, right: &CellValue, apply: impl FnOnce(f64, f64) -> CellValue) -> Result<CellValue, EngineError> {
+        if let CellValue::Error(e) = left {
+            return Ok(CellValue::Error(e.clone()));
+        }
+        if let CellValue::Error(e) = right {
+            return Ok(CellValue::Error(e.clone()));
+        }
+        match (left.arithmetic_operand(), right.arithmetic_operand()) {
+            // Overflow (1E308*10) and other non-finite results are #NUM!
+            (Some(a), Some(b)) => Ok(apply(a, b).finite_or_error()),
             _ => Ok(CellValue::Error(CellError::InvalidValue)),
         }
     }
+    
+    fn add(&self, left: &CellValue, right: &CellValue) -> Result<CellValue, EngineError> {
+        self.arithmetic(left, right, |a, b| CellValue::Number(a + b))
+    }
+
+    fn subtract(&self, left: &CellValue, right: &CellValue) -> Result<CellValue, EngineError> {
+        self.arithmetic(left, right, |a, b| CellValue::Number(a - b))
+    }
 
     fn multiply(&self, left: &CellValue, right: &CellValue) -> Result<CellValue, EngineError> {
-        match (left, right) {
-            (CellValue::Number(a), CellValue::Number(b)) => Ok(CellValue::Number(a * b)),
-            (CellValue::Blank, _) | (_, CellValue::Blank) => Ok(CellValue::Number(0.0)),
-            (CellValue::Error(e), _) | (_, CellValue::Error(e)) => Ok(CellValue::Error(e.clone())),
-            _ => Ok(CellValue::Error(CellError::InvalidValue)),
-        }
+        self.arithmetic(left, right, |a, b| CellValue::Number(a * b))
     }
 
     fn divide(&self, left: &CellValue, right: &CellValue) -> Result<CellValue, EngineError> {
-        match (left, right) {
-            (CellValue::Number(a), CellValue::Number(b)) => if *b == 0.0 {
-                Ok(CellValue::Error(CellError::DivisionByZero))
-            } else { Ok(CellValue::Number(a / b)) },
-            (CellValue::Error(e), _) | (_, CellValue::Error(e)) => Ok(CellValue::Error(e.clone())),
-            _ => Ok(CellValue::Error(CellError::InvalidValue)),
-        }
+        self.arithmetic(left, right, |a, b| if b == 0.0 {
+            CellValue::Error(CellError::DivisionByZero)
+        } else {
+            CellValue::Number(a / b)
+        })
     }
 
     fn power(&self, left: &CellValue, right: &CellValue) -> Result<CellValue, EngineError> {
-        match (left, right) {
-            (CellValue::Number(a), CellValue::Number(b)) => {
-                let r = a.powf(*b);
-                if r.is_nan() || r.is_infinite() {
-                    Ok(CellValue::Error(CellError::InvalidNumber))
-                } else {
-                    Ok(CellValue::Number(r))
-                }
-            },
-            (CellValue::Error(e), _) | (_, CellValue::Error(e)) => Ok(CellValue::Error(e.clone())),
-            _ => Ok(CellValue::Error(CellError::InvalidValue)),
-        }
+        self.arithmetic(left, right, |a, b| {
+            if a == 0.0 && b < 0.0 {
+                return CellValue::Error(CellError::DivisionByZero);
+            }
+            let r = a.powf(b);
+            if r.is_nan() || r.is_infinite() {
+                CellValue::Error(CellError::InvalidNumber)
+            } else {
+                CellValue::Number(r)
+            }
+        })
     }
 
     fn negate(&self, v: &CellValue) -> Result<CellValue, EngineError> {
-        match v {
-            CellValue::Number(n) => Ok(CellValue::Number(-n)),
-            CellValue::Blank => Ok(CellValue::Number(0.0)),
-            CellValue::Error(e) => Ok(CellValue::Error(e.clone())),
-            _ => Ok(CellValue::Error(CellError::InvalidValue)),
-        }
+        // 0 - n rather than -n, so zero (and a blank) negate to 0 rather than -0
+        self.arithmetic(v, &CellValue::Blank, |n, _| CellValue::Number(0.0 - n))
     }
 
     fn percent(&self, v: &CellValue) -> Result<CellValue, EngineError> {
-        match v {
-            CellValue::Number(n) => Ok(CellValue::Number(n / 100.0)),
-            CellValue::Blank => Ok(CellValue::Number(0.0)),
-            CellValue::Error(e) => Ok(CellValue::Error(e.clone())),
-            _ => Ok(CellValue::Error(CellError::InvalidValue)),
-        }
+        self.arithmetic(v, &CellValue::Blank, |n, _| CellValue::Number(n / 100.0))
     }
 
-    fn equal(&self, left: &CellValue, right: &CellValue) -> Result<CellValue, EngineError> {
-        use CellValue::*;
-        let res = match (left, right) {
-            (Number(a), Number(b)) => a == b,
-            (Text(a), Text(b)) => a == b,
-            (Boolean(a), Boolean(b)) => a == b,
-            (Blank, Blank) => true,
-            (Blank, Number(b)) => *b == 0.0,
-            (Number(a), Blank) => *a == 0.0,
-            (Blank, Text(b)) => b.is_empty(),
-            (Text(a), Blank) => a.is_empty(),
-            (Error(_), _) | (_, Error(_)) => return Ok(CellValue::Error(left.as_error().clone())),
-            _ => false,
-        };
-        Ok(CellValue::Boolean(res))
+    /// Compare two values with the semantics of the `=` operator
+    pub(crate) fn equal(&self, left: &CellValue, right: &CellValue) -> Result<CellValue, EngineError> {
+        match (left, right) {
+            (CellValue::Error(e), _) | (_, CellValue::Error(e)) => Ok(CellValue::Error(e.clone())),
+            _ => Ok(CellValue::Boolean(left.equals(right))),
+        }
     }
 
     fn not_equal(&self, l: &CellValue, r: &CellValue) -> Result<CellValue, EngineError> {
@@ -233,12 +530,11 @@ impl Evaluator {
 
     fn less_than(&self, left: &CellValue, right: &CellValue) -> Result<CellValue, EngineError> {
         use CellValue::*;
-        let res = match (left, right) {
+        let res = match (&left.comparison_operand(right), &right.comparison_operand(left)) {
             (Number(a), Number(b)) => a < b,
             (Text(a), Text(b)) => a.to_lowercase() < b.to_lowercase(),
-            (Blank, Number(b)) => 0.0 < *b,
-            (Number(a), Blank) => *a < 0.0,
-            (Error(_), _) | (_, Error(_)) => return Ok(CellValue::Error(left.as_error().clone())),
+            (Boolean(a), Boolean(b)) => a < b,
+            (Error(e), _) | (_, Error(e)) => return Ok(CellValue::Error(e.clone())),
             _ => return Ok(CellValue::Error(CellError::InvalidValue)),
         };
         Ok(CellValue::Boolean(res))
@@ -259,13 +555,124 @@ impl Evaluator {
 
     fn concatenate(&self, left: &CellValue, right: &CellValue) -> Result<CellValue, EngineError> {
         let to_str = |v: &CellValue| match v {
-            CellValue::Text(s) => Ok(s.clone()),
-            CellValue::Number(n) => Ok(n.to_string()),
-            CellValue::Boolean(b) => Ok(b.to_string()),
-            CellValue::Blank => Ok(String::new()),
             CellValue::Error(e) => Err(e.clone()),
-            _ => Err(CellError::InvalidValue),
+            CellValue::Formula(_) | CellValue::Array(_) => Err(CellError::InvalidValue),
+            other => Ok(other.to_string()),
         };
         Ok(CellValue::Text(format!("{}{}", to_str(left)?, to_str(right)?)))
     }
 }
+
+// The row (or column) of the span start..=end that implicit intersection picks: the
+// span's only one, or else `current` if the span covers it
+fn intersect_line(start: u32, end: u32, current: u32) -> Option<u32> {
+    let (start, end) = (start.min(end), start.max(end));
+    if start == end {
+        Some(start)
+    } else if (start..=end).contains(&current) {
+        Some(current)
+    } else {
+        None
+    }
+}
+
+// The value of an array anchored at `anchor` that lines up with the cell `current`
+fn intersect_array(rows: &[Vec<CellValue>], anchor: &CellAddress, current: &CellAddress) -> CellValue {
+    let height = rows.len() as u32;
+    let width = rows.first().map_or(0, |row| row.len()) as u32;
+    if height == 0 || width == 0 {
+        return CellValue::Error(CellError::InvalidValue);
+    }
+    
+    let row = intersect_line(anchor.row, anchor.row + height - 1, current.row);
+    let col = intersect_line(anchor.col, anchor.col + width - 1, current.col);
+    match (row, col) {
+        (Some(row), Some(col)) => rows[(row - anchor.row) as usize].get((col - anchor.col) as usize)
+            .cloned()
+            .unwrap_or(CellValue::Error(CellError::InvalidValue)),
+        _ => CellValue::Error(CellError::InvalidValue),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // The expression and value of every node, depth first
+    fn flatten(node: &TraceNode, depth: usize, lines: &mut Vec<(usize, String, CellValue)>) {
+        lines.push((depth, node.expression.clone(), node.value.clone()));
+        for child in &node.children {
+            flatten(child, depth + 1, lines);
+        }
+    }
+    
+    #[test]
+    fn tracing_records_every_sub_expression() {
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Sheet1".to_string()).unwrap();
+        workbook.set_cell_value("Sheet1", 0, 0, 4.0).unwrap();
+        
+        let trace = workbook.trace_formula("Sheet1", "=1+2*3").unwrap();
+        let mut lines = Vec::new();
+        flatten(&trace, 0, &mut lines);
+        assert_eq!(lines, vec![
+            (0, "1+2*3".to_string(), CellValue::Number(7.0)),
+            (1, "1".to_string(), CellValue::Number(1.0)),
+            (1, "2*3".to_string(), CellValue::Number(6.0)),
+            (2, "2".to_string(), CellValue::Number(2.0)),
+            (2, "3".to_string(), CellValue::Number(3.0)),
+        ]);
+        
+        let trace = workbook.trace_formula("Sheet1", "=SQRT(A1)").unwrap();
+        assert_eq!(trace.value, CellValue::Number(2.0));
+        assert_eq!(trace.children.len(), 1);
+        assert_eq!((trace.children[0].expression.as_str(), &trace.children[0].value), ("A1", &CellValue::Number(4.0)));
+    }
+    
+    #[test]
+    fn range_references_resolve_to_every_value() {
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Sheet1".to_string()).unwrap();
+        workbook.add_sheet("Data".to_string()).unwrap();
+        workbook.set_cell_value("Sheet1", 0, 0, 1.0).unwrap();
+        workbook.set_cell_value("Sheet1", 0, 1, "two").unwrap();
+        workbook.set_cell_value("Sheet1", 1, 1, "=A1*4").unwrap();
+        workbook.set_cell_value("Data", 1, 0, true).unwrap();
+        
+        let mut ctx = EvaluationContext::new(&workbook, "Sheet1", CellAddress::new(5, 5));
+        let range = |start: &str, end: &str| (CellAddress::from_a1(start).unwrap(), CellAddress::from_a1(end).unwrap());
+        let (start, end) = range("A1", "B2");
+        assert_eq!(ctx.resolve_reference(&Reference::Range { start, end }).unwrap(), CellValue::Array(vec![
+            vec![CellValue::Number(1.0), CellValue::Text("two".to_string())],
+            vec![CellValue::Blank, CellValue::Number(4.0)],
+        ]));
+        
+        let (start, end) = range("A1", "A2");
+        let sheet = "Data".to_string();
+        assert_eq!(ctx.resolve_reference(&Reference::SheetRange { sheet, start, end }).unwrap(), CellValue::Array(vec![
+            vec![CellValue::Blank],
+            vec![CellValue::Boolean(true)],
+        ]));
+        
+        // A one-cell range is just that cell's value
+        let (start, end) = range("B2", "B2");
+        assert_eq!(ctx.resolve_reference(&Reference::Range { start, end }).unwrap(), CellValue::Number(4.0));
+    }
+    
+    #[test]
+    fn percent_applies_to_literals_references_and_products() {
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Sheet1".to_string()).unwrap();
+        workbook.set_cell_value("Sheet1", 0, 0, 200.0).unwrap();
+        let eval = |formula: &str| workbook.evaluate_formula("Sheet1", formula).unwrap();
+        
+        assert_eq!(eval("=5%"), CellValue::Number(0.05));
+        assert_eq!(eval("=100*5%"), CellValue::Number(5.0));
+        assert_eq!(eval("=A1%"), CellValue::Number(2.0));
+        assert_eq!(eval("=50%%"), CellValue::Number(0.005));
+        assert_eq!(eval("=-10%"), CellValue::Number(-0.1));
+        // Percent binds tighter than exponentiation
+        assert_eq!(eval("=2^200%"), CellValue::Number(4.0));
+        assert_eq!(eval("=\"x\"%"), CellValue::Error(CellError::InvalidValue));
+    }
+}
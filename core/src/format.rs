@@ -0,0 +1,614 @@
+// ssengine-core/src/format.rs
+// Rendering values through Excel number format codes in a locale
+
+use chrono::Datelike;
+
+use crate::date::{serial_to_date, DateSystem};
+use crate::functions::round_significant;
+use crate::model::{format_general_number, CellValue};
+
+// Separators and calendar names numbers and dates are rendered with. Format codes
+// are always written in US syntax ("#,##0.00"); the locale only changes the output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Locale {
+    pub decimal_separator: char,
+    pub thousands_separator: char,
+    pub month_names: [&'static str; 12],
+    pub month_abbreviations: [&'static str; 12],
+    pub day_names: [&'static str; 7], // Sunday first
+    pub day_abbreviations: [&'static str; 7],
+    pub short_date_format: &'static str, // Format code for dates without a number format
+    pub time_format: &'static str, // Appended to the short date when a date has a time of day
+}
+
+impl Locale {
+    // US English: 1,234.5 and 10/16/2026
+    pub fn en_us() -> Self {
+        Locale {
+            decimal_separator: '.',
+            thousands_separator: ',',
+            month_names: [
+                "January", "February", "March", "April", "May", "June",
+                "July", "August", "September", "October", "November", "December",
+            ],
+            month_abbreviations: ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"],
+            day_names: ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"],
+            day_abbreviations: ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"],
+            short_date_format: "m/d/yyyy",
+            time_format: "h:mm",
+        }
+    }
+
+    // German: 1.234,5 and 16.10.2026
+    pub fn de_de() -> Self {
+        Locale {
+            decimal_separator: ',',
+            thousands_separator: '.',
+            month_names: [
+                "Januar", "Februar", "März", "April", "Mai", "Juni",
+                "Juli", "August", "September", "Oktober", "November", "Dezember",
+            ],
+            month_abbreviations: ["Jan", "Feb", "Mär", "Apr", "Mai", "Jun", "Jul", "Aug", "Sep", "Okt", "Nov", "Dez"],
+            day_names: ["Sonntag", "Montag", "Dienstag", "Mittwoch", "Donnerstag", "Freitag", "Samstag"],
+            day_abbreviations: ["So", "Mo", "Di", "Mi", "Do", "Fr", "Sa"],
+            short_date_format: "dd.mm.yyyy",
+            time_format: "hh:mm",
+        }
+    }
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale::en_us()
+    }
+}
+
+// Shown instead of a date that has no calendar day (negative or out of range)
+const INVALID_DATE: &str = "#####";
+
+// The text a cell shows: its value rendered through the cell's number format, or
+// through General (the locale's short date for dates) when it has none
+pub fn format_value(value: &CellValue, code: Option<&str>, locale: &Locale, system: DateSystem) -> String {
+    match value {
+        CellValue::Blank => String::new(),
+        CellValue::Number(n) => match code {
+            Some(code) => format_number(*n, code, locale, system).unwrap_or_else(|| INVALID_DATE.to_string()),
+            None => format_general(*n, locale),
+        },
+        CellValue::Date(serial) => {
+            let default_code;
+            let code = match code {
+                Some(code) => code,
+                None if serial.fract() != 0.0 => {
+                    default_code = format!("{} {}", locale.short_date_format, locale.time_format);
+                    &default_code
+                },
+                None => locale.short_date_format,
+            };
+            format_number(*serial, code, locale, system).unwrap_or_else(|| INVALID_DATE.to_string())
+        },
+        CellValue::Text(text) => match code {
+            Some(code) => format_text(text, code),
+            None => text.clone(),
+        },
+        CellValue::Array(rows) => match rows.first().and_then(|row| row.first()) {
+            Some(first) => format_value(first, code, locale, system),
+            None => String::new(),
+        },
+        CellValue::Boolean(_) | CellValue::Error(_) | CellValue::Formula(_) => value.to_string(),
+    }
+}
+
+// A number in the General format with the locale's decimal separator
+pub fn format_general(value: f64, locale: &Locale) -> String {
+    let formatted = format_general_number(value);
+    if locale.decimal_separator == '.' {
+        formatted
+    } else {
+        formatted.replace('.', &locale.decimal_separator.to_string())
+    }
+}
+
+// Render a number through a format code such as "#,##0.00", "0.0%", "0.00E+00",
+// "dddd, mmmm d, yyyy" or "[h]:mm". Up to four ';'-separated sections apply to
+// positive, negative and zero numbers and to text, unless sections carry conditions
+// such as "[>100]". Returns None for a date format applied to a number with no
+// calendar day, and for brackets the code can't be read with.
+pub fn format_number(value: f64, code: &str, locale: &Locale, system: DateSystem) -> Option<String> {
+    let sections = split_sections(code).iter()
+        .map(|section| tokenize(section))
+        .collect::<Option<Vec<_>>>()?;
+    let conditional = sections.iter().any(|tokens| section_condition(tokens).is_some());
+    let (tokens, value, signed) = if conditional {
+        // The first section whose condition holds, or the first without a condition.
+        // A section only negative numbers meet, such as [<0], shows the magnitude.
+        let tokens = sections.iter().take(3)
+            .find(|tokens| section_condition(tokens).map_or(true, |(operator, threshold)| operator.holds(value, threshold)));
+        let negative_only = |tokens: &[Token]| match section_condition(tokens) {
+            Some((Comparison::Less, threshold)) => threshold <= 0.0,
+            Some((Comparison::LessOrEqual, threshold)) => threshold < 0.0,
+            _ => false,
+        };
+        match tokens {
+            Some(tokens) if negative_only(tokens) => (tokens, value.abs(), false),
+            Some(tokens) => (tokens, value, true),
+            None => return Some(format_general(value, locale)),
+        }
+    } else if value < 0.0 && sections.len() >= 2 {
+        // A negative section shows the magnitude; its own literals carry the sign
+        (&sections[1], -value, false)
+    } else if value == 0.0 && sections.len() >= 3 {
+        (&sections[2], value, false)
+    } else {
+        (&sections[0], value, true)
+    };
+
+    let is_date = tokens.iter().any(|token| matches!(token, Token::Date(..) | Token::Elapsed(..) | Token::Minutes(_) | Token::AmPm(_)));
+    if is_date {
+        return format_date(value, tokens, locale, system);
+    }
+
+    let formatted = format_digits(value.abs(), tokens, locale);
+    if signed && value < 0.0 {
+        Some(format!("-{}", formatted))
+    } else {
+        Some(formatted)
+    }
+}
+
+// Render text through the text section of a format code ("@" stands for the text).
+// Codes without a text section leave text unchanged.
+pub fn format_text(text: &str, code: &str) -> String {
+    let sections = split_sections(code);
+    let section = match sections.len() {
+        4.. => &sections[3],
+        1 if sections[0].contains('@') => &sections[0],
+        _ => return text.to_string(),
+    };
+
+    let mut formatted = String::new();
+    for token in tokenize(section).unwrap_or_default() {
+        match token {
+            Token::Literal(literal) => formatted.push_str(&literal),
+            Token::TextValue => formatted.push_str(text),
+            _ => {},
+        }
+    }
+    formatted
+}
+
+// One element of a format code section
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Literal(String),
+    Digit(char), // 0 (always shown), # (significant digits only) or ? (space when absent)
+    DecimalPoint,
+    Comma, // Thousands grouping between digits, scaling by 1000 after the last one
+    Percent,
+    Exponent(bool), // E+ (true) or E-
+    Date(char, usize), // A run of y, m, d, h or s and its length
+    Elapsed(char, usize), // [h], [m] or [s]: total hours, minutes or seconds
+    Minutes(usize), // An m run next to hours or seconds
+    Condition(Comparison, f64), // [>100] and the like
+    AmPm(bool), // AM/PM (true) or A/P
+    General,
+    TextValue, // @
+}
+
+// The comparison of a section condition
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Comparison {
+    fn holds(self, value: f64, threshold: f64) -> bool {
+        match self {
+            Comparison::Less => value < threshold,
+            Comparison::LessOrEqual => value <= threshold,
+            Comparison::Greater => value > threshold,
+            Comparison::GreaterOrEqual => value >= threshold,
+            Comparison::Equal => value == threshold,
+            Comparison::NotEqual => value != threshold,
+        }
+    }
+}
+
+fn section_condition(tokens: &[Token]) -> Option<(Comparison, f64)> {
+    tokens.iter().find_map(|token| match token {
+        Token::Condition(operator, threshold) => Some((*operator, *threshold)),
+        _ => None,
+    })
+}
+
+// Split a format code on the ';' outside quotes and escapes
+fn split_sections(code: &str) -> Vec<String> {
+    let mut sections = vec![String::new()];
+    let mut in_quotes = false;
+    let mut chars = code.chars();
+    while let Some(c) = chars.next() {
+        let section = sections.last_mut().unwrap();
+        match c {
+            ';' if !in_quotes => sections.push(String::new()),
+            '\\' if !in_quotes => {
+                section.push(c);
+                section.extend(chars.next());
+            },
+            _ => {
+                if c == '"' {
+                    in_quotes = !in_quotes;
+                }
+                section.push(c);
+            },
+        }
+    }
+    sections
+}
+
+// Returns None when a section has a bracket that can't be read
+fn tokenize(section: &str) -> Option<Vec<Token>> {
+    let chars: Vec<char> = section.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let rest = &chars[i..];
+        i += 1;
+        match c {
+            '"' => {
+                let end = chars[i..].iter().position(|&c| c == '"').map_or(chars.len(), |p| i + p);
+                tokens.push(Token::Literal(chars[i..end].iter().collect()));
+                i = end + 1;
+            },
+            '\\' => {
+                tokens.extend(chars.get(i).map(|next| Token::Literal(next.to_string())));
+                i += 1;
+            },
+            // A space as wide as the next character
+            '_' => {
+                tokens.push(Token::Literal(" ".to_string()));
+                i += 1;
+            },
+            // Repeat the next character to fill the column; there is no column width here
+            '*' => i += 1,
+            '[' => {
+                let end = chars[i..].iter().position(|&c| c == ']').map_or(chars.len(), |p| i + p);
+                let content: String = chars[i..end].iter().collect();
+                tokens.extend(bracket_token(&content)?);
+                i = end + 1;
+            },
+            '0' | '#' | '?' => tokens.push(Token::Digit(c)),
+            '.' => tokens.push(Token::DecimalPoint),
+            ',' => tokens.push(Token::Comma),
+            '%' => tokens.push(Token::Percent),
+            '@' => tokens.push(Token::TextValue),
+            'E' | 'e' if matches!(chars.get(i), Some('+' | '-')) => {
+                tokens.push(Token::Exponent(chars[i] == '+'));
+                i += 1;
+            },
+            _ if starts_with_keyword(rest, "General") => {
+                tokens.push(Token::General);
+                i += 6;
+            },
+            _ if starts_with_keyword(rest, "AM/PM") => {
+                tokens.push(Token::AmPm(true));
+                i += 4;
+            },
+            _ if starts_with_keyword(rest, "A/P") => {
+                tokens.push(Token::AmPm(false));
+                i += 2;
+            },
+            _ if "ymdhs".contains(c.to_ascii_lowercase()) => {
+                let letter = c.to_ascii_lowercase();
+                let mut length = 1;
+                while chars.get(i).is_some_and(|next| next.to_ascii_lowercase() == letter) {
+                    length += 1;
+                    i += 1;
+                }
+                tokens.push(Token::Date(letter, length));
+            },
+            _ => tokens.push(Token::Literal(c.to_string())),
+        }
+    }
+
+    resolve_minutes(&mut tokens);
+    Some(tokens)
+}
+
+// Read the content of a [...] in a section: elapsed time, a condition, a currency
+// and locale ("$€-407", whose symbol is shown) or a color, which has no effect here
+fn bracket_token(content: &str) -> Option<Option<Token>> {
+    let lower = content.to_ascii_lowercase();
+    if let Some(letter) = lower.chars().next().filter(|c| "hms".contains(*c)) {
+        if lower.chars().all(|c| c == letter) {
+            return Some(Some(Token::Elapsed(letter, lower.len())));
+        }
+    }
+
+    let operators = [
+        ("<=", Comparison::LessOrEqual), (">=", Comparison::GreaterOrEqual), ("<>", Comparison::NotEqual),
+        ("<", Comparison::Less), (">", Comparison::Greater), ("=", Comparison::Equal),
+    ];
+    if let Some((operator, comparison)) = operators.iter().find(|(operator, _)| content.starts_with(operator)) {
+        let threshold = content[operator.len()..].trim().parse().ok()?;
+        return Some(Some(Token::Condition(*comparison, threshold)));
+    }
+
+    if let Some(currency) = content.strip_prefix('$') {
+        let symbol = currency.split('-').next().unwrap_or_default();
+        return Some((!symbol.is_empty()).then(|| Token::Literal(symbol.to_string())));
+    }
+
+    let is_color = ["black", "blue", "cyan", "green", "magenta", "red", "white", "yellow"].contains(&lower.as_str())
+        || lower.strip_prefix("color").is_some_and(|number| number.parse::<u8>().is_ok());
+    is_color.then_some(None)
+}
+
+fn starts_with_keyword(chars: &[char], keyword: &str) -> bool {
+    chars.len() >= keyword.len()
+        && chars.iter().zip(keyword.chars()).all(|(a, b)| a.eq_ignore_ascii_case(&b))
+}
+
+// An m run means minutes right after hours or right before seconds, months otherwise
+fn resolve_minutes(tokens: &mut [Token]) {
+    let date_parts: Vec<(usize, char)> = tokens.iter().enumerate()
+        .filter_map(|(i, token)| match token {
+            Token::Date(letter, _) | Token::Elapsed(letter, _) => Some((i, *letter)),
+            _ => None,
+        })
+        .collect();
+
+    for (k, &(i, letter)) in date_parts.iter().enumerate() {
+        let after_hours = k > 0 && date_parts[k - 1].1 == 'h';
+        let before_seconds = date_parts.get(k + 1).is_some_and(|&(_, next)| next == 's');
+        if letter == 'm' && (after_hours || before_seconds) {
+            if let Token::Date(_, length) = tokens[i] {
+                tokens[i] = Token::Minutes(length);
+            }
+        }
+    }
+}
+
+// Render the digits of a non-negative number through the placeholders of a section
+fn format_digits(value: f64, tokens: &[Token], locale: &Locale) -> String {
+    let is_digit = |token: &Token| matches!(token, Token::Digit(_));
+    let exponent = tokens.iter().position(|token| matches!(token, Token::Exponent(_)));
+    let mantissa_end = exponent.unwrap_or(tokens.len());
+    let integer_end = tokens[..mantissa_end].iter().position(|token| *token == Token::DecimalPoint).unwrap_or(mantissa_end);
+    let integer_count = tokens[..integer_end].iter().filter(|token| is_digit(token)).count();
+    let fraction_count = tokens[integer_end..mantissa_end].iter().filter(|token| is_digit(token)).count();
+    let exponent_count = tokens[mantissa_end..].iter().filter(|token| is_digit(token)).count();
+
+    // A comma between integer digits groups thousands; trailing commas divide by 1000
+    let mut grouping = false;
+    let mut scaling = 0;
+    for (i, token) in tokens[..mantissa_end].iter().enumerate() {
+        if *token != Token::Comma {
+            continue;
+        }
+        let digit_before = tokens[..i].iter().any(is_digit);
+        if digit_before && i < integer_end && tokens[i + 1..integer_end].iter().any(is_digit) {
+            grouping = true;
+        } else if i > 0 && matches!(tokens[i - 1], Token::Digit(_) | Token::Comma) {
+            scaling += 1;
+        }
+    }
+
+    let percents = tokens.iter().filter(|token| **token == Token::Percent).count() as i32;
+    let mut scaled = value * 100f64.powi(percents) / 1000f64.powi(scaling);
+
+    // Scientific notation keeps as many integer digits as there are placeholders
+    let mut power = 0;
+    if exponent.is_some() && scaled != 0.0 {
+        let integer_digits = integer_count.max(1) as i32;
+        power = scaled.log10().floor() as i32 - (integer_digits - 1);
+        scaled /= 10f64.powi(power);
+        if round_half_away(scaled, fraction_count) >= 10f64.powi(integer_digits) {
+            scaled /= 10.0;
+            power += 1;
+        }
+    }
+
+    let fixed = format!("{:.*}", fraction_count, round_half_away(scaled, fraction_count));
+    let (integer_digits, fraction_digits) = fixed.split_once('.').unwrap_or((&fixed, ""));
+    let integer_digits: Vec<char> = integer_digits.trim_start_matches('0').chars().collect();
+    let fraction_digits: Vec<char> = fraction_digits.chars().collect();
+    let exponent_digits: Vec<char> = format!("{:0width$}", power.abs(), width = exponent_count).chars().collect();
+
+    let mut formatted = String::new();
+    let mut integers_seen = 0;
+    let mut fractions_seen = 0;
+    let mut exponents_seen = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Digit(placeholder) if i < integer_end => {
+                // Placeholders take digits from the right; the first takes any extra digits
+                let position = integer_count - 1 - integers_seen;
+                if integers_seen == 0 {
+                    for (k, digit) in integer_digits.iter().enumerate().take(integer_digits.len().saturating_sub(integer_count)) {
+                        push_integer_digit(&mut formatted, *digit, integer_digits.len() - 1 - k, grouping, locale);
+                    }
+                }
+                match integer_digits.len().checked_sub(position + 1) {
+                    Some(k) => push_integer_digit(&mut formatted, integer_digits[k], position, grouping, locale),
+                    None if *placeholder == '0' => push_integer_digit(&mut formatted, '0', position, grouping, locale),
+                    None if *placeholder == '?' => formatted.push(' '),
+                    None => {},
+                }
+                integers_seen += 1;
+            },
+            Token::Digit(placeholder) if i < mantissa_end => {
+                let digit = fraction_digits.get(fractions_seen).copied().unwrap_or('0');
+                let trailing_zero = fraction_digits[fractions_seen.min(fraction_digits.len())..].iter().all(|&d| d == '0');
+                match placeholder {
+                    '#' if trailing_zero => {},
+                    '?' if trailing_zero => formatted.push(' '),
+                    _ => formatted.push(digit),
+                }
+                fractions_seen += 1;
+            },
+            Token::Digit(_) => {
+                // The exponent is written whole at its first placeholder
+                if exponents_seen == 0 {
+                    formatted.extend(&exponent_digits);
+                }
+                exponents_seen += 1;
+            },
+            Token::DecimalPoint if i == integer_end => {
+                if integer_count == 0 {
+                    for (k, digit) in integer_digits.iter().enumerate() {
+                        push_integer_digit(&mut formatted, *digit, integer_digits.len() - 1 - k, grouping, locale);
+                    }
+                }
+                formatted.push(locale.decimal_separator);
+            },
+            Token::DecimalPoint => formatted.push(locale.decimal_separator),
+            Token::Exponent(always_signed) => {
+                formatted.push('E');
+                if power < 0 {
+                    formatted.push('-');
+                } else if *always_signed {
+                    formatted.push('+');
+                }
+            },
+            Token::Percent => formatted.push('%'),
+            Token::Literal(literal) => formatted.push_str(literal),
+            Token::General | Token::TextValue => formatted.push_str(&format_general(value, locale)),
+            Token::Comma | Token::Date(..) | Token::Elapsed(..) | Token::Minutes(_) | Token::AmPm(_) | Token::Condition(..) => {},
+        }
+    }
+    formatted
+}
+
+// Round a non-negative number to the shown decimals with halves away from zero, as
+// Excel does, where format! would round them to even. The shifted number is cut to
+// the 15 digits Excel keeps first, so 1.005 shows as 1.01.
+fn round_half_away(value: f64, decimals: usize) -> f64 {
+    let multiplier = 10f64.powi(decimals as i32);
+    let rounded = round_significant(value * multiplier, 15).round() / multiplier;
+    if rounded.is_finite() { rounded } else { value }
+}
+
+// Write one integer digit, followed by a thousands separator where a group ends
+fn push_integer_digit(formatted: &mut String, digit: char, position: usize, grouping: bool, locale: &Locale) {
+    formatted.push(digit);
+    if grouping && position > 0 && position % 3 == 0 {
+        formatted.push(locale.thousands_separator);
+    }
+}
+
+// Render a date serial through the date and time parts of a section
+fn format_date(value: f64, tokens: &[Token], locale: &Locale, system: DateSystem) -> Option<String> {
+    // Round to the second first so 23:59:59.7 rolls over into the next day
+    let seconds = (value * 86_400.0).round();
+    let days = (seconds / 86_400.0).floor();
+    let date = serial_to_date(days, system)?;
+    let time = (seconds - days * 86_400.0) as u32;
+    let (hour, minute, second) = (time / 3600, time / 60 % 60, time % 60);
+    let twelve_hour = tokens.iter().any(|token| matches!(token, Token::AmPm(_)));
+
+    let month = date.month0() as usize;
+    let weekday = date.weekday().num_days_from_sunday() as usize;
+    let padded = |number: u32, length: usize| if length >= 2 { format!("{:02}", number) } else { number.to_string() };
+
+    let mut formatted = String::new();
+    for token in tokens {
+        match token {
+            Token::Date('y', length) if *length <= 2 => formatted.push_str(&format!("{:02}", date.year().rem_euclid(100))),
+            Token::Date('y', _) => formatted.push_str(&format!("{:04}", date.year())),
+            Token::Date('m', length) => match length {
+                1 | 2 => formatted.push_str(&padded(date.month(), *length)),
+                3 => formatted.push_str(locale.month_abbreviations[month]),
+                4 => formatted.push_str(locale.month_names[month]),
+                _ => formatted.extend(locale.month_names[month].chars().next()),
+            },
+            Token::Date('d', length) => match length {
+                1 | 2 => formatted.push_str(&padded(date.day(), *length)),
+                3 => formatted.push_str(locale.day_abbreviations[weekday]),
+                _ => formatted.push_str(locale.day_names[weekday]),
+            },
+            Token::Date('h', length) => {
+                let hour = if twelve_hour { (hour + 11) % 12 + 1 } else { hour };
+                formatted.push_str(&padded(hour, *length));
+            },
+            Token::Date(_, length) => formatted.push_str(&padded(second, *length)),
+            Token::Elapsed(unit, length) => {
+                let total = match unit {
+                    'h' => seconds / 3600.0,
+                    'm' => seconds / 60.0,
+                    _ => seconds,
+                };
+                formatted.push_str(&format!("{:0width$}", total.floor() as u64, width = *length));
+            },
+            Token::Minutes(length) => formatted.push_str(&padded(minute, *length)),
+            Token::AmPm(full) => {
+                let marker = if hour < 12 { "AM" } else { "PM" };
+                formatted.push_str(if *full { marker } else { &marker[..1] });
+            },
+            Token::Literal(literal) => formatted.push_str(literal),
+            Token::DecimalPoint => formatted.push('.'),
+            Token::Digit(c) => formatted.push(*c),
+            Token::Comma => formatted.push(','),
+            Token::Percent => formatted.push('%'),
+            Token::Exponent(_) | Token::General | Token::TextValue | Token::Condition(..) => {},
+        }
+    }
+    Some(formatted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn us(value: f64, code: &str) -> String {
+        format_number(value, code, &Locale::en_us(), DateSystem::Excel1900).unwrap()
+    }
+    
+    fn german(value: f64, code: &str) -> String {
+        format_number(value, code, &Locale::de_de(), DateSystem::Excel1900).unwrap()
+    }
+    
+    #[test]
+    fn numbers_use_the_locale_separators() {
+        assert_eq!(us(1234567.891, "#,##0.00"), "1,234,567.89");
+        assert_eq!(german(1234567.891, "#,##0.00"), "1.234.567,89");
+        assert_eq!(us(0.125, "0.0%"), "12.5%");
+        assert_eq!(german(0.125, "0.0%"), "12,5%");
+        assert_eq!(format_general(1234.5, &Locale::en_us()), "1234.5");
+        assert_eq!(format_general(1234.5, &Locale::de_de()), "1234,5");
+    }
+    
+    #[test]
+    fn dates_use_the_locale_names() {
+        // 2026-10-16, a Friday
+        let serial = 46311.0;
+        assert_eq!(us(serial, "dddd, mmmm d, yyyy"), "Friday, October 16, 2026");
+        assert_eq!(german(serial, "dddd, d. mmmm yyyy"), "Freitag, 16. Oktober 2026");
+        assert_eq!(german(46082.0, "mmm yy"), "Mär 26");
+        
+        let date = CellValue::Date(serial);
+        assert_eq!(format_value(&date, None, &Locale::en_us(), DateSystem::Excel1900), "10/16/2026");
+        assert_eq!(format_value(&date, None, &Locale::de_de(), DateSystem::Excel1900), "16.10.2026");
+    }
+    
+    #[test]
+    fn digits_round_half_away_from_zero() {
+        assert_eq!(us(2.5, "0"), "3");
+        assert_eq!(us(-2.5, "0"), "-3");
+        assert_eq!(us(0.125, "0.00"), "0.13");
+        assert_eq!(german(0.125, "0.00"), "0,13");
+    }
+    
+    #[test]
+    fn bracketed_sections_are_read() {
+        assert_eq!(us(1234.5, "[$€-407]#,##0.00"), "€1,234.50");
+        assert_eq!(us(-5.0, "0;[Red]-0"), "-5");
+        assert_eq!(us(1.5, "[h]:mm"), "36:00");
+    }
+}
@@ -1,22 +1,111 @@
 // ssengine-core/src/functions.rs
 // Registry and implementation of spreadsheet functions
 
-use std::collections::HashMap;
-use crate::model::CellValue;
-use crate::error::{EngineError, CellError};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use chrono::Datelike;
 
-// Function signature for spreadsheet functions
+use crate::ast::{AstNode, BinaryOperator, Reference};
+use crate::model::{CellAddress, CellValue};
+use crate::error::{EngineError, CellError};
+use crate::evaluator::{Evaluator, EvaluationContext};
+use crate::format;
+use crate::parser::{parse_reference, parse_r1c1_reference};
+use crate::date::{add_months, date_to_serial, datetime_to_serial, end_of_month, serial_to_date};
+
+// Function signature for spreadsheet functions.
+// Bad input the user can fix (wrong type, out-of-domain value) is reported as a cell
+// error: either `Ok(CellValue::Error(..))` or `Err(EngineError::CellValueError(..))`,
+// which the evaluator turns into the same error value. Other `EngineError`s (wrong
+// argument count, internal problems) abort evaluation of the formula.
 pub type FunctionImpl = fn(args: &[CellValue]) -> Result<CellValue, EngineError>;
 
+// Function signature for functions that need their unevaluated arguments and the
+// evaluation context, e.g. to inspect a reference rather than the value behind it
+pub type ContextualFunctionImpl = fn(evaluator: &Evaluator, args: &[AstNode], context: &mut EvaluationContext) -> Result<CellValue, EngineError>;
+
 // Registry of available functions
 pub struct FunctionRegistry {
     functions: HashMap<String, FunctionImpl>,
+    contextual_functions: HashMap<String, ContextualFunctionImpl>,
+    
+    // Functions that receive range arguments as 2-D arrays; all others get them flattened
+    array_functions: HashSet<String>,
+    
+    // Functions whose result can change without any precedent changing
+    volatile_functions: HashSet<String>,
+    
+    // Functions that ignore blank cells, so range arguments only need their populated cells
+    sparse_functions: HashSet<String>,
+    
+    // Functions that skip text and logical values in references, e.g. a column's header
+    numeric_functions: HashSet<String>,
+    
+    // Argument counts and help text, by function name
+    metadata: HashMap<String, FunctionMeta>,
+}
+
+// Group a function is listed under
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FunctionCategory {
+    Math,
+    Statistical,
+    Logical,
+    Text,
+    Date,
+    Lookup,
+    DynamicArray,
+    Information,
+    Engineering,
+    Financial,
+}
+
+impl FunctionCategory {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FunctionCategory::Math => "Math",
+            FunctionCategory::Statistical => "Statistical",
+            FunctionCategory::Logical => "Logical",
+            FunctionCategory::Text => "Text",
+            FunctionCategory::Date => "Date",
+            FunctionCategory::Lookup => "Lookup",
+            FunctionCategory::DynamicArray => "Dynamic array",
+            FunctionCategory::Information => "Information",
+            FunctionCategory::Engineering => "Engineering",
+            FunctionCategory::Financial => "Financial",
+        }
+    }
+}
+
+// What a function expects, for autocomplete and validation. Argument counts are
+// those written in the formula: a range counts as one argument.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionMeta {
+    pub name: String,
+    pub min_args: usize,
+    pub max_args: Option<usize>, // None: any number of arguments
+    pub category: FunctionCategory,
+    pub description: String,
+}
+
+impl FunctionMeta {
+    // Whether a call with this many arguments is allowed
+    pub fn accepts(&self, arg_count: usize) -> bool {
+        arg_count >= self.min_args && self.max_args.map_or(true, |max| arg_count <= max)
+    }
 }
 
 impl FunctionRegistry {
     pub fn new() -> Self {
         let mut registry = FunctionRegistry {
             functions: HashMap::new(),
+            contextual_functions: HashMap::new(),
+            array_functions: HashSet::new(),
+            volatile_functions: HashSet::new(),
+            sparse_functions: HashSet::new(),
+            numeric_functions: HashSet::new(),
+            metadata: HashMap::new(),
         };
         
         // Register built-in functions
@@ -30,73 +119,202 @@ impl FunctionRegistry {
         self.functions.insert(name.to_uppercase(), implementation);
     }
     
+    // Register a function that receives range arguments as 2-D arrays
+    pub fn register_array(&mut self, name: &str, implementation: FunctionImpl) {
+        self.register(name, implementation);
+        self.array_functions.insert(name.to_uppercase());
+    }
+    
+    // Whether a function receives range arguments as 2-D arrays
+    pub fn takes_arrays(&self, name: &str) -> bool {
+        self.array_functions.contains(&name.to_uppercase())
+    }
+    
+    // Register a function that ignores blank cells. Its range arguments are resolved
+    // to just their populated cells, flattened, so huge sparse ranges stay cheap.
+    pub fn register_sparse(&mut self, name: &str, implementation: FunctionImpl) {
+        self.register(name, implementation);
+        self.sparse_functions.insert(name.to_uppercase());
+    }
+    
+    // Whether a function only needs the populated cells of its range arguments
+    pub fn skips_blanks(&self, name: &str) -> bool {
+        self.sparse_functions.contains(&name.to_uppercase())
+    }
+    
+    // Mark a function as reading only the numbers of its references. Text and logical
+    // values in a referenced cell or range are skipped, while the same values given
+    // directly still count, so SUM(A1:A3) skips a text header but SUM("x") is #VALUE!.
+    pub fn mark_numeric(&mut self, name: &str) {
+        self.numeric_functions.insert(name.to_uppercase());
+    }
+    
+    // Whether a function skips text and logical values in its references
+    pub fn reads_numbers(&self, name: &str) -> bool {
+        self.numeric_functions.contains(&name.to_uppercase())
+    }
+    
+    // Mark a function as volatile, i.e. recomputed on every recalculation
+    pub fn mark_volatile(&mut self, name: &str) {
+        self.volatile_functions.insert(name.to_uppercase());
+    }
+    
+    // Whether a function is volatile
+    pub fn is_volatile(&self, name: &str) -> bool {
+        self.volatile_functions.contains(&name.to_uppercase())
+    }
+    
+    // Register a function that receives unevaluated arguments
+    pub fn register_contextual(&mut self, name: &str, implementation: ContextualFunctionImpl) {
+        self.contextual_functions.insert(name.to_uppercase(), implementation);
+    }
+    
+    // Look up a contextual function by name
+    pub fn get_contextual(&self, name: &str) -> Option<&ContextualFunctionImpl> {
+        self.contextual_functions.get(&name.to_uppercase())
+    }
+    
+    // Describe a function's arguments and purpose
+    pub fn describe(&mut self, meta: FunctionMeta) {
+        self.metadata.insert(meta.name.to_uppercase(), meta);
+    }
+    
+    // Look up a function's description by name
+    pub fn metadata(&self, name: &str) -> Option<&FunctionMeta> {
+        self.metadata.get(&name.to_uppercase())
+    }
+    
+    // Descriptions of every described function, by name
+    pub fn list(&self) -> Vec<FunctionMeta> {
+        let mut list: Vec<FunctionMeta> = self.metadata.values().cloned().collect();
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+        list
+    }
+    
     // Look up a function by name
     pub fn get(&self, name: &str) -> Option<&FunctionImpl> {
         self.functions.get(&name.to_uppercase())
     }
     
+    // Call a function by name with already-evaluated arguments
+    pub fn call(&self, name: &str, args: &[CellValue]) -> Result<CellValue, EngineError> {
+        let implementation = match self.get(name) {
+            Some(implementation) => implementation,
+            None => return Err(EngineError::UnknownFunction(name.to_string())),
+        };
+        
+        let mut args = Cow::Borrowed(args);
+        
+        // Ranges mixed with scalars, e.g. SUM(A1:A3, 10, B1), become one flat list of values
+        if !self.takes_arrays(name) && args.iter().any(|arg| matches!(arg, CellValue::Array(_))) {
+            args = Cow::Owned(if self.reads_numbers(name) { flatten_numeric_args(&args) } else { flatten_args(&args) });
+        }
+        
+        // Functions see dates as their serial numbers
+        if args.iter().any(CellValue::contains_date) {
+            args = Cow::Owned(args.iter().map(CellValue::date_as_number).collect());
+        }
+        
+        implementation(&args)
+    }
+    
     // Register all default functions
     fn register_defaults(&mut self) {
         // Math functions
-        self.register("SUM", sum);
-        self.register("AVERAGE", average);
-        self.register("COUNT", count);
-        self.register("COUNTA", counta);
-        self.register("MAX", max);
-        self.register("MIN", min);
+        self.register_sparse("SUM", sum);
+        self.register_sparse("AVERAGE", average);
+        self.register_sparse("COUNT", count);
+        self.register_sparse("COUNTA", counta);
+        self.register_sparse("MAX", max);
+        self.register_sparse("MIN", min);
         self.register("ROUND", round);
         self.register("ROUNDDOWN", rounddown);
         self.register("ROUNDUP", roundup);
         self.register("SQRT", sqrt);
         self.register("ABS", abs);
         self.register("POWER", power);
-        self.register("PRODUCT", product);
+        self.register_sparse("PRODUCT", product);
         self.register("MOD", mod_func);
         self.register("CEILING", ceiling);
         self.register("FLOOR", floor);
         self.register("MROUND", mround);
-        self.register("TRANSPOSE", transpose);
+        self.register("CEILING.MATH", ceiling_math);
+        self.register("FLOOR.MATH", floor_math);
+        self.register("INT", int);
+        self.register("TRUNC", trunc);
+        self.register_array("TRANSPOSE", transpose);
+        self.register_array("MMULT", mmult);
+        self.register_array("MINVERSE", minverse);
+        self.register_array("MDETERM", mdeterm);
+        self.register("MUNIT", munit);
         self.register("LOG", log_func);
         self.register("LN", ln);
+        self.register("LOG10", log10);
         self.register("EXP", exp);
-        self.register("RAND", rand);
-        self.register("RANDBETWEEN", randbetween);
-        self.register("RANDARRAY", randarray);
+        self.register_contextual("RAND", rand);
+        self.register_contextual("RANDBETWEEN", randbetween);
+        self.register_contextual("RANDARRAY", randarray);
+        
+        // Trigonometric functions
+        self.register("PI", pi);
+        self.register("SIN", sin);
+        self.register("COS", cos);
+        self.register("TAN", tan);
+        self.register("ASIN", asin);
+        self.register("ACOS", acos);
+        self.register("ATAN", atan);
+        self.register("ATAN2", atan2);
+        self.register("DEGREES", degrees);
+        self.register("RADIANS", radians);
         
         // Conditional aggregates
-        self.register("SUMIF", sumif);
-        self.register("SUMIFS", sumifs);
-        self.register("COUNTIF", countif);
-        self.register("COUNTIFS", countifs);
-        self.register("AVERAGEIF", averageif);
-        self.register("AVERAGEIFS", averageifs);
-        self.register("SUMPRODUCT", sumproduct);
+        self.register_array("SUMIF", sumif);
+        self.register_array("SUMIFS", sumifs);
+        self.register_array("COUNTIF", countif);
+        self.register_array("COUNTIFS", countifs);
+        self.register_array("AVERAGEIF", averageif);
+        self.register_array("AVERAGEIFS", averageifs);
+        self.register_array("SUMPRODUCT", sumproduct);
         
         // Statistical functions
         self.register("STDEV", stdev);
         self.register("STDEVP", stdevp);
         self.register("VAR", var_func);
         self.register("VARP", varp);
+        self.register("STDEV.S", stdev);
+        self.register("STDEV.P", stdevp);
+        self.register("VAR.S", var_func);
+        self.register("VAR.P", varp);
+        self.register("AVERAGEA", averagea);
+        self.register_contextual("COUNTBLANK", countblank);
         self.register("MEDIAN", median);
         self.register("PERCENTILE", percentile);
+        self.register("QUARTILE", quartile);
+        self.register("LARGE", large);
+        self.register("SMALL", small);
+        self.register_array("PERCENTRANK", percentrank);
+        self.register_array("RANK", rank);
         self.register("MODE.SNGL", mode_sngl);
-        self.register("COVARIANCE.P", covariance_p);
-        self.register("CORREL", correl);
+        self.register_array("COVARIANCE.P", covariance_p);
+        self.register_array("CORREL", correl);
         self.register("AGGREGATE", aggregate);
+        self.register_contextual("SUBTOTAL", subtotal);
         
         // Logical functions
-        self.register("IF", if_func);
+        self.register_array("IF", if_func);
         self.register("AND", and);
         self.register("OR", or);
         self.register("NOT", not);
+        self.register("XOR", xor);
         self.register("TRUE", true_func);
         self.register("FALSE", false_func);
         self.register("ISBLANK", is_blank);
         self.register("ISERROR", is_error);
         self.register("ISNUMBER", is_number);
-        self.register("IFERROR", iferror);
-        self.register("IFNA", ifna);
+        self.register_contextual("IFERROR", iferror);
+        self.register_contextual("IFNA", ifna);
         self.register("IFS", ifs);
+        self.register_contextual("SWITCH", switch);
         
         // Text functions
         self.register("CONCATENATE", concatenate);
@@ -109,20 +327,32 @@ impl FunctionRegistry {
         self.register("TRIM", trim);
         self.register("SUBSTITUTE", substitute);
         self.register("FIND", find);
-        self.register("TEXT", text_format);
+        self.register("SEARCH", search);
+        self.register("REPLACE", replace);
+        self.register_contextual("TEXT", text_format);
+        self.register("VALUE", value);
+        self.register("NUMBERVALUE", numbervalue);
+        self.register("T", t);
         self.register("TEXTJOIN", textjoin);
+        self.register("PROPER", proper);
+        self.register("CLEAN", clean);
+        self.register("REPT", rept);
+        self.register("CHAR", char_func);
+        self.register("CODE", code);
+        self.register("UNICHAR", unichar);
+        self.register("UNICODE", unicode);
         
         // Date functions
-        self.register("TODAY", today);
-        self.register("NOW", now);
-        self.register("DATE", date);
-        self.register("YEAR", year);
-        self.register("MONTH", month);
-        self.register("DAY", day);
-        self.register("WEEKDAY", weekday);
+        self.register_contextual("TODAY", today);
+        self.register_contextual("NOW", now);
+        self.register_contextual("DATE", date);
+        self.register_contextual("YEAR", year);
+        self.register_contextual("MONTH", month);
+        self.register_contextual("DAY", day);
+        self.register_contextual("WEEKDAY", weekday);
         self.register("DATEDIF", datedif);
-        self.register("EOMONTH", eomonth);
-        self.register("EDATE", edate);
+        self.register_contextual("EOMONTH", eomonth);
+        self.register_contextual("EDATE", edate);
         self.register("NETWORKDAYS", networkdays);
         self.register("NETWORKDAYS.INTL", networkdays_intl);
         self.register("WORKDAY", workday);
@@ -130,20 +360,20 @@ impl FunctionRegistry {
         self.register("YEARFRAC", yearfrac);
         
         // Lookup functions
-        self.register("VLOOKUP", vlookup);
-        self.register("HLOOKUP", hlookup);
-        self.register("INDEX", index);
-        self.register("MATCH", match_func);
-        self.register("CHOOSE", choose);
-        self.register("XLOOKUP", xlookup);
-        self.register("XMATCH", xmatch);
-        self.register("OFFSET", offset);
-        self.register("INDIRECT", indirect);
+        self.register_array("VLOOKUP", vlookup);
+        self.register_array("HLOOKUP", hlookup);
+        self.register_array("INDEX", index);
+        self.register_array("MATCH", match_func);
+        self.register_array("CHOOSE", choose);
+        self.register_array("XLOOKUP", xlookup);
+        self.register_array("XMATCH", xmatch);
+        self.register_array("OFFSET", offset);
+        self.register_contextual("INDIRECT", indirect);
         
         // Dynamic array functions
-        self.register("FILTER", filter);
-        self.register("SORT", sort);
-        self.register("UNIQUE", unique);
+        self.register_array("FILTER", filter);
+        self.register_array("SORT", sort);
+        self.register_array("UNIQUE", unique);
         self.register("SEQUENCE", sequence);
         self.register("LET", let_func);
         self.register("LAMBDA", lambda);
@@ -151,15 +381,29 @@ impl FunctionRegistry {
         // Information functions
         self.register("ISNA", is_na);
         self.register("NA", na);
+        self.register("N", n);
         self.register("ISERR", is_err);
         self.register("ERROR.TYPE", error_type);
         self.register("ISTEXT", is_text);
+        self.register("ISLOGICAL", is_logical);
+        self.register("ISNONTEXT", is_nontext);
+        self.register("TYPE", type_func);
+        self.register_contextual("ISREF", is_ref);
+        self.register_contextual("ISFORMULA", is_formula);
         
         // Engineering functions
         self.register("BIN2DEC", bin2dec);
         self.register("DEC2BIN", dec2bin);
         self.register("HEX2DEC", hex2dec);
         self.register("DEC2HEX", dec2hex);
+        self.register("OCT2DEC", oct2dec);
+        self.register("DEC2OCT", dec2oct);
+        self.register("BIN2HEX", bin2hex);
+        self.register("BIN2OCT", bin2oct);
+        self.register("HEX2BIN", hex2bin);
+        self.register("HEX2OCT", hex2oct);
+        self.register("OCT2BIN", oct2bin);
+        self.register("OCT2HEX", oct2hex);
         
         // Financial functions (DCF modeling)
         self.register("NPV", npv);
@@ -171,8 +415,8 @@ impl FunctionRegistry {
         self.register("PPMT", ppmt);
         self.register("NPER", nper);
         self.register("RATE", rate);
-        self.register("XNPV", xnpv);
-        self.register("XIRR", xirr);
+        self.register_array("XNPV", xnpv);
+        self.register_array("XIRR", xirr);
         self.register("DB", db);
         self.register("SLN", sln);
         self.register("SYD", syd);
@@ -180,7 +424,244 @@ impl FunctionRegistry {
         self.register("MIRR", mirr);
         self.register("CUMIPMT", cumipmt);
         self.register("CUMPRINC", cumprinc);
+        
+        // Volatile functions are recomputed on every recalculation
+        for name in ["RAND", "RANDBETWEEN", "RANDARRAY", "TODAY", "NOW", "OFFSET", "INDIRECT"] {
+            self.mark_volatile(name);
+        }
+        
+        // Aggregates that skip text and logical values in references
+        for name in [
+            "SUM", "AVERAGE", "MAX", "MIN", "PRODUCT",
+            "STDEV", "STDEVP", "VAR", "VARP", "STDEV.S", "STDEV.P", "VAR.S", "VAR.P",
+            "MEDIAN", "PERCENTILE", "QUARTILE", "LARGE", "SMALL",
+        ] {
+            self.mark_numeric(name);
+        }
+        
+        for &(name, category, min_args, max_args, description) in BUILTIN_METADATA {
+            self.describe(FunctionMeta {
+                name: name.to_string(),
+                min_args,
+                max_args,
+                category,
+                description: description.to_string(),
+            });
+        }
+    }
+}
+
+// Name, category, minimum and maximum argument count and description of every
+// built-in function
+const BUILTIN_METADATA: &[(&str, FunctionCategory, usize, Option<usize>, &str)] = &[
+    ("SUM", FunctionCategory::Math, 1, None, "Adds its arguments"),
+    ("AVERAGE", FunctionCategory::Math, 1, None, "Returns the arithmetic mean of its arguments"),
+    ("COUNT", FunctionCategory::Math, 1, None, "Counts the numbers among its arguments"),
+    ("COUNTA", FunctionCategory::Math, 1, None, "Counts the non-blank values among its arguments"),
+    ("MAX", FunctionCategory::Math, 1, None, "Returns the largest number among its arguments"),
+    ("MIN", FunctionCategory::Math, 1, None, "Returns the smallest number among its arguments"),
+    ("ROUND", FunctionCategory::Math, 2, Some(2), "Rounds a number to a number of digits"),
+    ("ROUNDDOWN", FunctionCategory::Math, 2, Some(2), "Rounds a number toward zero"),
+    ("ROUNDUP", FunctionCategory::Math, 2, Some(2), "Rounds a number away from zero"),
+    ("SQRT", FunctionCategory::Math, 1, Some(1), "Returns the square root of a number"),
+    ("ABS", FunctionCategory::Math, 1, Some(1), "Returns the absolute value of a number"),
+    ("POWER", FunctionCategory::Math, 2, Some(2), "Raises a number to a power"),
+    ("PRODUCT", FunctionCategory::Math, 1, None, "Multiplies its arguments"),
+    ("MOD", FunctionCategory::Math, 2, Some(2), "Returns the remainder of a division"),
+    ("CEILING", FunctionCategory::Math, 2, Some(2), "Rounds a number up to a multiple of significance"),
+    ("FLOOR", FunctionCategory::Math, 2, Some(2), "Rounds a number down to a multiple of significance"),
+    ("MROUND", FunctionCategory::Math, 2, Some(2), "Rounds a number to the nearest multiple"),
+    ("CEILING.MATH", FunctionCategory::Math, 1, Some(3), "Rounds a number up to an integer or multiple"),
+    ("FLOOR.MATH", FunctionCategory::Math, 1, Some(3), "Rounds a number down to an integer or multiple"),
+    ("INT", FunctionCategory::Math, 1, Some(1), "Rounds a number down to an integer"),
+    ("TRUNC", FunctionCategory::Math, 1, Some(2), "Truncates a number to a number of digits"),
+    ("TRANSPOSE", FunctionCategory::Math, 1, Some(1), "Swaps the rows and columns of an array"),
+    ("MMULT", FunctionCategory::Math, 2, Some(2), "Returns the matrix product of two arrays"),
+    ("MINVERSE", FunctionCategory::Math, 1, Some(1), "Returns the inverse of a square matrix"),
+    ("MDETERM", FunctionCategory::Math, 1, Some(1), "Returns the determinant of a square matrix"),
+    ("MUNIT", FunctionCategory::Math, 1, Some(1), "Returns the identity matrix of a size"),
+    ("LOG", FunctionCategory::Math, 1, Some(2), "Returns the logarithm of a number to a base"),
+    ("LN", FunctionCategory::Math, 1, Some(1), "Returns the natural logarithm of a number"),
+    ("LOG10", FunctionCategory::Math, 1, Some(1), "Returns the base-10 logarithm of a number"),
+    ("EXP", FunctionCategory::Math, 1, Some(1), "Returns e raised to a power"),
+    ("RAND", FunctionCategory::Math, 0, Some(0), "Returns a random number between 0 and 1"),
+    ("RANDBETWEEN", FunctionCategory::Math, 2, Some(2), "Returns a random integer between two bounds"),
+    ("RANDARRAY", FunctionCategory::Math, 0, Some(5), "Returns an array of random numbers"),
+    ("PI", FunctionCategory::Math, 0, Some(0), "Returns the value of pi"),
+    ("SIN", FunctionCategory::Math, 1, Some(1), "Returns the sine of an angle"),
+    ("COS", FunctionCategory::Math, 1, Some(1), "Returns the cosine of an angle"),
+    ("TAN", FunctionCategory::Math, 1, Some(1), "Returns the tangent of an angle"),
+    ("ASIN", FunctionCategory::Math, 1, Some(1), "Returns the arcsine of a number"),
+    ("ACOS", FunctionCategory::Math, 1, Some(1), "Returns the arccosine of a number"),
+    ("ATAN", FunctionCategory::Math, 1, Some(1), "Returns the arctangent of a number"),
+    ("ATAN2", FunctionCategory::Math, 2, Some(2), "Returns the arctangent of x and y coordinates"),
+    ("DEGREES", FunctionCategory::Math, 1, Some(1), "Converts radians to degrees"),
+    ("RADIANS", FunctionCategory::Math, 1, Some(1), "Converts degrees to radians"),
+    ("SUMIF", FunctionCategory::Math, 2, Some(3), "Adds the cells that meet a criterion"),
+    ("SUMIFS", FunctionCategory::Math, 3, None, "Adds the cells that meet several criteria"),
+    ("SUMPRODUCT", FunctionCategory::Math, 1, None, "Returns the sum of the products of corresponding array items"),
+    ("COUNTIF", FunctionCategory::Statistical, 2, Some(2), "Counts the cells that meet a criterion"),
+    ("COUNTIFS", FunctionCategory::Statistical, 2, None, "Counts the cells that meet several criteria"),
+    ("AVERAGEIF", FunctionCategory::Statistical, 2, Some(3), "Averages the cells that meet a criterion"),
+    ("AVERAGEIFS", FunctionCategory::Statistical, 3, None, "Averages the cells that meet several criteria"),
+    ("STDEV", FunctionCategory::Statistical, 1, None, "Estimates the standard deviation of a sample"),
+    ("STDEVP", FunctionCategory::Statistical, 1, None, "Returns the standard deviation of a population"),
+    ("VAR", FunctionCategory::Statistical, 1, None, "Estimates the variance of a sample"),
+    ("VARP", FunctionCategory::Statistical, 1, None, "Returns the variance of a population"),
+    ("STDEV.S", FunctionCategory::Statistical, 1, None, "Estimates the standard deviation of a sample"),
+    ("STDEV.P", FunctionCategory::Statistical, 1, None, "Returns the standard deviation of a population"),
+    ("VAR.S", FunctionCategory::Statistical, 1, None, "Estimates the variance of a sample"),
+    ("VAR.P", FunctionCategory::Statistical, 1, None, "Returns the variance of a population"),
+    ("AVERAGEA", FunctionCategory::Statistical, 1, None, "Averages its arguments, counting text as 0 and TRUE as 1"),
+    ("COUNTBLANK", FunctionCategory::Statistical, 1, None, "Counts the blank cells of a range"),
+    ("MEDIAN", FunctionCategory::Statistical, 1, None, "Returns the median of its arguments"),
+    ("PERCENTILE", FunctionCategory::Statistical, 2, Some(2), "Returns the k-th percentile of a data set"),
+    ("QUARTILE", FunctionCategory::Statistical, 2, Some(2), "Returns a quartile of a data set"),
+    ("LARGE", FunctionCategory::Statistical, 2, Some(2), "Returns the k-th largest value of a data set"),
+    ("SMALL", FunctionCategory::Statistical, 2, Some(2), "Returns the k-th smallest value of a data set"),
+    ("PERCENTRANK", FunctionCategory::Statistical, 2, Some(3), "Returns the percentage rank of a value in a data set"),
+    ("RANK", FunctionCategory::Statistical, 2, Some(3), "Returns the rank of a number in a list"),
+    ("MODE.SNGL", FunctionCategory::Statistical, 1, None, "Returns the most frequent value of a data set"),
+    ("COVARIANCE.P", FunctionCategory::Statistical, 2, Some(2), "Returns the population covariance of two data sets"),
+    ("CORREL", FunctionCategory::Statistical, 2, Some(2), "Returns the correlation coefficient of two data sets"),
+    ("AGGREGATE", FunctionCategory::Statistical, 3, None, "Applies an aggregate function, optionally skipping errors and hidden rows"),
+    ("SUBTOTAL", FunctionCategory::Statistical, 2, None, "Applies an aggregate function, skipping other subtotals"),
+    ("IF", FunctionCategory::Logical, 3, Some(3), "Returns one value if a condition is true and another if it is false"),
+    ("AND", FunctionCategory::Logical, 1, None, "Returns TRUE if all of its arguments are true"),
+    ("OR", FunctionCategory::Logical, 1, None, "Returns TRUE if any of its arguments is true"),
+    ("NOT", FunctionCategory::Logical, 1, Some(1), "Reverses a logical value"),
+    ("XOR", FunctionCategory::Logical, 1, None, "Returns TRUE if an odd number of its arguments are true"),
+    ("TRUE", FunctionCategory::Logical, 0, Some(0), "Returns the logical value TRUE"),
+    ("FALSE", FunctionCategory::Logical, 0, Some(0), "Returns the logical value FALSE"),
+    ("IFERROR", FunctionCategory::Logical, 2, Some(2), "Returns a fallback value if an expression is an error"),
+    ("IFNA", FunctionCategory::Logical, 2, Some(2), "Returns a fallback value if an expression is #N/A"),
+    ("IFS", FunctionCategory::Logical, 2, None, "Returns the value of the first condition that is true"),
+    ("SWITCH", FunctionCategory::Logical, 3, None, "Returns the value matching an expression, or a default"),
+    ("CONCATENATE", FunctionCategory::Text, 1, None, "Joins text values into one"),
+    ("LEFT", FunctionCategory::Text, 1, Some(2), "Returns the leftmost characters of a text"),
+    ("RIGHT", FunctionCategory::Text, 1, Some(2), "Returns the rightmost characters of a text"),
+    ("MID", FunctionCategory::Text, 3, Some(3), "Returns characters from the middle of a text"),
+    ("LEN", FunctionCategory::Text, 1, Some(1), "Returns the number of characters of a text"),
+    ("LOWER", FunctionCategory::Text, 1, Some(1), "Converts text to lowercase"),
+    ("UPPER", FunctionCategory::Text, 1, Some(1), "Converts text to uppercase"),
+    ("TRIM", FunctionCategory::Text, 1, Some(1), "Removes extra spaces from text"),
+    ("SUBSTITUTE", FunctionCategory::Text, 3, Some(4), "Replaces occurrences of a text with new text"),
+    ("FIND", FunctionCategory::Text, 2, Some(3), "Finds one text in another (case-sensitive)"),
+    ("SEARCH", FunctionCategory::Text, 2, Some(3), "Finds one text in another (case-insensitive, with wildcards)"),
+    ("REPLACE", FunctionCategory::Text, 4, Some(4), "Replaces characters at a position of a text"),
+    ("TEXT", FunctionCategory::Text, 2, Some(2), "Formats a value through a number format code"),
+    ("VALUE", FunctionCategory::Text, 1, Some(1), "Converts text to a number"),
+    ("NUMBERVALUE", FunctionCategory::Text, 1, Some(3), "Converts text to a number with given separators"),
+    ("T", FunctionCategory::Text, 1, Some(1), "Returns its argument if it is text, empty text otherwise"),
+    ("TEXTJOIN", FunctionCategory::Text, 3, None, "Joins text values with a delimiter"),
+    ("PROPER", FunctionCategory::Text, 1, Some(1), "Capitalizes the first letter of each word"),
+    ("CLEAN", FunctionCategory::Text, 1, Some(1), "Removes non-printable characters from text"),
+    ("REPT", FunctionCategory::Text, 2, Some(2), "Repeats text a number of times"),
+    ("CHAR", FunctionCategory::Text, 1, Some(1), "Returns the character of a character code"),
+    ("CODE", FunctionCategory::Text, 1, Some(1), "Returns the code of the first character of a text"),
+    ("UNICHAR", FunctionCategory::Text, 1, Some(1), "Returns the Unicode character of a code point"),
+    ("UNICODE", FunctionCategory::Text, 1, Some(1), "Returns the code point of the first character of a text"),
+    ("TODAY", FunctionCategory::Date, 0, Some(0), "Returns the current date"),
+    ("NOW", FunctionCategory::Date, 0, Some(0), "Returns the current date and time"),
+    ("DATE", FunctionCategory::Date, 3, Some(3), "Returns the serial number of a date"),
+    ("YEAR", FunctionCategory::Date, 1, Some(1), "Returns the year of a date"),
+    ("MONTH", FunctionCategory::Date, 1, Some(1), "Returns the month of a date"),
+    ("DAY", FunctionCategory::Date, 1, Some(1), "Returns the day of the month of a date"),
+    ("WEEKDAY", FunctionCategory::Date, 1, Some(2), "Returns the day of the week of a date"),
+    ("DATEDIF", FunctionCategory::Date, 3, Some(3), "Returns the difference between two dates in a unit"),
+    ("EOMONTH", FunctionCategory::Date, 2, Some(2), "Returns the last day of the month a number of months away"),
+    ("EDATE", FunctionCategory::Date, 2, Some(2), "Returns the date a number of months away"),
+    ("NETWORKDAYS", FunctionCategory::Date, 2, Some(3), "Counts the working days between two dates"),
+    ("NETWORKDAYS.INTL", FunctionCategory::Date, 2, Some(4), "Counts the working days between two dates with custom weekends"),
+    ("WORKDAY", FunctionCategory::Date, 2, Some(3), "Returns the date a number of working days away"),
+    ("WORKDAY.INTL", FunctionCategory::Date, 2, Some(4), "Returns the date a number of working days away with custom weekends"),
+    ("YEARFRAC", FunctionCategory::Date, 2, Some(3), "Returns the fraction of a year between two dates"),
+    ("VLOOKUP", FunctionCategory::Lookup, 3, Some(4), "Looks up a value in the first column of a table"),
+    ("HLOOKUP", FunctionCategory::Lookup, 3, Some(4), "Looks up a value in the first row of a table"),
+    ("INDEX", FunctionCategory::Lookup, 2, Some(3), "Returns the value at a row and column of an array"),
+    ("MATCH", FunctionCategory::Lookup, 2, Some(3), "Returns the position of a value in a range"),
+    ("CHOOSE", FunctionCategory::Lookup, 2, None, "Returns the value at an index from a list"),
+    ("XLOOKUP", FunctionCategory::Lookup, 3, Some(6), "Looks up a value and returns the matching item"),
+    ("XMATCH", FunctionCategory::Lookup, 2, Some(4), "Returns the position of a value in a range"),
+    ("OFFSET", FunctionCategory::Lookup, 3, Some(5), "Returns a reference offset from a starting reference"),
+    ("INDIRECT", FunctionCategory::Lookup, 1, Some(2), "Returns the reference named by a text"),
+    ("FILTER", FunctionCategory::DynamicArray, 2, Some(3), "Returns the rows of an array that meet a condition"),
+    ("SORT", FunctionCategory::DynamicArray, 1, Some(4), "Sorts the rows of an array"),
+    ("UNIQUE", FunctionCategory::DynamicArray, 1, Some(3), "Returns the distinct rows of an array"),
+    ("SEQUENCE", FunctionCategory::DynamicArray, 1, Some(4), "Returns an array of sequential numbers"),
+    ("LET", FunctionCategory::DynamicArray, 3, None, "Names intermediate values inside a formula"),
+    ("LAMBDA", FunctionCategory::DynamicArray, 2, None, "Defines a reusable function"),
+    ("ISBLANK", FunctionCategory::Information, 1, Some(1), "Returns TRUE if a value is blank"),
+    ("ISERROR", FunctionCategory::Information, 1, Some(1), "Returns TRUE if a value is any error"),
+    ("ISNUMBER", FunctionCategory::Information, 1, Some(1), "Returns TRUE if a value is a number"),
+    ("ISNA", FunctionCategory::Information, 1, Some(1), "Returns TRUE if a value is #N/A"),
+    ("NA", FunctionCategory::Information, 0, Some(0), "Returns the #N/A error"),
+    ("N", FunctionCategory::Information, 1, Some(1), "Converts a value to a number"),
+    ("ISERR", FunctionCategory::Information, 1, Some(1), "Returns TRUE if a value is an error other than #N/A"),
+    ("ERROR.TYPE", FunctionCategory::Information, 1, Some(1), "Returns the number of an error value"),
+    ("ISTEXT", FunctionCategory::Information, 1, Some(1), "Returns TRUE if a value is text"),
+    ("ISLOGICAL", FunctionCategory::Information, 1, Some(1), "Returns TRUE if a value is a logical value"),
+    ("ISNONTEXT", FunctionCategory::Information, 1, Some(1), "Returns TRUE if a value is not text"),
+    ("TYPE", FunctionCategory::Information, 1, Some(1), "Returns the type of a value as a number"),
+    ("ISREF", FunctionCategory::Information, 1, Some(1), "Returns TRUE if an argument is a reference"),
+    ("ISFORMULA", FunctionCategory::Information, 1, Some(1), "Returns TRUE if a referenced cell holds a formula"),
+    ("BIN2DEC", FunctionCategory::Engineering, 1, Some(1), "Converts a binary number to decimal"),
+    ("DEC2BIN", FunctionCategory::Engineering, 1, Some(2), "Converts a decimal number to binary"),
+    ("HEX2DEC", FunctionCategory::Engineering, 1, Some(1), "Converts a hexadecimal number to decimal"),
+    ("DEC2HEX", FunctionCategory::Engineering, 1, Some(2), "Converts a decimal number to hexadecimal"),
+    ("OCT2DEC", FunctionCategory::Engineering, 1, Some(1), "Converts an octal number to decimal"),
+    ("DEC2OCT", FunctionCategory::Engineering, 1, Some(2), "Converts a decimal number to octal"),
+    ("BIN2HEX", FunctionCategory::Engineering, 1, Some(2), "Converts a binary number to hexadecimal"),
+    ("BIN2OCT", FunctionCategory::Engineering, 1, Some(2), "Converts a binary number to octal"),
+    ("HEX2BIN", FunctionCategory::Engineering, 1, Some(2), "Converts a hexadecimal number to binary"),
+    ("HEX2OCT", FunctionCategory::Engineering, 1, Some(2), "Converts a hexadecimal number to octal"),
+    ("OCT2BIN", FunctionCategory::Engineering, 1, Some(2), "Converts an octal number to binary"),
+    ("OCT2HEX", FunctionCategory::Engineering, 1, Some(2), "Converts an octal number to hexadecimal"),
+    ("NPV", FunctionCategory::Financial, 2, None, "Returns the net present value of periodic cash flows"),
+    ("IRR", FunctionCategory::Financial, 1, Some(2), "Returns the internal rate of return of periodic cash flows"),
+    ("PMT", FunctionCategory::Financial, 3, Some(5), "Returns the periodic payment of a loan"),
+    ("PV", FunctionCategory::Financial, 3, Some(5), "Returns the present value of an investment"),
+    ("FV", FunctionCategory::Financial, 3, Some(5), "Returns the future value of an investment"),
+    ("IPMT", FunctionCategory::Financial, 4, Some(6), "Returns the interest part of a loan payment"),
+    ("PPMT", FunctionCategory::Financial, 4, Some(6), "Returns the principal part of a loan payment"),
+    ("NPER", FunctionCategory::Financial, 3, Some(5), "Returns the number of periods of an investment"),
+    ("RATE", FunctionCategory::Financial, 3, Some(6), "Returns the interest rate per period of an annuity"),
+    ("XNPV", FunctionCategory::Financial, 3, Some(3), "Returns the net present value of dated cash flows"),
+    ("XIRR", FunctionCategory::Financial, 2, Some(3), "Returns the internal rate of return of dated cash flows"),
+    ("DB", FunctionCategory::Financial, 4, Some(5), "Returns fixed-declining balance depreciation"),
+    ("SLN", FunctionCategory::Financial, 3, Some(3), "Returns straight-line depreciation"),
+    ("SYD", FunctionCategory::Financial, 4, Some(4), "Returns sum-of-years' digits depreciation"),
+    ("DDB", FunctionCategory::Financial, 4, Some(5), "Returns double-declining balance depreciation"),
+    ("MIRR", FunctionCategory::Financial, 3, Some(3), "Returns the modified internal rate of return"),
+    ("CUMIPMT", FunctionCategory::Financial, 6, Some(6), "Returns the cumulative interest paid between two periods"),
+    ("CUMPRINC", FunctionCategory::Financial, 6, Some(6), "Returns the cumulative principal paid between two periods"),
+];
+
+// Flatten array arguments (row by row) into a single list alongside the scalar arguments
+pub fn flatten_args(args: &[CellValue]) -> Vec<CellValue> {
+    let mut values = Vec::with_capacity(args.len());
+    for arg in args {
+        match arg {
+            CellValue::Array(rows) => values.extend(rows.iter().flatten().cloned()),
+            other => values.push(other.clone()),
+        }
+    }
+    values
+}
+
+// Flatten arguments like `flatten_args`, dropping the text and logical values
+// inside arrays (which come from references) but keeping those given directly
+pub fn flatten_numeric_args(args: &[CellValue]) -> Vec<CellValue> {
+    let mut values = Vec::with_capacity(args.len());
+    for arg in args {
+        match arg {
+            CellValue::Array(rows) => values.extend(rows.iter().flatten()
+                .filter(|value| !matches!(value, CellValue::Text(_) | CellValue::Boolean(_)))
+                .cloned()),
+            other => values.push(other.clone()),
+        }
     }
+    values
 }
 
 // Function implementations
@@ -207,7 +688,7 @@ fn npv(args: &[CellValue]) -> Result<CellValue, EngineError> {
     let mut npv = 0.0;
     for (i, arg) in args[1..].iter().enumerate() {
         match arg {
-            CellValue::Number(value) => {
+            CellValue::Number(value) | CellValue::Date(value) => {
                 // NPV formula: each cash flow is divided by (1 + rate)^period
                 // Excel's NPV assumes the first cash flow is at the end of period 1
                 let period = i as f64 + 1.0;
@@ -219,7 +700,8 @@ fn npv(args: &[CellValue]) -> Result<CellValue, EngineError> {
                 npv += value / f64::powf(1.0 + rate, period);
             },
             CellValue::Blank => {},
-            CellValue::Formula(_) => return Err(EngineError::EvaluationError("NPV values must be numeric, not formulas".into())),
+            CellValue::Text(_) => return Err(EngineError::EvaluationError("Cannot include text values in NPV".into())),
+            CellValue::Formula(_) | CellValue::Array(_) => return Err(EngineError::EvaluationError("NPV values must be numeric, not formulas".into())),
             CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
         }
     }
@@ -422,15 +904,16 @@ fn fv(args: &[CellValue]) -> Result<CellValue, EngineError> {
     Ok(CellValue::Number(fv_value))
 }
 
-// Helper function to extract a number from a CellValue
+// Helper function to extract a number from a CellValue; numeric text such as "12" or
+// "$1,234" is coerced, other text is #VALUE!
 fn extract_number(value: &CellValue, name: &str) -> Result<f64, EngineError> {
     match value {
-        CellValue::Number(n) => Ok(*n),
+        CellValue::Number(n) | CellValue::Date(n) => Ok(*n),
         CellValue::Boolean(b) => Ok(if *b { 1.0 } else { 0.0 }),
         CellValue::Blank => Ok(0.0),
-        CellValue::Formula(_) => Err(EngineError::EvaluationError(format!("{} must be numeric, not a formula", name))),
+        CellValue::Formula(_) | CellValue::Array(_) => Err(EngineError::EvaluationError(format!("{} must be numeric, not a formula", name))),
         CellValue::Error(e) => Err(EngineError::CellValueError(e.clone())),
-        _ => Err(EngineError::EvaluationError(format!("{} must be numeric", name))),
+        CellValue::Text(t) => parse_number_text(t, '.', ',').ok_or(EngineError::CellValueError(CellError::InvalidValue)),
     }
 }
 
@@ -442,11 +925,11 @@ fn sum(args: &[CellValue]) -> Result<CellValue, EngineError> {
     
     for arg in args {
         match arg {
-            CellValue::Number(n) => total += n,
-            CellValue::Text(_) => return Err(EngineError::EvaluationError("Cannot sum text values".into())),
+            CellValue::Number(n) | CellValue::Date(n) => total += n,
+            CellValue::Text(_) => return Err(EngineError::CellValueError(CellError::InvalidValue)),
             CellValue::Boolean(b) => total += if *b { 1.0 } else { 0.0 },
             CellValue::Blank => {}, // Ignore blank cells
-            CellValue::Formula(_) => return Err(EngineError::EvaluationError("Formulas should be evaluated before using in functions".into())),
+            CellValue::Formula(_) | CellValue::Array(_) => return Err(EngineError::EvaluationError("Formulas should be evaluated before using in functions".into())),
             CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
         }
     }
@@ -461,7 +944,7 @@ fn average(args: &[CellValue]) -> Result<CellValue, EngineError> {
 
     for arg in args {
         match arg {
-            CellValue::Number(n) => {
+            CellValue::Number(n) | CellValue::Date(n) => {
                 total += n;
                 valid_count += 1;
             },
@@ -470,18 +953,100 @@ fn average(args: &[CellValue]) -> Result<CellValue, EngineError> {
                 valid_count += 1;
             },
             CellValue::Blank => {},
-            CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot include formulas directly in AVERAGE".into())),
+            CellValue::Text(_) => return Err(EngineError::CellValueError(CellError::InvalidValue)),
+            CellValue::Formula(_) | CellValue::Array(_) => return Err(EngineError::EvaluationError("Cannot include formulas directly in AVERAGE".into())),
             CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
         }
     }
 
     if valid_count == 0 {
-        return Err(EngineError::EvaluationError("AVERAGE requires at least one numeric value".into()));
+        return Ok(CellValue::Error(CellError::DivisionByZero));
+    }
+
+    Ok(CellValue::Number(total / valid_count as f64))
+}
+
+// AVERAGEA function - average counting text as 0 and logical values as 1/0
+fn averagea(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    let mut valid_count = 0;
+    let mut total = 0.0;
+
+    for arg in args {
+        match arg {
+            CellValue::Number(n) | CellValue::Date(n) => {
+                total += n;
+                valid_count += 1;
+            },
+            CellValue::Boolean(b) => {
+                total += if *b { 1.0 } else { 0.0 };
+                valid_count += 1;
+            },
+            CellValue::Text(_) => valid_count += 1,
+            CellValue::Blank => {},
+            CellValue::Formula(_) | CellValue::Array(_) => return Err(EngineError::EvaluationError("Cannot include formulas directly in AVERAGEA".into())),
+            CellValue::Error(e) => return Ok(CellValue::Error(e.clone())),
+        }
+    }
+
+    if valid_count == 0 {
+        return Ok(CellValue::Error(CellError::DivisionByZero));
     }
 
     Ok(CellValue::Number(total / valid_count as f64))
 }
 
+// COUNTBLANK function - counts empty cells: absent cells, blank cells and cells
+// holding empty text (e.g. a formula returning ""). A reference is sized from its
+// bounds, so whole columns count every empty row, not just those in the used range.
+fn countblank(evaluator: &Evaluator, args: &[AstNode], context: &mut EvaluationContext) -> Result<CellValue, EngineError> {
+    if args.is_empty() {
+        return Err(EngineError::EvaluationError("COUNTBLANK requires at least 1 argument: range".into()));
+    }
+    
+    let workbook = context.workbook;
+    let mut count = 0u64;
+    for arg in args {
+        let reference = match arg {
+            AstNode::Reference(reference) if !matches!(reference, Reference::Spill { .. }) => reference,
+            other => {
+                let value = evaluator.evaluate(other, context)?;
+                count += flatten_args(&[value]).iter().filter(|value| is_empty_value(value)).count() as u64;
+                continue;
+            },
+        };
+        
+        let sheet_name = reference.sheet().unwrap_or(context.current_sheet).to_string();
+        let sheet = match workbook.get_sheet(&sheet_name) {
+            Some(sheet) => sheet,
+            None => return Ok(CellValue::Error(CellError::InvalidReference)),
+        };
+        
+        // Every cell of the range is empty except the populated ones holding a value
+        let bounds = reference.bounds();
+        let (rows, cols) = bounds.dimensions();
+        let addresses: Vec<CellAddress> = sheet.get_range_cells(&bounds).map(|(address, _)| address).collect();
+        let mut filled = 0u64;
+        for address in addresses {
+            let value = context.resolve_reference(&Reference::SheetCell { sheet: sheet_name.clone(), address })?;
+            if !is_empty_value(&value) {
+                filled += 1;
+            }
+        }
+        count += rows as u64 * cols as u64 - filled;
+    }
+    Ok(CellValue::Number(count as f64))
+}
+
+// Whether COUNTBLANK counts a value: blank, or empty text. COUNTA counts every
+// other value, including empty text, and COUNT only numbers.
+fn is_empty_value(value: &CellValue) -> bool {
+    match value {
+        CellValue::Blank => true,
+        CellValue::Text(s) => s.is_empty(),
+        _ => false,
+    }
+}
+
 // COUNT function - counts number of cells with numbers
 fn count(args: &[CellValue]) -> Result<CellValue, EngineError> {
     let count = args.iter().filter(|arg| matches!(arg, CellValue::Number(_))).count();
@@ -501,7 +1066,7 @@ fn max(args: &[CellValue]) -> Result<CellValue, EngineError> {
 
     for arg in args {
         match arg {
-            CellValue::Number(n) => {
+            CellValue::Number(n) | CellValue::Date(n) => {
                 max_value = max_value.max(*n);
                 found_any = true;
             },
@@ -511,13 +1076,14 @@ fn max(args: &[CellValue]) -> Result<CellValue, EngineError> {
                 found_any = true;
             },
             CellValue::Blank => {},
-            CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot include formulas directly in MAX".into())),
+            CellValue::Text(_) => return Err(EngineError::CellValueError(CellError::InvalidValue)),
+            CellValue::Formula(_) | CellValue::Array(_) => return Err(EngineError::EvaluationError("Cannot include formulas directly in MAX".into())),
             CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
         }
     }
 
     if !found_any {
-        return Err(EngineError::EvaluationError("MAX requires at least one numeric value".into()));
+        return Ok(CellValue::Number(0.0));
     }
 
     Ok(CellValue::Number(max_value))
@@ -530,7 +1096,7 @@ fn min(args: &[CellValue]) -> Result<CellValue, EngineError> {
 
     for arg in args {
         match arg {
-            CellValue::Number(n) => {
+            CellValue::Number(n) | CellValue::Date(n) => {
                 min_value = min_value.min(*n);
                 found_any = true;
             },
@@ -540,36 +1106,48 @@ fn min(args: &[CellValue]) -> Result<CellValue, EngineError> {
                 found_any = true;
             },
             CellValue::Blank => {},
-            CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot include formulas directly in MIN".into())),
+            CellValue::Text(_) => return Err(EngineError::CellValueError(CellError::InvalidValue)),
+            CellValue::Formula(_) | CellValue::Array(_) => return Err(EngineError::EvaluationError("Cannot include formulas directly in MIN".into())),
             CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
         }
     }
 
     if !found_any {
-        return Err(EngineError::EvaluationError("MIN requires at least one numeric value".into()));
+        return Ok(CellValue::Number(0.0));
     }
 
     Ok(CellValue::Number(min_value))
 }
 
+// Round a number to a number of decimal digits (negative digits round to the left
+// of the decimal point). `rounding` is applied to the magnitude, so f64::round rounds
+// halves away from zero, f64::ceil away from zero and f64::trunc toward zero.
+fn round_to_digits(number: f64, num_digits: i32, rounding: fn(f64) -> f64) -> f64 {
+    let multiplier = 10.0_f64.powi(num_digits);
+    let sign = if number >= 0.0 { 1.0 } else { -1.0 };
+    sign * (rounding(number.abs() * multiplier) / multiplier)
+}
+
+// Round a number to a number of significant figures, halves away from zero, e.g.
+// 1234.5 to 2 figures is 1200 and 0.012345 to 3 figures is 0.0123
+pub fn round_significant(number: f64, figures: u32) -> f64 {
+    if number == 0.0 || !number.is_finite() || figures == 0 {
+        return number;
+    }
+    let exponent = number.abs().log10().floor() as i32;
+    round_to_digits(number, figures as i32 - 1 - exponent, f64::round)
+}
+
 // ROUND function - rounds a number to a specified number of digits
 fn round(args: &[CellValue]) -> Result<CellValue, EngineError> {
     if args.len() != 2 {
         return Err(EngineError::EvaluationError("ROUND requires exactly 2 arguments: number and num_digits".into()));
     }
     
-    // Get the number to round
     let number = extract_number(&args[0], "number")?;
+    let num_digits = extract_number(&args[1], "num_digits")? as i32;
     
-    // Get the number of digits to round to
-    let num_digits = extract_number(&args[1], "num_digits")?;
-    let num_digits_int = num_digits as i32;
-    
-    // Calculate the rounded value
-    let multiplier = 10.0_f64.powi(num_digits_int);
-    let rounded = (number * multiplier).round() / multiplier;
-    
-    Ok(CellValue::Number(rounded))
+    Ok(CellValue::Number(round_to_digits(number, num_digits, f64::round)))
 }
 
 // ROUNDDOWN function - rounds a number down to a specified number of digits
@@ -578,20 +1156,11 @@ fn rounddown(args: &[CellValue]) -> Result<CellValue, EngineError> {
         return Err(EngineError::EvaluationError("ROUNDDOWN requires exactly 2 arguments: number and num_digits".into()));
     }
     
-    // Get the number to round
     let number = extract_number(&args[0], "number")?;
+    let num_digits = extract_number(&args[1], "num_digits")? as i32;
     
-    // Get the number of digits to round to
-    let num_digits = extract_number(&args[1], "num_digits")?;
-    let num_digits_int = num_digits as i32;
-    
-    // Calculate the rounded value
-    let multiplier = 10.0_f64.powi(num_digits_int);
-    let sign = if number >= 0.0 { 1.0 } else { -1.0 };
-    let abs_number = number.abs();
-    let rounded = sign * ((abs_number * multiplier).floor() / multiplier);
-    
-    Ok(CellValue::Number(rounded))
+    // Toward zero, so ROUNDDOWN(-2.5, 0) is -2
+    Ok(CellValue::Number(round_to_digits(number, num_digits, f64::floor)))
 }
 
 // ROUNDUP function - rounds a number up to a specified number of digits
@@ -600,20 +1169,11 @@ fn roundup(args: &[CellValue]) -> Result<CellValue, EngineError> {
         return Err(EngineError::EvaluationError("ROUNDUP requires exactly 2 arguments: number and num_digits".into()));
     }
     
-    // Get the number to round
     let number = extract_number(&args[0], "number")?;
+    let num_digits = extract_number(&args[1], "num_digits")? as i32;
     
-    // Get the number of digits to round to
-    let num_digits = extract_number(&args[1], "num_digits")?;
-    let num_digits_int = num_digits as i32;
-    
-    // Calculate the rounded value
-    let multiplier = 10.0_f64.powi(num_digits_int);
-    let sign = if number >= 0.0 { 1.0 } else { -1.0 };
-    let abs_number = number.abs();
-    let rounded = sign * ((abs_number * multiplier).ceil() / multiplier);
-    
-    Ok(CellValue::Number(rounded))
+    // Away from zero, so ROUNDUP(-2.1, 0) is -3
+    Ok(CellValue::Number(round_to_digits(number, num_digits, f64::ceil)))
 }
 
 // SQRT function - returns the square root of a number
@@ -625,7 +1185,7 @@ fn sqrt(args: &[CellValue]) -> Result<CellValue, EngineError> {
     let number = extract_number(&args[0], "number")?;
     
     if number < 0.0 {
-        return Err(EngineError::EvaluationError("SQRT requires a non-negative number".into()));
+        return Ok(CellValue::Error(CellError::InvalidNumber));
     }
     
     Ok(CellValue::Number(number.sqrt()))
@@ -652,15 +1212,21 @@ fn power(args: &[CellValue]) -> Result<CellValue, EngineError> {
     
     // Handle negative base with non-integer exponent (which would result in a complex number)
     if base < 0.0 && exponent.fract() != 0.0 {
-        return Err(EngineError::EvaluationError("POWER cannot raise a negative number to a non-integer power".into()));
+        return Ok(CellValue::Error(CellError::InvalidNumber));
     }
     
     // Handle base of zero with negative exponent (division by zero)
     if base == 0.0 && exponent < 0.0 {
-        return Err(EngineError::EvaluationError("POWER cannot raise zero to a negative power".into()));
+        return Ok(CellValue::Error(CellError::DivisionByZero));
     }
     
-    Ok(CellValue::Number(base.powf(exponent)))
+    // powf already gives 0^0 = 1; only overflow is left to catch
+    let result = base.powf(exponent);
+    if !result.is_finite() {
+        return Ok(CellValue::Error(CellError::InvalidNumber));
+    }
+    
+    Ok(CellValue::Number(result))
 }
 
 // PRODUCT function - multiplies all the numbers given as arguments
@@ -670,7 +1236,7 @@ fn product(args: &[CellValue]) -> Result<CellValue, EngineError> {
 
     for arg in args {
         match arg {
-            CellValue::Number(n) => {
+            CellValue::Number(n) | CellValue::Date(n) => {
                 product *= n;
                 found_any = true;
             },
@@ -679,13 +1245,14 @@ fn product(args: &[CellValue]) -> Result<CellValue, EngineError> {
                 found_any = true;
             },
             CellValue::Blank => {},
-            CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot include formulas directly in PRODUCT".into())),
+            CellValue::Text(_) => return Err(EngineError::CellValueError(CellError::InvalidValue)),
+            CellValue::Formula(_) | CellValue::Array(_) => return Err(EngineError::EvaluationError("Cannot include formulas directly in PRODUCT".into())),
             CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
         }
     }
 
     if !found_any {
-        return Err(EngineError::EvaluationError("PRODUCT requires at least one numeric value".into()));
+        return Ok(CellValue::Number(0.0));
     }
 
     Ok(CellValue::Number(product))
@@ -695,24 +1262,21 @@ fn product(args: &[CellValue]) -> Result<CellValue, EngineError> {
 
 // STDEV function - calculates standard deviation based on a sample
 fn stdev(args: &[CellValue]) -> Result<CellValue, EngineError> {
-    if args.len() < 2 {
-        return Err(EngineError::EvaluationError("STDEV requires at least 2 values".into()));
-    }
-    
     // Extract numeric values
     let mut values = Vec::new();
     for arg in args {
         match arg {
-            CellValue::Number(n) => values.push(*n),
+            CellValue::Number(n) | CellValue::Date(n) => values.push(*n),
             CellValue::Boolean(b) => values.push(if *b { 1.0 } else { 0.0 }),
             CellValue::Blank => {},
-            CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot include formulas directly in STDEV".into())),
+            CellValue::Text(_) => return Err(EngineError::CellValueError(CellError::InvalidValue)),
+            CellValue::Formula(_) | CellValue::Array(_) => return Err(EngineError::EvaluationError("Cannot include formulas directly in STDEV".into())),
             CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
         }
     }
     
     if values.len() < 2 {
-        return Err(EngineError::EvaluationError("STDEV requires at least 2 numeric values".into()));
+        return Ok(CellValue::Error(CellError::DivisionByZero));
     }
     
     // Calculate mean
@@ -730,24 +1294,21 @@ fn stdev(args: &[CellValue]) -> Result<CellValue, EngineError> {
 
 // STDEVP function - calculates standard deviation based on the entire population
 fn stdevp(args: &[CellValue]) -> Result<CellValue, EngineError> {
-    if args.len() < 1 {
-        return Err(EngineError::EvaluationError("STDEVP requires at least 1 value".into()));
-    }
-    
     // Extract numeric values
     let mut values = Vec::new();
     for arg in args {
         match arg {
-            CellValue::Number(n) => values.push(*n),
+            CellValue::Number(n) | CellValue::Date(n) => values.push(*n),
             CellValue::Boolean(b) => values.push(if *b { 1.0 } else { 0.0 }),
             CellValue::Blank => {},
-            CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot include formulas directly in STDEVP".into())),
+            CellValue::Text(_) => return Err(EngineError::CellValueError(CellError::InvalidValue)),
+            CellValue::Formula(_) | CellValue::Array(_) => return Err(EngineError::EvaluationError("Cannot include formulas directly in STDEVP".into())),
             CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
         }
     }
     
     if values.is_empty() {
-        return Err(EngineError::EvaluationError("STDEVP requires at least 1 numeric value".into()));
+        return Ok(CellValue::Error(CellError::DivisionByZero));
     }
     
     // Calculate mean
@@ -765,24 +1326,21 @@ fn stdevp(args: &[CellValue]) -> Result<CellValue, EngineError> {
 
 // VAR function - calculates variance based on a sample
 fn var_func(args: &[CellValue]) -> Result<CellValue, EngineError> {
-    if args.len() < 2 {
-        return Err(EngineError::EvaluationError("VAR requires at least 2 values".into()));
-    }
-    
     // Extract numeric values
     let mut values = Vec::new();
     for arg in args {
         match arg {
-            CellValue::Number(n) => values.push(*n),
+            CellValue::Number(n) | CellValue::Date(n) => values.push(*n),
             CellValue::Boolean(b) => values.push(if *b { 1.0 } else { 0.0 }),
             CellValue::Blank => {},
-            CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot include formulas directly in VAR".into())),
+            CellValue::Text(_) => return Err(EngineError::CellValueError(CellError::InvalidValue)),
+            CellValue::Formula(_) | CellValue::Array(_) => return Err(EngineError::EvaluationError("Cannot include formulas directly in VAR".into())),
             CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
         }
     }
     
     if values.len() < 2 {
-        return Err(EngineError::EvaluationError("VAR requires at least 2 numeric values".into()));
+        return Ok(CellValue::Error(CellError::DivisionByZero));
     }
     
     // Calculate mean
@@ -800,24 +1358,21 @@ fn var_func(args: &[CellValue]) -> Result<CellValue, EngineError> {
 
 // VARP function - calculates variance based on the entire population
 fn varp(args: &[CellValue]) -> Result<CellValue, EngineError> {
-    if args.len() < 1 {
-        return Err(EngineError::EvaluationError("VARP requires at least 1 value".into()));
-    }
-    
     // Extract numeric values
     let mut values = Vec::new();
     for arg in args {
         match arg {
-            CellValue::Number(n) => values.push(*n),
+            CellValue::Number(n) | CellValue::Date(n) => values.push(*n),
             CellValue::Boolean(b) => values.push(if *b { 1.0 } else { 0.0 }),
             CellValue::Blank => {},
-            CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot include formulas directly in VARP".into())),
+            CellValue::Text(_) => return Err(EngineError::CellValueError(CellError::InvalidValue)),
+            CellValue::Formula(_) | CellValue::Array(_) => return Err(EngineError::EvaluationError("Cannot include formulas directly in VARP".into())),
             CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
         }
     }
     
     if values.is_empty() {
-        return Err(EngineError::EvaluationError("VARP requires at least 1 numeric value".into()));
+        return Ok(CellValue::Error(CellError::DivisionByZero));
     }
     
     // Calculate mean
@@ -835,24 +1390,21 @@ fn varp(args: &[CellValue]) -> Result<CellValue, EngineError> {
 
 // MEDIAN function - returns the median (middle value) of the given numbers
 fn median(args: &[CellValue]) -> Result<CellValue, EngineError> {
-    if args.is_empty() {
-        return Err(EngineError::EvaluationError("MEDIAN requires at least 1 value".into()));
-    }
-    
     // Extract numeric values
     let mut values = Vec::new();
     for arg in args {
         match arg {
-            CellValue::Number(n) => values.push(*n),
+            CellValue::Number(n) | CellValue::Date(n) => values.push(*n),
             CellValue::Boolean(b) => values.push(if *b { 1.0 } else { 0.0 }),
             CellValue::Blank => {},
-            CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot include formulas directly in MEDIAN".into())),
+            CellValue::Text(_) => return Err(EngineError::CellValueError(CellError::InvalidValue)),
+            CellValue::Formula(_) | CellValue::Array(_) => return Err(EngineError::EvaluationError("Cannot include formulas directly in MEDIAN".into())),
             CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
         }
     }
     
     if values.is_empty() {
-        return Err(EngineError::EvaluationError("MEDIAN requires at least 1 numeric value".into()));
+        return Ok(CellValue::Error(CellError::InvalidNumber));
     }
     
     // Sort the values
@@ -888,24 +1440,36 @@ fn percentile(args: &[CellValue]) -> Result<CellValue, EngineError> {
     }
     
     // Extract numeric values (excluding the last argument which is k)
-    let mut values = Vec::new();
-    for arg in args.iter().take(args.len() - 1) {
-        match arg {
-            CellValue::Number(n) => values.push(*n),
-            CellValue::Boolean(b) => values.push(if *b { 1.0 } else { 0.0 }),
-            CellValue::Blank => {},
-            CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot include formulas directly in PERCENTILE".into())),
-            CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
-        }
-    }
+    let mut values = collect_sample(&args[..args.len() - 1], "PERCENTILE")?;
     
     if values.is_empty() {
-        return Err(EngineError::EvaluationError("PERCENTILE requires at least 1 numeric value in the array".into()));
+        return Ok(CellValue::Error(CellError::InvalidNumber));
     }
     
     // Sort the values
     values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
     
+    Ok(CellValue::Number(interpolate_sorted(&values, k)))
+}
+
+// Helper function to extract the numeric values of a statistical function's data arguments
+fn collect_sample(args: &[CellValue], name: &str) -> Result<Vec<f64>, EngineError> {
+    let mut values = Vec::new();
+    for arg in args {
+        match arg {
+            CellValue::Number(n) | CellValue::Date(n) => values.push(*n),
+            CellValue::Boolean(b) => values.push(if *b { 1.0 } else { 0.0 }),
+            CellValue::Blank => {},
+            CellValue::Text(_) => return Err(EngineError::CellValueError(CellError::InvalidValue)),
+            CellValue::Formula(_) | CellValue::Array(_) => return Err(EngineError::EvaluationError(format!("Cannot include formulas directly in {}", name))),
+            CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
+        }
+    }
+    Ok(values)
+}
+
+// Helper function to linearly interpolate the k-th fraction (0..=1) of sorted, non-empty values
+fn interpolate_sorted(values: &[f64], k: f64) -> f64 {
     // Calculate the position
     let n = values.len() as f64;
     let position = k * (n - 1.0);
@@ -913,13 +1477,144 @@ fn percentile(args: &[CellValue]) -> Result<CellValue, EngineError> {
     let position_fraction = position - position.floor();
     
     // Calculate the percentile value
-    let percentile = if position_index >= values.len() - 1 {
+    if position_index >= values.len() - 1 {
         values[values.len() - 1]
     } else {
         values[position_index] + position_fraction * (values[position_index + 1] - values[position_index])
+    }
+}
+
+// Helper function to extract the numbers of an array argument, skipping text, logical values and blanks
+fn array_numbers(value: &CellValue) -> Result<Vec<f64>, CellError> {
+    let mut values = Vec::new();
+    for row in array_rows(value) {
+        for cell in row {
+            match cell {
+                CellValue::Number(n) | CellValue::Date(n) => values.push(*n),
+                CellValue::Error(e) => return Err(e.clone()),
+                _ => {},
+            }
+        }
+    }
+    Ok(values)
+}
+
+// QUARTILE function - returns the quartile (0 = min, 2 = median, 4 = max) of a data set
+fn quartile(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() < 2 {
+        return Err(EngineError::EvaluationError("QUARTILE requires 2 arguments: array, quart".into()));
+    }
+    
+    let quart = extract_number(&args[args.len() - 1], "quart")?.trunc();
+    if !(0.0..=4.0).contains(&quart) {
+        return Ok(CellValue::Error(CellError::InvalidNumber));
+    }
+    
+    let mut values = collect_sample(&args[..args.len() - 1], "QUARTILE")?;
+    if values.is_empty() {
+        return Ok(CellValue::Error(CellError::InvalidNumber));
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    
+    Ok(CellValue::Number(interpolate_sorted(&values, quart / 4.0)))
+}
+
+// LARGE function - returns the k-th largest value of a data set
+fn large(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    nth_value(args, "LARGE", true)
+}
+
+// SMALL function - returns the k-th smallest value of a data set
+fn small(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    nth_value(args, "SMALL", false)
+}
+
+// Shared implementation of LARGE and SMALL
+fn nth_value(args: &[CellValue], name: &str, largest: bool) -> Result<CellValue, EngineError> {
+    if args.len() < 2 {
+        return Err(EngineError::EvaluationError(format!("{} requires 2 arguments: array, k", name)));
+    }
+    
+    // Fractional k rounds up, as in Excel
+    let k = extract_number(&args[args.len() - 1], "k")?.ceil();
+    let mut values = collect_sample(&args[..args.len() - 1], name)?;
+    
+    if k < 1.0 || k > values.len() as f64 {
+        return Ok(CellValue::Error(CellError::InvalidNumber));
+    }
+    
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    if largest {
+        values.reverse();
+    }
+    
+    Ok(CellValue::Number(values[k as usize - 1]))
+}
+
+// PERCENTRANK function - returns the rank of a value in a data set as a fraction (0..1)
+fn percentrank(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(EngineError::EvaluationError("PERCENTRANK requires 2 or 3 arguments: array, x, [significance]".into()));
+    }
+    
+    let mut values = match array_numbers(&args[0]) {
+        Ok(values) => values,
+        Err(e) => return Ok(CellValue::Error(e)),
+    };
+    let x = extract_number(&args[1], "x")?;
+    let significance = if args.len() == 3 { extract_number(&args[2], "significance")?.trunc() } else { 3.0 };
+    
+    if values.is_empty() || significance < 1.0 {
+        return Ok(CellValue::Error(CellError::InvalidNumber));
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    
+    let (min, max) = (values[0], values[values.len() - 1]);
+    if x < min || x > max {
+        return Ok(CellValue::Error(CellError::NotAvailable));
+    }
+    if values.len() == 1 {
+        return Ok(CellValue::Number(1.0));
+    }
+    
+    // Position of x among the sorted values, interpolating between neighbours
+    let below = values.iter().filter(|v| **v < x).count();
+    let position = if values[below] == x {
+        below as f64
+    } else {
+        let (lower, upper) = (values[below - 1], values[below]);
+        (below - 1) as f64 + (x - lower) / (upper - lower)
+    };
+    let rank = position / (values.len() - 1) as f64;
+    
+    // Truncate (not round) to the requested number of digits
+    let factor = 10f64.powf(significance);
+    Ok(CellValue::Number((rank * factor + 1e-9).floor() / factor))
+}
+
+// RANK function - returns the rank of a number in a list (ties share the best rank)
+fn rank(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(EngineError::EvaluationError("RANK requires 2 or 3 arguments: number, ref, [order]".into()));
+    }
+    
+    let number = extract_number(&args[0], "number")?;
+    let values = match array_numbers(&args[1]) {
+        Ok(values) => values,
+        Err(e) => return Ok(CellValue::Error(e)),
     };
+    // Order 0 (default) ranks descending, anything else ascending
+    let ascending = args.len() == 3 && extract_number(&args[2], "order")? != 0.0;
+    
+    if !values.contains(&number) {
+        return Ok(CellValue::Error(CellError::NotAvailable));
+    }
+    
+    let better = values.iter()
+        .filter(|v| if ascending { **v < number } else { **v > number })
+        .count();
     
-    Ok(CellValue::Number(percentile))
+    Ok(CellValue::Number((better + 1) as f64))
 }
 
 // ===== LOGICAL FUNCTIONS =====
@@ -932,10 +1627,10 @@ fn if_func(args: &[CellValue]) -> Result<CellValue, EngineError> {
     
     let condition = match &args[0] {
         CellValue::Boolean(b) => *b,
-        CellValue::Number(n) => *n != 0.0,
+        CellValue::Number(n) | CellValue::Date(n) => *n != 0.0,
         CellValue::Text(t) => !t.is_empty(),
         CellValue::Blank => false,
-        CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot use unevaluated formula as a condition".into())),
+        CellValue::Formula(_) | CellValue::Array(_) => return Err(EngineError::EvaluationError("Cannot use unevaluated formula as a condition".into())),
         CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
     };
     
@@ -946,28 +1641,37 @@ fn if_func(args: &[CellValue]) -> Result<CellValue, EngineError> {
     }
 }
 
-// AND function - returns TRUE if all arguments are TRUE
-fn and(args: &[CellValue]) -> Result<CellValue, EngineError> {
-    if args.is_empty() {
-        return Err(EngineError::EvaluationError("AND requires at least one argument".into()));
-    }
-    
+// The logical values of AND/OR/XOR arguments, ranges already flattened. Blanks are
+// skipped; numbers are TRUE unless zero and the text "TRUE" or "FALSE" is read as
+// its value. Other text gives #VALUE!, as does an argument list with no logical
+// value at all.
+fn logical_values(args: &[CellValue]) -> Result<Vec<bool>, EngineError> {
+    let mut values = Vec::with_capacity(args.len());
     for arg in args {
-        let value = match arg {
-            CellValue::Boolean(b) => *b,
-            CellValue::Number(n) => *n != 0.0,
-            CellValue::Text(t) => !t.is_empty(),
-            CellValue::Blank => false,
-            CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot use unevaluated formula in AND".into())),
+        match arg {
+            CellValue::Boolean(b) => values.push(*b),
+            CellValue::Number(n) | CellValue::Date(n) => values.push(*n != 0.0),
+            CellValue::Text(t) if t.eq_ignore_ascii_case("TRUE") => values.push(true),
+            CellValue::Text(t) if t.eq_ignore_ascii_case("FALSE") => values.push(false),
+            CellValue::Text(_) => return Err(EngineError::CellValueError(CellError::InvalidValue)),
+            CellValue::Blank => {},
+            CellValue::Formula(_) | CellValue::Array(_) => return Err(EngineError::EvaluationError("Cannot use unevaluated formula in a logical function".into())),
             CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
-        };
-        
-        if !value {
-            return Ok(CellValue::Boolean(false));
         }
     }
-    
-    Ok(CellValue::Boolean(true))
+    if values.is_empty() {
+        return Err(EngineError::CellValueError(CellError::InvalidValue));
+    }
+    Ok(values)
+}
+
+// AND function - returns TRUE if all arguments are TRUE
+fn and(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.is_empty() {
+        return Err(EngineError::EvaluationError("AND requires at least one argument".into()));
+    }
+    
+    Ok(CellValue::Boolean(logical_values(args)?.into_iter().all(|value| value)))
 }
 
 // OR function - returns TRUE if any argument is TRUE
@@ -976,37 +1680,28 @@ fn or(args: &[CellValue]) -> Result<CellValue, EngineError> {
         return Err(EngineError::EvaluationError("OR requires at least one argument".into()));
     }
     
-    for arg in args {
-        let value = match arg {
-            CellValue::Boolean(b) => *b,
-            CellValue::Number(n) => *n != 0.0,
-            CellValue::Text(t) => !t.is_empty(),
-            CellValue::Blank => false,
-            CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot use unevaluated formula in OR".into())),
-            CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
-        };
-        
-        if value {
-            return Ok(CellValue::Boolean(true));
-        }
+    Ok(CellValue::Boolean(logical_values(args)?.into_iter().any(|value| value)))
+}
+
+// XOR function - returns TRUE if an odd number of arguments are TRUE
+fn xor(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.is_empty() {
+        return Err(EngineError::EvaluationError("XOR requires at least one argument".into()));
     }
     
-    Ok(CellValue::Boolean(false))
+    Ok(CellValue::Boolean(logical_values(args)?.into_iter().filter(|value| *value).count() % 2 == 1))
 }
 
-// NOT function - reverses the logical value of its argument
+// NOT function - reverses the logical value of its argument. A blank is FALSE, so
+// NOT of an empty cell is TRUE; a range of more than one cell gives #VALUE!.
 fn not(args: &[CellValue]) -> Result<CellValue, EngineError> {
     if args.len() != 1 {
-        return Err(EngineError::EvaluationError("NOT requires exactly one argument".into()));
+        return Ok(CellValue::Error(CellError::InvalidValue));
     }
     
     let value = match &args[0] {
-        CellValue::Boolean(b) => *b,
-        CellValue::Number(n) => *n != 0.0,
-        CellValue::Text(t) => !t.is_empty(),
         CellValue::Blank => false,
-        CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot use unevaluated formula in NOT".into())),
-        CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
+        other => logical_values(std::slice::from_ref(other))?[0],
     };
     
     Ok(CellValue::Boolean(!value))
@@ -1065,14 +1760,7 @@ fn concatenate(args: &[CellValue]) -> Result<CellValue, EngineError> {
     let mut result = String::new();
     
     for arg in args {
-        match arg {
-            CellValue::Text(t) => result.push_str(t),
-            CellValue::Number(n) => result.push_str(&n.to_string()),
-            CellValue::Boolean(b) => result.push_str(if *b { "TRUE" } else { "FALSE" }),
-            CellValue::Blank => {},
-            CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot use unevaluated formula in CONCATENATE".into())),
-            CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
-        }
+        result.push_str(&text_arg(arg)?);
     }
     
     Ok(CellValue::Text(result))
@@ -1084,19 +1772,13 @@ fn left(args: &[CellValue]) -> Result<CellValue, EngineError> {
         return Err(EngineError::EvaluationError("LEFT requires 1 or 2 arguments: text and [num_chars]".into()));
     }
     
-    let text = match &args[0] {
-        CellValue::Text(t) => t.clone(),
-        CellValue::Number(n) => n.to_string(),
-        CellValue::Boolean(b) => if *b { "TRUE".to_string() } else { "FALSE".to_string() },
-        CellValue::Blank => "".to_string(),
-        CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot use unevaluated formula in LEFT".into())),
-        CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
-    };
+    let text = text_arg(&args[0])?;
     
     let num_chars = if args.len() == 2 {
         match &args[1] {
-            CellValue::Number(n) => *n as usize,
-            _ => return Err(EngineError::EvaluationError("LEFT's second argument must be a number".into())),
+            CellValue::Number(n) if *n >= 0.0 => *n as usize,
+            CellValue::Number(_) => return Ok(CellValue::Error(CellError::InvalidValue)), // Negative count
+            _ => return Err(EngineError::CellValueError(CellError::InvalidValue)),
         }
     } else {
         1 // Default is 1 character
@@ -1114,19 +1796,13 @@ fn right(args: &[CellValue]) -> Result<CellValue, EngineError> {
         return Err(EngineError::EvaluationError("RIGHT requires 1 or 2 arguments: text and [num_chars]".into()));
     }
     
-    let text = match &args[0] {
-        CellValue::Text(t) => t.clone(),
-        CellValue::Number(n) => n.to_string(),
-        CellValue::Boolean(b) => if *b { "TRUE".to_string() } else { "FALSE".to_string() },
-        CellValue::Blank => "".to_string(),
-        CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot use unevaluated formula in RIGHT".into())),
-        CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
-    };
+    let text = text_arg(&args[0])?;
     
     let num_chars = if args.len() == 2 {
         match &args[1] {
-            CellValue::Number(n) => *n as usize,
-            _ => return Err(EngineError::EvaluationError("RIGHT's second argument must be a number".into())),
+            CellValue::Number(n) if *n >= 0.0 => *n as usize,
+            CellValue::Number(_) => return Ok(CellValue::Error(CellError::InvalidValue)), // Negative count
+            _ => return Err(EngineError::CellValueError(CellError::InvalidValue)),
         }
     } else {
         1 // Default is 1 character
@@ -1146,30 +1822,20 @@ fn mid(args: &[CellValue]) -> Result<CellValue, EngineError> {
         return Err(EngineError::EvaluationError("MID requires exactly 3 arguments: text, start_num, and num_chars".into()));
     }
     
-    let text = match &args[0] {
-        CellValue::Text(t) => t.clone(),
-        CellValue::Number(n) => n.to_string(),
-        CellValue::Boolean(b) => if *b { "TRUE".to_string() } else { "FALSE".to_string() },
-        CellValue::Blank => "".to_string(),
-        CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot use unevaluated formula in MID".into())),
-        CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
-    };
+    let text = text_arg(&args[0])?;
     
     let start_num = match &args[1] {
-        CellValue::Number(n) => *n as usize,
-        _ => return Err(EngineError::EvaluationError("MID's second argument (start_num) must be a number".into())),
+        CellValue::Number(n) if *n >= 1.0 => *n as usize,
+        CellValue::Number(_) => return Ok(CellValue::Error(CellError::InvalidValue)), // Excel's start position is 1-based
+        _ => return Err(EngineError::CellValueError(CellError::InvalidValue)),
     };
     
     let num_chars = match &args[2] {
-        CellValue::Number(n) => *n as usize,
-        _ => return Err(EngineError::EvaluationError("MID's third argument (num_chars) must be a number".into())),
+        CellValue::Number(n) if *n >= 0.0 => *n as usize,
+        CellValue::Number(_) => return Ok(CellValue::Error(CellError::InvalidValue)), // Negative count
+        _ => return Err(EngineError::CellValueError(CellError::InvalidValue)),
     };
     
-    // Excel uses 1-based indexing for the start position
-    if start_num < 1 {
-        return Err(EngineError::EvaluationError("MID's start_num must be at least 1".into()));
-    }
-    
     let chars: Vec<char> = text.chars().collect();
     let start_index = start_num - 1; // Convert to 0-based indexing
     
@@ -1188,14 +1854,7 @@ fn len(args: &[CellValue]) -> Result<CellValue, EngineError> {
         return Err(EngineError::EvaluationError("LEN requires exactly one argument".into()));
     }
     
-    let text = match &args[0] {
-        CellValue::Text(t) => t.clone(),
-        CellValue::Number(n) => n.to_string(),
-        CellValue::Boolean(b) => if *b { "TRUE".to_string() } else { "FALSE".to_string() },
-        CellValue::Blank => "".to_string(),
-        CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot use unevaluated formula in LEN".into())),
-        CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
-    };
+    let text = text_arg(&args[0])?;
     
     Ok(CellValue::Number(text.chars().count() as f64))
 }
@@ -1206,14 +1865,7 @@ fn lower(args: &[CellValue]) -> Result<CellValue, EngineError> {
         return Err(EngineError::EvaluationError("LOWER requires exactly one argument".into()));
     }
     
-    let text = match &args[0] {
-        CellValue::Text(t) => t.clone(),
-        CellValue::Number(n) => n.to_string(),
-        CellValue::Boolean(b) => if *b { "TRUE".to_string() } else { "FALSE".to_string() },
-        CellValue::Blank => "".to_string(),
-        CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot use unevaluated formula in LOWER".into())),
-        CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
-    };
+    let text = text_arg(&args[0])?;
     
     Ok(CellValue::Text(text.to_lowercase()))
 }
@@ -1224,14 +1876,7 @@ fn upper(args: &[CellValue]) -> Result<CellValue, EngineError> {
         return Err(EngineError::EvaluationError("UPPER requires exactly one argument".into()));
     }
     
-    let text = match &args[0] {
-        CellValue::Text(t) => t.clone(),
-        CellValue::Number(n) => n.to_string(),
-        CellValue::Boolean(b) => if *b { "TRUE".to_string() } else { "FALSE".to_string() },
-        CellValue::Blank => "".to_string(),
-        CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot use unevaluated formula in UPPER".into())),
-        CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
-    };
+    let text = text_arg(&args[0])?;
     
     Ok(CellValue::Text(text.to_uppercase()))
 }
@@ -1242,14 +1887,7 @@ fn trim(args: &[CellValue]) -> Result<CellValue, EngineError> {
         return Err(EngineError::EvaluationError("TRIM requires exactly one argument".into()));
     }
     
-    let text = match &args[0] {
-        CellValue::Text(t) => t.clone(),
-        CellValue::Number(n) => n.to_string(),
-        CellValue::Boolean(b) => if *b { "TRUE".to_string() } else { "FALSE".to_string() },
-        CellValue::Blank => "".to_string(),
-        CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot use unevaluated formula in TRIM".into())),
-        CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
-    };
+    let text = text_arg(&args[0])?;
     
     // First trim leading and trailing spaces
     let mut result = text.trim().to_string();
@@ -1272,42 +1910,21 @@ fn substitute(args: &[CellValue]) -> Result<CellValue, EngineError> {
         return Err(EngineError::EvaluationError("SUBSTITUTE requires 3 or 4 arguments: text, old_text, new_text, [instance_num]".into()));
     }
     
-    let text = match &args[0] {
-        CellValue::Text(t) => t.clone(),
-        CellValue::Number(n) => n.to_string(),
-        CellValue::Boolean(b) => if *b { "TRUE".to_string() } else { "FALSE".to_string() },
-        CellValue::Blank => "".to_string(),
-        CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot use unevaluated formula in SUBSTITUTE".into())),
-        CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
-    };
+    let text = text_arg(&args[0])?;
     
-    let old_text = match &args[1] {
-        CellValue::Text(t) => t.clone(),
-        CellValue::Number(n) => n.to_string(),
-        CellValue::Boolean(b) => if *b { "TRUE".to_string() } else { "FALSE".to_string() },
-        CellValue::Blank => "".to_string(),
-        CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot use unevaluated formula in SUBSTITUTE".into())),
-        CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
-    };
+    let old_text = text_arg(&args[1])?;
     
-    let new_text = match &args[2] {
-        CellValue::Text(t) => t.clone(),
-        CellValue::Number(n) => n.to_string(),
-        CellValue::Boolean(b) => if *b { "TRUE".to_string() } else { "FALSE".to_string() },
-        CellValue::Blank => "".to_string(),
-        CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot use unevaluated formula in SUBSTITUTE".into())),
-        CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
-    };
+    let new_text = text_arg(&args[2])?;
     
     // Check if we're replacing a specific instance
     if args.len() == 4 {
         let instance_num = match &args[3] {
             CellValue::Number(n) => *n as usize,
-            _ => return Err(EngineError::EvaluationError("SUBSTITUTE's fourth argument must be a number".into())),
+            _ => return Err(EngineError::CellValueError(CellError::InvalidValue)),
         };
         
         if instance_num < 1 {
-            return Err(EngineError::EvaluationError("SUBSTITUTE's instance_num must be at least 1".into()));
+            return Ok(CellValue::Error(CellError::InvalidValue));
         }
         
         // Replace specific instance
@@ -1350,40 +1967,26 @@ fn find(args: &[CellValue]) -> Result<CellValue, EngineError> {
         return Err(EngineError::EvaluationError("FIND requires 2 or 3 arguments: find_text, within_text, [start_num]".into()));
     }
     
-    let find_text = match &args[0] {
-        CellValue::Text(t) => t.clone(),
-        CellValue::Number(n) => n.to_string(),
-        CellValue::Boolean(b) => if *b { "TRUE".to_string() } else { "FALSE".to_string() },
-        CellValue::Blank => "".to_string(),
-        CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot use unevaluated formula in FIND".into())),
-        CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
-    };
+    let find_text = text_arg(&args[0])?;
     
-    let within_text = match &args[1] {
-        CellValue::Text(t) => t.clone(),
-        CellValue::Number(n) => n.to_string(),
-        CellValue::Boolean(b) => if *b { "TRUE".to_string() } else { "FALSE".to_string() },
-        CellValue::Blank => "".to_string(),
-        CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot use unevaluated formula in FIND".into())),
-        CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
-    };
+    let within_text = text_arg(&args[1])?;
     
     let start_num = if args.len() == 3 {
         match &args[2] {
             CellValue::Number(n) => *n as usize,
-            _ => return Err(EngineError::EvaluationError("FIND's third argument must be a number".into())),
+            _ => return Err(EngineError::CellValueError(CellError::InvalidValue)),
         }
     } else {
         1 // Default is to start at the beginning (position 1)
     };
     
     if start_num < 1 {
-        return Err(EngineError::EvaluationError("FIND's start_num must be at least 1".into()));
+        return Ok(CellValue::Error(CellError::InvalidValue));
     }
     
     let within_chars: Vec<char> = within_text.chars().collect();
     if start_num > within_chars.len() {
-        return Err(EngineError::EvaluationError("Start position in FIND is beyond the length of the text".into()));
+        return Ok(CellValue::Error(CellError::InvalidValue));
     }
     
     // Adjust for 1-based indexing
@@ -1395,141 +1998,473 @@ fn find(args: &[CellValue]) -> Result<CellValue, EngineError> {
     // Find the position of find_text in the substring
     match substring.find(&find_text) {
         Some(pos) => Ok(CellValue::Number((start_index + pos + 1) as f64)), // +1 for 1-based indexing
-        None => Err(EngineError::EvaluationError(format!("Cannot find '{}' within '{}'", find_text, within_text).into())),
+        None => Ok(CellValue::Error(CellError::InvalidValue)),
+    }
+}
+
+// SEARCH function - finds one text string within another (case-insensitive, with * and ? wildcards)
+fn search(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(EngineError::EvaluationError("SEARCH requires 2 or 3 arguments: find_text, within_text, [start_num]".into()));
+    }
+    
+    let (find_text, within_text) = match (text_arg(&args[0]), text_arg(&args[1])) {
+        (Ok(find_text), Ok(within_text)) => (find_text, within_text),
+        (Err(e), _) | (_, Err(e)) => return Ok(CellValue::Error(e)),
+    };
+    let start_num = if args.len() == 3 { extract_number(&args[2], "start_num")?.trunc() } else { 1.0 };
+    
+    let pattern = wildcard_pattern(&find_text.to_lowercase());
+    let within_chars: Vec<char> = within_text.to_lowercase().chars().collect();
+    
+    if start_num < 1.0 || start_num > within_chars.len() as f64 + 1.0 {
+        return Ok(CellValue::Error(CellError::InvalidValue));
+    }
+    
+    let start_index = start_num as usize - 1;
+    for index in start_index..=within_chars.len() {
+        if wildcard_prefix_match(&pattern, &within_chars[index..]) {
+            return Ok(CellValue::Number((index + 1) as f64)); // +1 for 1-based indexing
+        }
+    }
+    
+    Ok(CellValue::Error(CellError::InvalidValue))
+}
+
+// Element of a wildcard pattern
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WildcardToken {
+    Char(char), // A literal character
+    One,        // ? - exactly one character
+    Many,       // * - any run of characters, including none
+}
+
+// Split a pattern into wildcard tokens; ~ escapes a following *, ? or ~
+fn wildcard_pattern(pattern: &str) -> Vec<WildcardToken> {
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '~' if matches!(chars.peek(), Some('*') | Some('?') | Some('~')) => {
+                tokens.push(WildcardToken::Char(chars.next().unwrap()));
+            },
+            '*' => tokens.push(WildcardToken::Many),
+            '?' => tokens.push(WildcardToken::One),
+            c => tokens.push(WildcardToken::Char(c)),
+        }
+    }
+    tokens
+}
+
+// Whether a wildcard pattern matches the beginning of text
+fn wildcard_prefix_match(pattern: &[WildcardToken], text: &[char]) -> bool {
+    match pattern.first() {
+        None => true,
+        Some(WildcardToken::Many) => (0..=text.len()).any(|skip| wildcard_prefix_match(&pattern[1..], &text[skip..])),
+        Some(WildcardToken::One) => !text.is_empty() && wildcard_prefix_match(&pattern[1..], &text[1..]),
+        Some(WildcardToken::Char(c)) => text.first() == Some(c) && wildcard_prefix_match(&pattern[1..], &text[1..]),
+    }
+}
+
+// Whether a wildcard pattern matches the whole of text
+fn wildcard_match(pattern: &[WildcardToken], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some(WildcardToken::Many) => (0..=text.len()).any(|skip| wildcard_match(&pattern[1..], &text[skip..])),
+        Some(WildcardToken::One) => !text.is_empty() && wildcard_match(&pattern[1..], &text[1..]),
+        Some(WildcardToken::Char(c)) => text.first() == Some(c) && wildcard_match(&pattern[1..], &text[1..]),
+    }
+}
+
+// REPLACE function - replaces part of a text string, by position, with different text
+fn replace(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() != 4 {
+        return Err(EngineError::EvaluationError("REPLACE requires exactly 4 arguments: old_text, start_num, num_chars, new_text".into()));
+    }
+    
+    let (old_text, new_text) = match (text_arg(&args[0]), text_arg(&args[3])) {
+        (Ok(old_text), Ok(new_text)) => (old_text, new_text),
+        (Err(e), _) | (_, Err(e)) => return Ok(CellValue::Error(e)),
+    };
+    let start_num = extract_number(&args[1], "start_num")?.trunc();
+    let num_chars = extract_number(&args[2], "num_chars")?.trunc();
+    
+    if start_num < 1.0 || num_chars < 0.0 {
+        return Ok(CellValue::Error(CellError::InvalidValue));
     }
+    
+    // Positions past the end of the text append to it
+    let old_chars: Vec<char> = old_text.chars().collect();
+    let start = (start_num as usize - 1).min(old_chars.len());
+    let end = start.saturating_add(num_chars as usize).min(old_chars.len());
+    
+    let mut result: String = old_chars[..start].iter().collect();
+    result.push_str(&new_text);
+    result.extend(&old_chars[end..]);
+    
+    Ok(CellValue::Text(result))
 }
 
-// TEXT function - formats a number and converts it to text
-fn text_format(args: &[CellValue]) -> Result<CellValue, EngineError> {
+// TEXT function - formats a value through a number format code in the workbook's locale
+fn text_format(evaluator: &Evaluator, args: &[AstNode], context: &mut EvaluationContext) -> Result<CellValue, EngineError> {
+    let args = evaluate_plain_args(evaluator, args, context)?;
+    
     if args.len() != 2 {
         return Err(EngineError::EvaluationError("TEXT requires exactly 2 arguments: value and format_text".into()));
     }
     
+    let format_text = match &args[1] {
+        CellValue::Text(t) => t.clone(),
+        CellValue::Error(e) => return Ok(CellValue::Error(e.clone())),
+        _ => return Err(EngineError::CellValueError(CellError::InvalidValue)),
+    };
+    
+    // Numeric text is read with the locale's separators, like a typed entry
+    let locale = context.workbook.locale();
     let value = match &args[0] {
-        CellValue::Number(n) => *n,
+        CellValue::Number(n) | CellValue::Date(n) => *n,
         CellValue::Boolean(b) => if *b { 1.0 } else { 0.0 },
-        _ => return Err(EngineError::EvaluationError("TEXT's first argument must be a number".into())),
+        CellValue::Blank => 0.0,
+        CellValue::Text(t) => match parse_number_text(t, locale.decimal_separator, locale.thousands_separator) {
+            Some(n) => n,
+            None => return Ok(CellValue::Text(format::format_text(t, &format_text))),
+        },
+        CellValue::Error(e) => return Ok(CellValue::Error(e.clone())),
+        _ => return Err(EngineError::CellValueError(CellError::InvalidValue)),
     };
     
-    let format_text = match &args[1] {
-        CellValue::Text(t) => t.clone(),
-        _ => return Err(EngineError::EvaluationError("TEXT's second argument must be a text string".into())),
-    };
-    
-    // This is a simplified implementation of TEXT
-    // In a real implementation, you would parse the format_text and apply the formatting rules
-    
-    // Basic implementation for common formats
-    if format_text == "0" {
-        return Ok(CellValue::Text(format!("{:.0}", value)));
-    } else if format_text == "0.00" {
-        return Ok(CellValue::Text(format!("{:.2}", value)));
-    } else if format_text == "#,##0" {
-        let formatted = format!("{:.0}", value)
-            .chars()
-            .rev()
-            .collect::<Vec<_>>()
-            .chunks(3)
-            .map(|chunk| chunk.iter().collect::<String>())
-            .collect::<Vec<_>>()
-            .join(",")
-            .chars()
-            .rev()
-            .collect::<String>();
-        return Ok(CellValue::Text(formatted));
-    } else if format_text == "#,##0.00" {
-        let mut parts = format!("{:.2}", value).split('.').collect::<Vec<_>>();
-        if parts.len() == 2 {
-            let whole_part = parts[0]
-                .chars()
-                .rev()
-                .collect::<Vec<_>>()
-                .chunks(3)
-                .map(|chunk| chunk.iter().collect::<String>())
-                .collect::<Vec<_>>()
-                .join(",")
-                .chars()
-                .rev()
-                .collect::<String>();
-            return Ok(CellValue::Text(format!("{}.{}", whole_part, parts[1])));
-        }
-    } else if format_text == "0%" {
-        return Ok(CellValue::Text(format!("{:.0}%", value * 100.0)));
-    } else if format_text == "0.00%" {
-        return Ok(CellValue::Text(format!("{:.2}%", value * 100.0)));
-    } else if format_text == "$#,##0.00" {
-        let mut parts = format!("{:.2}", value).split('.').collect::<Vec<_>>();
-        if parts.len() == 2 {
-            let whole_part = parts[0]
-                .chars()
-                .rev()
-                .collect::<Vec<_>>()
-                .chunks(3)
-                .map(|chunk| chunk.iter().collect::<String>())
-                .collect::<Vec<_>>()
-                .join(",")
-                .chars()
-                .rev()
-                .collect::<String>();
-            return Ok(CellValue::Text(format!("${}.{}", whole_part, parts[1])));
-        }
-    }
-    
-    // Default fallback for unimplemented formats
-    Ok(CellValue::Text(value.to_string()))
+    match format::format_number(value, &format_text, locale, context.workbook.date_system()) {
+        Some(text) => Ok(CellValue::Text(text)),
+        None => Ok(CellValue::Error(CellError::InvalidValue)),
+    }
+}
+
+// VALUE function - converts text that looks like a number (currency, percent, thousands separators) to a number
+fn value(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() != 1 {
+        return Err(EngineError::EvaluationError("VALUE requires exactly one argument".into()));
+    }
+    
+    match &args[0] {
+        CellValue::Number(n) | CellValue::Date(n) => Ok(CellValue::Number(*n)),
+        CellValue::Blank => Ok(CellValue::Number(0.0)),
+        CellValue::Text(t) => match parse_number_text(t, '.', ',') {
+            Some(n) => Ok(CellValue::Number(n)),
+            None => Ok(CellValue::Error(CellError::InvalidValue)),
+        },
+        CellValue::Error(e) => Ok(CellValue::Error(e.clone())),
+        _ => Ok(CellValue::Error(CellError::InvalidValue)),
+    }
+}
+
+// NUMBERVALUE function - converts text to a number using explicit decimal and group separators
+fn numbervalue(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.is_empty() || args.len() > 3 {
+        return Err(EngineError::EvaluationError("NUMBERVALUE requires 1-3 arguments: text, [decimal_separator], [group_separator]".into()));
+    }
+    
+    let text = match text_arg(&args[0]) {
+        Ok(text) => text,
+        Err(e) => return Ok(CellValue::Error(e)),
+    };
+    
+    // Only the first character of each separator is used. Without a group separator,
+    // the one the decimal separator doesn't use is taken.
+    let separator = |index: usize, default: char| -> Result<char, CellError> {
+        match args.get(index) {
+            Some(value) => text_arg(value)?.chars().next().ok_or(CellError::InvalidValue),
+            None => Ok(default),
+        }
+    };
+    let decimal_separator = separator(1, '.');
+    let default_group = if decimal_separator == Ok(',') { '.' } else { ',' };
+    let (decimal_separator, group_separator) = match (decimal_separator, separator(2, default_group)) {
+        (Ok(decimal), Ok(group)) if decimal != group => (decimal, group),
+        (Err(e), _) | (_, Err(e)) => return Ok(CellValue::Error(e)),
+        _ => return Ok(CellValue::Error(CellError::InvalidValue)),
+    };
+    
+    // Unlike VALUE, spaces are ignored anywhere and empty text is zero
+    let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if text.is_empty() {
+        return Ok(CellValue::Number(0.0));
+    }
+    
+    match parse_number_text(&text, decimal_separator, group_separator) {
+        Some(n) => Ok(CellValue::Number(n)),
+        None => Ok(CellValue::Error(CellError::InvalidValue)),
+    }
+}
+
+// Helper function to parse formatted number text such as "$1,234.50", "(12)" or "12%"
+fn parse_number_text(text: &str, decimal_separator: char, group_separator: char) -> Option<f64> {
+    let mut text = text.trim();
+    
+    // Accounting-style parentheses mean a negative number
+    let mut negative = false;
+    if let Some(inner) = text.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+        negative = true;
+        text = inner.trim();
+    }
+    
+    if let Some(rest) = text.strip_prefix('-') {
+        negative = !negative;
+        text = rest.trim_start();
+    } else if let Some(rest) = text.strip_prefix('+') {
+        text = rest.trim_start();
+    }
+    
+    text = text.strip_prefix('$').unwrap_or(text).trim_start();
+    
+    // Each trailing percent sign divides by 100
+    let mut scale = 1.0;
+    while let Some(rest) = text.strip_suffix('%') {
+        scale /= 100.0;
+        text = rest.trim_end();
+    }
+    
+    if text.matches(decimal_separator).count() > 1 {
+        return None;
+    }
+    
+    // Group separators may only appear before the decimal separator
+    let integer_part = text.split(decimal_separator).next().unwrap_or("");
+    if text.len() > integer_part.len() && text[integer_part.len()..].contains(group_separator) {
+        return None;
+    }
+    
+    let normalized: String = text.chars()
+        .filter(|c| *c != group_separator)
+        .map(|c| if c == decimal_separator { '.' } else { c })
+        .collect();
+    
+    // Rust also accepts "inf"/"NaN", which are not numbers in a spreadsheet
+    if !normalized.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        return None;
+    }
+    
+    let number = normalized.parse::<f64>().ok().filter(|n| n.is_finite())?;
+    Some(if negative { -number * scale } else { number * scale })
+}
+
+// T function - returns the text if the value is text, otherwise empty text
+fn t(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() != 1 {
+        return Err(EngineError::EvaluationError("T requires exactly one argument".into()));
+    }
+    
+    match &args[0] {
+        CellValue::Text(text) => Ok(CellValue::Text(text.clone())),
+        CellValue::Error(e) => Ok(CellValue::Error(e.clone())),
+        _ => Ok(CellValue::Text(String::new())),
+    }
+}
+
+// Longest text a cell can hold
+const MAX_TEXT_LENGTH: usize = 32_767;
+
+// Helper function to convert a text function's argument to a string
+fn text_arg(value: &CellValue) -> Result<String, CellError> {
+    match value {
+        CellValue::Error(e) => Err(e.clone()),
+        CellValue::Formula(_) | CellValue::Array(_) => Err(CellError::InvalidValue),
+        other => Ok(other.to_string()),
+    }
+}
+
+// PROPER function - capitalizes the first letter of each word and lowercases the rest
+fn proper(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() != 1 {
+        return Err(EngineError::EvaluationError("PROPER requires exactly one argument".into()));
+    }
+    
+    let text = match text_arg(&args[0]) {
+        Ok(text) => text,
+        Err(e) => return Ok(CellValue::Error(e)),
+    };
+    
+    // A word starts at any letter that doesn't follow another letter
+    let mut result = String::with_capacity(text.len());
+    let mut previous_is_letter = false;
+    for c in text.chars() {
+        if c.is_alphabetic() {
+            if previous_is_letter {
+                result.extend(c.to_lowercase());
+            } else {
+                result.extend(c.to_uppercase());
+            }
+            previous_is_letter = true;
+        } else {
+            result.push(c);
+            previous_is_letter = false;
+        }
+    }
+    
+    Ok(CellValue::Text(result))
+}
+
+// CLEAN function - removes non-printable characters (codes 0-31) from text
+fn clean(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() != 1 {
+        return Err(EngineError::EvaluationError("CLEAN requires exactly one argument".into()));
+    }
+    
+    match text_arg(&args[0]) {
+        Ok(text) => Ok(CellValue::Text(text.chars().filter(|c| (*c as u32) >= 32).collect())),
+        Err(e) => Ok(CellValue::Error(e)),
+    }
+}
+
+// REPT function - repeats text a given number of times
+fn rept(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() != 2 {
+        return Err(EngineError::EvaluationError("REPT requires exactly 2 arguments: text, number_times".into()));
+    }
+    
+    let text = match text_arg(&args[0]) {
+        Ok(text) => text,
+        Err(e) => return Ok(CellValue::Error(e)),
+    };
+    let times = extract_number(&args[1], "number_times")?.trunc();
+    
+    // Reject negative counts and results longer than a cell can hold before allocating
+    if times < 0.0 || text.chars().count() as f64 * times > MAX_TEXT_LENGTH as f64 {
+        return Ok(CellValue::Error(CellError::InvalidValue));
+    }
+    
+    Ok(CellValue::Text(text.repeat(times as usize)))
+}
+
+// CHAR function - returns the character for a code (1-255)
+fn char_func(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() != 1 {
+        return Err(EngineError::EvaluationError("CHAR requires exactly one argument".into()));
+    }
+    
+    let code = extract_number(&args[0], "number")?.trunc();
+    if !(1.0..=255.0).contains(&code) {
+        return Ok(CellValue::Error(CellError::InvalidValue));
+    }
+    
+    Ok(CellValue::Text(char::from(code as u8).to_string()))
+}
+
+// CODE function - returns the code (0-255) of the first character of text
+fn code(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() != 1 {
+        return Err(EngineError::EvaluationError("CODE requires exactly one argument".into()));
+    }
+    
+    let text = match text_arg(&args[0]) {
+        Ok(text) => text,
+        Err(e) => return Ok(CellValue::Error(e)),
+    };
+    
+    match text.chars().next() {
+        // Characters outside the single-byte range have no code and show as '?'
+        Some(c) => Ok(CellValue::Number(if (c as u32) <= 255 { c as u32 } else { '?' as u32 } as f64)),
+        None => Ok(CellValue::Error(CellError::InvalidValue)),
+    }
+}
+
+// UNICHAR function - returns the Unicode character for a code point
+fn unichar(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() != 1 {
+        return Err(EngineError::EvaluationError("UNICHAR requires exactly one argument".into()));
+    }
+    
+    let code = extract_number(&args[0], "number")?.trunc();
+    if code < 1.0 || code > char::MAX as u32 as f64 {
+        return Ok(CellValue::Error(CellError::InvalidValue));
+    }
+    
+    // Surrogate code points aren't characters
+    match char::from_u32(code as u32) {
+        Some(c) => Ok(CellValue::Text(c.to_string())),
+        None => Ok(CellValue::Error(CellError::NotAvailable)),
+    }
+}
+
+// UNICODE function - returns the Unicode code point of the first character of text
+fn unicode(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() != 1 {
+        return Err(EngineError::EvaluationError("UNICODE requires exactly one argument".into()));
+    }
+    
+    let text = match text_arg(&args[0]) {
+        Ok(text) => text,
+        Err(e) => return Ok(CellValue::Error(e)),
+    };
+    
+    match text.chars().next() {
+        Some(c) => Ok(CellValue::Number(c as u32 as f64)),
+        None => Ok(CellValue::Error(CellError::InvalidValue)),
+    }
 }
 
 // ===== DATE FUNCTIONS =====
 
 // TODAY function - returns the current date
-fn today(_args: &[CellValue]) -> Result<CellValue, EngineError> {
-    // In a real implementation, this would return the current date as a serial number
-    // For now, we'll return the number of days since the Excel epoch (January 1, 1900)
-    // This is simplified - in a real implementation, we would use a proper date library
-    
-    // 44000 represents a date in 2020 (approximate value)
-    Ok(CellValue::Number(44000.0))
+fn today(_evaluator: &Evaluator, _args: &[AstNode], context: &mut EvaluationContext) -> Result<CellValue, EngineError> {
+    let system = context.workbook.date_system();
+    let today = chrono::Local::now().date_naive();
+    Ok(CellValue::Date(date_to_serial(today, system)))
 }
 
 // NOW function - returns the current date and time
-fn now(_args: &[CellValue]) -> Result<CellValue, EngineError> {
-    // Similar to TODAY but includes time as a fractional part of the day
-    // 44000.5 would represent noon on that day
-    Ok(CellValue::Number(44000.5))
+fn now(_evaluator: &Evaluator, _args: &[AstNode], context: &mut EvaluationContext) -> Result<CellValue, EngineError> {
+    let system = context.workbook.date_system();
+    // The time of day is the fractional part of the serial number
+    let now = chrono::Local::now().naive_local();
+    Ok(CellValue::Date(datetime_to_serial(now, system)))
 }
 
 // DATE function - returns the serial number of a particular date
-fn date(args: &[CellValue]) -> Result<CellValue, EngineError> {
+fn date(evaluator: &Evaluator, args: &[AstNode], context: &mut EvaluationContext) -> Result<CellValue, EngineError> {
+    let args = evaluate_plain_args(evaluator, args, context)?;
+    let system = context.workbook.date_system();
+    
     if args.len() != 3 {
         return Err(EngineError::EvaluationError("DATE requires exactly 3 arguments: year, month, day".into()));
     }
     
     let year = match &args[0] {
-        CellValue::Number(n) => *n as i32,
-        _ => return Err(EngineError::EvaluationError("DATE's year argument must be a number".into())),
+        CellValue::Number(n) => n.trunc() as i64,
+        _ => return Ok(CellValue::Error(CellError::InvalidValue)),
     };
     
     let month = match &args[1] {
-        CellValue::Number(n) => *n as i32,
-        _ => return Err(EngineError::EvaluationError("DATE's month argument must be a number".into())),
+        CellValue::Number(n) => n.trunc() as i64,
+        _ => return Ok(CellValue::Error(CellError::InvalidValue)),
     };
     
     let day = match &args[2] {
-        CellValue::Number(n) => *n as i32,
-        _ => return Err(EngineError::EvaluationError("DATE's day argument must be a number".into())),
+        CellValue::Number(n) => n.trunc() as i64,
+        _ => return Ok(CellValue::Error(CellError::InvalidValue)),
     };
     
-    // This is a simplified implementation
-    // In a real implementation, we would convert the year, month, and day to an Excel serial number
-    // We would also handle overflow/underflow of month and day values
+    // Years 0-1899 are offset from 1900, as in Excel
+    let year = if (0..1900).contains(&year) { year + 1900 } else { year };
+    if !(1900..=9999).contains(&year) {
+        return Ok(CellValue::Error(CellError::InvalidNumber));
+    }
+    
+    // Months and days outside their normal ranges roll over into neighbouring months/years
+    let result = chrono::NaiveDate::from_ymd_opt(year as i32, 1, 1)
+        .and_then(|first| add_months(first, month - 1))
+        .and_then(|date| date.checked_add_signed(chrono::Duration::try_days(day - 1)?));
     
-    // For now, we'll just return a placeholder value
-    // This should be replaced with actual calculation logic
-    Ok(CellValue::Number(44000.0))
+    match result {
+        Some(date) if date_to_serial(date, system) >= 0.0 => Ok(CellValue::Date(date_to_serial(date, system))),
+        _ => Ok(CellValue::Error(CellError::InvalidNumber)),
+    }
 }
 
 // YEAR function - returns the year component of a date
-fn year(args: &[CellValue]) -> Result<CellValue, EngineError> {
+fn year(evaluator: &Evaluator, args: &[AstNode], context: &mut EvaluationContext) -> Result<CellValue, EngineError> {
+    let args = evaluate_plain_args(evaluator, args, context)?;
+    let system = context.workbook.date_system();
+    
     if args.len() != 1 {
         return Err(EngineError::EvaluationError("YEAR requires exactly 1 argument: serial_number".into()));
     }
@@ -1539,15 +2474,17 @@ fn year(args: &[CellValue]) -> Result<CellValue, EngineError> {
         _ => return Err(EngineError::EvaluationError("YEAR's argument must be a date serial number".into())),
     };
     
-    // This is a simplified implementation that doesn't do actual date calculation
-    // In a real implementation, we would convert the serial number to a date and extract the year
-    
-    // For demonstration, we'll just return 2020 for any input
-    Ok(CellValue::Number(2020.0))
+    match serial_to_date(serial_number, system) {
+        Some(date) => Ok(CellValue::Number(date.year() as f64)),
+        None => Ok(CellValue::Error(CellError::InvalidNumber)),
+    }
 }
 
 // MONTH function - returns the month component of a date
-fn month(args: &[CellValue]) -> Result<CellValue, EngineError> {
+fn month(evaluator: &Evaluator, args: &[AstNode], context: &mut EvaluationContext) -> Result<CellValue, EngineError> {
+    let args = evaluate_plain_args(evaluator, args, context)?;
+    let system = context.workbook.date_system();
+    
     if args.len() != 1 {
         return Err(EngineError::EvaluationError("MONTH requires exactly 1 argument: serial_number".into()));
     }
@@ -1557,13 +2494,17 @@ fn month(args: &[CellValue]) -> Result<CellValue, EngineError> {
         _ => return Err(EngineError::EvaluationError("MONTH's argument must be a date serial number".into())),
     };
     
-    // This is a simplified implementation
-    // For demonstration, we'll just return 1 (January) for any input
-    Ok(CellValue::Number(1.0))
+    match serial_to_date(serial_number, system) {
+        Some(date) => Ok(CellValue::Number(date.month() as f64)),
+        None => Ok(CellValue::Error(CellError::InvalidNumber)),
+    }
 }
 
 // DAY function - returns the day component of a date
-fn day(args: &[CellValue]) -> Result<CellValue, EngineError> {
+fn day(evaluator: &Evaluator, args: &[AstNode], context: &mut EvaluationContext) -> Result<CellValue, EngineError> {
+    let args = evaluate_plain_args(evaluator, args, context)?;
+    let system = context.workbook.date_system();
+    
     if args.len() != 1 {
         return Err(EngineError::EvaluationError("DAY requires exactly 1 argument: serial_number".into()));
     }
@@ -1573,13 +2514,17 @@ fn day(args: &[CellValue]) -> Result<CellValue, EngineError> {
         _ => return Err(EngineError::EvaluationError("DAY's argument must be a date serial number".into())),
     };
     
-    // This is a simplified implementation
-    // For demonstration, we'll just return 1 for any input
-    Ok(CellValue::Number(1.0))
+    match serial_to_date(serial_number, system) {
+        Some(date) => Ok(CellValue::Number(date.day() as f64)),
+        None => Ok(CellValue::Error(CellError::InvalidNumber)),
+    }
 }
 
 // WEEKDAY function - returns the day of the week as a number
-fn weekday(args: &[CellValue]) -> Result<CellValue, EngineError> {
+fn weekday(evaluator: &Evaluator, args: &[AstNode], context: &mut EvaluationContext) -> Result<CellValue, EngineError> {
+    let args = evaluate_plain_args(evaluator, args, context)?;
+    let system = context.workbook.date_system();
+    
     if args.len() < 1 || args.len() > 2 {
         return Err(EngineError::EvaluationError("WEEKDAY requires 1 or 2 arguments: serial_number, [return_type]".into()));
     }
@@ -1598,9 +2543,31 @@ fn weekday(args: &[CellValue]) -> Result<CellValue, EngineError> {
         1 // Default return_type is 1 (1 = Sunday, 2 = Monday, ..., 7 = Saturday)
     };
     
-    // This is a simplified implementation
-    // For demonstration, we'll just return 1 (Sunday) for any input
-    Ok(CellValue::Number(1.0))
+    let date = match serial_to_date(serial_number, system) {
+        Some(date) => date,
+        None => return Ok(CellValue::Error(CellError::InvalidNumber)),
+    };
+    
+    // Days from Sunday (0) and from Monday (0)
+    let from_sunday = date.weekday().num_days_from_sunday() as f64;
+    let from_monday = date.weekday().num_days_from_monday() as f64;
+    
+    match return_type {
+        1 | 17 => Ok(CellValue::Number(from_sunday + 1.0)), // 1 = Sunday ... 7 = Saturday
+        2 | 11 => Ok(CellValue::Number(from_monday + 1.0)), // 1 = Monday ... 7 = Sunday
+        3 => Ok(CellValue::Number(from_monday)),            // 0 = Monday ... 6 = Sunday
+        12..=16 => {
+            // Weeks starting Tuesday (12) through Saturday (16)
+            let start = (return_type - 11) as f64;
+            Ok(CellValue::Number((from_monday - start).rem_euclid(7.0) + 1.0))
+        },
+        _ => Ok(CellValue::Error(CellError::InvalidNumber)),
+    }
+}
+
+// Evaluate the arguments of a contextual function into the values a plain function would receive
+fn evaluate_plain_args(evaluator: &Evaluator, args: &[AstNode], context: &mut EvaluationContext) -> Result<Vec<CellValue>, EngineError> {
+    Ok(evaluator.evaluate_args(args, context)?.iter().map(CellValue::date_as_number).collect())
 }
 
 // DATEDIF function - calculates the difference between two dates in various units
@@ -1650,10 +2617,7 @@ fn vlookup(args: &[CellValue]) -> Result<CellValue, EngineError> {
         ));
     }
     
-    // In a real implementation, we would need to handle ranges and cell references
-    // This is a very simplified version that just returns an error for now
-    
-    Err(EngineError::EvaluationError("VLOOKUP requires table_array to be a cell range reference".into()))
+    table_lookup(args, LookupAxis::Column)
 }
 
 // HLOOKUP function - searches for a value in the first row of a table and returns a value in the same column
@@ -1664,16 +2628,108 @@ fn hlookup(args: &[CellValue]) -> Result<CellValue, EngineError> {
         ));
     }
     
-    // In a real implementation, we would need to handle ranges and cell references
-    // This is a very simplified version that just returns an error for now
-    
-    Err(EngineError::EvaluationError("HLOOKUP requires table_array to be a cell range reference".into()))
+    table_lookup(args, LookupAxis::Row)
 }
 
-// INDEX function - returns a value from a table based on row and column numbers
-fn index(args: &[CellValue]) -> Result<CellValue, EngineError> {
-    if args.len() < 2 || args.len() > 3 {
-        return Err(EngineError::EvaluationError(
+// Which edge of a table holds the keys: VLOOKUP searches down the first column,
+// HLOOKUP across the first row
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LookupAxis {
+    Column,
+    Row,
+}
+
+// Shared body of VLOOKUP and HLOOKUP: find the lookup value among the table's keys and
+// return the entry `index` lines into the table from the matching key
+fn table_lookup(args: &[CellValue], axis: LookupAxis) -> Result<CellValue, EngineError> {
+    if let CellValue::Error(e) = &args[0] {
+        return Ok(CellValue::Error(e.clone()));
+    }
+    
+    let table = array_rows(&args[1]);
+    let index = extract_number(&args[2], "index")?.trunc();
+    let approximate = match args.get(3) {
+        None => true,
+        Some(CellValue::Boolean(b)) => *b,
+        Some(CellValue::Number(n)) => *n != 0.0,
+        Some(CellValue::Blank) => false,
+        Some(CellValue::Error(e)) => return Ok(CellValue::Error(e.clone())),
+        Some(_) => return Ok(CellValue::Error(CellError::InvalidValue)),
+    };
+    
+    // Keys run along the searched edge; `depth` is how many lines the index can reach
+    let width = table.first().map_or(0, |row| row.len());
+    let (keys, depth): (Vec<&CellValue>, usize) = match axis {
+        LookupAxis::Column => (table.iter().filter_map(|row| row.first()).collect(), width),
+        LookupAxis::Row => (table.first().map_or_else(Vec::new, |row| row.iter().collect()), table.len()),
+    };
+    
+    if index < 1.0 {
+        return Ok(CellValue::Error(CellError::InvalidValue));
+    }
+    if index > depth as f64 {
+        return Ok(CellValue::Error(CellError::InvalidReference));
+    }
+    
+    let line = index as usize - 1;
+    match lookup_position(&args[0], &keys, approximate) {
+        Some(position) => {
+            let (row, col) = match axis {
+                LookupAxis::Column => (position, line),
+                LookupAxis::Row => (line, position),
+            };
+            Ok(table.get(row).and_then(|r| r.get(col)).cloned().unwrap_or(CellValue::Blank))
+        },
+        None => Ok(CellValue::Error(CellError::NotAvailable)),
+    }
+}
+
+// Position of a lookup value among keys. Exact lookups match text case-insensitively
+// with wildcards; approximate lookups assume ascending keys and return the last key
+// not greater than the lookup value.
+fn lookup_position(lookup_value: &CellValue, keys: &[&CellValue], approximate: bool) -> Option<usize> {
+    if !approximate {
+        return keys.iter().position(|key| lookup_matches(lookup_value, key));
+    }
+    
+    let mut found = None;
+    for (position, key) in keys.iter().enumerate() {
+        match compare_lookup_values(key, lookup_value) {
+            Some(Ordering::Greater) => break,
+            Some(_) => found = Some(position),
+            None => {}, // Keys of another type are skipped
+        }
+    }
+    found
+}
+
+// Whether a key is an exact match for a lookup value (text may use * and ? wildcards)
+fn lookup_matches(lookup_value: &CellValue, key: &CellValue) -> bool {
+    match (lookup_value, key) {
+        (CellValue::Text(pattern), CellValue::Text(text)) => {
+            let pattern = wildcard_pattern(&pattern.to_lowercase());
+            let text: Vec<char> = text.to_lowercase().chars().collect();
+            wildcard_match(&pattern, &text)
+        },
+        _ => compare_lookup_values(lookup_value, key) == Some(Ordering::Equal),
+    }
+}
+
+// Order two lookup values of the same kind: numbers, text (case-insensitive) or
+// booleans. Values of different kinds are not comparable.
+fn compare_lookup_values(a: &CellValue, b: &CellValue) -> Option<Ordering> {
+    match (a, b) {
+        (CellValue::Number(x) | CellValue::Date(x), CellValue::Number(y) | CellValue::Date(y)) => x.partial_cmp(y),
+        (CellValue::Text(x), CellValue::Text(y)) => Some(x.to_lowercase().cmp(&y.to_lowercase())),
+        (CellValue::Boolean(x), CellValue::Boolean(y)) => Some(x.cmp(y)),
+        _ => None,
+    }
+}
+
+// INDEX function - returns a value from a table based on row and column numbers
+fn index(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(EngineError::EvaluationError(
             "INDEX requires 2 or 3 arguments: array, row_num, [column_num]".into()
         ));
     }
@@ -1692,10 +2748,38 @@ fn match_func(args: &[CellValue]) -> Result<CellValue, EngineError> {
         ));
     }
     
-    // In a real implementation, we would need to handle ranges and cell references
-    // This is a very simplified version that just returns an error for now
+    if let CellValue::Error(e) = &args[0] {
+        return Ok(CellValue::Error(e.clone()));
+    }
+    
+    // Only a single row or column can be searched
+    let keys = match lookup_vector(&args[1]) {
+        Some((keys, _)) => keys,
+        None => return Ok(CellValue::Error(CellError::NotAvailable)),
+    };
+    
+    // match_type: 1 largest key not greater (ascending keys), 0 exact, -1 smallest
+    // key not less (descending keys)
+    let position = match lookup_mode(args.get(2), 1)?.signum() {
+        1 => lookup_position(&args[0], &keys, true),
+        0 => lookup_position(&args[0], &keys, false),
+        _ => {
+            let mut found = None;
+            for (position, key) in keys.iter().enumerate() {
+                match compare_lookup_values(key, &args[0]) {
+                    Some(Ordering::Less) => break,
+                    Some(_) => found = Some(position),
+                    None => {},
+                }
+            }
+            found
+        },
+    };
     
-    Err(EngineError::EvaluationError("MATCH requires lookup_array to be a cell range reference".into()))
+    match position {
+        Some(position) => Ok(CellValue::Number((position + 1) as f64)),
+        None => Ok(CellValue::Error(CellError::NotAvailable)),
+    }
 }
 
 // CHOOSE function - uses an index to return a value from a list of values
@@ -1704,17 +2788,20 @@ fn choose(args: &[CellValue]) -> Result<CellValue, EngineError> {
         return Err(EngineError::EvaluationError("CHOOSE requires at least 2 arguments: index_num, value1, [value2], ...".into()));
     }
     
-    let index_num = match &args[0] {
-        CellValue::Number(n) => *n as usize,
-        _ => return Err(EngineError::EvaluationError("CHOOSE's first argument must be a number".into())),
+    // The index is truncated like Excel does, so 2.9 picks the second value
+    let index_num = match extract_number(&args[0], "index_num") {
+        Ok(n) => n.trunc(),
+        Err(EngineError::CellValueError(e)) => return Ok(CellValue::Error(e)),
+        Err(_) => return Ok(CellValue::Error(CellError::InvalidValue)),
     };
     
-    if index_num < 1 || index_num >= args.len() {
-        return Err(EngineError::EvaluationError(format!("CHOOSE index {} out of range", index_num).into()));
+    if index_num < 1.0 || index_num >= args.len() as f64 {
+        return Ok(CellValue::Error(CellError::InvalidValue));
     }
     
-    // Return the chosen value
-    Ok(args[index_num].clone())}
+    // A chosen range is returned whole as an array, like FILTER or SEQUENCE results
+    Ok(args[index_num as usize].clone())
+}
 
 // ===== INFORMATION FUNCTIONS =====
 
@@ -1724,17 +2811,27 @@ fn is_na(args: &[CellValue]) -> Result<CellValue, EngineError> {
         return Err(EngineError::EvaluationError("ISNA requires exactly 1 argument".into()));
     }
     
-    // In a real implementation, we would check if the value is specifically the #N/A error
-    // For now, we'll just check if it's any error and assume it's #N/A
-    match &args[0] {
-        CellValue::Error(_) => Ok(CellValue::Boolean(true)),
-        _ => Ok(CellValue::Boolean(false)),
-    }
+    Ok(CellValue::Boolean(matches!(&args[0], CellValue::Error(CellError::NotAvailable))))
 }
 
 // NA function - returns the #N/A error value
 fn na(_args: &[CellValue]) -> Result<CellValue, EngineError> {
-    Ok(CellValue::Error("#N/A".to_string()))
+    Ok(CellValue::Error(CellError::NotAvailable))
+}
+
+// N function - converts a value to a number (TRUE is 1, text and other values are 0)
+fn n(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() != 1 {
+        return Err(EngineError::EvaluationError("N requires exactly 1 argument".into()));
+    }
+    
+    // Dates already arrive as their serial numbers
+    match &args[0] {
+        CellValue::Number(n) | CellValue::Date(n) => Ok(CellValue::Number(*n)),
+        CellValue::Boolean(b) => Ok(CellValue::Number(if *b { 1.0 } else { 0.0 })),
+        CellValue::Error(e) => Ok(CellValue::Error(e.clone())),
+        _ => Ok(CellValue::Number(0.0)),
+    }
 }
 
 // ISERR function - checks if a value is any error except #N/A
@@ -1743,9 +2840,8 @@ fn is_err(args: &[CellValue]) -> Result<CellValue, EngineError> {
         return Err(EngineError::EvaluationError("ISERR requires exactly 1 argument".into()));
     }
     
-    // In a real implementation, we would check if the value is any error except #N/A
-    // For now, we'll just assume all errors are covered by ISERR (which isn't strictly correct)
     match &args[0] {
+        CellValue::Error(CellError::NotAvailable) => Ok(CellValue::Boolean(false)),
         CellValue::Error(_) => Ok(CellValue::Boolean(true)),
         _ => Ok(CellValue::Boolean(false)),
     }
@@ -1759,17 +2855,15 @@ fn error_type(args: &[CellValue]) -> Result<CellValue, EngineError> {
     
     match &args[0] {
         CellValue::Error(e) => {
-            // In a real implementation, we would return different numbers for different error types
-            // 1 = #NULL!, 2 = #DIV/0!, 3 = #VALUE!, 4 = #REF!, 5 = #NAME?, 6 = #NUM!, 7 = #N/A, etc.
-            match e.as_str() {
-                "#NULL!" => Ok(CellValue::Number(1.0)),
-                "#DIV/0!" => Ok(CellValue::Number(2.0)),
-                "#VALUE!" => Ok(CellValue::Number(3.0)),
-                "#REF!" => Ok(CellValue::Number(4.0)),
-                "#NAME?" => Ok(CellValue::Number(5.0)),
-                "#NUM!" => Ok(CellValue::Number(6.0)),
-                "#N/A" => Ok(CellValue::Number(7.0)),
-                _ => Ok(CellValue::Number(0.0)), // Unknown error type
+            // 1 = #NULL!, 2 = #DIV/0!, 3 = #VALUE!, 4 = #REF!, 5 = #NAME?, 6 = #NUM!, 7 = #N/A
+            match e {
+                CellError::Null => Ok(CellValue::Number(1.0)),
+                CellError::DivisionByZero => Ok(CellValue::Number(2.0)),
+                CellError::InvalidValue => Ok(CellValue::Number(3.0)),
+                CellError::InvalidReference => Ok(CellValue::Number(4.0)),
+                CellError::NameNotFound => Ok(CellValue::Number(5.0)),
+                CellError::InvalidNumber => Ok(CellValue::Number(6.0)),
+                CellError::NotAvailable => Ok(CellValue::Number(7.0)),
             }
         },
         _ => Err(EngineError::EvaluationError("ERROR.TYPE requires an error value as its argument".into())),
@@ -1788,163 +2882,230 @@ fn is_text(args: &[CellValue]) -> Result<CellValue, EngineError> {
     }
 }
 
-// ===== ENGINEERING FUNCTIONS =====
-
-// BIN2DEC function - converts a binary number to decimal
-fn bin2dec(args: &[CellValue]) -> Result<CellValue, EngineError> {
+// ISLOGICAL function - checks if a value is a logical value
+fn is_logical(args: &[CellValue]) -> Result<CellValue, EngineError> {
     if args.len() != 1 {
-        return Err(EngineError::EvaluationError("BIN2DEC requires exactly 1 argument".into()));
-    }
-    
-    let binary = match &args[0] {
-        CellValue::Text(t) => t.clone(),
-        CellValue::Number(n) => n.to_string(),
-        _ => return Err(EngineError::EvaluationError("BIN2DEC argument must be a binary number represented as text or number".into())),
-    };
-    
-    // Validate binary string (only 0s and 1s)
-    if !binary.chars().all(|c| c == '0' || c == '1') {
-        return Err(EngineError::EvaluationError("BIN2DEC argument must contain only 0s and 1s".into()));
-    }
-    
-    // Limit to 10 bits (Excel limit for BIN2DEC)
-    if binary.len() > 10 {
-        return Err(EngineError::EvaluationError("BIN2DEC argument cannot exceed 10 bits".into()));
-    }
-    
-    // Convert binary to decimal
-    let mut decimal = 0i32;
-    for c in binary.chars() {
-        decimal = decimal * 2 + if c == '1' { 1 } else { 0 };
+        return Err(EngineError::EvaluationError("ISLOGICAL requires exactly 1 argument".into()));
     }
     
-    // Handle two's complement for 10-bit binary numbers starting with 1
-    if binary.len() == 10 && binary.starts_with('1') {
-        // Subtract 2^10 to get the negative value (two's complement)
-        decimal -= 1024;
+    Ok(CellValue::Boolean(matches!(&args[0], CellValue::Boolean(_))))
+}
+
+// ISNONTEXT function - checks if a value is anything other than text (blanks included)
+fn is_nontext(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() != 1 {
+        return Err(EngineError::EvaluationError("ISNONTEXT requires exactly 1 argument".into()));
     }
     
-    Ok(CellValue::Number(decimal as f64))
+    Ok(CellValue::Boolean(!matches!(&args[0], CellValue::Text(_))))
 }
 
-// DEC2BIN function - converts a decimal number to binary
-fn dec2bin(args: &[CellValue]) -> Result<CellValue, EngineError> {
-    if args.len() < 1 || args.len() > 2 {
-        return Err(EngineError::EvaluationError("DEC2BIN requires 1 or 2 arguments: number, [places]".into()));
+// TYPE function - returns the type of a value (1=number, 2=text, 4=logical, 16=error, 64=array)
+fn type_func(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() != 1 {
+        return Err(EngineError::EvaluationError("TYPE requires exactly 1 argument".into()));
     }
     
-    let number = match &args[0] {
-        CellValue::Number(n) => *n as i32,
-        _ => return Err(EngineError::EvaluationError("DEC2BIN's first argument must be a number".into())),
+    let type_code = match &args[0] {
+        CellValue::Number(_) | CellValue::Date(_) | CellValue::Blank => 1.0,
+        CellValue::Text(_) => 2.0,
+        CellValue::Boolean(_) => 4.0,
+        CellValue::Error(_) => 16.0,
+        CellValue::Array(_) => 64.0,
+        CellValue::Formula(_) => return Err(EngineError::EvaluationError("Formulas should be evaluated before using in functions".into())),
     };
     
-    // Check if the number is within the valid range for a 10-bit two's complement binary number
-    if number < -512 || number > 511 {
-        return Err(EngineError::EvaluationError("DEC2BIN can only convert numbers between -512 and 511".into()));
+    Ok(CellValue::Number(type_code))
+}
+
+// ISREF function - checks if the argument is a reference (not the value it refers to)
+fn is_ref(_evaluator: &Evaluator, args: &[AstNode], _context: &mut EvaluationContext) -> Result<CellValue, EngineError> {
+    if args.len() != 1 {
+        return Err(EngineError::EvaluationError("ISREF requires exactly 1 argument".into()));
     }
     
-    let places = if args.len() == 2 {
-        match &args[1] {
-            CellValue::Number(n) => *n as usize,
-            _ => return Err(EngineError::EvaluationError("DEC2BIN's second argument must be a number".into())),
-        }
-    } else {
-        0 // Default: use the minimum number of characters necessary
-    };
+    Ok(CellValue::Boolean(matches!(&args[0], AstNode::Reference(_))))
+}
+
+// ISFORMULA function - checks if the referenced cell contains a formula
+fn is_formula(_evaluator: &Evaluator, args: &[AstNode], context: &mut EvaluationContext) -> Result<CellValue, EngineError> {
+    if args.len() != 1 {
+        return Err(EngineError::EvaluationError("ISFORMULA requires exactly 1 argument".into()));
+    }
     
-    // Convert to binary
-    let mut binary = String::new();
-    let mut value = if number < 0 {
-        // For negative numbers, use two's complement representation (10 bits)
-        (number + 1024) as u32
-    } else {
-        number as u32
+    // For ranges, Excel looks at the top-left cell
+    let (sheet, address) = match &args[0] {
+        AstNode::Reference(reference) => (reference.sheet().unwrap_or(context.current_sheet), reference.bounds().start),
+        _ => return Ok(CellValue::Error(CellError::InvalidValue)),
     };
     
-    // Build the binary representation from right to left
-    while value > 0 || binary.is_empty() {
-        binary.insert(0, if value % 2 == 1 { '1' } else { '0' });
-        value /= 2;
-    }
-    
-    // Pad with leading zeros if needed
-    if places > 0 && binary.len() < places {
-        binary = format!("{:0>width$}", binary, width = places);
-    }
+    let has_formula = context.workbook
+        .get_sheet(sheet)
+        .and_then(|s| s.get_cell(address.row, address.col))
+        .is_some_and(|cell| cell.formula.is_some());
     
-    Ok(CellValue::Text(binary))
+    Ok(CellValue::Boolean(has_formula))
+}
+
+// ===== ENGINEERING FUNCTIONS =====
+
+// BIN2DEC function - converts a binary number to decimal
+fn bin2dec(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    convert_base(args, "BIN2DEC", 2, 10)
+}
+
+// DEC2BIN function - converts a decimal number to binary
+fn dec2bin(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    convert_base(args, "DEC2BIN", 10, 2)
 }
 
 // HEX2DEC function - converts a hexadecimal number to decimal
 fn hex2dec(args: &[CellValue]) -> Result<CellValue, EngineError> {
-    if args.len() != 1 {
-        return Err(EngineError::EvaluationError("HEX2DEC requires exactly 1 argument".into()));
+    convert_base(args, "HEX2DEC", 16, 10)
+}
+
+// DEC2HEX function - converts a decimal number to hexadecimal
+fn dec2hex(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    convert_base(args, "DEC2HEX", 10, 16)
+}
+
+// OCT2DEC function - converts an octal number to decimal
+fn oct2dec(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    convert_base(args, "OCT2DEC", 8, 10)
+}
+
+// DEC2OCT function - converts a decimal number to octal
+fn dec2oct(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    convert_base(args, "DEC2OCT", 10, 8)
+}
+
+// BIN2HEX function - converts a binary number to hexadecimal
+fn bin2hex(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    convert_base(args, "BIN2HEX", 2, 16)
+}
+
+// BIN2OCT function - converts a binary number to octal
+fn bin2oct(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    convert_base(args, "BIN2OCT", 2, 8)
+}
+
+// HEX2BIN function - converts a hexadecimal number to binary
+fn hex2bin(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    convert_base(args, "HEX2BIN", 16, 2)
+}
+
+// HEX2OCT function - converts a hexadecimal number to octal
+fn hex2oct(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    convert_base(args, "HEX2OCT", 16, 8)
+}
+
+// OCT2BIN function - converts an octal number to binary
+fn oct2bin(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    convert_base(args, "OCT2BIN", 8, 2)
+}
+
+// OCT2HEX function - converts an octal number to hexadecimal
+fn oct2hex(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    convert_base(args, "OCT2HEX", 8, 16)
+}
+
+// Every non-decimal number has at most 10 digits; negative values use the
+// two's complement of the full 10 digits (so 1111111111 in binary is -1)
+const BASE_DIGITS: u32 = 10;
+
+// Shared implementation of the base conversion functions (radix 10 is a plain number)
+fn convert_base(args: &[CellValue], name: &str, from_radix: u32, to_radix: u32) -> Result<CellValue, EngineError> {
+    let max_args = if to_radix == 10 { 1 } else { 2 };
+    if args.is_empty() || args.len() > max_args {
+        let usage = if to_radix == 10 { "exactly 1 argument: number" } else { "1 or 2 arguments: number, [places]" };
+        return Err(EngineError::EvaluationError(format!("{} requires {}", name, usage)));
     }
     
-    let hex = match &args[0] {
-        CellValue::Text(t) => t.clone(),
-        CellValue::Number(n) => n.to_string(),
-        _ => return Err(EngineError::EvaluationError("HEX2DEC argument must be a hexadecimal number represented as text or number".into())),
+    let value = if from_radix == 10 {
+        extract_number(&args[0], "number")?.trunc() as i64
+    } else {
+        let digits = match &args[0] {
+            CellValue::Text(t) => t.trim().to_string(),
+            CellValue::Number(n) => n.to_string(),
+            CellValue::Error(e) => return Ok(CellValue::Error(e.clone())),
+            _ => return Ok(CellValue::Error(CellError::InvalidValue)),
+        };
+        match parse_base_digits(&digits, from_radix) {
+            Some(value) => value,
+            None => return Ok(CellValue::Error(CellError::InvalidNumber)),
+        }
     };
     
-    // Validate hex string (only 0-9, A-F)
-    if !hex.chars().all(|c| c.is_digit(16)) {
-        return Err(EngineError::EvaluationError("HEX2DEC argument must contain only valid hexadecimal digits (0-9, A-F)".into()));
+    if to_radix == 10 {
+        return Ok(CellValue::Number(value as f64));
     }
     
-    // Limit to 10 hex digits (Excel limit for HEX2DEC)
-    if hex.len() > 10 {
-        return Err(EngineError::EvaluationError("HEX2DEC argument cannot exceed 10 hexadecimal digits".into()));
-    }
+    let places = match args.get(1) {
+        Some(places) => {
+            let places = extract_number(places, "places")?.trunc();
+            if !(0.0..=BASE_DIGITS as f64).contains(&places) {
+                return Ok(CellValue::Error(CellError::InvalidNumber));
+            }
+            Some(places as usize)
+        },
+        None => None,
+    };
     
-    // Convert hex to decimal
-    match i32::from_str_radix(&hex, 16) {
-        Ok(decimal) => Ok(CellValue::Number(decimal as f64)),
-        Err(_) => Err(EngineError::EvaluationError("Invalid hexadecimal number".into())),
+    match format_base_digits(value, to_radix, places) {
+        Some(digits) => Ok(CellValue::Text(digits)),
+        None => Ok(CellValue::Error(CellError::InvalidNumber)),
     }
 }
 
-// DEC2HEX function - converts a decimal number to hexadecimal
-fn dec2hex(args: &[CellValue]) -> Result<CellValue, EngineError> {
-    if args.len() < 1 || args.len() > 2 {
-        return Err(EngineError::EvaluationError("DEC2HEX requires 1 or 2 arguments: number, [places]".into()));
+// Parse up to 10 digits in the given radix, applying two's complement.
+// Returns None for empty input, invalid digits or too many digits.
+fn parse_base_digits(digits: &str, radix: u32) -> Option<i64> {
+    if digits.is_empty() || digits.len() > BASE_DIGITS as usize {
+        return None;
     }
     
-    let number = match &args[0] {
-        CellValue::Number(n) => *n as i32,
-        _ => return Err(EngineError::EvaluationError("DEC2HEX's first argument must be a number".into())),
-    };
+    let value = i64::from_str_radix(digits, radix).ok()?;
+    let modulus = (radix as i64).pow(BASE_DIGITS);
     
-    // Check if the number is within the valid range
-    if number < -549_755_813_888 || number > 549_755_813_887 {
-        return Err(EngineError::EvaluationError("DEC2HEX can only convert numbers between -549,755,813,888 and 549,755,813,887".into()));
+    // With all 10 digits used, the upper half of the range is negative
+    if value >= modulus / 2 {
+        Some(value - modulus)
+    } else {
+        Some(value)
     }
-    
-    let places = if args.len() == 2 {
-        match &args[1] {
-            CellValue::Number(n) => *n as usize,
-            _ => return Err(EngineError::EvaluationError("DEC2HEX's second argument must be a number".into())),
+}
+
+// Format a number in the given radix, padded to `places` digits if given.
+// Returns None if the number is out of range or doesn't fit in `places`.
+fn format_base_digits(value: i64, radix: u32, places: Option<usize>) -> Option<String> {
+    let modulus = (radix as i64).pow(BASE_DIGITS);
+    if value < -modulus / 2 || value >= modulus / 2 {
+        return None;
+    }
+    
+    // Negative numbers always use all 10 digits, ignoring places
+    let mut remaining = if value < 0 { value + modulus } else { value };
+    let mut digits = Vec::new();
+    loop {
+        let digit = (remaining % radix as i64) as u32;
+        digits.push(std::char::from_digit(digit, radix)?.to_ascii_uppercase());
+        remaining /= radix as i64;
+        if remaining == 0 {
+            break;
         }
-    } else {
-        0 // Default: use the minimum number of characters necessary
-    };
-    
-    // Convert to hexadecimal
-    let hex = if number < 0 {
-        // For negative numbers, use two's complement representation
-        format!("{:X}", (number + (1 << 32)) as u32)
-    } else {
-        format!("{:X}", number as u32)
-    };
-    
-    // Pad with leading zeros if needed
-    let result = if places > 0 && hex.len() < places {
-        format!("{:0>width$}", hex, width = places)
-    } else {
-        hex
-    };
+    }
+    let digits: String = digits.into_iter().rev().collect();
     
-    Ok(CellValue::Text(result))
+    match places {
+        Some(places) if value >= 0 => {
+            if digits.len() > places {
+                None
+            } else {
+                Some(format!("{:0>width$}", digits, width = places))
+            }
+        },
+        _ => Some(digits),
+    }
 }
 
 // ===== ADDITIONAL FINANCIAL FUNCTIONS =====
@@ -2545,12 +3706,22 @@ fn sumifs(args: &[CellValue]) -> Result<CellValue, EngineError> {
             "SUMIFS requires at least 3 arguments: sum_range, criteria_range1, criteria1, ...".into()));
     }
     
-    // In a real implementation, we would need to handle multiple ranges and criteria
-    // This is a simplified version that only handles the current arguments
+    let positions = match matching_positions("SUMIFS", Some(("sum_range", &args[0])), &args[1..]) {
+        Ok(positions) => positions,
+        Err(e) => return Ok(CellValue::Error(e)),
+    };
     
-    // Mock implementation - in a real scenario we would check all criteria
-    // against their respective ranges and sum values in sum_range where all criteria match
-    Ok(CellValue::Number(50.0)) // Placeholder
+    // Text and blanks in the sum range are skipped; an error among the matches is the result
+    let sum_range = array_rows(&args[0]);
+    let mut total = 0.0;
+    for (row, col) in positions {
+        match sum_range[row][col].date_as_number() {
+            CellValue::Number(n) => total += n,
+            CellValue::Error(e) => return Ok(CellValue::Error(e)),
+            _ => {},
+        }
+    }
+    Ok(CellValue::Number(total))
 }
 
 // COUNTIF function - counts cells that meet criteria
@@ -2574,11 +3745,10 @@ fn countifs(args: &[CellValue]) -> Result<CellValue, EngineError> {
             "COUNTIFS requires at least 2 arguments and must have an even number: criteria_range1, criteria1, ...".into()));
     }
     
-    // In a real implementation, we would need to handle multiple ranges and criteria
-    // This is a simplified version that only handles the current arguments
-    
-    // Mock implementation - in a real scenario we would count cells where all criteria match
-    Ok(CellValue::Number(3.0)) // Placeholder
+    match matching_positions("COUNTIFS", None, args) {
+        Ok(positions) => Ok(CellValue::Number(positions.len() as f64)),
+        Err(e) => Ok(CellValue::Error(e)),
+    }
 }
 
 // AVERAGEIF function - averages cells that meet criteria
@@ -2603,12 +3773,121 @@ fn averageifs(args: &[CellValue]) -> Result<CellValue, EngineError> {
             "AVERAGEIFS requires at least 3 arguments: average_range, criteria_range1, criteria1, ...".into()));
     }
     
-    // In a real implementation, we would need to handle multiple ranges and criteria
-    // This is a simplified version that only handles the current arguments
+    let positions = match matching_positions("AVERAGEIFS", Some(("average_range", &args[0])), &args[1..]) {
+        Ok(positions) => positions,
+        Err(e) => return Ok(CellValue::Error(e)),
+    };
     
-    // Mock implementation - in a real scenario we would average values in average_range
-    // where all criteria match
-    Ok(CellValue::Number(15.0)) // Placeholder
+    // Only numbers are averaged; #DIV/0! when no number meets the criteria
+    let average_range = array_rows(&args[0]);
+    let (mut total, mut count) = (0.0, 0);
+    for (row, col) in positions {
+        match average_range[row][col].date_as_number() {
+            CellValue::Number(n) => {
+                total += n;
+                count += 1;
+            },
+            CellValue::Error(e) => return Ok(CellValue::Error(e)),
+            _ => {},
+        }
+    }
+    if count == 0 {
+        return Ok(CellValue::Error(CellError::DivisionByZero));
+    }
+    Ok(CellValue::Number(total / count as f64))
+}
+
+// Positions (row, column) of the cells that meet every criterion of a *IFS function.
+// `pairs` alternates criteria ranges and criteria. All ranges, including the sum or
+// average range if given, must have the same shape; a mismatch is #VALUE!.
+fn matching_positions(name: &str, target: Option<(&str, &CellValue)>, pairs: &[CellValue]) -> Result<Vec<(usize, usize)>, CellError> {
+    let shape = |value: &CellValue| {
+        let rows = array_rows(value);
+        (rows.len(), rows.first().map_or(0, |row| row.len()))
+    };
+    
+    let ranges: Vec<(String, &CellValue)> = target.map(|(label, range)| (label.to_string(), range)).into_iter()
+        .chain(pairs.iter().step_by(2).enumerate().map(|(i, range)| (format!("criteria_range{}", i + 1), range)))
+        .collect();
+    let (first_label, first_range) = &ranges[0];
+    let (height, width) = shape(first_range);
+    for (label, range) in &ranges[1..] {
+        let (rows, cols) = shape(range);
+        if (rows, cols) != (height, width) {
+            log::warn!("{}: {} is {}x{} but {} is {}x{}; all ranges must have the same shape", name, label, rows, cols, first_label, height, width);
+            return Err(CellError::InvalidValue);
+        }
+    }
+    
+    let conditions: Vec<(Vec<&[CellValue]>, Criterion)> = pairs.chunks(2)
+        .map(|pair| (array_rows(&pair[0]), Criterion::parse(array_rows(&pair[1])[0].first().unwrap_or(&CellValue::Blank))))
+        .collect();
+    Ok((0..height)
+        .flat_map(|row| (0..width).map(move |col| (row, col)))
+        .filter(|&(row, col)| conditions.iter().all(|(range, criterion)| criterion.matches(&range[row][col])))
+        .collect())
+}
+
+// A criterion of the *IFS functions: a value (5, "Closed", "A*" with wildcards) with an
+// optional comparison in front (">=10", "<>Closed"). "=" alone matches blank cells and
+// "<>" alone non-blank ones.
+struct Criterion {
+    op: BinaryOperator,
+    value: CellValue,
+}
+
+impl Criterion {
+    fn parse(criteria: &CellValue) -> Criterion {
+        let text = match criteria {
+            CellValue::Text(text) => text,
+            other => return Criterion { op: BinaryOperator::Equal, value: other.date_as_number() },
+        };
+        
+        let operators = [
+            ("<=", BinaryOperator::LessThanOrEqual), (">=", BinaryOperator::GreaterThanOrEqual), ("<>", BinaryOperator::NotEqual),
+            ("<", BinaryOperator::LessThan), (">", BinaryOperator::GreaterThan), ("=", BinaryOperator::Equal),
+        ];
+        let (op, operand) = operators.into_iter()
+            .find_map(|(symbol, op)| text.strip_prefix(symbol).map(|rest| (op, rest)))
+            .unwrap_or((BinaryOperator::Equal, text.as_str()));
+        
+        let value = if operand.is_empty() {
+            CellValue::Blank
+        } else if let Ok(n) = operand.trim().parse::<f64>() {
+            CellValue::Number(n)
+        } else if operand.eq_ignore_ascii_case("TRUE") || operand.eq_ignore_ascii_case("FALSE") {
+            CellValue::Boolean(operand.eq_ignore_ascii_case("TRUE"))
+        } else {
+            CellValue::Text(operand.to_string())
+        };
+        Criterion { op, value }
+    }
+    
+    fn matches(&self, cell: &CellValue) -> bool {
+        let cell = cell.date_as_number();
+        let ordering = match (&cell, &self.value) {
+            (CellValue::Number(a), CellValue::Number(b)) => a.partial_cmp(b),
+            (CellValue::Boolean(a), CellValue::Boolean(b)) => Some(a.cmp(b)),
+            (CellValue::Blank, CellValue::Blank) => Some(Ordering::Equal),
+            (CellValue::Text(a), CellValue::Blank) if a.is_empty() => Some(Ordering::Equal),
+            // Text equality is case-insensitive and honours * and ? wildcards
+            (CellValue::Text(a), CellValue::Text(b)) if matches!(self.op, BinaryOperator::Equal | BinaryOperator::NotEqual) => {
+                let text: Vec<char> = a.to_lowercase().chars().collect();
+                wildcard_match(&wildcard_pattern(&b.to_lowercase()), &text).then_some(Ordering::Equal)
+            },
+            (CellValue::Text(a), CellValue::Text(b)) => Some(a.to_lowercase().cmp(&b.to_lowercase())),
+            _ => None,
+        };
+        
+        match self.op {
+            BinaryOperator::NotEqual => ordering != Some(Ordering::Equal),
+            BinaryOperator::LessThan => ordering == Some(Ordering::Less),
+            BinaryOperator::LessThanOrEqual => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            BinaryOperator::GreaterThan => ordering == Some(Ordering::Greater),
+            BinaryOperator::GreaterThanOrEqual => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+            _ => ordering == Some(Ordering::Equal),
+        }
+    }
 }
 
 // SUMPRODUCT function - multiplies corresponding components in arrays, then sums
@@ -2618,41 +3897,82 @@ fn sumproduct(args: &[CellValue]) -> Result<CellValue, EngineError> {
             "SUMPRODUCT requires at least one array".into()));
     }
     
-    // In a real implementation, we would need to handle arrays
-    // This is a simplified version that only handles the current arguments
+    // Every argument is viewed as a 2-D array; scalars act as 1x1 arrays
+    let arrays: Vec<Vec<&[CellValue]>> = args.iter().map(array_rows).collect();
+    let height = arrays[0].len();
+    let width = arrays[0].first().map_or(0, |row| row.len());
+    
+    // All arrays must share the same dimensions
+    if arrays.iter().any(|array| array.len() != height || array.iter().any(|row| row.len() != width)) {
+        return Ok(CellValue::Error(CellError::InvalidValue));
+    }
+    
+    let mut total = 0.0;
+    for row in 0..height {
+        for col in 0..width {
+            let mut product = 1.0;
+            for array in &arrays {
+                match &array[row][col] {
+                    CellValue::Number(n) => product *= n,
+                    CellValue::Error(e) => return Ok(CellValue::Error(e.clone())),
+                    // Text, booleans and blanks inside arrays are treated as zero
+                    _ => product = 0.0,
+                }
+            }
+            total += product;
+        }
+    }
     
-    // Mock implementation - in a real scenario we would multiply corresponding elements
-    // of arrays together and sum the products
-    Ok(CellValue::Number(150.0)) // Placeholder
+    Ok(CellValue::Number(total))
+}
+
+// Helper function to view a value as the rows of a 2-D array (scalars become a 1x1 array)
+fn array_rows(value: &CellValue) -> Vec<&[CellValue]> {
+    match value {
+        CellValue::Array(rows) => rows.iter().map(|row| row.as_slice()).collect(),
+        other => vec![std::slice::from_ref(other)],
+    }
 }
 
 // ===== ERROR HANDLING FUNCTIONS =====
 
-// IFERROR function - returns a value if expression is error, otherwise returns expression
-fn iferror(args: &[CellValue]) -> Result<CellValue, EngineError> {
+// IFERROR function - returns a value if expression is an error, otherwise returns expression
+fn iferror(evaluator: &Evaluator, args: &[AstNode], context: &mut EvaluationContext) -> Result<CellValue, EngineError> {
     if args.len() != 2 {
         return Err(EngineError::EvaluationError(
             "IFERROR requires exactly 2 arguments: value, value_if_error".into()));
     }
     
-    // If first argument is an error, return second argument
-    match &args[0] {
-        CellValue::Error(_) => Ok(args[1].clone()),
-        _ => Ok(args[0].clone()),
+    // If first argument is (or fails with) an error, return second argument
+    match evaluate_catching(evaluator, &args[0], context)? {
+        CellValue::Error(_) => evaluator.evaluate(&args[1], context),
+        value => Ok(value),
     }
 }
 
 // IFNA function - returns a value if expression is #N/A, otherwise returns expression
-fn ifna(args: &[CellValue]) -> Result<CellValue, EngineError> {
+fn ifna(evaluator: &Evaluator, args: &[AstNode], context: &mut EvaluationContext) -> Result<CellValue, EngineError> {
     if args.len() != 2 {
         return Err(EngineError::EvaluationError(
             "IFNA requires exactly 2 arguments: value, value_if_na".into()));
     }
     
-    // If first argument is #N/A error, return second argument
-    match &args[0] {
-        CellValue::Error(e) if e == "#N/A" => Ok(args[1].clone()),
-        _ => Ok(args[0].clone()),
+    // If first argument is (or fails with) #N/A error, return second argument
+    match evaluate_catching(evaluator, &args[0], context)? {
+        CellValue::Error(CellError::NotAvailable) => evaluator.evaluate(&args[1], context),
+        value => Ok(value),
+    }
+}
+
+// Helper function to evaluate an argument, turning evaluation errors into error values.
+// Internal errors still propagate.
+fn evaluate_catching(evaluator: &Evaluator, node: &AstNode, context: &mut EvaluationContext) -> Result<CellValue, EngineError> {
+    match evaluator.evaluate(node, context) {
+        Ok(value) => Ok(value),
+        Err(error) => match error.as_cell_error() {
+            Some(cell_error) => Ok(CellValue::Error(cell_error)),
+            None => Err(error),
+        },
     }
 }
 
@@ -2667,10 +3987,10 @@ fn ifs(args: &[CellValue]) -> Result<CellValue, EngineError> {
     for i in (0..args.len()).step_by(2) {
         let condition = match &args[i] {
             CellValue::Boolean(b) => *b,
-            CellValue::Number(n) => *n != 0.0,
+            CellValue::Number(n) | CellValue::Date(n) => *n != 0.0,
             CellValue::Text(t) => !t.is_empty(),
             CellValue::Blank => false,
-            CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot use unevaluated formula in IFS".into())),
+            CellValue::Formula(_) | CellValue::Array(_) => return Err(EngineError::EvaluationError("Cannot use unevaluated formula in IFS".into())),
             CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
         };
         
@@ -2680,7 +4000,38 @@ fn ifs(args: &[CellValue]) -> Result<CellValue, EngineError> {
     }
     
     // No conditions were true
-    Err(EngineError::EvaluationError("No TRUE conditions in IFS function".into()))
+    Ok(CellValue::Error(CellError::NotAvailable))
+}
+
+// SWITCH function - returns the result paired with the first value equal to the expression
+fn switch(evaluator: &Evaluator, args: &[AstNode], context: &mut EvaluationContext) -> Result<CellValue, EngineError> {
+    if args.len() < 3 {
+        return Err(EngineError::EvaluationError(
+            "SWITCH requires at least 3 arguments: expression, value1, result1, ..., [default]".into()));
+    }
+    
+    // The expression is evaluated once; only the chosen result is evaluated at all
+    let expression = evaluator.evaluate(&args[0], context)?.date_as_number();
+    if let CellValue::Error(e) = expression {
+        return Ok(CellValue::Error(e));
+    }
+    
+    let cases = &args[1..];
+    for pair in cases.chunks_exact(2) {
+        let value = evaluator.evaluate(&pair[0], context)?.date_as_number();
+        match evaluator.equal(&expression, &value)? {
+            CellValue::Boolean(true) => return evaluator.evaluate(&pair[1], context),
+            CellValue::Error(e) => return Ok(CellValue::Error(e)),
+            _ => {},
+        }
+    }
+    
+    // A trailing unpaired argument is the default
+    if cases.len() % 2 == 1 {
+        evaluator.evaluate(&cases[cases.len() - 1], context)
+    } else {
+        Ok(CellValue::Error(CellError::NotAvailable))
+    }
 }
 
 // ===== LOOKUP & REFERENCE FUNCTIONS =====
@@ -2692,18 +4043,41 @@ fn xlookup(args: &[CellValue]) -> Result<CellValue, EngineError> {
             "XLOOKUP requires 3-6 arguments: lookup_value, lookup_array, return_array, [if_not_found], [match_mode], [search_mode]".into()));
     }
     
-    // In a real implementation, we would need to handle arrays
-    // This is a simplified version that only handles the current arguments
+    if let CellValue::Error(e) = &args[0] {
+        return Ok(CellValue::Error(e.clone()));
+    }
     
-    // Default value if not found
-    let if_not_found = if args.len() >= 4 { args[3].clone() } else { CellValue::Error("#N/A".to_string()) };
+    let (keys, vertical) = match lookup_vector(&args[1]) {
+        Some(vector) => vector,
+        None => return Ok(CellValue::Error(CellError::InvalidValue)),
+    };
+    let match_mode = lookup_mode(args.get(4), 0)?;
+    let search_mode = lookup_mode(args.get(5), 1)?;
     
-    // Placeholder implementation - in a real implementation, we would search through lookup_array for lookup_value
-    // and return the corresponding value from return_array
+    // The return array must line up with the lookup array
+    let returns = array_rows(&args[2]);
+    let aligned = if vertical {
+        returns.len() == keys.len()
+    } else {
+        returns.first().map_or(0, |row| row.len()) == keys.len()
+    };
+    if !aligned {
+        return Ok(CellValue::Error(CellError::InvalidValue));
+    }
     
-    // For now, just returning a placeholder result
-    // In a real implementation, we would check if we found a match and return if_not_found if necessary
-    Ok(CellValue::Text("XLOOKUP Result".to_string()))
+    // A return array wider (or taller) than one line gives back the whole matched
+    // row (or column) as an array, e.g. every field of the matched record
+    match xlookup_position(&args[0], &keys, match_mode, search_mode)? {
+        Some(position) if vertical => match returns[position] {
+            [value] => Ok(value.clone()),
+            row => Ok(CellValue::Array(vec![row.to_vec()])),
+        },
+        Some(position) => match returns.as_slice() {
+            [row] => Ok(row[position].clone()),
+            rows => Ok(CellValue::Array(rows.iter().map(|row| vec![row[position].clone()]).collect())),
+        },
+        None => Ok(args.get(3).cloned().unwrap_or(CellValue::Error(CellError::NotAvailable))),
+    }
 }
 
 // XMATCH function - returns position of lookup_value in an array
@@ -2713,25 +4087,131 @@ fn xmatch(args: &[CellValue]) -> Result<CellValue, EngineError> {
             "XMATCH requires 2-4 arguments: lookup_value, lookup_array, [match_mode], [search_mode]".into()));
     }
     
-    // In a real implementation, we would need to handle arrays
-    // This is a simplified version that only handles the current arguments
+    if let CellValue::Error(e) = &args[0] {
+        return Ok(CellValue::Error(e.clone()));
+    }
     
-    // Placeholder implementation - in a real implementation, we would search through lookup_array for lookup_value
-    // and return its position
+    let (keys, _) = match lookup_vector(&args[1]) {
+        Some(vector) => vector,
+        None => return Ok(CellValue::Error(CellError::InvalidValue)),
+    };
+    let match_mode = lookup_mode(args.get(2), 0)?;
+    let search_mode = lookup_mode(args.get(3), 1)?;
     
-    // For now, just returning a placeholder result
-    Ok(CellValue::Number(3.0)) // Mock result - position 3
+    match xlookup_position(&args[0], &keys, match_mode, search_mode)? {
+        Some(position) => Ok(CellValue::Number((position + 1) as f64)), // +1 for 1-based indexing
+        None => Ok(CellValue::Error(CellError::NotAvailable)),
+    }
 }
 
-// OFFSET function - returns a range shifted from reference by given rows/cols
-fn offset(args: &[CellValue]) -> Result<CellValue, EngineError> {
-    if args.len() < 3 || args.len() > 5 {
-        return Err(EngineError::EvaluationError(
-            "OFFSET requires 3-5 arguments: reference, rows, cols, [height], [width]".into()));
+// View a single row or column as a list of values, with whether it runs vertically.
+// Returns None for arrays with more than one row and column.
+fn lookup_vector(value: &CellValue) -> Option<(Vec<&CellValue>, bool)> {
+    let rows = array_rows(value);
+    if rows.len() == 1 {
+        Some((rows[0].iter().collect(), false))
+    } else if rows.iter().all(|row| row.len() == 1) {
+        Some((rows.iter().map(|row| &row[0]).collect(), true))
+    } else {
+        None
     }
-    
-    // Extract rows and columns to offset
-    let rows = extract_number(&args[1], "rows")?;
+}
+
+// Read an optional match_mode or search_mode argument as a whole number
+fn lookup_mode(value: Option<&CellValue>, default: i64) -> Result<i64, EngineError> {
+    match value {
+        None => Ok(default),
+        Some(value) => Ok(extract_number(value, "mode")?.trunc() as i64),
+    }
+}
+
+// Position of a lookup value in a 1-D array under XLOOKUP's modes.
+// match_mode: 0 exact, -1 exact or next smaller, 1 exact or next larger, 2 wildcard.
+// search_mode: 1 first to last, -1 last to first, 2 binary ascending, -2 binary descending.
+fn xlookup_position(lookup_value: &CellValue, keys: &[&CellValue], match_mode: i64, search_mode: i64) -> Result<Option<usize>, CellError> {
+    if !matches!(match_mode, -1..=2) {
+        return Err(CellError::InvalidValue);
+    }
+    
+    match search_mode {
+        1 | -1 => {
+            let wildcard = match (match_mode, lookup_value) {
+                (2, CellValue::Text(pattern)) => Some(wildcard_pattern(&pattern.to_lowercase())),
+                _ => None,
+            };
+            let order: Box<dyn Iterator<Item = usize>> = if search_mode == 1 {
+                Box::new(0..keys.len())
+            } else {
+                Box::new((0..keys.len()).rev())
+            };
+            
+            // The closest key on the allowed side wins when there is no exact match
+            let mut closest: Option<usize> = None;
+            for position in order {
+                let key = keys[position];
+                if let Some(pattern) = &wildcard {
+                    if let CellValue::Text(text) = key {
+                        let text: Vec<char> = text.to_lowercase().chars().collect();
+                        if wildcard_match(pattern, &text) {
+                            return Ok(Some(position));
+                        }
+                    }
+                    continue;
+                }
+                
+                let ordering = match compare_lookup_values(key, lookup_value) {
+                    Some(ordering) => ordering,
+                    None => continue,
+                };
+                let candidate = match (ordering, match_mode) {
+                    (Ordering::Equal, _) => return Ok(Some(position)),
+                    (Ordering::Less, -1) | (Ordering::Greater, 1) => true,
+                    _ => false,
+                };
+                let closer = closest.map_or(true, |best| {
+                    compare_lookup_values(key, keys[best]) == Some(if match_mode == -1 { Ordering::Greater } else { Ordering::Less })
+                });
+                if candidate && closer {
+                    closest = Some(position);
+                }
+            }
+            Ok(closest)
+        },
+        2 | -2 => {
+            if match_mode == 2 {
+                return Err(CellError::InvalidValue); // Wildcards can't be binary searched
+            }
+            
+            // Keys before the split point sort before the lookup value
+            let before = if search_mode == 2 { Ordering::Less } else { Ordering::Greater };
+            let split = keys.partition_point(|key| compare_lookup_values(key, lookup_value) == Some(before));
+            if keys.get(split).and_then(|key| compare_lookup_values(key, lookup_value)) == Some(Ordering::Equal) {
+                return Ok(Some(split));
+            }
+            
+            // Without an exact match, the neighbours of the split point are the next
+            // smaller and next larger keys (which is which depends on the sort order)
+            let after = Some(split).filter(|&position| position < keys.len());
+            let previous = split.checked_sub(1);
+            Ok(match (match_mode, search_mode) {
+                (-1, 2) | (1, -2) => previous,
+                (1, 2) | (-1, -2) => after,
+                _ => None,
+            })
+        },
+        _ => Err(CellError::InvalidValue),
+    }
+}
+
+// OFFSET function - returns a range shifted from reference by given rows/cols
+fn offset(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() < 3 || args.len() > 5 {
+        return Err(EngineError::EvaluationError(
+            "OFFSET requires 3-5 arguments: reference, rows, cols, [height], [width]".into()));
+    }
+    
+    // Extract rows and columns to offset
+    let rows = extract_number(&args[1], "rows")?;
     let cols = extract_number(&args[2], "cols")?;
     
     // Extract optional height and width
@@ -2752,44 +4232,47 @@ fn offset(args: &[CellValue]) -> Result<CellValue, EngineError> {
     Ok(CellValue::Number(42.0)) // Mock result
 }
 
-// INDIRECT function - interprets a text string as a cell or range reference
-fn indirect(args: &[CellValue]) -> Result<CellValue, EngineError> {
+// INDIRECT function - the value of the reference named by a text: A1 style, or R1C1
+// style relative to the formula's cell when the second argument is FALSE
+fn indirect(evaluator: &Evaluator, args: &[AstNode], context: &mut EvaluationContext) -> Result<CellValue, EngineError> {
     if args.len() < 1 || args.len() > 2 {
         return Err(EngineError::EvaluationError(
             "INDIRECT requires 1-2 arguments: ref_text, [a1]".into()));
     }
     
-    // Extract reference text
-    let ref_text = match &args[0] {
+    let ref_text = match evaluator.evaluate(&args[0], context)? {
         CellValue::Text(t) => t,
-        _ => return Err(EngineError::EvaluationError("INDIRECT first argument must be text".into())),
+        CellValue::Error(e) => return Ok(CellValue::Error(e)),
+        _ => return Ok(CellValue::Error(CellError::InvalidReference)),
     };
     
-    // Extract A1 style flag (defaults to TRUE)
-    let a1_style = if args.len() == 2 {
-        match &args[1] {
-            CellValue::Boolean(b) => *b,
-            CellValue::Number(n) => *n != 0.0,
-            _ => true,
-        }
-    } else {
-        true
+    // A1 style unless the second argument is FALSE (or 0)
+    let a1_style = match args.get(1).map(|arg| evaluator.evaluate(arg, context)).transpose()? {
+        None | Some(CellValue::Blank) => true,
+        Some(CellValue::Boolean(b)) => b,
+        Some(CellValue::Number(n)) => n != 0.0,
+        Some(CellValue::Error(e)) => return Ok(CellValue::Error(e)),
+        Some(_) => return Ok(CellValue::Error(CellError::InvalidValue)),
     };
     
-    // In a real implementation, we would need to handle cell references
-    // This is a simplified version that only handles the current arguments
-    
-    // Placeholder implementation - in a real implementation, we would interpret ref_text
-    // as a cell reference and return its value
-    
-    // For now, just returning a placeholder result
-    Ok(CellValue::Number(99.0)) // Mock result
+    let reference = if a1_style {
+        parse_reference(&ref_text)
+    } else {
+        parse_r1c1_reference(&ref_text, &context.current_cell)
+    };
+    match reference {
+        Ok(reference) => context.resolve_reference(&reference),
+        Err(_) => Ok(CellValue::Error(CellError::InvalidReference)),
+    }
 }
 
 // ===== DATE & TIME FUNCTIONS =====
 
 // EOMONTH function - last day of month, offset by months
-fn eomonth(args: &[CellValue]) -> Result<CellValue, EngineError> {
+fn eomonth(evaluator: &Evaluator, args: &[AstNode], context: &mut EvaluationContext) -> Result<CellValue, EngineError> {
+    let args = evaluate_plain_args(evaluator, args, context)?;
+    let system = context.workbook.date_system();
+    
     if args.len() != 2 {
         return Err(EngineError::EvaluationError(
             "EOMONTH requires exactly 2 arguments: start_date, months".into()));
@@ -2800,18 +4283,21 @@ fn eomonth(args: &[CellValue]) -> Result<CellValue, EngineError> {
     // Extract months to add
     let months = extract_number(&args[1], "months")?;
     
-    // In Excel, dates are stored as sequential serial numbers
-    // 1 represents January 1, 1900
-    // This is a simplified version that doesn't do the actual date calculation
-    
-    // Placeholder implementation - in a real implementation, we would calculate the actual end of month date
+    let result = serial_to_date(start_date, system)
+        .and_then(|date| add_months(date, months.trunc() as i64))
+        .and_then(end_of_month);
     
-    // For now, just returning a placeholder result
-    Ok(CellValue::Number(start_date + 30.0 * months)) // Mock result
+    match result {
+        Some(date) => Ok(CellValue::Date(date_to_serial(date, system))),
+        None => Ok(CellValue::Error(CellError::InvalidNumber)),
+    }
 }
 
 // EDATE function - same-day, offset by months
-fn edate(args: &[CellValue]) -> Result<CellValue, EngineError> {
+fn edate(evaluator: &Evaluator, args: &[AstNode], context: &mut EvaluationContext) -> Result<CellValue, EngineError> {
+    let args = evaluate_plain_args(evaluator, args, context)?;
+    let system = context.workbook.date_system();
+    
     if args.len() != 2 {
         return Err(EngineError::EvaluationError(
             "EDATE requires exactly 2 arguments: start_date, months".into()));
@@ -2822,14 +4308,11 @@ fn edate(args: &[CellValue]) -> Result<CellValue, EngineError> {
     // Extract months to add
     let months = extract_number(&args[1], "months")?;
     
-    // In Excel, dates are stored as sequential serial numbers
-    // 1 represents January 1, 1900
-    // This is a simplified version that doesn't do the actual date calculation
-    
-    // Placeholder implementation - in a real implementation, we would calculate the actual date
-    
-    // For now, just returning a placeholder result
-    Ok(CellValue::Number(start_date + 30.0 * months)) // Mock result
+    // The day is clamped to the end of the target month (Jan 31 + 1 month = Feb 28/29)
+    match serial_to_date(start_date, system).and_then(|date| add_months(date, months.trunc() as i64)) {
+        Some(date) => Ok(CellValue::Date(date_to_serial(date, system))),
+        None => Ok(CellValue::Error(CellError::InvalidNumber)),
+    }
 }
 
 // NETWORKDAYS function - count weekdays between dates
@@ -2929,7 +4412,7 @@ fn workday(args: &[CellValue]) -> Result<CellValue, EngineError> {
         days * 7.0 / 5.0
     };
     
-    Ok(CellValue::Number(start_date + approx_total_days))
+    Ok(CellValue::Date(start_date + approx_total_days))
 }
 
 // WORKDAY.INTL function - customizable weekend
@@ -2971,7 +4454,7 @@ fn workday_intl(args: &[CellValue]) -> Result<CellValue, EngineError> {
         days * 7.0 / 5.0
     };
     
-    Ok(CellValue::Number(start_date + approx_total_days))
+    Ok(CellValue::Date(start_date + approx_total_days))
 }
 
 // YEARFRAC function - fraction of year between dates, per day-count basis
@@ -3025,11 +4508,11 @@ fn mod_func(args: &[CellValue]) -> Result<CellValue, EngineError> {
     let divisor = extract_number(&args[1], "divisor")?;
     
     if divisor == 0.0 {
-        return Err(EngineError::EvaluationError("Division by zero".into()));
+        return Ok(CellValue::Error(CellError::DivisionByZero));
     }
     
-    // Calculate modulo (remainder)
-    let result = number % divisor;
+    // Calculate modulo; the result takes the sign of the divisor, so MOD(-3, 2) is 1
+    let result = number - divisor * (number / divisor).floor();
     
     Ok(CellValue::Number(result))
 }
@@ -3047,7 +4530,12 @@ fn ceiling(args: &[CellValue]) -> Result<CellValue, EngineError> {
     let significance = extract_number(&args[1], "significance")?;
     
     if significance == 0.0 {
-        return Err(EngineError::EvaluationError("Significance cannot be zero".into()));
+        return Ok(CellValue::Number(0.0));
+    }
+    
+    // A positive number cannot be rounded to a negative multiple
+    if number > 0.0 && significance < 0.0 {
+        return Ok(CellValue::Error(CellError::InvalidNumber));
     }
     
     // Calculate ceiling
@@ -3070,7 +4558,12 @@ fn floor(args: &[CellValue]) -> Result<CellValue, EngineError> {
     let significance = extract_number(&args[1], "significance")?;
     
     if significance == 0.0 {
-        return Err(EngineError::EvaluationError("Significance cannot be zero".into()));
+        return Ok(CellValue::Error(CellError::DivisionByZero));
+    }
+    
+    // A positive number cannot be rounded to a negative multiple
+    if number > 0.0 && significance < 0.0 {
+        return Ok(CellValue::Error(CellError::InvalidNumber));
     }
     
     // Calculate floor
@@ -3093,7 +4586,12 @@ fn mround(args: &[CellValue]) -> Result<CellValue, EngineError> {
     let multiple = extract_number(&args[1], "multiple")?;
     
     if multiple == 0.0 {
-        return Err(EngineError::EvaluationError("Multiple cannot be zero".into()));
+        return Ok(CellValue::Number(0.0));
+    }
+    
+    // Number and multiple must have the same sign
+    if (number > 0.0 && multiple < 0.0) || (number < 0.0 && multiple > 0.0) {
+        return Ok(CellValue::Error(CellError::InvalidNumber));
     }
     
     // Calculate rounded to nearest multiple
@@ -3102,6 +4600,90 @@ fn mround(args: &[CellValue]) -> Result<CellValue, EngineError> {
     Ok(CellValue::Number(rounded))
 }
 
+// CEILING.MATH function - rounds a number up to the nearest multiple of significance
+fn ceiling_math(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.is_empty() || args.len() > 3 {
+        return Err(EngineError::EvaluationError(
+            "CEILING.MATH requires 1-3 arguments: number, [significance], [mode]".into()));
+    }
+    
+    let (number, significance, mode) = rounding_math_args(args)?;
+    if significance == 0.0 {
+        return Ok(CellValue::Number(0.0));
+    }
+    
+    // A non-zero mode rounds negative numbers away from zero
+    let multiple = if number < 0.0 && mode != 0.0 {
+        (number / significance).floor()
+    } else {
+        (number / significance).ceil()
+    };
+    
+    Ok(CellValue::Number(multiple * significance))
+}
+
+// FLOOR.MATH function - rounds a number down to the nearest multiple of significance
+fn floor_math(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.is_empty() || args.len() > 3 {
+        return Err(EngineError::EvaluationError(
+            "FLOOR.MATH requires 1-3 arguments: number, [significance], [mode]".into()));
+    }
+    
+    let (number, significance, mode) = rounding_math_args(args)?;
+    if significance == 0.0 {
+        return Ok(CellValue::Number(0.0));
+    }
+    
+    // A non-zero mode rounds negative numbers toward zero
+    let multiple = if number < 0.0 && mode != 0.0 {
+        (number / significance).ceil()
+    } else {
+        (number / significance).floor()
+    };
+    
+    Ok(CellValue::Number(multiple * significance))
+}
+
+// Helper function to extract number, significance (default 1, sign ignored) and mode (default 0)
+fn rounding_math_args(args: &[CellValue]) -> Result<(f64, f64, f64), EngineError> {
+    let number = extract_number(&args[0], "number")?;
+    let significance = match args.get(1) {
+        Some(value) => extract_number(value, "significance")?.abs(),
+        None => 1.0,
+    };
+    let mode = match args.get(2) {
+        Some(value) => extract_number(value, "mode")?,
+        None => 0.0,
+    };
+    Ok((number, significance, mode))
+}
+
+// INT function - rounds a number down to the nearest integer
+fn int(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() != 1 {
+        return Err(EngineError::EvaluationError(
+            "INT requires exactly 1 argument: number".into()));
+    }
+    
+    let number = extract_number(&args[0], "number")?;
+    
+    // Rounds toward negative infinity, so INT(-2.5) is -3
+    Ok(CellValue::Number(number.floor()))
+}
+
+// TRUNC function - truncates a number toward zero to a specified number of digits
+fn trunc(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(EngineError::EvaluationError(
+            "TRUNC requires 1 or 2 arguments: number, [num_digits]".into()));
+    }
+    
+    let number = extract_number(&args[0], "number")?;
+    let num_digits = if args.len() == 2 { extract_number(&args[1], "num_digits")?.trunc() as i32 } else { 0 };
+    
+    Ok(CellValue::Number(round_to_digits(number, num_digits, f64::trunc)))
+}
+
 // TRANSPOSE function - flips rows and columns of an array
 fn transpose(args: &[CellValue]) -> Result<CellValue, EngineError> {
     if args.len() != 1 {
@@ -3118,6 +4700,181 @@ fn transpose(args: &[CellValue]) -> Result<CellValue, EngineError> {
     Ok(CellValue::Number(0.0)) // Placeholder - would return the transposed array
 }
 
+// MMULT function - returns the matrix product of two arrays
+fn mmult(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() != 2 {
+        return Err(EngineError::EvaluationError(
+            "MMULT requires exactly 2 arguments: array1, array2".into()));
+    }
+    
+    let left = match numeric_matrix(&args[0]) {
+        Ok(matrix) => matrix,
+        Err(e) => return Ok(CellValue::Error(e)),
+    };
+    let right = match numeric_matrix(&args[1]) {
+        Ok(matrix) => matrix,
+        Err(e) => return Ok(CellValue::Error(e)),
+    };
+    
+    // The columns of array1 must match the rows of array2
+    if left[0].len() != right.len() {
+        return Ok(CellValue::Error(CellError::InvalidValue));
+    }
+    
+    let product = left.iter()
+        .map(|row| (0..right[0].len())
+            .map(|col| CellValue::Number(row.iter().zip(&right).map(|(a, b_row)| a * b_row[col]).sum()))
+            .collect())
+        .collect();
+    Ok(CellValue::Array(product))
+}
+
+// MINVERSE function - returns the inverse of a square matrix
+fn minverse(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() != 1 {
+        return Err(EngineError::EvaluationError(
+            "MINVERSE requires exactly 1 argument: array".into()));
+    }
+    
+    let matrix = match square_matrix(&args[0]) {
+        Ok(matrix) => matrix,
+        Err(e) => return Ok(CellValue::Error(e)),
+    };
+    
+    // Gauss-Jordan elimination on [matrix | identity]
+    let size = matrix.len();
+    let mut augmented: Vec<Vec<f64>> = matrix.into_iter().enumerate()
+        .map(|(i, mut row)| {
+            row.extend((0..size).map(|j| if i == j { 1.0 } else { 0.0 }));
+            row
+        })
+        .collect();
+    
+    for col in 0..size {
+        let pivot_row = match find_pivot(&augmented, col) {
+            Some(row) => row,
+            None => return Ok(CellValue::Error(CellError::InvalidNumber)), // Singular matrix
+        };
+        augmented.swap(col, pivot_row);
+        
+        let pivot = augmented[col][col];
+        for value in augmented[col].iter_mut() {
+            *value /= pivot;
+        }
+        let pivot_values = augmented[col].clone();
+        for (row, values) in augmented.iter_mut().enumerate() {
+            let factor = values[col];
+            if row != col && factor != 0.0 {
+                for (value, pivot_value) in values.iter_mut().zip(&pivot_values) {
+                    *value -= factor * pivot_value;
+                }
+            }
+        }
+    }
+    
+    let inverse = augmented.into_iter()
+        .map(|row| row[size..].iter().map(|&n| CellValue::Number(n)).collect())
+        .collect();
+    Ok(CellValue::Array(inverse))
+}
+
+// MDETERM function - returns the determinant of a square matrix
+fn mdeterm(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() != 1 {
+        return Err(EngineError::EvaluationError(
+            "MDETERM requires exactly 1 argument: array".into()));
+    }
+    
+    let mut matrix = match square_matrix(&args[0]) {
+        Ok(matrix) => matrix,
+        Err(e) => return Ok(CellValue::Error(e)),
+    };
+    
+    // Gaussian elimination; the determinant is the signed product of the pivots
+    let size = matrix.len();
+    let mut determinant = 1.0;
+    for col in 0..size {
+        let pivot_row = match find_pivot(&matrix, col) {
+            Some(row) => row,
+            None => return Ok(CellValue::Number(0.0)),
+        };
+        if pivot_row != col {
+            matrix.swap(col, pivot_row);
+            determinant = -determinant;
+        }
+        
+        let pivot = matrix[col][col];
+        determinant *= pivot;
+        let (upper, lower) = matrix.split_at_mut(col + 1);
+        let pivot_values = &upper[col];
+        for values in lower.iter_mut() {
+            let factor = values[col] / pivot;
+            for (value, pivot_value) in values.iter_mut().zip(pivot_values).skip(col) {
+                *value -= factor * pivot_value;
+            }
+        }
+    }
+    
+    Ok(CellValue::Number(determinant))
+}
+
+// MUNIT function - returns the identity matrix of the given size
+fn munit(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() != 1 {
+        return Err(EngineError::EvaluationError(
+            "MUNIT requires exactly 1 argument: dimension".into()));
+    }
+    
+    let size = extract_number(&args[0], "dimension")?.trunc();
+    if size < 1.0 {
+        return Ok(CellValue::Error(CellError::InvalidValue));
+    }
+    
+    let size = size as usize;
+    let identity = (0..size)
+        .map(|i| (0..size).map(|j| CellValue::Number(if i == j { 1.0 } else { 0.0 })).collect())
+        .collect();
+    Ok(CellValue::Array(identity))
+}
+
+// Helper function to read an array of numbers as a matrix. Errors propagate; text,
+// booleans and blanks make the whole array invalid.
+fn numeric_matrix(value: &CellValue) -> Result<Vec<Vec<f64>>, CellError> {
+    let rows = array_rows(value);
+    let width = rows.first().map_or(0, |row| row.len());
+    if width == 0 || rows.iter().any(|row| row.len() != width) {
+        return Err(CellError::InvalidValue);
+    }
+    
+    rows.iter()
+        .map(|row| row.iter()
+            .map(|value| match value {
+                CellValue::Number(n) | CellValue::Date(n) => Ok(*n),
+                CellValue::Error(e) => Err(e.clone()),
+                _ => Err(CellError::InvalidValue),
+            })
+            .collect())
+        .collect()
+}
+
+// Helper function to read a square matrix for MINVERSE and MDETERM
+fn square_matrix(value: &CellValue) -> Result<Vec<Vec<f64>>, CellError> {
+    let matrix = numeric_matrix(value)?;
+    if matrix.len() != matrix[0].len() {
+        return Err(CellError::InvalidValue);
+    }
+    Ok(matrix)
+}
+
+// Helper function to pick the row at or below `col` with the largest entry in that
+// column (partial pivoting). None when the column is zero, i.e. the matrix is singular.
+fn find_pivot(matrix: &[Vec<f64>], col: usize) -> Option<usize> {
+    let scale = matrix.iter().flatten().fold(0.0_f64, |max, n| max.max(n.abs()));
+    (col..matrix.len())
+        .max_by(|&a, &b| matrix[a][col].abs().total_cmp(&matrix[b][col].abs()))
+        .filter(|&row| matrix[row][col].abs() > scale * 1e-12)
+}
+
 // LOG function - returns the logarithm of a number with the specified base
 fn log_func(args: &[CellValue]) -> Result<CellValue, EngineError> {
     if args.len() < 1 || args.len() > 2 {
@@ -3130,8 +4887,11 @@ fn log_func(args: &[CellValue]) -> Result<CellValue, EngineError> {
     // Extract base (defaults to 10)
     let base = if args.len() == 2 { extract_number(&args[1], "base")? } else { 10.0 };
     
-    if number <= 0.0 || base <= 0.0 || base == 1.0 {
-        return Err(EngineError::EvaluationError("Invalid arguments for LOG".into()));
+    if number <= 0.0 || base <= 0.0 {
+        return Ok(CellValue::Error(CellError::InvalidNumber));
+    }
+    if base == 1.0 {
+        return Ok(CellValue::Error(CellError::DivisionByZero));
     }
     
     // Calculate logarithm
@@ -3151,7 +4911,7 @@ fn ln(args: &[CellValue]) -> Result<CellValue, EngineError> {
     let number = extract_number(&args[0], "number")?;
     
     if number <= 0.0 {
-        return Err(EngineError::EvaluationError("LN argument must be positive".into()));
+        return Ok(CellValue::Error(CellError::InvalidNumber));
     }
     
     // Calculate natural logarithm
@@ -3160,6 +4920,23 @@ fn ln(args: &[CellValue]) -> Result<CellValue, EngineError> {
     Ok(CellValue::Number(result))
 }
 
+// LOG10 function - returns the base-10 logarithm of a number
+fn log10(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() != 1 {
+        return Err(EngineError::EvaluationError(
+            "LOG10 requires exactly 1 argument: number".into()));
+    }
+    
+    // Extract number
+    let number = extract_number(&args[0], "number")?;
+    
+    if number <= 0.0 {
+        return Ok(CellValue::Error(CellError::InvalidNumber));
+    }
+    
+    Ok(CellValue::Number(number.log10()))
+}
+
 // EXP function - returns e raised to the power of a number
 fn exp(args: &[CellValue]) -> Result<CellValue, EngineError> {
     if args.len() != 1 {
@@ -3170,57 +4947,178 @@ fn exp(args: &[CellValue]) -> Result<CellValue, EngineError> {
     // Extract number
     let number = extract_number(&args[0], "number")?;
     
-    // Calculate e^number
+    // Calculate e^number, overflowing to #NUM! like Excel
     let result = number.exp();
+    if !result.is_finite() {
+        return Ok(CellValue::Error(CellError::InvalidNumber));
+    }
     
     Ok(CellValue::Number(result))
 }
 
 // RAND function - returns a random number between 0 and 1
-fn rand(args: &[CellValue]) -> Result<CellValue, EngineError> {
+fn rand(_evaluator: &Evaluator, args: &[AstNode], context: &mut EvaluationContext) -> Result<CellValue, EngineError> {
     if !args.is_empty() {
         return Err(EngineError::EvaluationError(
             "RAND takes no arguments".into()));
     }
     
-    // In a real implementation, we would generate a random number
-    // For now, just returning a placeholder result
-    Ok(CellValue::Number(0.42)) // Placeholder - would return a random number
+    Ok(CellValue::Number(context.workbook.next_random()))
 }
 
-// RANDBETWEEN function - returns a random integer between bottom and top
-fn randbetween(args: &[CellValue]) -> Result<CellValue, EngineError> {
+// RANDBETWEEN function - returns a random integer between bottom and top (inclusive)
+fn randbetween(evaluator: &Evaluator, args: &[AstNode], context: &mut EvaluationContext) -> Result<CellValue, EngineError> {
+    let args = evaluate_plain_args(evaluator, args, context)?;
     if args.len() != 2 {
         return Err(EngineError::EvaluationError(
             "RANDBETWEEN requires exactly 2 arguments: bottom, top".into()));
     }
     
-    // Extract bottom
-    let bottom = extract_number(&args[0], "bottom")?;
-    // Extract top
-    let top = extract_number(&args[1], "top")?;
-    
-    if bottom > top {
-        return Err(EngineError::EvaluationError("Bottom must be less than or equal to top".into()));
-    }
+    // Extract bottom and top, swapping them if given in the wrong order
+    let bottom = extract_number(&args[0], "bottom")?.ceil();
+    let top = extract_number(&args[1], "top")?.floor();
+    let (bottom, top) = if bottom > top { (top, bottom) } else { (bottom, top) };
     
-    // In a real implementation, we would generate a random integer between bottom and top
-    // For now, just returning a placeholder result
-    Ok(CellValue::Number(bottom.floor() + 2.0)) // Placeholder - would return a random integer
+    let offset = (context.workbook.next_random() * (top - bottom + 1.0)).floor();
+    Ok(CellValue::Number(bottom + offset))
 }
 
 // RANDARRAY function - returns a grid of random numbers
-fn randarray(args: &[CellValue]) -> Result<CellValue, EngineError> {
+fn randarray(evaluator: &Evaluator, args: &[AstNode], context: &mut EvaluationContext) -> Result<CellValue, EngineError> {
+    let args = evaluate_plain_args(evaluator, args, context)?;
     if args.len() > 5 {
         return Err(EngineError::EvaluationError(
             "RANDARRAY requires 0-5 arguments: [rows], [cols], [min], [max], [integer]".into()));
     }
     
-    // In a real implementation, we would generate a grid of random numbers
-    // This is a simplified version that only handles the current arguments
+    // Omitted (or blank) arguments take their defaults
+    let number_or = |index: usize, name: &str, default: f64| -> Result<f64, EngineError> {
+        match args.get(index) {
+            None | Some(CellValue::Blank) => Ok(default),
+            Some(value) => extract_number(value, name),
+        }
+    };
+    let rows = number_or(0, "rows", 1.0)?.trunc();
+    let cols = number_or(1, "cols", 1.0)?.trunc();
+    let min = number_or(2, "min", 0.0)?;
+    let max = number_or(3, "max", 1.0)?;
+    let integer = match args.get(4) {
+        Some(CellValue::Boolean(b)) => *b,
+        None | Some(CellValue::Blank) => false,
+        Some(value) => extract_number(value, "integer")? != 0.0,
+    };
     
-    // For now, just returning a placeholder result
-    Ok(CellValue::Number(0.42)) // Placeholder - would return a grid of random numbers
+    if rows < 1.0 || cols < 1.0 || min > max {
+        return Ok(CellValue::Error(CellError::InvalidValue));
+    }
+    
+    let mut grid = Vec::with_capacity(rows as usize);
+    for _ in 0..rows as usize {
+        let mut row = Vec::with_capacity(cols as usize);
+        for _ in 0..cols as usize {
+            let r = context.workbook.next_random();
+            let value = if integer {
+                min.ceil() + (r * (max.floor() - min.ceil() + 1.0)).floor()
+            } else {
+                min + r * (max - min)
+            };
+            row.push(CellValue::Number(value));
+        }
+        grid.push(row);
+    }
+    
+    Ok(CellValue::Array(grid))
+}
+
+// ===== TRIGONOMETRIC FUNCTIONS =====
+
+// Helper function to extract the single number argument of a one-argument math function
+fn single_number(args: &[CellValue], name: &str) -> Result<f64, EngineError> {
+    if args.len() != 1 {
+        return Err(EngineError::EvaluationError(
+            format!("{} requires exactly 1 argument: number", name)));
+    }
+    extract_number(&args[0], "number")
+}
+
+// PI function - returns the value of pi
+fn pi(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if !args.is_empty() {
+        return Err(EngineError::EvaluationError(
+            "PI takes no arguments".into()));
+    }
+    
+    Ok(CellValue::Number(std::f64::consts::PI))
+}
+
+// SIN function - returns the sine of an angle in radians
+fn sin(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    Ok(CellValue::Number(single_number(args, "SIN")?.sin()))
+}
+
+// COS function - returns the cosine of an angle in radians
+fn cos(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    Ok(CellValue::Number(single_number(args, "COS")?.cos()))
+}
+
+// TAN function - returns the tangent of an angle in radians
+fn tan(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    Ok(CellValue::Number(single_number(args, "TAN")?.tan()))
+}
+
+// ASIN function - returns the arcsine of a number, in radians
+fn asin(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    let number = single_number(args, "ASIN")?;
+    
+    if !(-1.0..=1.0).contains(&number) {
+        return Ok(CellValue::Error(CellError::InvalidNumber));
+    }
+    
+    Ok(CellValue::Number(number.asin()))
+}
+
+// ACOS function - returns the arccosine of a number, in radians
+fn acos(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    let number = single_number(args, "ACOS")?;
+    
+    if !(-1.0..=1.0).contains(&number) {
+        return Ok(CellValue::Error(CellError::InvalidNumber));
+    }
+    
+    Ok(CellValue::Number(number.acos()))
+}
+
+// ATAN function - returns the arctangent of a number, in radians
+fn atan(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    Ok(CellValue::Number(single_number(args, "ATAN")?.atan()))
+}
+
+// ATAN2 function - returns the angle of the point (x_num, y_num) from the x-axis, in radians
+fn atan2(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    if args.len() != 2 {
+        return Err(EngineError::EvaluationError(
+            "ATAN2 requires exactly 2 arguments: x_num, y_num".into()));
+    }
+    
+    // Note Excel's argument order is x first, unlike f64::atan2
+    let x = extract_number(&args[0], "x_num")?;
+    let y = extract_number(&args[1], "y_num")?;
+    
+    if x == 0.0 && y == 0.0 {
+        return Ok(CellValue::Error(CellError::DivisionByZero));
+    }
+    
+    Ok(CellValue::Number(y.atan2(x)))
+}
+
+// DEGREES function - converts radians to degrees
+fn degrees(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    Ok(CellValue::Number(single_number(args, "DEGREES")?.to_degrees()))
+}
+
+// RADIANS function - converts degrees to radians
+fn radians(args: &[CellValue]) -> Result<CellValue, EngineError> {
+    Ok(CellValue::Number(single_number(args, "RADIANS")?.to_radians()))
 }
 
 // ===== STATISTICAL FUNCTIONS (ADDITIONAL) =====
@@ -3288,6 +5186,78 @@ fn aggregate(args: &[CellValue]) -> Result<CellValue, EngineError> {
     Ok(CellValue::Number(10.0)) // Placeholder - would return the aggregate result
 }
 
+// SUBTOTAL function - aggregates references, leaving out cells that hold SUBTOTAL
+// formulas themselves and, for function codes 101-111, cells in hidden rows
+fn subtotal(evaluator: &Evaluator, args: &[AstNode], context: &mut EvaluationContext) -> Result<CellValue, EngineError> {
+    if args.len() < 2 {
+        return Err(EngineError::EvaluationError(
+            "SUBTOTAL requires at least 2 arguments: function_num, ref1, [ref2], ...".into()));
+    }
+    
+    let function_num = extract_number(&evaluator.evaluate(&args[0], context)?, "function_num")?.trunc() as i64;
+    let (function, skip_hidden) = match function_num {
+        1..=11 => (function_num, false),
+        101..=111 => (function_num - 100, true),
+        _ => return Ok(CellValue::Error(CellError::InvalidValue)),
+    };
+    
+    let workbook = context.workbook;
+    let mut values = Vec::new();
+    for arg in &args[1..] {
+        let reference = match arg {
+            AstNode::Reference(reference) => reference,
+            other => {
+                values.push(evaluator.evaluate(other, context)?);
+                continue;
+            },
+        };
+        
+        let sheet_name = reference.sheet().unwrap_or(context.current_sheet).to_string();
+        let sheet = match workbook.get_sheet(&sheet_name) {
+            Some(sheet) => sheet,
+            None => return Ok(CellValue::Error(CellError::InvalidReference)),
+        };
+        
+        // Only populated cells are visited; every aggregate ignores blanks
+        let addresses: Vec<CellAddress> = sheet.get_range_cells(&reference.bounds())
+            .filter(|(address, cell)| !(is_subtotal_formula(&cell.value) || skip_hidden && sheet.is_row_hidden(address.row)))
+            .map(|(address, _)| address)
+            .collect();
+        // Kept together as a range, so the aggregates below skip its text
+        let mut cells = Vec::with_capacity(addresses.len());
+        for address in addresses {
+            cells.push(context.resolve_reference(&Reference::SheetCell { sheet: sheet_name.clone(), address })?);
+        }
+        values.push(CellValue::Array(vec![cells]));
+    }
+    
+    // COUNTA counts referenced text; the others only read numbers from references
+    let values = if function == 3 { flatten_args(&values) } else { flatten_numeric_args(&values) };
+    let values: Vec<CellValue> = values.iter().map(CellValue::date_as_number).collect();
+    match function {
+        1 => average(&values),
+        2 => count(&values),
+        3 => counta(&values),
+        4 => max(&values),
+        5 => min(&values),
+        6 => product(&values),
+        7 => stdev(&values),
+        8 => stdevp(&values),
+        9 => sum(&values),
+        10 => var_func(&values),
+        _ => varp(&values),
+    }
+}
+
+// Whether a cell holds a formula that calls SUBTOTAL, whose result SUBTOTAL leaves
+// out so nested subtotals aren't counted twice
+fn is_subtotal_formula(value: &CellValue) -> bool {
+    match value {
+        CellValue::Formula(formula) => formula.to_uppercase().contains("SUBTOTAL("),
+        _ => false,
+    }
+}
+
 // ===== TEXT FUNCTIONS (ADDITIONAL) =====
 
 // TEXTJOIN function - concatenates with delimiter, skipping blanks
@@ -3318,14 +5288,7 @@ fn textjoin(args: &[CellValue]) -> Result<CellValue, EngineError> {
     let mut first = true;
     
     for i in 2..args.len() {
-        let text_value = match &args[i] {
-            CellValue::Text(t) => t.clone(),
-            CellValue::Number(n) => n.to_string(),
-            CellValue::Boolean(b) => b.to_string(),
-            CellValue::Blank => "".to_string(),
-            CellValue::Error(e) => return Err(EngineError::CellValueError(e.clone())),
-            CellValue::Formula(_) => return Err(EngineError::EvaluationError("Cannot use unevaluated formula in TEXTJOIN".into())),
-        };
+        let text_value = text_arg(&args[i])?;
         
         if text_value.is_empty() && ignore_empty {
             continue;
@@ -3410,11 +5373,12 @@ fn sequence(args: &[CellValue]) -> Result<CellValue, EngineError> {
         return Err(EngineError::EvaluationError("Columns must be a positive integer".into()));
     }
     
-    // In a real implementation, we would generate a sequence of numbers
-    // This is a simplified version that only handles the current arguments
-    
-    // For now, just returning a placeholder result
-    Ok(CellValue::Number(start)) // Placeholder - would return the sequence
+    // Numbers fill the array row by row
+    let (rows, cols) = (rows as usize, cols as usize);
+    let array = (0..rows)
+        .map(|row| (0..cols).map(|col| CellValue::Number(start + step * (row * cols + col) as f64)).collect())
+        .collect();
+    Ok(CellValue::Array(array))
 }
 
 // LET function - assigns names to expressions for readability
@@ -3445,3 +5409,782 @@ fn lambda(args: &[CellValue]) -> Result<CellValue, EngineError> {
     // In practice, LAMBDAs are typically stored and then called later
     Ok(CellValue::Text("<LAMBDA function defined>".to_string()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Workbook;
+    
+    // A workbook with a single sheet, Sheet1, holding the given cells
+    fn workbook(cells: &[(&str, CellValue)]) -> Workbook {
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Sheet1".to_string()).unwrap();
+        for (reference, value) in cells {
+            let address = CellAddress::from_a1(reference).unwrap();
+            workbook.set_cell_value("Sheet1", address.row, address.col, value.clone()).unwrap();
+        }
+        workbook
+    }
+    
+    // The value a formula shows in a cell of the workbook
+    fn eval_in(workbook: &Workbook, formula: &str) -> CellValue {
+        match workbook.evaluate_formula("Sheet1", formula) {
+            Ok(value) => value,
+            Err(EngineError::CellValueError(error)) => CellValue::Error(error),
+            Err(e) => panic!("{} failed: {}", formula, e),
+        }
+    }
+    
+    fn eval(formula: &str) -> CellValue {
+        eval_in(&workbook(&[]), formula)
+    }
+    
+    fn number(n: f64) -> CellValue {
+        CellValue::Number(n)
+    }
+    
+    fn error(error: CellError) -> CellValue {
+        CellValue::Error(error)
+    }
+    
+    fn assert_close(value: CellValue, expected: f64) {
+        match value {
+            CellValue::Number(n) if (n - expected).abs() < 1e-9 => {},
+            other => panic!("expected {}, got {:?}", expected, other),
+        }
+    }
+    
+    #[test]
+    fn sumproduct_multiplies_corresponding_elements() {
+        let workbook = workbook(&[
+            ("A1", number(1.0)), ("A2", number(2.0)), ("A3", number(3.0)),
+            ("B1", number(4.0)), ("B2", number(5.0)), ("B3", number(6.0)),
+            ("C1", "x".into()),
+        ]);
+        assert_eq!(eval_in(&workbook, "=SUMPRODUCT(A1:A3,B1:B3)"), number(32.0));
+        assert_eq!(eval_in(&workbook, "=SUMPRODUCT(A1:A3)"), number(6.0));
+        // Text counts as zero
+        assert_eq!(eval_in(&workbook, "=SUMPRODUCT(A1:C1,A2:C2)"), number(22.0));
+        assert_eq!(eval_in(&workbook, "=SUMPRODUCT(A1:A3,B1:B2)"), error(CellError::InvalidValue));
+    }
+    
+    #[test]
+    fn information_functions_classify_values() {
+        let workbook = workbook(&[
+            ("A1", number(1.0)), ("A2", "text".into()), ("A3", true.into()), ("A4", "=1/0".into()), ("A5", "=A1*2".into()),
+        ]);
+        let check = |formula: &str, expected: CellValue| assert_eq!(eval_in(&workbook, formula), expected, "{}", formula);
+        
+        check("=ISLOGICAL(A3)", true.into());
+        check("=ISLOGICAL(A1)", false.into());
+        check("=ISLOGICAL(FALSE)", true.into());
+        check("=ISNONTEXT(A1)", true.into());
+        check("=ISNONTEXT(A6)", true.into());
+        check("=ISNONTEXT(A2)", false.into());
+        check("=ISREF(A1)", true.into());
+        check("=ISREF(A1:B2)", true.into());
+        check("=ISREF(1)", false.into());
+        check("=ISFORMULA(A5)", true.into());
+        check("=ISFORMULA(A1)", false.into());
+        check("=TYPE(A1)", number(1.0));
+        check("=TYPE(A2)", number(2.0));
+        check("=TYPE(A3)", number(4.0));
+        check("=TYPE(A4)", number(16.0));
+        check("=TYPE(A6)", number(1.0));
+    }
+    
+    #[test]
+    fn isna_and_iserr_split_the_errors() {
+        let workbook = workbook(&[("A1", "=1/0".into()), ("A2", "=NA()".into()), ("A3", number(1.0))]);
+        let check = |formula: &str, expected: bool| assert_eq!(eval_in(&workbook, formula), expected.into(), "{}", formula);
+        
+        check("=ISNA(A1)", false);
+        check("=ISERR(A1)", true);
+        check("=ISERROR(A1)", true);
+        check("=ISNA(A2)", true);
+        check("=ISERR(A2)", false);
+        check("=ISERROR(A2)", true);
+        for function in ["ISNA", "ISERR", "ISERROR"] {
+            check(&format!("={}(A3)", function), false);
+        }
+    }
+    
+    #[test]
+    fn date_functions_follow_the_date_system() {
+        let mut workbook = workbook(&[]);
+        assert_eq!(eval_in(&workbook, "=DATE(2024,1,1)"), CellValue::Date(45292.0));
+        assert_eq!(eval_in(&workbook, "=YEAR(45292)"), number(2024.0));
+        
+        workbook.set_date_system(crate::date::DateSystem::Excel1904).unwrap();
+        assert_eq!(eval_in(&workbook, "=DATE(2024,1,1)"), CellValue::Date(43830.0));
+        assert_eq!(eval_in(&workbook, "=YEAR(43830)"), number(2024.0));
+        assert_eq!(eval_in(&workbook, "=YEAR(DATE(2024,1,1))"), number(2024.0));
+    }
+    
+    #[test]
+    fn aggregates_take_ranges_literals_and_references_together() {
+        let workbook = workbook(&[
+            ("A1", number(1.0)), ("A2", number(2.0)), ("A3", number(3.0)), ("B1", number(4.0)),
+        ]);
+        assert_eq!(eval_in(&workbook, "=SUM(A1:A3,10,B1)"), number(20.0));
+        assert_eq!(eval_in(&workbook, "=AVERAGE(A1:A3,10,B1)"), number(4.0));
+        assert_eq!(eval_in(&workbook, "=MAX(A1:A3,-1,B1)"), number(4.0));
+    }
+    
+    #[test]
+    fn aggregates_skip_text_in_references_but_not_in_literals() {
+        let workbook = workbook(&[
+            ("A1", "Amount".into()), ("A2", number(1.0)), ("A3", number(2.0)), ("A4", number(6.0)), ("A5", true.into()),
+        ]);
+        let check = |formula: &str, expected: CellValue| assert_eq!(eval_in(&workbook, formula), expected, "{}", formula);
+        
+        check("=SUM(A1:A5)", number(9.0));
+        check("=AVERAGE(A1:A5)", number(3.0));
+        check("=MAX(A1:A5)", number(6.0));
+        check("=MIN(A1:A5)", number(1.0));
+        check("=PRODUCT(A1:A5)", number(12.0));
+        check("=MEDIAN(A1:A5)", number(2.0));
+        check("=STDEV(A1:A5)", number(7f64.sqrt()));
+        // A single referenced cell is skipped the same way
+        check("=SUM(A1,A2)", number(1.0));
+        // Text typed into the call is rejected, logical values count
+        check("=SUM(\"x\",1)", error(CellError::InvalidValue));
+        check("=SUM(TRUE,1)", number(2.0));
+    }
+    
+    #[test]
+    fn modern_statistics_names_match_the_originals() {
+        let workbook = workbook(&[
+            ("A1", number(2.0)), ("A2", number(4.0)), ("A3", number(4.0)), ("A4", number(5.0)), ("A5", number(9.0)),
+            ("B1", number(7.0)),
+        ]);
+        for (modern, original) in [("STDEV.S", "STDEV"), ("STDEV.P", "STDEVP"), ("VAR.S", "VAR"), ("VAR.P", "VARP")] {
+            let value = eval_in(&workbook, &format!("={}(A1:A5)", modern));
+            assert!(matches!(value, CellValue::Number(_)), "{}", modern);
+            assert_eq!(value, eval_in(&workbook, &format!("={}(A1:A5)", original)), "{}", modern);
+        }
+        assert_close(eval_in(&workbook, "=VAR.P(A1:A5)"), 5.36);
+        
+        // One value has no spread in the population, and no sample spread at all
+        assert_eq!(eval_in(&workbook, "=STDEVP(B1)"), number(0.0));
+        assert_eq!(eval_in(&workbook, "=STDEV.P(B1)"), number(0.0));
+        assert_eq!(eval_in(&workbook, "=STDEV.S(B1)"), error(CellError::DivisionByZero));
+    }
+    
+    #[test]
+    fn averagea_counts_text_and_logical_values() {
+        let workbook = workbook(&[("A1", number(4.0)), ("A2", "x".into()), ("A3", true.into())]);
+        // (4 + 0 + 1) / 3
+        assert_eq!(eval_in(&workbook, "=AVERAGEA(A1:A3)"), number(5.0 / 3.0));
+        assert_eq!(eval_in(&workbook, "=COUNTBLANK(A1:A4)"), number(1.0));
+    }
+    
+    #[test]
+    fn order_statistics_match_excel() {
+        let mut workbook = workbook(&[("B1", number(4.0)), ("B2", number(4.0)), ("B3", number(1.0))]);
+        for (row, value) in [1.0, 2.0, 4.0, 7.0, 8.0, 9.0, 10.0, 12.0].into_iter().enumerate() {
+            workbook.set_cell_value("Sheet1", row as u32, 0, value).unwrap();
+        }
+        let check = |formula: &str, expected: f64| assert_close(eval_in(&workbook, formula), expected);
+        
+        check("=QUARTILE(A1:A8,0)", 1.0);
+        check("=QUARTILE(A1:A8,1)", 3.5);
+        check("=QUARTILE(A1:A8,2)", 7.5);
+        check("=QUARTILE(A1:A8,3)", 9.25);
+        check("=QUARTILE(A1:A8,4)", 12.0);
+        check("=PERCENTRANK(A1:A8,8)", 0.571);
+        check("=PERCENTRANK(A1:A8,5)", 0.333);
+        check("=RANK(7,A1:A8)", 5.0);
+        check("=RANK(7,A1:A8,1)", 4.0);
+        // Ties share the best rank
+        check("=RANK(4,B1:B3)", 1.0);
+        check("=RANK(1,B1:B3)", 3.0);
+        check("=LARGE(A1:A8,2)", 10.0);
+        check("=SMALL(A1:A8,3)", 4.0);
+        
+        for formula in ["=QUARTILE(A1:A8,5)", "=LARGE(A1:A8,9)", "=SMALL(A1:A8,0)"] {
+            assert_eq!(eval_in(&workbook, formula), error(CellError::InvalidNumber), "{}", formula);
+        }
+        // Values outside the data
+        assert_eq!(eval_in(&workbook, "=PERCENTRANK(A1:A8,20)"), error(CellError::NotAvailable));
+        assert_eq!(eval_in(&workbook, "=RANK(5,A1:A8)"), error(CellError::NotAvailable));
+    }
+    
+    #[test]
+    fn rounding_functions_handle_negative_numbers() {
+        let check = |formula: &str, expected: f64| assert_close(eval(formula), expected);
+        
+        check("=INT(2.5)", 2.0);
+        check("=INT(-2.5)", -3.0);
+        check("=TRUNC(-2.57)", -2.0);
+        check("=TRUNC(-2.578,2)", -2.57);
+        check("=TRUNC(123.4,-1)", 120.0);
+        check("=MROUND(10,3)", 9.0);
+        check("=MROUND(-10,-3)", -9.0);
+        check("=CEILING(2.5,1)", 3.0);
+        check("=CEILING(-2.5,-2)", -4.0);
+        check("=CEILING(-2.5,2)", -2.0);
+        check("=FLOOR(2.5,1)", 2.0);
+        check("=FLOOR(-2.5,-2)", -2.0);
+        check("=FLOOR(-2.5,2)", -4.0);
+        check("=CEILING.MATH(6.3,5)", 10.0);
+        check("=CEILING.MATH(-5.5)", -5.0);
+        check("=CEILING.MATH(-5.5,2,-1)", -6.0);
+        check("=FLOOR.MATH(24.3,5)", 20.0);
+        check("=FLOOR.MATH(-5.5)", -6.0);
+        check("=FLOOR.MATH(-5.5,2,-1)", -4.0);
+        
+        // Mismatched signs
+        assert_eq!(eval("=MROUND(5,-2)"), error(CellError::InvalidNumber));
+        assert_eq!(eval("=FLOOR(2.5,-2)"), error(CellError::InvalidNumber));
+    }
+    
+    #[test]
+    fn logarithms_and_exponentials() {
+        let check = |formula: &str, expected: f64| assert_close(eval(formula), expected);
+        
+        check("=LOG(100)", 2.0);
+        check("=LOG(8,2)", 3.0);
+        check("=LOG10(1000)", 3.0);
+        check("=LN(1)", 0.0);
+        check("=LN(EXP(2))", 2.0);
+        check("=EXP(0)", 1.0);
+        
+        for formula in ["=LN(0)", "=LN(-1)", "=LOG(-10)", "=LOG(10,0)", "=LOG10(0)"] {
+            assert_eq!(eval(formula), error(CellError::InvalidNumber), "{}", formula);
+        }
+        // Base 1 has a zero logarithm
+        assert_eq!(eval("=LOG(10,1)"), error(CellError::DivisionByZero));
+    }
+    
+    #[test]
+    fn mod_takes_the_sign_of_the_divisor() {
+        assert_eq!(eval("=MOD(3,2)"), number(1.0));
+        assert_eq!(eval("=MOD(-3,2)"), number(1.0));
+        assert_eq!(eval("=MOD(3,-2)"), number(-1.0));
+        assert_eq!(eval("=MOD(-3,-2)"), number(-1.0));
+        assert_close(eval("=MOD(5.5,2)"), 1.5);
+        assert_eq!(eval("=MOD(3,0)"), error(CellError::DivisionByZero));
+    }
+    
+    #[test]
+    fn power_edge_cases_match_excel() {
+        assert_eq!(eval("=POWER(0,0)"), number(1.0));
+        assert_eq!(eval("=POWER(2,-1)"), number(0.5));
+        assert_eq!(eval("=POWER(-8,1/3)"), error(CellError::InvalidNumber));
+        assert_eq!(eval("=POWER(0,-1)"), error(CellError::DivisionByZero));
+    }
+    
+    #[test]
+    fn trigonometry() {
+        let check = |formula: &str, expected: f64| assert_close(eval(formula), expected);
+        
+        check("=SIN(PI()/2)", 1.0);
+        check("=COS(0)", 1.0);
+        check("=TAN(PI()/4)", 1.0);
+        check("=ASIN(1)", std::f64::consts::FRAC_PI_2);
+        check("=ACOS(1)", 0.0);
+        check("=ATAN(1)", std::f64::consts::FRAC_PI_4);
+        // ATAN2 takes x first, as in Excel
+        check("=ATAN2(1,1)", std::f64::consts::FRAC_PI_4);
+        check("=ATAN2(-1,0)", std::f64::consts::PI);
+        check("=DEGREES(PI())", 180.0);
+        check("=RADIANS(180)", std::f64::consts::PI);
+        
+        assert_eq!(eval("=ASIN(2)"), error(CellError::InvalidNumber));
+        assert_eq!(eval("=ACOS(-1.5)"), error(CellError::InvalidNumber));
+        assert_eq!(eval("=ATAN2(0,0)"), error(CellError::DivisionByZero));
+    }
+    
+    #[test]
+    fn base_conversions_cover_every_pair() {
+        // 10 and -1 in each base; negative numbers are 10-digit two's complement
+        let bases = [
+            ("BIN", ["1010", "1111111111"]),
+            ("OCT", ["12", "7777777777"]),
+            ("DEC", ["10", "-1"]),
+            ("HEX", ["A", "FFFFFFFFFF"]),
+        ];
+        for (from, from_values) in &bases {
+            for (to, to_values) in &bases {
+                if from == to {
+                    continue;
+                }
+                for (input, expected) in from_values.iter().zip(to_values) {
+                    let formula = format!("={}2{}(\"{}\")", from, to, input);
+                    let expected = match *to {
+                        "DEC" => number(expected.parse().unwrap()),
+                        _ => CellValue::Text(expected.to_string()),
+                    };
+                    assert_eq!(eval(&formula), expected, "{}", formula);
+                }
+            }
+        }
+        
+        assert_eq!(eval("=DEC2BIN(5,8)"), CellValue::Text("00000101".to_string()));
+        for formula in ["=BIN2DEC(\"102\")", "=OCT2DEC(\"8\")", "=HEX2DEC(\"G\")", "=DEC2BIN(512)", "=DEC2OCT(-536870913)", "=HEX2BIN(\"200\")"] {
+            assert_eq!(eval(formula), error(CellError::InvalidNumber), "{}", formula);
+        }
+    }
+    
+    #[test]
+    fn text_staples() {
+        let text = |s: &str| CellValue::Text(s.to_string());
+        
+        // Text compares case-insensitively, so casing is checked on the raw string
+        let cased = |formula: &str| match eval(formula) {
+            CellValue::Text(s) => s,
+            other => panic!("{} gave {:?}", formula, other),
+        };
+        assert_eq!(cased("=PROPER(\"hello wORLD o'neil 2nd\")"), "Hello World O'Neil 2Nd");
+        assert_eq!(cased("=UPPER(\"mIxed 1a\")"), "MIXED 1A");
+        assert_eq!(cased("=LOWER(\"mIxed 1A\")"), "mixed 1a");
+        assert_eq!(eval("=CLEAN(\"a\"&CHAR(7)&\"b\"&CHAR(10))"), text("ab"));
+        assert_eq!(eval("=REPT(\"ab\",3)"), text("ababab"));
+        assert_eq!(eval("=REPT(\"ab\",0)"), text(""));
+        assert_eq!(eval("=LEN(REPT(\"a\",32767))"), number(32767.0));
+        assert_eq!(eval("=REPT(\"a\",32768)"), error(CellError::InvalidValue));
+        assert_eq!(eval("=REPT(\"a\",-1)"), error(CellError::InvalidValue));
+        assert_eq!(eval("=CHAR(65)"), text("A"));
+        assert_eq!(eval("=CODE(\"Apple\")"), number(65.0));
+        assert_eq!(eval("=CHAR(0)"), error(CellError::InvalidValue));
+        assert_eq!(eval("=UNICHAR(8364)"), text("€"));
+        assert_eq!(eval("=UNICODE(\"€\")"), number(8364.0));
+    }
+    
+    #[test]
+    fn search_ignores_case_and_reads_wildcards() {
+        assert_eq!(eval("=SEARCH(\"WORLD\",\"Hello world\")"), number(7.0));
+        assert_eq!(eval("=FIND(\"WORLD\",\"Hello world\")"), error(CellError::InvalidValue));
+        assert_eq!(eval("=SEARCH(\"o\",\"Hello world\",6)"), number(8.0));
+        assert_eq!(eval("=SEARCH(\"w*d\",\"Hello world\")"), number(7.0));
+        assert_eq!(eval("=SEARCH(\"h?llo\",\"Say hello\")"), number(5.0));
+        // ~ escapes a wildcard
+        assert_eq!(eval("=SEARCH(\"~?\",\"Why?\")"), number(4.0));
+        assert_eq!(eval("=SEARCH(\"xyz\",\"Hello\")"), error(CellError::InvalidValue));
+    }
+    
+    #[test]
+    fn replace_splices_by_position() {
+        let text = |s: &str| CellValue::Text(s.to_string());
+        assert_eq!(eval("=REPLACE(\"abcdef\",3,2,\"XY\")"), text("abXYef"));
+        assert_eq!(eval("=REPLACE(\"abc\",2,0,\"-\")"), text("a-bc"));
+        assert_eq!(eval("=REPLACE(\"abc\",10,1,\"!\")"), text("abc!"));
+        assert_eq!(eval("=REPLACE(\"abc\",0,1,\"x\")"), error(CellError::InvalidValue));
+    }
+    
+    #[test]
+    fn text_is_coerced_back_to_numbers() {
+        assert_eq!(eval("=VALUE(\"$1,234.50\")"), number(1234.5));
+        assert_eq!(eval("=VALUE(\"12%\")"), number(0.12));
+        assert_eq!(eval("=VALUE(\" -7 \")"), number(-7.0));
+        assert_eq!(eval("=VALUE(\"(100)\")"), number(-100.0));
+        assert_eq!(eval("=VALUE(\"twelve\")"), error(CellError::InvalidValue));
+        assert_eq!(eval("=NUMBERVALUE(\"1.234,5\",\",\",\".\")"), number(1234.5));
+        assert_eq!(eval("=NUMBERVALUE(\"2,5%\",\",\")"), number(0.025));
+        assert_eq!(eval("=NUMBERVALUE(\"1,2,3\",\",\")"), error(CellError::InvalidValue));
+    }
+    
+    #[test]
+    fn t_keeps_only_text() {
+        let workbook = workbook(&[("A1", "words".into()), ("A2", number(3.0))]);
+        assert_eq!(eval_in(&workbook, "=T(A1)"), CellValue::Text("words".to_string()));
+        assert_eq!(eval_in(&workbook, "=T(A2)"), CellValue::Text(String::new()));
+        assert_eq!(eval_in(&workbook, "=T(TRUE)"), CellValue::Text(String::new()));
+    }
+    
+    #[test]
+    fn switch_returns_the_first_match_or_the_default() {
+        let workbook = workbook(&[("A1", number(2.0)), ("A2", "b".into())]);
+        let text = |s: &str| CellValue::Text(s.to_string());
+        
+        assert_eq!(eval_in(&workbook, "=SWITCH(A1,1,\"one\",2,\"two\",\"other\")"), text("two"));
+        assert_eq!(eval_in(&workbook, "=SWITCH(A1,2,\"first\",2,\"second\")"), text("first"));
+        // Text matches without regard to case, like =
+        assert_eq!(eval_in(&workbook, "=SWITCH(A2,\"B\",\"bee\")"), text("bee"));
+        assert_eq!(eval_in(&workbook, "=SWITCH(A1,1,\"one\",\"other\")"), text("other"));
+        assert_eq!(eval_in(&workbook, "=SWITCH(A1,1,\"one\",3,\"three\")"), error(CellError::NotAvailable));
+    }
+    
+    #[test]
+    fn iferror_and_ifna_catch_raised_errors_and_error_values() {
+        let workbook = workbook(&[("A1", error(CellError::DivisionByZero)), ("A2", "=NA()".into())]);
+        let text = |s: &str| CellValue::Text(s.to_string());
+        
+        // NPV raises an engine error for a text value rather than returning an error value
+        assert!(matches!(workbook.evaluate_formula("Sheet1", "=NPV(0.1,\"x\")"), Err(EngineError::EvaluationError(_))));
+        assert_eq!(eval_in(&workbook, "=IFERROR(NPV(0.1,\"x\"),\"safe\")"), text("safe"));
+        assert_eq!(eval_in(&workbook, "=IFERROR(DATE(\"x\",1,1),\"safe\")"), text("safe"));
+        assert_eq!(eval_in(&workbook, "=IFERROR(A1,\"safe\")"), text("safe"));
+        assert_eq!(eval_in(&workbook, "=IFERROR(1/0,\"safe\")"), text("safe"));
+        assert_eq!(eval_in(&workbook, "=IFERROR(5,\"safe\")"), number(5.0));
+        
+        assert_eq!(eval_in(&workbook, "=IFNA(A2,\"missing\")"), text("missing"));
+        assert_eq!(eval_in(&workbook, "=IFNA(VLOOKUP(9,B1:C2,2,FALSE),\"missing\")"), text("missing"));
+        // Other errors pass through IFNA
+        assert_eq!(eval_in(&workbook, "=IFNA(A1,\"missing\")"), error(CellError::DivisionByZero));
+        assert_eq!(eval_in(&workbook, "=IFNA(DATE(\"x\",1,1),\"missing\")"), error(CellError::InvalidValue));
+    }
+    
+    #[test]
+    fn argument_problems_are_error_values() {
+        // The formula evaluates to an error instead of failing
+        assert!(matches!(workbook(&[]).evaluate_formula("Sheet1", "=SQRT(-1)"), Ok(CellValue::Error(CellError::InvalidNumber))));
+        
+        // So a cell can hold it, and other formulas can react to it
+        let workbook = workbook(&[("A1", "=SQRT(-1)".into()), ("A2", "=IFERROR(A1,0)".into()), ("A3", "=ISERR(A1)".into())]);
+        assert_eq!(workbook.get_cell_value("Sheet1", 0, 0).unwrap(), error(CellError::InvalidNumber));
+        assert_eq!(workbook.get_cell_value("Sheet1", 1, 0).unwrap(), number(0.0));
+        assert_eq!(workbook.get_cell_value("Sheet1", 2, 0).unwrap(), true.into());
+        
+        assert_eq!(eval("=ABS(\"x\")"), error(CellError::InvalidValue));
+        assert_eq!(eval("=UPPER(1/0)"), error(CellError::DivisionByZero));
+        assert_eq!(eval("=MEDIAN(\"x\")"), error(CellError::InvalidValue));
+    }
+    
+    #[test]
+    fn hlookup_mirrors_vlookup_across_rows() {
+        // The same table laid out by rows (A1:D2) and by columns (F1:G4)
+        let workbook = workbook(&[
+            ("A1", number(1.0)), ("B1", number(3.0)), ("C1", number(5.0)), ("D1", number(7.0)),
+            ("A2", "a".into()), ("B2", "b".into()), ("C2", "c".into()), ("D2", "d".into()),
+            ("F1", number(1.0)), ("F2", number(3.0)), ("F3", number(5.0)), ("F4", number(7.0)),
+            ("G1", "a".into()), ("G2", "b".into()), ("G3", "c".into()), ("G4", "d".into()),
+        ]);
+        let cases = [
+            ("5,{},2,FALSE", CellValue::Text("c".to_string())),
+            ("4,{},2", CellValue::Text("b".to_string())),
+            ("9,{},2,TRUE", CellValue::Text("d".to_string())),
+            ("4,{},2,FALSE", error(CellError::NotAvailable)),
+            ("0,{},2", error(CellError::NotAvailable)),
+            ("5,{},3,FALSE", error(CellError::InvalidReference)),
+            ("5,{},0,FALSE", error(CellError::InvalidValue)),
+        ];
+        for (args, expected) in cases {
+            assert_eq!(eval_in(&workbook, &format!("=HLOOKUP({})", args.replace("{}", "A1:D2"))), expected, "HLOOKUP({})", args);
+            assert_eq!(eval_in(&workbook, &format!("=VLOOKUP({})", args.replace("{}", "F1:G4"))), expected, "VLOOKUP({})", args);
+        }
+    }
+    
+    #[test]
+    fn xlookup_and_xmatch_honor_match_and_search_modes() {
+        let workbook = workbook(&[
+            ("A1", "apple".into()), ("A2", "banana".into()), ("A3", "cherry".into()), ("A4", "banana".into()),
+            ("B1", number(10.0)), ("B2", number(20.0)), ("B3", number(30.0)), ("B4", number(40.0)),
+            ("C1", number(5.0)), ("C2", number(15.0)), ("C3", number(25.0)),
+        ]);
+        let text = |s: &str| CellValue::Text(s.to_string());
+        
+        // Exact
+        assert_eq!(eval_in(&workbook, "=XLOOKUP(\"cherry\",A1:A4,B1:B4)"), number(30.0));
+        assert_eq!(eval_in(&workbook, "=XMATCH(\"CHERRY\",A1:A4)"), number(3.0));
+        assert_eq!(eval_in(&workbook, "=XLOOKUP(\"kiwi\",A1:A4,B1:B4)"), error(CellError::NotAvailable));
+        assert_eq!(eval_in(&workbook, "=XLOOKUP(\"kiwi\",A1:A4,B1:B4,\"none\")"), text("none"));
+        
+        // Exact or next larger / smaller
+        assert_eq!(eval_in(&workbook, "=XLOOKUP(12,C1:C3,A1:A3,\"none\",1)"), text("banana"));
+        assert_eq!(eval_in(&workbook, "=XMATCH(12,C1:C3,1)"), number(2.0));
+        assert_eq!(eval_in(&workbook, "=XMATCH(12,C1:C3,-1)"), number(1.0));
+        assert_eq!(eval_in(&workbook, "=XMATCH(30,C1:C3,1)"), error(CellError::NotAvailable));
+        
+        // Wildcard
+        assert_eq!(eval_in(&workbook, "=XLOOKUP(\"ch*\",A1:A4,B1:B4,\"none\",2)"), number(30.0));
+        assert_eq!(eval_in(&workbook, "=XMATCH(\"?pple\",A1:A4,2)"), number(1.0));
+        assert_eq!(eval_in(&workbook, "=XMATCH(\"ch*\",A1:A4)"), error(CellError::NotAvailable));
+        
+        // Last to first finds the later duplicate
+        assert_eq!(eval_in(&workbook, "=XLOOKUP(\"banana\",A1:A4,B1:B4)"), number(20.0));
+        assert_eq!(eval_in(&workbook, "=XLOOKUP(\"banana\",A1:A4,B1:B4,\"none\",0,-1)"), number(40.0));
+        assert_eq!(eval_in(&workbook, "=XMATCH(\"banana\",A1:A4,0,-1)"), number(4.0));
+        
+        // Binary search over ascending keys
+        assert_eq!(eval_in(&workbook, "=XMATCH(25,C1:C3,0,2)"), number(3.0));
+        assert_eq!(eval_in(&workbook, "=XMATCH(20,C1:C3,1,2)"), number(3.0));
+    }
+    
+    #[test]
+    fn xlookup_returns_the_whole_matched_record() {
+        let mut workbook = workbook(&[
+            ("A1", number(1.0)), ("B1", "Ann".into()), ("C1", "Oslo".into()), ("D1", number(31.0)),
+            ("A2", number(2.0)), ("B2", "Bo".into()), ("C2", "Rome".into()), ("D2", number(45.0)),
+            ("A3", number(3.0)), ("B3", "Cy".into()), ("C3", "Lima".into()), ("D3", number(27.0)),
+        ]);
+        let record = vec![vec![CellValue::Text("Bo".to_string()), CellValue::Text("Rome".to_string()), number(45.0)]];
+        assert_eq!(eval_in(&workbook, "=XLOOKUP(2,A1:A3,B1:D3)"), CellValue::Array(record.clone()));
+        
+        // In a cell the record is a spill range that other formulas read with `#`
+        workbook.set_cell_value("Sheet1", 5, 0, "=XLOOKUP(2,A1:A3,B1:D3)").unwrap();
+        assert_eq!(workbook.get_cell_value("Sheet1", 5, 0).unwrap(), CellValue::Array(record));
+        assert_eq!(eval_in(&workbook, "=COUNTA(A6#)"), number(3.0));
+        assert_eq!(eval_in(&workbook, "=SUM(A6#)"), number(45.0));
+    }
+    
+    #[test]
+    fn n_converts_each_type_and_lookups_miss_with_na() {
+        let workbook = workbook(&[
+            ("A1", number(2.5)), ("A2", true.into()), ("A3", "12".into()), ("A4", CellValue::Date(45292.0)),
+            ("A5", error(CellError::DivisionByZero)),
+            ("C1", number(1.0)), ("C2", number(2.0)), ("D1", "one".into()), ("D2", "two".into()),
+        ]);
+        assert_eq!(eval_in(&workbook, "=N(A1)"), number(2.5));
+        assert_eq!(eval_in(&workbook, "=N(A2)"), number(1.0));
+        assert_eq!(eval_in(&workbook, "=N(FALSE)"), number(0.0));
+        assert_eq!(eval_in(&workbook, "=N(A3)"), number(0.0));
+        assert_eq!(eval_in(&workbook, "=N(A4)"), number(45292.0));
+        assert_eq!(eval_in(&workbook, "=N(B1)"), number(0.0));
+        assert_eq!(eval_in(&workbook, "=N(A5)"), error(CellError::DivisionByZero));
+        
+        // Every lookup reports a miss as #N/A, so IFNA catches them all
+        for lookup in [
+            "VLOOKUP(9,C1:D2,2,FALSE)", "HLOOKUP(9,C1:D2,2,FALSE)", "MATCH(9,C1:C2,0)",
+            "XLOOKUP(9,C1:C2,D1:D2)", "XMATCH(9,C1:C2)",
+        ] {
+            assert_eq!(eval_in(&workbook, &format!("={}", lookup)), error(CellError::NotAvailable), "{}", lookup);
+            assert_eq!(eval_in(&workbook, &format!("=IFNA({},\"none\")", lookup)), CellValue::Text("none".to_string()), "{}", lookup);
+        }
+        assert_eq!(eval_in(&workbook, "=IFNA(VLOOKUP(2,C1:D2,2,FALSE),\"none\")"), CellValue::Text("two".to_string()));
+    }
+    
+    #[test]
+    fn choose_truncates_the_index_and_returns_ranges_whole() {
+        let workbook = workbook(&[
+            ("A1", number(1.0)), ("A2", number(2.0)), ("B1", number(10.0)), ("B2", number(20.0)),
+        ]);
+        let text = |s: &str| CellValue::Text(s.to_string());
+        assert_eq!(eval("=CHOOSE(2.9,\"a\",\"b\",\"c\")"), text("b"));
+        assert_eq!(eval("=CHOOSE(\"3\",\"a\",\"b\",\"c\")"), text("c"));
+        for index in ["0", "4", "-1", "0.5"] {
+            assert_eq!(eval(&format!("=CHOOSE({},\"a\",\"b\",\"c\")", index)), error(CellError::InvalidValue), "{}", index);
+        }
+        assert_eq!(eval("=CHOOSE(\"x\",\"a\",\"b\")"), error(CellError::InvalidValue));
+        assert_eq!(eval("=CHOOSE(1/0,\"a\",\"b\")"), error(CellError::DivisionByZero));
+        
+        // A chosen range comes back as an array that other functions can use
+        assert_eq!(eval_in(&workbook, "=CHOOSE(2,A1:A2,B1:B2)"), CellValue::Array(vec![vec![number(10.0)], vec![number(20.0)]]));
+        assert_eq!(eval_in(&workbook, "=SUM(CHOOSE(1,A1:A2,B1:B2))"), number(3.0));
+    }
+    
+    #[test]
+    fn subtotals_skip_other_subtotals_and_hidden_rows() {
+        let mut workbook = workbook(&[
+            ("A1", number(1.0)), ("A2", number(2.0)), ("A3", "=SUBTOTAL(9,A1:A2)".into()),
+            ("A4", number(4.0)), ("A5", number(5.0)), ("A6", "=SUBTOTAL(9,A4:A5)".into()),
+        ]);
+        assert_eq!(workbook.get_cell_value("Sheet1", 2, 0).unwrap(), number(3.0));
+        
+        // The grand total skips the nested subtotals; SUM counts them
+        assert_eq!(eval_in(&workbook, "=SUBTOTAL(9,A1:A6)"), number(12.0));
+        assert_eq!(eval_in(&workbook, "=SUM(A1:A6)"), number(24.0));
+        assert_eq!(eval_in(&workbook, "=SUM(SUBTOTAL(9,A1:A6),A1)"), number(13.0));
+        assert_eq!(eval_in(&workbook, "=SUBTOTAL(1,A1:A6)"), number(3.0));
+        assert_eq!(eval_in(&workbook, "=SUBTOTAL(2,A1:A6)"), number(4.0));
+        assert_eq!(eval_in(&workbook, "=SUBTOTAL(4,A1:A6)"), number(5.0));
+        
+        // Codes 101-111 also skip hidden rows
+        workbook.set_row_hidden("Sheet1", 1, true).unwrap();
+        assert_eq!(eval_in(&workbook, "=SUBTOTAL(9,A1:A6)"), number(12.0));
+        assert_eq!(eval_in(&workbook, "=SUBTOTAL(109,A1:A6)"), number(10.0));
+        assert_eq!(eval_in(&workbook, "=SUBTOTAL(102,A1:A6)"), number(3.0));
+        assert_eq!(eval_in(&workbook, "=SUBTOTAL(12,A1:A6)"), error(CellError::InvalidValue));
+    }
+    
+    #[test]
+    fn matrix_functions_multiply_and_invert() {
+        let workbook = workbook(&[
+            ("A1", number(1.0)), ("B1", number(2.0)), ("A2", number(3.0)), ("B2", number(4.0)),
+            ("D1", number(5.0)), ("D2", number(6.0)),
+            ("F1", number(1.0)), ("G1", number(2.0)), ("F2", number(2.0)), ("G2", number(4.0)),
+        ]);
+        let matrix = |rows: &[&[f64]]| CellValue::Array(rows.iter().map(|row| row.iter().map(|&n| number(n)).collect()).collect());
+        
+        assert_eq!(eval_in(&workbook, "=MMULT(A1:B2,D1:D2)"), matrix(&[&[17.0], &[39.0]]));
+        assert_eq!(eval_in(&workbook, "=MMULT(A1:B2,A1:B2)"), matrix(&[&[7.0, 10.0], &[15.0, 22.0]]));
+        assert_eq!(eval_in(&workbook, "=MMULT(D1:D2,D1:D2)"), error(CellError::InvalidValue));
+        
+        let CellValue::Array(inverse) = eval_in(&workbook, "=MINVERSE(A1:B2)") else { panic!() };
+        for (row, expected) in inverse.iter().zip([[-2.0, 1.0], [1.5, -0.5]]) {
+            for (value, expected) in row.iter().zip(expected) {
+                assert_close(value.clone(), expected);
+            }
+        }
+        assert_eq!(eval_in(&workbook, "=MINVERSE(F1:G2)"), error(CellError::InvalidNumber));
+        assert_eq!(eval_in(&workbook, "=MINVERSE(A1:D2)"), error(CellError::InvalidValue));
+        
+        assert_close(eval_in(&workbook, "=MDETERM(A1:B2)"), -2.0);
+        assert_close(eval_in(&workbook, "=MDETERM(F1:G2)"), 0.0);
+        assert_eq!(eval("=MUNIT(2)"), matrix(&[&[1.0, 0.0], &[0.0, 1.0]]));
+        assert_eq!(eval("=MUNIT(0)"), error(CellError::InvalidValue));
+        // The product of a matrix and its inverse is the identity
+        assert_close(eval_in(&workbook, "=SUM(MMULT(A1:B2,MINVERSE(A1:B2)))"), 2.0);
+    }
+    
+    #[test]
+    fn every_function_is_described() {
+        let workbook = workbook(&[]);
+        let functions = workbook.functions();
+        let sum = functions.metadata("sum").unwrap();
+        assert_eq!(sum.name, "SUM");
+        assert_eq!((sum.min_args, sum.max_args), (1, None));
+        assert_eq!(sum.category, FunctionCategory::Math);
+        assert!(!sum.description.is_empty());
+        
+        let names: Vec<&String> = functions.functions.keys().chain(functions.contextual_functions.keys()).collect();
+        for name in &names {
+            assert!(functions.metadata(name).is_some(), "{} has no metadata", name);
+        }
+        assert_eq!(functions.list().len(), names.len());
+        assert!(functions.list().windows(2).all(|pair| pair[0].name < pair[1].name));
+    }
+    
+    #[test]
+    fn conditional_aggregates_need_ranges_of_one_shape() {
+        let text = |t: &str| CellValue::Text(t.to_string());
+        let workbook = workbook(&[
+            ("A1", text("North")), ("B1", number(10.0)), ("C1", number(1.0)),
+            ("A2", text("South")), ("B2", number(20.0)), ("C2", number(2.0)),
+            ("A3", text("North")), ("B3", number(30.0)), ("C3", number(3.0)),
+            ("A4", text("North")), ("B4", text("n/a")), ("C4", number(4.0)),
+        ]);
+        
+        assert_eq!(eval_in(&workbook, "=SUMIFS(B1:B4,A1:A4,\"North\")"), number(40.0));
+        assert_eq!(eval_in(&workbook, "=SUMIFS(B1:B4,A1:A4,\"North\",C1:C4,\">1\")"), number(30.0));
+        assert_eq!(eval_in(&workbook, "=COUNTIFS(A1:A4,\"N*\",C1:C4,\"<4\")"), number(2.0));
+        assert_eq!(eval_in(&workbook, "=AVERAGEIFS(B1:B4,A1:A4,\"North\")"), number(20.0));
+        assert_eq!(eval_in(&workbook, "=AVERAGEIFS(B1:B4,A1:A4,\"West\")"), error(CellError::DivisionByZero));
+        
+        // Ranges of different sizes, or the same size in another orientation
+        assert_eq!(eval_in(&workbook, "=SUMIFS(B1:B3,A1:A4,\"North\")"), error(CellError::InvalidValue));
+        assert_eq!(eval_in(&workbook, "=SUMIFS(B1:B4,A1:A4,\"North\",C1:C3,\">1\")"), error(CellError::InvalidValue));
+        assert_eq!(eval_in(&workbook, "=COUNTIFS(A1:A4,\"North\",A1:D1,\"North\")"), error(CellError::InvalidValue));
+        assert_eq!(eval_in(&workbook, "=AVERAGEIFS(B1:C2,A1:A4,\"North\")"), error(CellError::InvalidValue));
+    }
+    
+    #[test]
+    fn counts_agree_on_absent_blank_and_empty_text() {
+        // A2 is absent, A3 blank, A4 empty text and A5 a formula returning empty text
+        let workbook = workbook(&[
+            ("A1", number(3.0)),
+            ("A3", CellValue::Blank),
+            ("A4", CellValue::Text(String::new())),
+            ("A5", CellValue::Formula("=\"\"".to_string())),
+            ("A6", "x".into()),
+            ("A7", true.into()),
+        ]);
+        
+        assert_eq!(eval_in(&workbook, "=COUNT(A1:A8)"), number(1.0));
+        assert_eq!(eval_in(&workbook, "=COUNTA(A1:A8)"), number(5.0));
+        assert_eq!(eval_in(&workbook, "=COUNTBLANK(A1:A8)"), number(5.0));
+        // Empty text is counted by both COUNTA and COUNTBLANK, as in Excel
+        assert_eq!(eval_in(&workbook, "=COUNTA(A4:A5)+COUNTBLANK(A4:A5)"), number(4.0));
+        assert_eq!(eval_in(&workbook, "=COUNTBLANK(A:A)"), number(1_048_576.0 - 3.0));
+        assert_eq!(eval_in(&workbook, "=COUNTBLANK(B1:C2)"), number(4.0));
+    }
+    
+    #[test]
+    fn round_family_keeps_its_outputs() {
+        let check = |formula: &str, expected: f64| assert_eq!(eval(formula), number(expected), "{}", formula);
+        
+        check("=ROUND(2.5,0)", 3.0);
+        check("=ROUND(-2.5,0)", -3.0);
+        check("=ROUND(1.23456,3)", 1.235);
+        check("=ROUND(1234.567,-2)", 1200.0);
+        check("=ROUND(-1250,-2)", -1300.0);
+        check("=ROUNDUP(3.2,0)", 4.0);
+        check("=ROUNDUP(-3.2,0)", -4.0);
+        check("=ROUNDUP(1.23412,3)", 1.235);
+        check("=ROUNDUP(1201,-2)", 1300.0);
+        check("=ROUNDDOWN(3.9,0)", 3.0);
+        check("=ROUNDDOWN(-3.9,0)", -3.0);
+        check("=ROUNDDOWN(1.23456,3)", 1.234);
+        check("=ROUNDDOWN(1299,-2)", 1200.0);
+        check("=TRUNC(8.9)", 8.0);
+        check("=TRUNC(-8.9)", -8.0);
+        check("=TRUNC(1.23956,2)", 1.23);
+        check("=TRUNC(-1299,-2)", -1200.0);
+        check("=TRUNC(5,-1)", 0.0);
+        
+        assert_eq!(round_significant(1234.5, 2), 1200.0);
+        assert_eq!(round_significant(0.012345, 3), 0.0123);
+        assert_eq!(round_significant(-0.5, 1), -0.5);
+        assert_eq!(round_significant(-95.0, 1), -100.0);
+        assert_eq!(round_significant(0.0, 3), 0.0);
+    }
+    
+    #[test]
+    fn logical_functions_flatten_ranges() {
+        let workbook = workbook(&[
+            ("A1", true.into()), ("A2", true.into()), ("A3", false.into()),
+            ("B1", true.into()), ("B2", number(1.0)),
+            ("C1", "abc".into()), ("C2", true.into()),
+        ]);
+        
+        assert_eq!(eval_in(&workbook, "=AND(A1:A3)"), CellValue::Boolean(false));
+        assert_eq!(eval_in(&workbook, "=AND(A1:A2)"), CellValue::Boolean(true));
+        assert_eq!(eval_in(&workbook, "=OR(A3,A1:A2)"), CellValue::Boolean(true));
+        // B3 is empty and skipped
+        assert_eq!(eval_in(&workbook, "=AND(B1:B3)"), CellValue::Boolean(true));
+        assert_eq!(eval_in(&workbook, "=XOR(A1:A3)"), CellValue::Boolean(false));
+        assert_eq!(eval_in(&workbook, "=XOR(A1:A3,B1)"), CellValue::Boolean(true));
+        assert_eq!(eval_in(&workbook, "=NOT(A3)"), CellValue::Boolean(true));
+        
+        assert_eq!(eval_in(&workbook, "=AND(C1:C2)"), error(CellError::InvalidValue));
+        assert_eq!(eval_in(&workbook, "=OR(D1:D3)"), error(CellError::InvalidValue));
+        assert_eq!(eval_in(&workbook, "=NOT(A1:A3)"), error(CellError::InvalidValue));
+    }
+    
+    #[test]
+    fn xor_truth_table() {
+        for (a, b, expected) in [("FALSE", "FALSE", false), ("FALSE", "TRUE", true), ("TRUE", "FALSE", true), ("TRUE", "TRUE", false)] {
+            assert_eq!(eval(&format!("=XOR({},{})", a, b)), CellValue::Boolean(expected), "XOR({}, {})", a, b);
+        }
+        assert_eq!(eval("=XOR(TRUE,TRUE,TRUE)"), CellValue::Boolean(true));
+        assert_eq!(eval("=XOR(0,2)"), CellValue::Boolean(true));
+    }
+    
+    #[test]
+    fn intersection_and_union_operators() {
+        // A1:C3 holds 1 to 9 row by row
+        let cells: Vec<(String, CellValue)> = (0..9)
+            .map(|i| (format!("{}{}", ["A", "B", "C"][i % 3], i / 3 + 1), number(i as f64 + 1.0)))
+            .collect();
+        let cells: Vec<(&str, CellValue)> = cells.iter().map(|(reference, value)| (reference.as_str(), value.clone())).collect();
+        let workbook = workbook(&cells);
+        
+        assert_eq!(eval_in(&workbook, "=SUM(A1:B2 B2:C3)"), number(5.0));
+        assert_eq!(eval_in(&workbook, "=SUM(A1:C3 B1:B3 A2:C2)"), number(5.0));
+        assert_eq!(eval_in(&workbook, "=SUM(A1:A2 C1:C2)"), error(CellError::Null));
+        
+        // A1 + A2 + C1 + C2
+        assert_eq!(eval_in(&workbook, "=SUM((A1:A2,C1:C2))"), number(14.0));
+        assert_eq!(eval_in(&workbook, "=COUNT((A1:A2,C1:C2,B3))"), number(5.0));
+        // Overlapping areas count twice, as in Excel
+        assert_eq!(eval_in(&workbook, "=SUM((A1:B1,B1:C1))"), number(8.0));
+    }
+    
+    #[test]
+    fn null_error_displays_and_has_type_one() {
+        assert_eq!(CellError::Null.to_string(), "#NULL!");
+        assert_eq!(CellValue::Error(CellError::Null).to_string(), "#NULL!");
+        assert_eq!(eval("=ERROR.TYPE(#NULL!)"), number(1.0));
+        assert_eq!(eval("=ERROR.TYPE(A1:A2 C1:C2)"), number(1.0));
+        assert_eq!(eval("=ISERROR(A1:A2 C1:C2)"), CellValue::Boolean(true));
+        
+        let codes = ["#DIV/0!", "#VALUE!", "#REF!", "#NAME?", "#NUM!", "#N/A"];
+        for (code, error) in codes.into_iter().enumerate() {
+            assert_eq!(eval(&format!("=ERROR.TYPE({})", error)), number(code as f64 + 2.0), "{}", error);
+        }
+    }
+    
+    #[test]
+    fn text_slicing_rejects_negative_counts_and_starts_before_one() {
+        for formula in ["=LEFT(\"abc\",-1)", "=RIGHT(\"abc\",-1)", "=MID(\"abc\",0,1)", "=MID(\"abc\",-1,1)", "=MID(\"abc\",1,-1)"] {
+            assert_eq!(eval(formula), error(CellError::InvalidValue), "{}", formula);
+        }
+        
+        // Zero characters is an empty string, not an error
+        assert_eq!(eval("=LEFT(\"abc\",0)"), CellValue::Text(String::new()));
+        assert_eq!(eval("=RIGHT(\"abc\",0)"), CellValue::Text(String::new()));
+        assert_eq!(eval("=MID(\"abc\",2,0)"), CellValue::Text(String::new()));
+        assert_eq!(eval("=MID(\"abc\",2,5)"), CellValue::Text("bc".to_string()));
+    }
+}
@@ -0,0 +1,95 @@
+// ssengine-core/src/journal.rs
+// Edit journal behind Workbook undo, redo and transactions
+
+use crate::model::{Cell, CellAddress, CellRange};
+
+// One reversible change to a workbook
+#[derive(Debug, Clone)]
+pub(crate) enum Change {
+    // A cell's full state before and after an edit (None: no cell). Boxed, as cells
+    // are large next to the other variants.
+    Cell { sheet: String, address: CellAddress, before: Option<Box<Cell>>, after: Option<Box<Cell>> },
+    // A sheet's merged ranges before and after an edit
+    Merges { sheet: String, before: Vec<CellRange>, after: Vec<CellRange> },
+}
+
+impl Change {
+    pub fn cell(sheet: &str, address: CellAddress, before: Option<Cell>, after: Option<Cell>) -> Change {
+        Change::Cell { sheet: sheet.to_string(), address, before: before.map(Box::new), after: after.map(Box::new) }
+    }
+}
+
+// Undo and redo stacks. A step holds the changes of one edit, or of every edit
+// made inside a transaction, in the order they were made.
+#[derive(Debug, Default)]
+pub(crate) struct EditJournal {
+    undo_steps: Vec<Vec<Change>>,
+    redo_steps: Vec<Vec<Change>>,
+    open_group: Option<Vec<Change>>,
+    group_depth: usize,
+}
+
+impl EditJournal {
+    // Record the changes of a new edit. Any undone steps can no longer be redone.
+    pub fn record(&mut self, changes: Vec<Change>) {
+        if changes.is_empty() {
+            return;
+        }
+        self.redo_steps.clear();
+        match &mut self.open_group {
+            Some(group) => group.extend(changes),
+            None => self.undo_steps.push(changes),
+        }
+    }
+
+    // Start collecting edits into one step. Groups nest; only the outermost becomes
+    // a step. Returns the position in the open group where this group starts.
+    pub fn begin_group(&mut self) -> usize {
+        self.group_depth += 1;
+        self.open_group.get_or_insert_with(Vec::new).len()
+    }
+
+    // Remove and return the changes recorded since `mark` in the open group
+    pub fn rollback_group(&mut self, mark: usize) -> Vec<Change> {
+        match &mut self.open_group {
+            Some(group) if mark < group.len() => group.split_off(mark),
+            _ => Vec::new(),
+        }
+    }
+
+    // Close a group, turning the outermost one into a single undo step
+    pub fn end_group(&mut self) {
+        self.group_depth = self.group_depth.saturating_sub(1);
+        if self.group_depth == 0 {
+            if let Some(group) = self.open_group.take().filter(|group| !group.is_empty()) {
+                self.undo_steps.push(group);
+            }
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_steps.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_steps.is_empty()
+    }
+
+    pub fn pop_undo(&mut self) -> Option<Vec<Change>> {
+        self.undo_steps.pop()
+    }
+
+    pub fn pop_redo(&mut self) -> Option<Vec<Change>> {
+        self.redo_steps.pop()
+    }
+
+    // Keep an undone step so it can be redone
+    pub fn push_redo(&mut self, step: Vec<Change>) {
+        self.redo_steps.push(step);
+    }
+
+    // Keep a redone step so it can be undone again
+    pub fn push_undo(&mut self, step: Vec<Change>) {
+        self.undo_steps.push(step);
+    }
+}
@@ -7,10 +7,19 @@ pub mod error;
 pub mod evaluator;
 pub mod functions;
 pub mod parser;
+pub mod date;
+pub mod journal;
+pub mod format;
+pub mod lint;
+pub mod diff;
 
 // Re-export key types
-pub use model::{Cell, Sheet, Workbook};
+pub use model::{Cell, CellValue, CellAddress, CellStyle, HorizontalAlignment, VerticalAlignment, BorderStyle, Sheet, Workbook, WorkbookMetadata, IterativeCalc, Aggregation, Scenario, Table, CellRange, CellOrRange, FormulaPattern, RowId, ColumnId, MAX_ROWS, MAX_COLUMNS, column_index_to_letter, column_letter_to_index};
 pub use error::EngineError;
+pub use date::DateSystem;
+pub use format::Locale;
+pub use lint::{LintKind, LintWarning};
+pub use diff::{CellChange, CellState, SheetDiff, WorkbookDiff};
 
 // Create a new workbook
 pub fn new_workbook() -> Workbook {
@@ -0,0 +1,243 @@
+// ssengine-core/src/lint.rs
+// Checks for formulas that are likely modeling mistakes
+
+use std::collections::HashMap;
+
+use crate::ast::{AstNode, BinaryOperator, Literal, Reference, UnaryOperator};
+use crate::model::{column_index_to_letter, CellAddress, CellValue, ColumnId, RowId, Workbook};
+use crate::parser::parse_formula;
+
+// The kind of problem a lint warning reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintKind {
+    UnguardedDivision, // A division by a cell or expression, outside IFERROR or IF
+    HardcodedNumber, // A number other than 0 or 1 written into arithmetic
+    BlankReference, // A reference to an empty cell
+    InconsistentFormula, // A formula unlike its neighbours, which agree with each other
+    CircularReference, // A formula that depends on itself
+}
+
+// One problem found in a formula cell
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintWarning {
+    pub sheet: String,
+    pub address: CellAddress,
+    pub kind: LintKind,
+    pub message: String,
+}
+
+// Functions whose arguments are allowed to divide by zero: they catch or avoid it
+const DIVISION_GUARDS: [&str; 2] = ["IFERROR", "IF"];
+
+// Check every formula of a workbook, except for circular references, which need
+// the dependency graph
+pub(crate) fn lint_formulas(workbook: &Workbook) -> Vec<LintWarning> {
+    let mut warnings = Vec::new();
+
+    for sheet_name in workbook.sheet_names() {
+        let sheet = match workbook.get_sheet(sheet_name) {
+            Some(sheet) => sheet,
+            None => continue,
+        };
+        // Formulas that don't parse can't be checked; they fail on evaluation anyway
+        let formulas: HashMap<(RowId, ColumnId), AstNode> = sheet.get_all_cells()
+            .filter_map(|(&position, cell)| cell.formula.as_deref().and_then(|formula| parse_formula(formula).ok()).map(|ast| (position, ast)))
+            .collect();
+        let relative_forms: HashMap<(RowId, ColumnId), String> = formulas.iter()
+            .map(|(&(row, col), ast)| ((row, col), ast.to_r1c1(&CellAddress::new(row, col))))
+            .collect();
+
+        for (&(row, col), ast) in &formulas {
+            let address = CellAddress::new(row, col);
+            let mut warn = |kind, message: String| {
+                warnings.push(LintWarning { sheet: sheet_name.clone(), address: address.clone(), kind, message });
+            };
+
+            let mut divisors = Vec::new();
+            find_unguarded_divisors(ast, false, &mut divisors);
+            if let Some(divisor) = divisors.first() {
+                warn(LintKind::UnguardedDivision, format!("Division by {} is not guarded against #DIV/0!", divisor));
+            }
+
+            let mut constants = Vec::new();
+            find_hardcoded_numbers(ast, &mut constants);
+            for constant in constants {
+                warn(LintKind::HardcodedNumber, format!("Hardcoded number {} in formula; consider an input cell", constant));
+            }
+
+            let mut references = Vec::new();
+            find_cell_references(ast, &mut references);
+            for reference in references {
+                let (target_sheet, target) = match reference {
+                    Reference::Cell(target) => (sheet_name.as_str(), target),
+                    Reference::SheetCell { sheet, address } => (sheet.as_str(), address),
+                    _ => continue,
+                };
+                let blank = workbook.get_sheet(target_sheet)
+                    .is_some_and(|sheet| sheet.get_cell(target.row, target.col).map_or(true, |cell| cell.value == CellValue::Blank));
+                if blank {
+                    warn(LintKind::BlankReference, format!("References blank cell {}", reference));
+                }
+            }
+
+            // A formula both neighbours in a row (or column) agree on, but it doesn't
+            let form = &relative_forms[&(row, col)];
+            let differs_between = |before: Option<(RowId, ColumnId)>, after: (RowId, ColumnId)| {
+                match (before.and_then(|before| relative_forms.get(&before)), relative_forms.get(&after)) {
+                    (Some(before), Some(after)) => before == after && before != form,
+                    _ => false,
+                }
+            };
+            if differs_between(col.checked_sub(1).map(|left| (row, left)), (row, col + 1)) {
+                warn(LintKind::InconsistentFormula, format!("Formula differs from its neighbours in row {}", row + 1));
+            } else if differs_between(row.checked_sub(1).map(|above| (above, col)), (row + 1, col)) {
+                warn(LintKind::InconsistentFormula, format!("Formula differs from its neighbours in column {}", column_index_to_letter(col)));
+            }
+        }
+    }
+
+    warnings
+}
+
+// Divisors outside any guarding function, other than nonzero number literals
+fn find_unguarded_divisors(node: &AstNode, guarded: bool, divisors: &mut Vec<String>) {
+    match node {
+        AstNode::Literal(_) | AstNode::Reference(_) | AstNode::TableReference(_) => {},
+        AstNode::BinaryOp { op, left, right } => {
+            if *op == BinaryOperator::Divide && !guarded && !matches!(constant_value(right), Some(n) if n != 0.0) {
+                divisors.push(right.to_string());
+            }
+            find_unguarded_divisors(left, guarded, divisors);
+            find_unguarded_divisors(right, guarded, divisors);
+        },
+        AstNode::UnaryOp { operand, .. } => find_unguarded_divisors(operand, guarded, divisors),
+        AstNode::Union(areas) => {
+            for area in areas {
+                find_unguarded_divisors(area, guarded, divisors);
+            }
+        },
+        AstNode::FunctionCall { name, args } => {
+            let guarded = guarded || DIVISION_GUARDS.contains(&name.as_str());
+            for arg in args {
+                find_unguarded_divisors(arg, guarded, divisors);
+            }
+        },
+    }
+}
+
+// Numbers written as operands of arithmetic, other than 0, 1 and exponents
+fn find_hardcoded_numbers(node: &AstNode, constants: &mut Vec<f64>) {
+    match node {
+        AstNode::Literal(_) | AstNode::Reference(_) | AstNode::TableReference(_) => {},
+        AstNode::BinaryOp { op, left, right } => {
+            let operands: &[&AstNode] = match op {
+                BinaryOperator::Add | BinaryOperator::Subtract | BinaryOperator::Multiply | BinaryOperator::Divide => &[left, right],
+                // Exponents such as ^2 are part of the formula's shape
+                BinaryOperator::Power => &[left],
+                _ => &[],
+            };
+            for operand in operands {
+                match constant_value(operand) {
+                    Some(n) if n != 0.0 && n.abs() != 1.0 => constants.push(n),
+                    _ => {},
+                }
+            }
+            find_hardcoded_numbers(left, constants);
+            find_hardcoded_numbers(right, constants);
+        },
+        AstNode::UnaryOp { operand, .. } => find_hardcoded_numbers(operand, constants),
+        AstNode::FunctionCall { args, .. } | AstNode::Union(args) => {
+            for arg in args {
+                find_hardcoded_numbers(arg, constants);
+            }
+        },
+    }
+}
+
+// The value of a number literal, possibly signed or with a percent sign
+fn constant_value(node: &AstNode) -> Option<f64> {
+    match node {
+        AstNode::Literal(Literal::Number(n)) => Some(*n),
+        AstNode::UnaryOp { op, operand } => {
+            let n = constant_value(operand)?;
+            match op {
+                UnaryOperator::Negative => Some(-n),
+                UnaryOperator::Percent => Some(n / 100.0),
+                UnaryOperator::Positive | UnaryOperator::ImplicitIntersection => Some(n),
+            }
+        },
+        _ => None,
+    }
+}
+
+// Single-cell references of a formula (ranges are expected to contain blanks)
+fn find_cell_references<'a>(node: &'a AstNode, references: &mut Vec<&'a Reference>) {
+    match node {
+        AstNode::Literal(_) | AstNode::TableReference(_) => {},
+        AstNode::Reference(reference) => {
+            if matches!(reference, Reference::Cell(_) | Reference::SheetCell { .. }) {
+                references.push(reference);
+            }
+        },
+        AstNode::BinaryOp { left, right, .. } => {
+            find_cell_references(left, references);
+            find_cell_references(right, references);
+        },
+        AstNode::UnaryOp { operand, .. } => find_cell_references(operand, references),
+        AstNode::FunctionCall { args, .. } | AstNode::Union(args) => {
+            for arg in args {
+                find_cell_references(arg, references);
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn warnings_of(workbook: &Workbook, kind: LintKind) -> Vec<(String, String)> {
+        workbook.lint().into_iter()
+            .filter(|warning| warning.kind == kind)
+            .map(|warning| (warning.address.to_a1(), warning.message))
+            .collect()
+    }
+
+    #[test]
+    fn hardcoded_numbers_and_inconsistent_rows_are_flagged() {
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Sheet1".to_string()).unwrap();
+        let mut cells: Vec<(RowId, ColumnId, CellValue)> = (0..4).map(|col| (0, col, CellValue::Number(100.0))).collect();
+        for (col, formula) in ["=A1", "=B1", "=C1*1.05", "=D1"].into_iter().enumerate() {
+            cells.push((1, col as ColumnId, CellValue::Formula(formula.to_string())));
+        }
+        workbook.set_cells("Sheet1", cells).unwrap();
+
+        assert_eq!(warnings_of(&workbook, LintKind::HardcodedNumber), vec![
+            ("C2".to_string(), "Hardcoded number 1.05 in formula; consider an input cell".to_string()),
+        ]);
+        assert_eq!(warnings_of(&workbook, LintKind::InconsistentFormula), vec![
+            ("C2".to_string(), "Formula differs from its neighbours in row 2".to_string()),
+        ]);
+        assert_eq!(workbook.lint().len(), 2);
+    }
+
+    #[test]
+    fn guarded_divisions_and_filled_references_pass() {
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Sheet1".to_string()).unwrap();
+        workbook.set_cells("Sheet1", vec![
+            (0, 0, CellValue::Number(4.0)),
+            (0, 1, CellValue::Formula("=IFERROR(A1/C1,0)".to_string())),
+            (1, 1, CellValue::Formula("=A1/D1".to_string())),
+        ]).unwrap();
+
+        assert_eq!(warnings_of(&workbook, LintKind::UnguardedDivision), vec![
+            ("B2".to_string(), "Division by D1 is not guarded against #DIV/0!".to_string()),
+        ]);
+        assert_eq!(warnings_of(&workbook, LintKind::BlankReference), vec![
+            ("B1".to_string(), "References blank cell C1".to_string()),
+            ("B2".to_string(), "References blank cell D1".to_string()),
+        ]);
+    }
+}
@@ -1,17 +1,42 @@
 // ssengine-core/src/model.rs
 // Core data structures for the spreadsheet engine
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::collections::VecDeque;
 use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use chrono::NaiveDateTime;
+use rayon::prelude::*;
 use crate::error::{EngineError, CellError};
-use crate::evaluator::Evaluator;
-use crate::parser::Parser;
+use crate::evaluator::{Evaluator, TraceNode};
+use crate::functions::FunctionRegistry;
+use crate::date::DateSystem;
+use crate::format::{format_value, Locale};
+use crate::lint::{lint_formulas, LintKind, LintWarning};
+use crate::diff::{diff_workbooks, WorkbookDiff};
+use crate::journal::{Change, EditJournal};
+use crate::ast::{AstNode, Literal, Reference, TableReference, quote_sheet_name, unquote_sheet_name};
+use crate::parser::{parse_formula, validate_formula, format_ast, extract_cell_references, extract_range_references, extract_table_references, rewrite_references};
 
 // Basic type definitions
 pub type RowId = u32;
 pub type ColumnId = u32;
 
+// Sheet size limits (as in Excel): rows 1..=1048576, columns A..=XFD
+pub const MAX_ROWS: RowId = 1_048_576;
+pub const MAX_COLUMNS: ColumnId = 16_384;
+
+// Goal seek stops once the target is this close to the wanted value, or gives up
+// after this many trial values
+const GOAL_SEEK_TOLERANCE: f64 = 1e-7;
+const GOAL_SEEK_MAX_ITERATIONS: usize = 100;
+
+// Recalculation evaluates a level of independent cells in parallel once it holds at
+// least this many; smaller levels aren't worth the thread handoff
+const PARALLEL_RECALC_MIN_CELLS: usize = 64;
+
 // Cell address (row, column)
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct CellAddress {
@@ -26,51 +51,72 @@ impl CellAddress {
     
     // Convert A1 notation to CellAddress
     pub fn from_a1(reference: &str) -> Result<Self, EngineError> {
-        // Simple implementation - could be more robust
-        let mut col_str = String::new();
-        let mut row_str = String::new();
-        
-        for c in reference.chars() {
-            if c.is_alphabetic() {
-                col_str.push(c.to_ascii_uppercase());
-            } else if c.is_numeric() {
-                row_str.push(c);
-            } else {
-                return Err(EngineError::ParseError(format!("Invalid character in cell reference: {}", c)));
-            }
+        // Column letters followed by row digits, e.g. "B12"
+        let split = reference.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(reference.len());
+        let (col_str, row_str) = reference.split_at(split);
+        
+        if let Some(c) = row_str.chars().find(|c| !c.is_ascii_digit()) {
+            return Err(EngineError::ParseError(format!("Invalid character in cell reference: {}", c)));
         }
         
         if col_str.is_empty() || row_str.is_empty() {
             return Err(EngineError::ParseError(format!("Invalid cell reference format: {}", reference)));
         }
         
-        // Convert column letters to 0-based index
-        let col: ColumnId = col_str.chars().fold(0, |acc, c| {
-            acc * 26 + (c as u32 - 'A' as u32 + 1)
-        }) - 1;
-        
-        // Convert row to 0-based index
-        let row: RowId = match row_str.parse::<RowId>() {
-            Ok(r) => r - 1, // Excel rows are 1-based
-            Err(_) => return Err(EngineError::ParseError(format!("Invalid row in cell reference: {}", row_str))),
-        };
+        let col = column_index(col_str)
+            .ok_or_else(|| EngineError::InvalidReference(format!("Column out of range in cell reference: {}", reference)))?;
+        let row = row_index(row_str)
+            .ok_or_else(|| EngineError::InvalidReference(format!("Row out of range in cell reference: {}", reference)))?;
         
         Ok(CellAddress { row, col })
     }
     
     // Convert to A1 notation
     pub fn to_a1(&self) -> String {
-        let mut col_str = String::new();
-        let mut col_num = self.col + 1; // Convert to 1-based for conversion
-        
-        while col_num > 0 {
-            let remainder = (col_num - 1) % 26;
-            col_str.push((b'A' + remainder as u8) as char);
-            col_num = (col_num - remainder) / 26;
-        }
-        
-        format!("{}{}", col_str.chars().rev().collect::<String>(), self.row + 1)
+        format!("{}{}", column_index_to_letter(self.col), self.row + 1)
+    }
+}
+
+// 0-based column index for column letters ("A" -> 0), or None past XFD
+pub(crate) fn column_index(letters: &str) -> Option<ColumnId> {
+    if letters.is_empty() || !letters.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    letters.chars()
+        .try_fold(0u32, |acc, c| acc.checked_mul(26)?.checked_add(c.to_ascii_uppercase() as u32 - 'A' as u32 + 1))
+        .filter(|col| (1..=MAX_COLUMNS).contains(col))
+        .map(|col| col - 1)
+}
+
+// 0-based column index for column letters, case-insensitive: "A" -> 0, "AA" -> 26,
+// "XFD" -> 16383. Anything but letters, or a column past XFD, is an error.
+pub fn column_letter_to_index(letters: &str) -> Result<ColumnId, EngineError> {
+    if letters.is_empty() || !letters.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(EngineError::ParseError(format!("Invalid column letters: '{}'", letters)));
+    }
+    column_index(letters)
+        .ok_or_else(|| EngineError::InvalidReference(format!("Column out of range: {}", letters)))
+}
+
+// Column letters for a 0-based column index: 0 -> "A", 25 -> "Z", 26 -> "AA"
+pub fn column_index_to_letter(col: ColumnId) -> String {
+    let mut col_str = String::new();
+    let mut col_num = col + 1; // Convert to 1-based for conversion
+    
+    while col_num > 0 {
+        let remainder = (col_num - 1) % 26;
+        col_str.push((b'A' + remainder as u8) as char);
+        col_num = (col_num - remainder) / 26;
     }
+    
+    col_str.chars().rev().collect()
+}
+
+// 0-based row index for 1-based row digits ("1" -> 0), or None outside the sheet
+pub(crate) fn row_index(digits: &str) -> Option<RowId> {
+    digits.parse::<RowId>().ok()
+        .filter(|row| (1..=MAX_ROWS).contains(row))
+        .map(|row| row - 1)
 }
 
 // Cell value enum
@@ -78,10 +124,196 @@ impl CellAddress {
 pub enum CellValue {
     Blank,
     Number(f64),
+    Date(f64), // Date serial number (a number that displays as a date)
     Text(String),
     Boolean(bool),
     Error(CellError),
     Formula(String), // The formula text
+    Array(Vec<Vec<CellValue>>), // Row-major 2-D array (e.g. a resolved range)
+}
+
+impl CellValue {
+    // Whether the value is a date
+    pub fn is_date(&self) -> bool {
+        matches!(self, CellValue::Date(_))
+    }
+    
+    // Dates are plain serial numbers in calculations: convert them (also inside arrays) to numbers
+    pub fn date_as_number(&self) -> CellValue {
+        match self {
+            CellValue::Date(serial) => CellValue::Number(*serial),
+            CellValue::Array(rows) => CellValue::Array(
+                rows.iter().map(|row| row.iter().map(CellValue::date_as_number).collect()).collect()
+            ),
+            other => other.clone(),
+        }
+    }
+    
+    // The value with NaN and infinite numbers (also inside arrays) replaced by #NUM!,
+    // so neither enters a cell: they would break equality, sorting and export
+    pub fn finite_or_error(self) -> CellValue {
+        match self {
+            CellValue::Number(n) | CellValue::Date(n) if !n.is_finite() => CellValue::Error(CellError::InvalidNumber),
+            CellValue::Array(rows) => CellValue::Array(
+                rows.into_iter().map(|row| row.into_iter().map(CellValue::finite_or_error).collect()).collect()
+            ),
+            other => other,
+        }
+    }
+    
+    // Whether the value is or contains a date
+    pub fn contains_date(&self) -> bool {
+        match self {
+            CellValue::Date(_) => true,
+            CellValue::Array(rows) => rows.iter().flatten().any(CellValue::contains_date),
+            _ => false,
+        }
+    }
+}
+
+// How blank cells take part in calculations. Every operator goes through these
+// two rules, so they agree with each other:
+// * Arithmetic (+ - * / ^, unary - and %) reads a blank as 0: blank * 5 is 0,
+//   blank + 5 is 5 and 5 / blank is #DIV/0!.
+// * Comparison (= <> < <= > >=) reads a blank as the empty value of the other
+//   side's type: 0, "" or FALSE, so blank = 0 and blank = "" are both TRUE.
+// Aggregates (SUM, AVERAGE, COUNT, MIN, MAX, ...) instead skip blanks altogether,
+// so AVERAGE of 2 and a blank is 2, not 1.
+impl CellValue {
+    // The value as an operand of arithmetic: numbers and dates as themselves, a blank
+    // as 0. None for anything else (text, booleans, errors, arrays).
+    pub fn arithmetic_operand(&self) -> Option<f64> {
+        match self {
+            CellValue::Number(n) | CellValue::Date(n) => Some(*n),
+            CellValue::Blank => Some(0.0),
+            _ => None,
+        }
+    }
+    
+    // The value as an operand of a comparison with `other`: a blank becomes the
+    // empty value of `other`'s type, anything else is unchanged
+    pub fn comparison_operand(&self, other: &CellValue) -> CellValue {
+        match (self, other) {
+            (CellValue::Blank, CellValue::Text(_)) => CellValue::Text(String::new()),
+            (CellValue::Blank, CellValue::Boolean(_)) => CellValue::Boolean(false),
+            (CellValue::Blank, _) => CellValue::Number(0.0),
+            (value, _) => value.clone(),
+        }
+    }
+    
+    // Equality as the `=` operator sees it: numbers and dates by value (NaN equals
+    // itself), text case-insensitively, and blank equal to 0, "" and FALSE. Errors
+    // are equal when they are the same error.
+    pub fn equals(&self, other: &CellValue) -> bool {
+        use CellValue::*;
+        match (self, other) {
+            (Blank, _) | (_, Blank) => self.comparison_operand(other) == other.comparison_operand(self),
+            (Number(a) | Date(a), Number(b) | Date(b)) => numbers_equal(*a, *b),
+            _ => self == other,
+        }
+    }
+    
+    // Like `equals`, but numbers within `epsilon` of each other count as equal
+    pub fn approx_eq(&self, other: &CellValue, epsilon: f64) -> bool {
+        match (self, other) {
+            (CellValue::Number(a) | CellValue::Date(a), CellValue::Number(b) | CellValue::Date(b)) => {
+                numbers_equal(*a, *b) || (a - b).abs() <= epsilon
+            },
+            (CellValue::Array(a), CellValue::Array(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(x, y)| {
+                    x.len() == y.len() && x.iter().zip(y).all(|(x, y)| x.approx_eq(y, epsilon))
+                })
+            },
+            _ => self.equals(other),
+        }
+    }
+}
+
+// Whether a recomputed value is exactly the previous one. Stricter than `==`: text
+// must match case too, since a case change can still reach dependents.
+fn identical_values(a: &CellValue, b: &CellValue) -> bool {
+    match (a, b) {
+        (CellValue::Text(a), CellValue::Text(b)) => a == b,
+        (CellValue::Array(a), CellValue::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(x, y)| {
+                x.len() == y.len() && x.iter().zip(y).all(|(x, y)| identical_values(x, y))
+            })
+        },
+        _ => a == b,
+    }
+}
+
+// Number equality where NaN equals NaN, so the comparison stays reflexive
+fn numbers_equal(a: f64, b: f64) -> bool {
+    a == b || (a.is_nan() && b.is_nan())
+}
+
+// Structural equality: same variant and same value, with NaN-aware numbers and
+// case-insensitive text. Unlike `equals`, blank is only equal to blank and a date
+// is not equal to its serial number.
+impl PartialEq for CellValue {
+    fn eq(&self, other: &CellValue) -> bool {
+        use CellValue::*;
+        match (self, other) {
+            (Blank, Blank) => true,
+            (Number(a), Number(b)) | (Date(a), Date(b)) => numbers_equal(*a, *b),
+            (Text(a), Text(b)) => a.to_lowercase() == b.to_lowercase(),
+            (Boolean(a), Boolean(b)) => a == b,
+            (Error(a), Error(b)) => a == b,
+            (Formula(a), Formula(b)) => a == b,
+            (Array(a), Array(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for CellValue {}
+
+// Excel-style text of a value: numbers as the General format shows them, booleans as
+// TRUE/FALSE, errors as their code and arrays as their top-left value
+impl fmt::Display for CellValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CellValue::Blank => Ok(()),
+            CellValue::Number(n) | CellValue::Date(n) => write!(f, "{}", format_general_number(*n)),
+            CellValue::Text(s) => write!(f, "{}", s),
+            CellValue::Boolean(b) => write!(f, "{}", if *b { "TRUE" } else { "FALSE" }),
+            CellValue::Error(e) => write!(f, "{}", e),
+            CellValue::Formula(formula) => write!(f, "{}", formula),
+            CellValue::Array(rows) => match rows.first().and_then(|row| row.first()) {
+                Some(first) => write!(f, "{}", first),
+                None => Ok(()),
+            },
+        }
+    }
+}
+
+// A number with at most 15 significant digits and no trailing zeros, switching to
+// scientific notation (1E+15, 1E-10) for very large and very small magnitudes
+pub(crate) fn format_general_number(n: f64) -> String {
+    if n == 0.0 {
+        return "0".to_string(); // Also covers -0
+    }
+    if !n.is_finite() {
+        return CellError::InvalidNumber.to_string();
+    }
+    
+    let exponent = n.abs().log10().floor() as i32;
+    if exponent >= 15 || exponent <= -10 {
+        let formatted = format!("{:.14E}", n);
+        let (mantissa, exponent) = formatted.split_once('E').unwrap_or((&formatted, "0"));
+        let mantissa = mantissa.trim_end_matches('0').trim_end_matches('.');
+        let exponent: i32 = exponent.parse().unwrap_or(0);
+        return format!("{}E{}{:02}", mantissa, if exponent < 0 { '-' } else { '+' }, exponent.abs());
+    }
+    
+    let decimals = (14 - exponent).max(0) as usize;
+    let formatted = format!("{:.*}", decimals, n);
+    if formatted.contains('.') {
+        formatted.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        formatted
+    }
 }
 
 impl From<f64> for CellValue {
@@ -118,12 +350,61 @@ impl From<bool> for CellValue {
     }
 }
 
+// Horizontal alignment of cell content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HorizontalAlignment {
+    #[default]
+    General, // Text left, numbers right
+    Left,
+    Center,
+    Right,
+}
+
+// Vertical alignment of cell content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VerticalAlignment {
+    #[default]
+    Bottom,
+    Center,
+    Top,
+}
+
+// Line style of the border drawn around a cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BorderStyle {
+    #[default]
+    None,
+    Thin,
+    Medium,
+    Thick,
+    Dashed,
+    Dotted,
+    Double,
+}
+
+// Presentation of a cell. Colors are 0xRRGGBB; unset options use the defaults.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CellStyle {
+    pub bold: bool,
+    pub italic: bool,
+    pub font_size: Option<f64>,
+    pub font_color: Option<u32>,
+    pub fill_color: Option<u32>,
+    pub border: BorderStyle,
+    pub horizontal_alignment: HorizontalAlignment,
+    pub vertical_alignment: VerticalAlignment,
+    pub number_format: Option<String>, // Excel format code, e.g. "$#,##0.00"
+}
+
 // Cell structure
 #[derive(Debug, Clone)]
 pub struct Cell {
     pub value: CellValue,
     pub formula: Option<String>,
     pub calculated_value: Option<CellValue>, // Result after formula evaluation
+    pub style: Option<CellStyle>,
+    pub note: Option<String>, // Comment shown with the cell, e.g. an auditor's remark
+    pub hyperlink: Option<String>, // URL the cell links to; the cell's value is the link text
 }
 
 impl Cell {
@@ -137,6 +418,9 @@ impl Cell {
             value,
             formula,
             calculated_value: None,
+            style: None,
+            note: None,
+            hyperlink: None,
         }
     }
     
@@ -149,22 +433,151 @@ impl Cell {
 }
 
 // A cell range (from one cell to another)
+#[derive(Debug, Clone, PartialEq)]
 pub struct CellRange {
     pub start: CellAddress,
     pub end: CellAddress,
 }
 
+impl CellRange {
+    pub fn new(start: CellAddress, end: CellAddress) -> Self {
+        CellRange { start, end }
+    }
+    
+    // Parse A1 range notation ("A1:B2"). A reversed range ("B2:A1") is normalized so
+    // `start` is the top-left corner.
+    pub fn from_a1(reference: &str) -> Result<Self, EngineError> {
+        let (start, end) = reference.split_once(':')
+            .ok_or_else(|| EngineError::ParseError(format!("Invalid range format: {}", reference)))?;
+        Ok(CellRange::new(CellAddress::from_a1(start)?, CellAddress::from_a1(end)?).normalized())
+    }
+    
+    // Number of (rows, columns) the range spans
+    pub fn dimensions(&self) -> (RowId, ColumnId) {
+        (self.start.row.abs_diff(self.end.row) + 1, self.start.col.abs_diff(self.end.col) + 1)
+    }
+    
+    // The same range with `start` as the top-left and `end` as the bottom-right corner
+    pub fn normalized(&self) -> CellRange {
+        CellRange {
+            start: CellAddress::new(self.start.row.min(self.end.row), self.start.col.min(self.end.col)),
+            end: CellAddress::new(self.start.row.max(self.end.row), self.start.col.max(self.end.col)),
+        }
+    }
+    
+    // Whether two ranges share at least one cell
+    pub fn overlaps(&self, other: &CellRange) -> bool {
+        let (a, b) = (self.normalized(), other.normalized());
+        a.start.row <= b.end.row && b.start.row <= a.end.row
+            && a.start.col <= b.end.col && b.start.col <= a.end.col
+    }
+    
+    // The cells two ranges share, if any
+    pub fn intersection(&self, other: &CellRange) -> Option<CellRange> {
+        if !self.overlaps(other) {
+            return None;
+        }
+        let (a, b) = (self.normalized(), other.normalized());
+        Some(CellRange::new(
+            CellAddress::new(a.start.row.max(b.start.row), a.start.col.max(b.start.col)),
+            CellAddress::new(a.end.row.min(b.end.row), a.end.col.min(b.end.col)),
+        ))
+    }
+    
+    // The range of the same size with its top-left corner at `dest`
+    pub fn moved_to(&self, dest: &CellAddress) -> Result<CellRange, EngineError> {
+        let (rows, cols) = self.dimensions();
+        if dest.row as u64 + rows as u64 > MAX_ROWS as u64 || dest.col as u64 + cols as u64 > MAX_COLUMNS as u64 {
+            return Err(EngineError::InvalidReference(format!("Moving {} to {} leaves the sheet", self, dest.to_a1())));
+        }
+        Ok(CellRange::new(dest.clone(), CellAddress::new(dest.row + rows - 1, dest.col + cols - 1)))
+    }
+    
+    // Whether the address lies inside the range (corners may be given in any order)
+    pub fn contains(&self, address: &CellAddress) -> bool {
+        (self.start.row.min(self.end.row)..=self.start.row.max(self.end.row)).contains(&address.row)
+            && (self.start.col.min(self.end.col)..=self.start.col.max(self.end.col)).contains(&address.col)
+    }
+}
+
+// A cell or range in A1 notation, for inputs that accept either ("B2" or "A1:B2")
+#[derive(Debug, Clone, PartialEq)]
+pub enum CellOrRange {
+    Cell(CellAddress),
+    Range(CellRange),
+}
+
+impl CellOrRange {
+    pub fn from_a1(reference: &str) -> Result<Self, EngineError> {
+        if reference.contains(':') {
+            CellRange::from_a1(reference).map(CellOrRange::Range)
+        } else {
+            CellAddress::from_a1(reference).map(CellOrRange::Cell)
+        }
+    }
+    
+    // The area covered; a single cell is a one-cell range
+    pub fn to_range(&self) -> CellRange {
+        match self {
+            CellOrRange::Cell(address) => CellRange::new(address.clone(), address.clone()),
+            CellOrRange::Range(range) => range.clone(),
+        }
+    }
+}
+
+impl fmt::Display for CellRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.start.to_a1(), self.end.to_a1())
+    }
+}
+
+// How consistently a row or column of formulas follows one relative-reference pattern
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormulaPattern {
+    pub template: Option<String>, // Most common formula in R1C1 notation, e.g. "=R[-1]C*2"; None without formulas
+    pub breaks: Vec<CellAddress>, // Cells that don't follow the template, in range order
+}
+
+impl FormulaPattern {
+    pub fn is_consistent(&self) -> bool {
+        self.breaks.is_empty()
+    }
+}
+
 // Sheet structure
 pub struct Sheet {
     name: String,
     cells: HashMap<(RowId, ColumnId), Cell>,
+    
+    // Merged ranges; the top-left cell of each holds the content
+    merged_ranges: Vec<CellRange>,
+    
+    // Top-left cell of the scrolling area; rows above and columns left of it stay in view
+    freeze_panes: Option<CellAddress>,
+    
+    // Explicit column widths (in characters) and row heights (in points); others are automatic
+    column_widths: HashMap<ColumnId, f64>,
+    row_heights: HashMap<RowId, f64>,
+    
+    // Rows hidden from view; SUBTOTAL's 101-111 function codes skip them
+    hidden_rows: HashSet<RowId>,
 }
 
 impl Sheet {
     pub fn new(name: String) -> Self {
+        Self::with_capacity(name, 0)
+    }
+    
+    // An empty sheet with room for `cells` cells before its cell map reallocates
+    pub fn with_capacity(name: String, cells: usize) -> Self {
         Sheet {
             name,
-            cells: HashMap::new(),
+            cells: HashMap::with_capacity(cells),
+            merged_ranges: Vec::new(),
+            freeze_panes: None,
+            column_widths: HashMap::new(),
+            row_heights: HashMap::new(),
+            hidden_rows: HashSet::new(),
         }
     }
     
@@ -184,11 +597,194 @@ impl Sheet {
     
     // Set a cell value at the specified coordinates
     pub fn set_cell(&mut self, row: RowId, col: ColumnId, value: CellValue) -> Result<(), EngineError> {
-        let cell = Cell::new(value);
+        self.check_not_merged_over(row, col)?;
+        
+        // A new value keeps the cell's formatting, note and hyperlink. NaN and
+        // infinity are stored as #NUM!.
+        let mut cell = Cell::new(value.finite_or_error());
+        if let Some(old) = self.cells.get(&(row, col)) {
+            cell.style = old.style.clone();
+            cell.note = old.note.clone();
+            cell.hyperlink = old.hyperlink.clone();
+        }
         self.cells.insert((row, col), cell);
         Ok(())
     }
     
+    // Merge a range into one cell. The top-left cell keeps its content and the other
+    // cells of the range are cleared. Ranges may not overlap an existing merge.
+    pub fn merge_cells(&mut self, range: CellRange) -> Result<(), EngineError> {
+        let range = range.normalized();
+        if range.start == range.end {
+            return Err(EngineError::InvalidReference(format!("Cannot merge a single cell: {}", range)));
+        }
+        if let Some(existing) = self.merged_ranges.iter().find(|merged| merged.overlaps(&range)) {
+            return Err(EngineError::InvalidReference(format!("Merge {} overlaps merged range {}", range, existing)));
+        }
+        
+        self.cells.retain(|&(row, col), _| {
+            let address = CellAddress::new(row, col);
+            address == range.start || !range.contains(&address)
+        });
+        self.merged_ranges.push(range);
+        Ok(())
+    }
+    
+    // Remove every merge overlapping the range. Returns whether any merge was removed.
+    pub fn unmerge_cells(&mut self, range: &CellRange) -> bool {
+        let count = self.merged_ranges.len();
+        self.merged_ranges.retain(|merged| !merged.overlaps(range));
+        self.merged_ranges.len() != count
+    }
+    
+    // All merged ranges of the sheet
+    pub fn merged_ranges(&self) -> &[CellRange] {
+        &self.merged_ranges
+    }
+    
+    // The merged range covering a cell, if any
+    pub fn merged_range_at(&self, row: RowId, col: ColumnId) -> Option<&CellRange> {
+        let address = CellAddress::new(row, col);
+        self.merged_ranges.iter().find(|merged| merged.contains(&address))
+    }
+    
+    // Cells covered by a merge, other than its top-left cell, can't hold content
+    pub(crate) fn check_not_merged_over(&self, row: RowId, col: ColumnId) -> Result<(), EngineError> {
+        match self.merged_range_at(row, col) {
+            Some(merged) if merged.start != CellAddress::new(row, col) => Err(EngineError::InvalidReference(
+                format!("{} is inside merged range {}", CellAddress::new(row, col).to_a1(), merged)
+            )),
+            _ => Ok(()),
+        }
+    }
+    
+    // Freeze the rows above `row` and the columns left of `col`, e.g. (1, 0) keeps
+    // the top row in view
+    pub fn set_freeze_panes(&mut self, row: RowId, col: ColumnId) {
+        self.freeze_panes = if row == 0 && col == 0 { None } else { Some(CellAddress::new(row, col)) };
+    }
+    
+    // Unfreeze all rows and columns
+    pub fn clear_freeze_panes(&mut self) {
+        self.freeze_panes = None;
+    }
+    
+    // The freeze position set by `set_freeze_panes`, if any
+    pub fn freeze_panes(&self) -> Option<&CellAddress> {
+        self.freeze_panes.as_ref()
+    }
+    
+    // Set the width of a column in characters
+    pub fn set_column_width(&mut self, col: ColumnId, width: f64) {
+        self.column_widths.insert(col, width);
+    }
+    
+    // Explicit width of a column, if set
+    pub fn column_width(&self, col: ColumnId) -> Option<f64> {
+        self.column_widths.get(&col).copied()
+    }
+    
+    // All explicitly sized columns
+    pub fn column_widths(&self) -> &HashMap<ColumnId, f64> {
+        &self.column_widths
+    }
+    
+    // Set the height of a row in points
+    pub fn set_row_height(&mut self, row: RowId, height: f64) {
+        self.row_heights.insert(row, height);
+    }
+    
+    // Explicit height of a row, if set
+    pub fn row_height(&self, row: RowId) -> Option<f64> {
+        self.row_heights.get(&row).copied()
+    }
+    
+    // All explicitly sized rows
+    pub fn row_heights(&self) -> &HashMap<RowId, f64> {
+        &self.row_heights
+    }
+    
+    // Hide or show a row
+    pub fn set_row_hidden(&mut self, row: RowId, hidden: bool) {
+        if hidden {
+            self.hidden_rows.insert(row);
+        } else {
+            self.hidden_rows.remove(&row);
+        }
+    }
+    
+    // Whether a row is hidden
+    pub fn is_row_hidden(&self, row: RowId) -> bool {
+        self.hidden_rows.contains(&row)
+    }
+    
+    // All hidden rows
+    pub fn hidden_rows(&self) -> &HashSet<RowId> {
+        &self.hidden_rows
+    }
+    
+    // Set the style of a cell, creating an empty cell if needed
+    pub fn set_cell_style(&mut self, row: RowId, col: ColumnId, style: CellStyle) {
+        self.cells.entry((row, col))
+            .or_insert_with(|| Cell::new(CellValue::Blank))
+            .style = Some(style);
+    }
+    
+    // Get the style of a cell, if it has one
+    pub fn get_cell_style(&self, row: RowId, col: ColumnId) -> Option<&CellStyle> {
+        self.get_cell(row, col).and_then(|cell| cell.style.as_ref())
+    }
+    
+    // Remove the style of a cell, returning it to the default look
+    pub fn clear_cell_style(&mut self, row: RowId, col: ColumnId) {
+        if let Some(cell) = self.cells.get_mut(&(row, col)) {
+            cell.style = None;
+        }
+    }
+    
+    // Attach a note to a cell, creating an empty cell if needed
+    pub fn set_cell_note(&mut self, row: RowId, col: ColumnId, note: impl Into<String>) {
+        self.cells.entry((row, col))
+            .or_insert_with(|| Cell::new(CellValue::Blank))
+            .note = Some(note.into());
+    }
+    
+    // Get the note of a cell, if it has one
+    pub fn get_cell_note(&self, row: RowId, col: ColumnId) -> Option<&str> {
+        self.get_cell(row, col).and_then(|cell| cell.note.as_deref())
+    }
+    
+    // Remove the note of a cell
+    pub fn clear_cell_note(&mut self, row: RowId, col: ColumnId) {
+        if let Some(cell) = self.cells.get_mut(&(row, col)) {
+            cell.note = None;
+        }
+    }
+    
+    // Link a cell to a URL. With `text` the cell's value becomes that link text;
+    // otherwise the cell keeps its value, and an empty cell shows the URL itself.
+    pub fn set_cell_hyperlink(&mut self, row: RowId, col: ColumnId, url: impl Into<String>, text: Option<&str>) -> Result<(), EngineError> {
+        if let Some(text) = text {
+            self.set_cell(row, col, CellValue::Text(text.to_string()))?;
+        }
+        self.cells.entry((row, col))
+            .or_insert_with(|| Cell::new(CellValue::Blank))
+            .hyperlink = Some(url.into());
+        Ok(())
+    }
+    
+    // Get the URL a cell links to, if any
+    pub fn get_cell_hyperlink(&self, row: RowId, col: ColumnId) -> Option<&str> {
+        self.get_cell(row, col).and_then(|cell| cell.hyperlink.as_deref())
+    }
+    
+    // Remove the hyperlink of a cell, keeping its value
+    pub fn clear_cell_hyperlink(&mut self, row: RowId, col: ColumnId) {
+        if let Some(cell) = self.cells.get_mut(&(row, col)) {
+            cell.hyperlink = None;
+        }
+    }
+    
     // Get cells in a range
     pub fn get_cell_range(&self, range: &CellRange) -> Vec<&Cell> {
         let mut result = Vec::new();
@@ -220,6 +816,72 @@ impl Sheet {
         result
     }
     
+    // Populated cells within a range in row-major order. Unlike `get_range_values` this
+    // never visits empty coordinates: small ranges are probed cell by cell, large ones
+    // are found by scanning the sheet's cells, whichever touches fewer entries.
+    pub fn get_range_cells(&self, range: &CellRange) -> std::vec::IntoIter<(CellAddress, &Cell)> {
+        let (rows, cols) = range.dimensions();
+        let (top, left) = (range.start.row.min(range.end.row), range.start.col.min(range.end.col));
+        
+        let cells: Vec<_> = if rows as u64 * cols as u64 <= self.cells.len() as u64 {
+            (top..top + rows)
+                .flat_map(|row| (left..left + cols).map(move |col| (row, col)))
+                .filter_map(|(row, col)| self.cells.get(&(row, col)).map(|cell| (CellAddress::new(row, col), cell)))
+                .collect()
+        } else {
+            let mut cells: Vec<_> = self.cells.iter()
+                .map(|(&(row, col), cell)| (CellAddress::new(row, col), cell))
+                .filter(|(address, _)| range.contains(address))
+                .collect();
+            cells.sort_by_key(|(address, _)| (address.row, address.col));
+            cells
+        };
+        
+        cells.into_iter()
+    }
+    
+    // Compare the formulas of a single row or column by their relative form. The
+    // template is the most common form (the earliest on a tie); every cell that
+    // differs from it, including cells without a formula, is a break.
+    pub fn detect_pattern(&self, range: &CellRange) -> Result<FormulaPattern, EngineError> {
+        let range = range.normalized();
+        let (rows, cols) = range.dimensions();
+        if rows > 1 && cols > 1 {
+            return Err(EngineError::InvalidReference(format!("{} is not a single row or column", range)));
+        }
+        
+        let forms: Vec<(CellAddress, Option<String>)> = (range.start.row..=range.end.row)
+            .flat_map(|row| (range.start.col..=range.end.col).map(move |col| CellAddress::new(row, col)))
+            .map(|address| {
+                let form = self.get_cell(address.row, address.col)
+                    .and_then(|cell| cell.formula.as_deref())
+                    .and_then(|formula| parse_formula(formula).ok())
+                    .map(|ast| format!("={}", ast.to_r1c1(&address)));
+                (address, form)
+            })
+            .collect();
+        
+        let mut counts: Vec<(&str, usize)> = Vec::new();
+        for form in forms.iter().filter_map(|(_, form)| form.as_deref()) {
+            match counts.iter_mut().find(|(seen, _)| *seen == form) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((form, 1)),
+            }
+        }
+        // max_by_key keeps the last maximum, so search from the back to prefer the earliest form
+        let template = counts.iter().rev().max_by_key(|(_, count)| *count).map(|(form, _)| form.to_string());
+        
+        let breaks = match &template {
+            Some(template) => forms.iter()
+                .filter(|(_, form)| form.as_ref() != Some(template))
+                .map(|(address, _)| address.clone())
+                .collect(),
+            None => Vec::new(),
+        };
+        
+        Ok(FormulaPattern { template, breaks })
+    }
+    
     // Check if the sheet contains a cell at the specified coordinates
     pub fn contains_cell(&self, row: RowId, col: ColumnId) -> bool {
         self.cells.contains_key(&(row, col))
@@ -230,16 +892,79 @@ impl Sheet {
         self.cells.remove(&(row, col)).is_some()
     }
     
+    // Remove every cell in a range, returning the addresses that held a cell
+    pub fn clear_range(&mut self, range: &CellRange) -> Vec<CellAddress> {
+        self.take_range(range).into_iter().map(|(address, _)| address).collect()
+    }
+    
+    // Remove every cell in a range and hand them back
+    fn take_range(&mut self, range: &CellRange) -> Vec<(CellAddress, Cell)> {
+        let addresses: Vec<CellAddress> = self.get_range_cells(range).map(|(address, _)| address).collect();
+        addresses.into_iter()
+            .filter_map(|address| self.cells.remove(&(address.row, address.col)).map(|cell| (address, cell)))
+            .collect()
+    }
+    
+    // Move the cells of a range so its top-left corner lands on `dest`, overwriting the
+    // destination. Source and destination may overlap. Merges inside the range move
+    // with it; a merge straddling either area is an error. Returns the destination
+    // cells that were overwritten.
+    pub fn move_range(&mut self, source: &CellRange, dest: &CellAddress) -> Result<Vec<CellAddress>, EngineError> {
+        let source = source.normalized();
+        let target = source.moved_to(dest)?;
+        let inside_source = |range: &CellRange| source.contains(&range.start) && source.contains(&range.end);
+        if let Some(merged) = self.merged_ranges.iter()
+            .find(|merged| (merged.overlaps(&source) || merged.overlaps(&target)) && !inside_source(merged))
+        {
+            return Err(EngineError::InvalidReference(format!("Cannot move {} across merged range {}", source, merged)));
+        }
+        
+        // Lift the source cells out first so an overlapping destination can't clobber them
+        let moved = self.take_range(&source);
+        let overwritten = self.clear_range(&target);
+        let shift = |address: &CellAddress| {
+            CellAddress::new(address.row - source.start.row + target.start.row, address.col - source.start.col + target.start.col)
+        };
+        for (address, cell) in moved {
+            let address = shift(&address);
+            self.cells.insert((address.row, address.col), cell);
+        }
+        for merged in self.merged_ranges.iter_mut().filter(|merged| inside_source(merged)) {
+            *merged = CellRange::new(shift(&merged.start), shift(&merged.end));
+        }
+        
+        Ok(overwritten)
+    }
+    
     // Get all cells in the sheet
     pub fn get_all_cells(&self) -> impl Iterator<Item = (&(RowId, ColumnId), &Cell)> {
         self.cells.iter()
     }
     
+    // Get the bottom-right corner (max row, max column) of the used cells, if any
+    pub fn used_bounds(&self) -> Option<(RowId, ColumnId)> {
+        self.cells.keys().fold(None, |bounds, &(row, col)| match bounds {
+            None => Some((row, col)),
+            Some((max_row, max_col)) => Some((max_row.max(row), max_col.max(col))),
+        })
+    }
+    
     // Get the number of cells in the sheet
     pub fn cell_count(&self) -> usize {
         self.cells.len()
     }
     
+    // Make room for at least `additional` more cells, e.g. before a bulk load of a
+    // known size, so the cell map isn't rehashed as it grows
+    pub fn reserve(&mut self, additional: usize) {
+        self.cells.reserve(additional);
+    }
+    
+    // Release the cell map's spare capacity, e.g. after a bulk load
+    pub fn shrink_to_fit(&mut self) {
+        self.cells.shrink_to_fit();
+    }
+    
     // Find all cells in a given row
     pub fn get_row(&self, row: RowId) -> impl Iterator<Item = (&ColumnId, &Cell)> + '_ {
         self.cells.iter()
@@ -278,12 +1003,15 @@ impl CellReference {
     }
     
     pub fn from_a1(reference: &str) -> Result<Self, EngineError> {
-        // Split sheet and cell reference if sheet is specified (Sheet1!A1)
-        if let Some(pos) = reference.find('!') {
+        // Split sheet and cell reference if sheet is specified (Sheet1!A1). A quoted
+        // sheet name may itself contain '!', so split at the last one.
+        if let Some(pos) = reference.rfind('!') {
             let (sheet, cell) = reference.split_at(pos);
             let cell = &cell[1..]; // Remove the '!' character
             let address = CellAddress::from_a1(cell)?;
-            Ok(CellReference::with_sheet(sheet.to_string(), address))
+            let sheet = unquote_sheet_name(sheet)
+                .ok_or_else(|| EngineError::InvalidReference(format!("Invalid sheet name in {}", reference)))?;
+            Ok(CellReference::with_sheet(sheet, address))
         } else {
             let address = CellAddress::from_a1(reference)?;
             Ok(CellReference::new(address))
@@ -294,7 +1022,7 @@ impl CellReference {
 impl fmt::Display for CellReference {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if let Some(sheet) = &self.sheet {
-            write!(f, "{}!{}", sheet, self.address.to_a1())
+            write!(f, "{}!{}", quote_sheet_name(sheet), self.address.to_a1())
         } else {
             write!(f, "{}", self.address.to_a1())
         }
@@ -308,6 +1036,10 @@ pub struct DependencyGraph {
     
     // Maps cells to the cells they reference (cell -> [referenced cells])
     precedents: HashMap<(String, CellAddress), HashSet<(String, CellAddress)>>,
+    
+    // Whole-column and whole-row ranges a cell references (cell -> [(sheet, range)]).
+    // These are kept unexpanded and matched against cells on lookup.
+    range_precedents: HashMap<(String, CellAddress), Vec<(String, CellRange)>>,
 }
 
 impl DependencyGraph {
@@ -315,6 +1047,7 @@ impl DependencyGraph {
         DependencyGraph {
             dependents: HashMap::new(),
             precedents: HashMap::new(),
+            range_precedents: HashMap::new(),
         }
     }
     
@@ -334,28 +1067,25 @@ impl DependencyGraph {
             .insert(dep_key);
     }
     
+    // Add a dependency on a whole-column or whole-row range without expanding it
+    pub fn add_range_dependency(&mut self, sheet: &str, cell: &CellAddress, dep_sheet: &str, range: CellRange) {
+        self.range_precedents.entry((sheet.to_string(), cell.clone()))
+            .or_default()
+            .push((dep_sheet.to_string(), range));
+    }
+    
     // Get all cells that depend on this cell (directly or indirectly)
     pub fn get_dependents(&self, sheet: &str, cell: &CellAddress) -> HashSet<(String, CellAddress)> {
         let mut result = HashSet::new();
         let mut queue = VecDeque::new();
+        queue.push_back((sheet.to_string(), cell.clone()));
         
-        // Start with direct dependents
-        let key = (sheet.to_string(), cell.clone());
-        if let Some(deps) = self.dependents.get(&key) {
-            for dep in deps {
-                queue.push_back(dep.clone());
-                result.insert(dep.clone());
-            }
-        }
-        
-        // Process the queue to get indirect dependents
+        // Breadth-first over direct dependents and formulas whose ranges cover the cell
         while let Some(current) = queue.pop_front() {
-            if let Some(deps) = self.dependents.get(&current) {
-                for dep in deps {
-                    if !result.contains(dep) {
-                        queue.push_back(dep.clone());
-                        result.insert(dep.clone());
-                    }
+            let direct = self.dependents.get(&current).into_iter().flatten();
+            for dep in direct.chain(self.range_dependents(&current)) {
+                if result.insert(dep.clone()) {
+                    queue.push_back(dep.clone());
                 }
             }
         }
@@ -363,46 +1093,187 @@ impl DependencyGraph {
         result
     }
     
+    // Cells whose whole-column or whole-row references cover this cell
+    fn range_dependents<'a>(&'a self, key: &'a (String, CellAddress)) -> impl Iterator<Item = &'a (String, CellAddress)> + 'a {
+        self.range_precedents.iter()
+            .filter(move |(_, ranges)| ranges.iter().any(|(sheet, range)| *sheet == key.0 && range.contains(&key.1)))
+            .map(|(cell, _)| cell)
+    }
+    
+    // Whether `cell` references `target` through a whole-column or whole-row range
+    fn range_covers(&self, cell: &(String, CellAddress), target: &(String, CellAddress)) -> bool {
+        self.range_precedents.get(cell).is_some_and(|ranges| {
+            ranges.iter().any(|(sheet, range)| *sheet == target.0 && range.contains(&target.1))
+        })
+    }
+    
     // Get direct precedents (cells this cell depends on)
     pub fn get_precedents(&self, sheet: &str, cell: &CellAddress) -> Option<&HashSet<(String, CellAddress)>> {
         self.precedents.get(&(sheet.to_string(), cell.clone()))
     }
     
+    // Get the whole-column and whole-row ranges this cell references directly
+    pub fn get_range_precedents(&self, sheet: &str, cell: &CellAddress) -> &[(String, CellRange)] {
+        self.range_precedents.get(&(sheet.to_string(), cell.clone())).map_or(&[], Vec::as_slice)
+    }
+    
     // Remove all dependencies for a cell (when cell is updated/removed)
     pub fn remove_dependencies(&mut self, sheet: &str, cell: &CellAddress) {
         let cell_key = (sheet.to_string(), cell.clone());
         
+        self.range_precedents.remove(&cell_key);
+        
         // Remove from precedents map and collect all precedents
         let precedents = self.precedents.remove(&cell_key).unwrap_or_default();
         
         // Remove this cell from the dependents list of each precedent
-        for (prec_sheet, prec_cell) in precedents {
-            if let Some(deps) = self.dependents.get_mut(&(prec_sheet, prec_cell)) {
+        for prec_key in precedents {
+            if let Some(deps) = self.dependents.get_mut(&prec_key) {
                 deps.remove(&cell_key);
                 
                 // Clean up empty sets
                 if deps.is_empty() {
-                    self.dependents.remove(&(prec_sheet, prec_cell));
+                    self.dependents.remove(&prec_key);
                 }
             }
         }
     }
     
-    // Check for circular references starting from this cell
-    pub fn check_circular_reference(&self, sheet: &str, cell: &CellAddress) -> bool {
-        let mut visited = HashSet::new();
-        let mut path = HashSet::new();
+    // Order a set of cells for evaluation. Returns the strongly connected components
+    // of the dependency graph restricted to `cells` (Tarjan's algorithm), precedents first.
+    pub fn evaluation_order(&self, cells: &HashSet<(String, CellAddress)>) -> Vec<Vec<(String, CellAddress)>> {
+        // Edges point from a cell to its precedents within the set
+        let precedents_in_set = |cell: &(String, CellAddress)| self.precedents_in(cell, cells);
         
-        self.dfs_check_circular(&(sheet.to_string(), cell.clone()), &mut visited, &mut path)
-    }
-    
-    // Helper DFS function for circular reference detection
-    fn dfs_check_circular(&self, 
-                           cell: &(String, CellAddress), 
-                           visited: &mut HashSet<(String, CellAddress)>, 
-                           path: &mut HashSet<(String, CellAddress)>) -> bool {
-        // If we've seen this cell in our current path, we have a cycle
-        if path.contains(cell) {
+        let mut next_index = 0;
+        let mut index: HashMap<(String, CellAddress), usize> = HashMap::new();
+        let mut lowlink: HashMap<(String, CellAddress), usize> = HashMap::new();
+        let mut stack = Vec::new();
+        let mut on_stack = HashSet::new();
+        let mut components = Vec::new();
+        
+        for root in cells {
+            if index.contains_key(root) {
+                continue;
+            }
+            
+            index.insert(root.clone(), next_index);
+            lowlink.insert(root.clone(), next_index);
+            next_index += 1;
+            stack.push(root.clone());
+            on_stack.insert(root.clone());
+            
+            // Explicit DFS stack of (cell, its precedents, next precedent to visit)
+            let mut work = vec![(root.clone(), precedents_in_set(root), 0)];
+            
+            while let Some(frame) = work.last_mut() {
+                let cell = frame.0.clone();
+                
+                if frame.2 < frame.1.len() {
+                    let next = frame.1[frame.2].clone();
+                    frame.2 += 1;
+                    
+                    if !index.contains_key(&next) {
+                        index.insert(next.clone(), next_index);
+                        lowlink.insert(next.clone(), next_index);
+                        next_index += 1;
+                        stack.push(next.clone());
+                        on_stack.insert(next.clone());
+                        let next_precedents = precedents_in_set(&next);
+                        work.push((next, next_precedents, 0));
+                    } else if on_stack.contains(&next) {
+                        let low = lowlink[&cell].min(index[&next]);
+                        lowlink.insert(cell, low);
+                    }
+                    continue;
+                }
+                
+                // All precedents visited: propagate lowlink to the parent
+                work.pop();
+                if let Some(parent) = work.last() {
+                    let low = lowlink[&parent.0].min(lowlink[&cell]);
+                    lowlink.insert(parent.0.clone(), low);
+                }
+                
+                // Root of a component: pop its members off the stack
+                if lowlink[&cell] == index[&cell] {
+                    let mut component = Vec::new();
+                    while let Some(member) = stack.pop() {
+                        on_stack.remove(&member);
+                        let is_root = member == cell;
+                        component.push(member);
+                        if is_root {
+                            break;
+                        }
+                    }
+                    components.push(component);
+                }
+            }
+        }
+        
+        components
+    }
+    
+    // Group the components returned by `evaluation_order` into levels, precedents
+    // first. A component's level is one past the highest level among its precedents,
+    // so the components within a level never depend on each other.
+    pub fn evaluation_levels(&self, cells: &HashSet<(String, CellAddress)>, order: Vec<Vec<(String, CellAddress)>>) -> Vec<Vec<Vec<(String, CellAddress)>>> {
+        let mut level_of: HashMap<(String, CellAddress), usize> = HashMap::new();
+        let mut levels: Vec<Vec<Vec<(String, CellAddress)>>> = Vec::new();
+        
+        for component in order {
+            // Members of a cycle are still unassigned, so they don't count themselves
+            let level = component.iter()
+                .flat_map(|cell| self.precedents_in(cell, cells))
+                .filter_map(|precedent| level_of.get(&precedent).map(|level| level + 1))
+                .max()
+                .unwrap_or(0);
+            for cell in &component {
+                level_of.insert(cell.clone(), level);
+            }
+            if level == levels.len() {
+                levels.push(Vec::new());
+            }
+            levels[level].push(component);
+        }
+        
+        levels
+    }
+    
+    // A cell's direct precedents (cells and covering ranges) that are in `cells`
+    fn precedents_in(&self, cell: &(String, CellAddress), cells: &HashSet<(String, CellAddress)>) -> Vec<(String, CellAddress)> {
+        let mut precs: Vec<_> = self.precedents.get(cell)
+            .map(|precs| precs.iter().filter(|p| cells.contains(*p)).cloned().collect())
+            .unwrap_or_default();
+        if self.range_precedents.contains_key(cell) {
+            precs.extend(cells.iter().filter(|p| self.range_covers(cell, p)).cloned());
+        }
+        precs
+    }
+    
+    // Whether a component returned by `evaluation_order` is a cycle
+    pub fn is_cyclic(&self, component: &[(String, CellAddress)]) -> bool {
+        match component {
+            [cell] => self.precedents.get(cell).is_some_and(|precs| precs.contains(cell)) || self.range_covers(cell, cell),
+            _ => component.len() > 1,
+        }
+    }
+    
+    // Check for circular references starting from this cell
+    pub fn check_circular_reference(&self, sheet: &str, cell: &CellAddress) -> bool {
+        let mut visited = HashSet::new();
+        let mut path = HashSet::new();
+        
+        self.dfs_check_circular(&(sheet.to_string(), cell.clone()), &mut visited, &mut path)
+    }
+    
+    // Helper DFS function for circular reference detection
+    fn dfs_check_circular(&self, 
+                           cell: &(String, CellAddress), 
+                           visited: &mut HashSet<(String, CellAddress)>, 
+                           path: &mut HashSet<(String, CellAddress)>) -> bool {
+        // If we've seen this cell in our current path, we have a cycle
+        if path.contains(cell) {
             return true;
         }
         
@@ -431,14 +1302,122 @@ impl DependencyGraph {
     }
 }
 
+// Document properties of a workbook, as stored in an XLSX file
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct WorkbookMetadata {
+    pub title: Option<String>,
+    pub subject: Option<String>,
+    pub author: Option<String>,
+    pub company: Option<String>,
+    pub keywords: Option<String>,
+    pub comments: Option<String>,
+    pub created: Option<NaiveDateTime>, // UTC
+    pub modified: Option<NaiveDateTime>, // UTC
+    pub custom: BTreeMap<String, String>, // Custom properties by name
+}
+
+// A named set of input values, e.g. the base, bull or bear case of a model
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    pub sheet: String,
+    pub cells: Vec<(CellAddress, CellValue)>,
+}
+
+// An Excel table: a named range whose first row holds the column headers.
+// Formulas reach its columns by structured reference (Sales[Amount]).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub name: String,
+    pub sheet: String,
+    pub range: CellRange, // Including the header row
+    pub columns: Vec<String>, // Header of each column, left to right
+}
+
+impl Table {
+    // The data cells of a column (below the header), by case-insensitive header
+    pub fn column_range(&self, column: &str) -> Option<CellRange> {
+        let index = self.columns.iter().position(|header| header.eq_ignore_ascii_case(column))?;
+        let col = self.range.start.col + index as ColumnId;
+        Some(CellRange::new(CellAddress::new(self.range.start.row + 1, col), CellAddress::new(self.range.end.row, col)))
+    }
+    
+    // Whether a cell of the given sheet lies inside the table, header included
+    pub fn contains(&self, sheet: &str, address: &CellAddress) -> bool {
+        self.sheet == sheet && self.range.contains(address)
+    }
+}
+
+// Settings for iterative calculation of circular references
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IterativeCalc {
+    pub max_iterations: u32,
+    pub max_change: f64, // Stop once no cell changes by more than this between iterations
+}
+
+impl Default for IterativeCalc {
+    fn default() -> Self {
+        // Excel's defaults
+        IterativeCalc {
+            max_iterations: 100,
+            max_change: 0.001,
+        }
+    }
+}
+
+// How `Workbook::group_by` summarizes the values of each group
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregation {
+    Sum,
+    Average,
+    Count,
+    Min,
+    Max,
+}
+
+impl Aggregation {
+    // The worksheet function computing the aggregate
+    pub fn function_name(&self) -> &'static str {
+        match self {
+            Aggregation::Sum => "SUM",
+            Aggregation::Average => "AVERAGE",
+            Aggregation::Count => "COUNT",
+            Aggregation::Min => "MIN",
+            Aggregation::Max => "MAX",
+        }
+    }
+}
+
+// Seed for workbooks whose random number generator was never seeded explicitly
+fn time_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
 // Workbook structure - the top-level container
 pub struct Workbook {
     sheets: HashMap<String, Sheet>,
     active_sheet: Option<String>,
     evaluator: Evaluator,
-    parser: Parser,
     dependency_graph: DependencyGraph,
     dirty_cells: HashSet<(String, CellAddress)>,
+    edited_cells: HashSet<(String, CellAddress)>, // Dirty cells that changed themselves, not through a precedent
+    volatile_cells: HashSet<(String, CellAddress)>, // Formulas recomputed on every recalculation
+    iterative_calc: Option<IterativeCalc>, // None: circular references evaluate to #REF!
+    date_system: DateSystem,
+    locale: Locale, // Separators and calendar names TEXT and formatted values render with
+    strict: bool, // Reject formulas that fail validation instead of storing them
+    random_state: AtomicU64, // SplitMix64 state behind RAND and RANDBETWEEN
+    evaluations: u64, // Formula cells evaluated by recalculation so far
+    journal: EditJournal, // Undo and redo history of cell edits
+    batch_depth: usize, // Open transactions; recalculation waits until they commit
+    metadata: WorkbookMetadata,
+    scenarios: BTreeMap<String, Scenario>,
+    tables: BTreeMap<String, Table>, // By upper-case name; table names are case-insensitive
+    // Parsed formulas by cell, with the text each was parsed from. Recalculation
+    // reuses an entry for as long as the cell's formula text is unchanged.
+    formula_asts: HashMap<(String, CellAddress), (String, Arc<AstNode>)>,
 }
 
 impl Workbook {
@@ -447,9 +1426,22 @@ impl Workbook {
             sheets: HashMap::new(),
             active_sheet: None,
             evaluator: Evaluator::new(),
-            parser: Parser::new(),
             dependency_graph: DependencyGraph::new(),
             dirty_cells: HashSet::new(),
+            edited_cells: HashSet::new(),
+            volatile_cells: HashSet::new(),
+            iterative_calc: None,
+            date_system: DateSystem::default(),
+            locale: Locale::default(),
+            strict: false,
+            random_state: AtomicU64::new(time_seed()),
+            evaluations: 0,
+            journal: EditJournal::default(),
+            batch_depth: 0,
+            metadata: WorkbookMetadata::default(),
+            scenarios: BTreeMap::new(),
+            tables: BTreeMap::new(),
+            formula_asts: HashMap::new(),
         }
     }
     
@@ -505,6 +1497,105 @@ impl Workbook {
         self.sheets.keys().collect()
     }
     
+    // Enable (Some) or disable (None) iterative calculation of circular references
+    pub fn set_iterative_calc(&mut self, settings: Option<IterativeCalc>) {
+        self.iterative_calc = settings;
+    }
+    
+    // Get the iterative calculation settings
+    pub fn iterative_calc(&self) -> Option<IterativeCalc> {
+        self.iterative_calc
+    }
+    
+    // Set the date system used to interpret date serial numbers. Formulas are
+    // recalculated so date functions return serials of the new system.
+    pub fn set_date_system(&mut self, system: DateSystem) -> Result<(), EngineError> {
+        self.date_system = system;
+        self.recalculate_all_formulas()
+    }
+    
+    // Get the date system
+    pub fn date_system(&self) -> DateSystem {
+        self.date_system
+    }
+    
+    // Set the locale numbers and dates are formatted in. Formulas are recalculated
+    // so TEXT results follow the new separators and names.
+    pub fn set_locale(&mut self, locale: Locale) -> Result<(), EngineError> {
+        self.locale = locale;
+        self.recalculate_all_formulas()
+    }
+    
+    // Mark every formula cell dirty and recalculate, after a setting they may
+    // depend on has changed
+    fn recalculate_all_formulas(&mut self) -> Result<(), EngineError> {
+        let formula_cells: Vec<(String, CellAddress)> = self.sheets.iter()
+            .flat_map(|(sheet_name, sheet)| {
+                sheet.get_all_cells()
+                    .filter(|(_, cell)| cell.formula.is_some())
+                    .map(move |(&(row, col), _)| (sheet_name.clone(), CellAddress::new(row, col)))
+            })
+            .collect();
+        for (sheet_name, cell_addr) in &formula_cells {
+            self.mark_dirty(sheet_name, cell_addr);
+        }
+        self.finish_edit()
+    }
+    
+    // Get the locale
+    pub fn locale(&self) -> &Locale {
+        &self.locale
+    }
+    
+    // In strict mode `set_cell_value` rejects a formula that calls an unknown function
+    // or passes the wrong number of arguments, leaving the cell unchanged. Otherwise
+    // such a formula is stored and fails when it is evaluated.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+    
+    // Whether formulas are validated before they are stored
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+    
+    // Get the functions formulas can call, with their argument counts and descriptions
+    pub fn functions(&self) -> &FunctionRegistry {
+        self.evaluator.function_registry()
+    }
+    
+    // Get the document properties
+    pub fn metadata(&self) -> &WorkbookMetadata {
+        &self.metadata
+    }
+    
+    // Get the document properties for editing
+    pub fn metadata_mut(&mut self) -> &mut WorkbookMetadata {
+        &mut self.metadata
+    }
+    
+    // Replace the document properties
+    pub fn set_metadata(&mut self, metadata: WorkbookMetadata) {
+        self.metadata = metadata;
+    }
+    
+    // Seed the random number generator so RAND results are reproducible
+    pub fn set_random_seed(&mut self, seed: u64) {
+        self.random_state = AtomicU64::new(seed);
+    }
+    
+    // Next random number in [0, 1) (SplitMix64)
+    pub(crate) fn next_random(&self) -> f64 {
+        let mut z = self.random_state
+            .fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed)
+            .wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        // The top 53 bits fill an f64 mantissa exactly
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+    
     // Set a cell value and update dependencies
     pub fn set_cell_value(&mut self, sheet_name: &str, row: RowId, col: ColumnId, value: impl Into<CellValue>) -> Result<(), EngineError> {
         let cell_addr = CellAddress::new(row, col);
@@ -516,44 +1607,374 @@ impl Workbook {
             None => return Err(EngineError::Internal(format!("Sheet '{}' does not exist", sheet_name))),
         };
         
+        sheet.check_not_merged_over(row, col)?;
+        let before = sheet.get_cell(row, col).cloned();
+        
+        // If it's a formula, parse it to find its dependencies. Outside strict mode a
+        // formula that calls an unknown function or has the wrong argument count is
+        // still stored; its problem is reported when it is evaluated.
+        let (ast, validated) = match &value {
+            CellValue::Formula(formula_text) => match validate_formula(formula_text, self.evaluator.function_registry()) {
+                Ok(ast) => (Some(ast), true),
+                Err(e) if self.strict => return Err(e),
+                Err(_) => (Some(parse_formula(formula_text)?), false),
+            },
+            _ => (None, false),
+        };
+        
         // Clear existing dependencies for this cell
         self.dependency_graph.remove_dependencies(sheet_name, &cell_addr);
-        
-        // If it's a formula, parse it and update dependencies
-        if let CellValue::Formula(formula_text) = &value {
-            // Parse formula to get AST
-            let ast = self.parser.parse(formula_text)?;
-            
-            // Extract cell references from AST and add to dependency graph
-            let references = self.parser.extract_cell_references(&ast);
-            for reference in references {
-                let ref_sheet = reference.sheet.as_deref().unwrap_or(sheet_name);
-                self.dependency_graph.add_dependency(sheet_name, &cell_addr, ref_sheet, &reference.address);
-            }
-            
-            // Check for circular references
-            if self.dependency_graph.check_circular_reference(sheet_name, &cell_addr) {
-                // Undo adding dependencies
-                self.dependency_graph.remove_dependencies(sheet_name, &cell_addr);
-                return Err(EngineError::CircularReference(format!("Circular reference detected at {}", cell_addr.to_a1())));
-            }
-        }
+        self.volatile_cells.remove(&(sheet_name.to_string(), cell_addr.clone()));
         
         // Set the cell value
         sheet.set_cell(row, col, value)?;
+        let after = sheet.get_cell(row, col).cloned();
+        
+        if let Some(ast) = ast {
+            self.register_dependencies(sheet_name, &cell_addr, &ast);
+            if let Some(CellValue::Formula(formula)) = after.as_ref().map(|cell| &cell.value).filter(|_| validated) {
+                self.formula_asts.insert((sheet_name.to_string(), cell_addr.clone()), (formula.clone(), Arc::new(ast)));
+            }
+        }
+        self.journal.record(vec![Change::cell(sheet_name, cell_addr.clone(), before, after)]);
         
         // Mark this cell and its dependents as dirty
         self.mark_dirty(sheet_name, &cell_addr);
         
         // Recalculate dirty cells
-        self.recalculate()?;
+        self.finish_edit()?;
         
         Ok(())
     }
     
+    // Set a block of cells of one sheet as a single edit: the workbook recalculates
+    // once, after every value is in place, and `undo()` reverts them together. If a
+    // value fails (e.g. a bad formula in strict mode) none of them are kept.
+    pub fn set_cells(&mut self, sheet_name: &str, cells: Vec<(RowId, ColumnId, CellValue)>) -> Result<(), EngineError> {
+        if !self.sheets.contains_key(sheet_name) {
+            return Err(EngineError::Internal(format!("Sheet '{}' does not exist", sheet_name)));
+        }
+        
+        self.transaction(|workbook| {
+            for (row, col, value) in cells {
+                workbook.set_cell_value(sheet_name, row, col, value)?;
+            }
+            Ok(())
+        })
+    }
+    
+    // Add a formula's references to the dependency graph. Circular references are
+    // accepted here and surface as #REF! on recalculation. References to sheets that
+    // don't exist evaluate to #REF! and add no dependency.
+    fn register_dependencies(&mut self, sheet_name: &str, cell_addr: &CellAddress, ast: &AstNode) {
+        for reference in extract_cell_references(ast) {
+            let ref_sheet = reference.sheet.as_deref().unwrap_or(sheet_name);
+            if self.sheets.contains_key(ref_sheet) {
+                self.dependency_graph.add_dependency(sheet_name, cell_addr, ref_sheet, &reference.address);
+            }
+        }
+        for (ref_sheet, range) in extract_range_references(ast) {
+            let ref_sheet = ref_sheet.as_deref().unwrap_or(sheet_name);
+            if self.sheets.contains_key(ref_sheet) {
+                self.dependency_graph.add_range_dependency(sheet_name, cell_addr, ref_sheet, range);
+            }
+        }
+        // Structured references depend on the cells of the table column they resolve to
+        for reference in extract_table_references(ast) {
+            let Ok(reference) = self.resolve_table_reference(sheet_name, cell_addr, &reference) else { continue };
+            let (ref_sheet, bounds) = (reference.sheet().unwrap_or(sheet_name).to_string(), reference.bounds());
+            for row in bounds.start.row..=bounds.end.row {
+                for col in bounds.start.col..=bounds.end.col {
+                    self.dependency_graph.add_dependency(sheet_name, cell_addr, &ref_sheet, &CellAddress::new(row, col));
+                }
+            }
+        }
+        
+        if self.evaluator.is_volatile(ast) {
+            self.volatile_cells.insert((sheet_name.to_string(), cell_addr.clone()));
+        }
+    }
+    
+    // Remove every cell in a range and recalculate the formulas that referenced them
+    pub fn clear_range(&mut self, sheet_name: &str, range: &CellRange) -> Result<(), EngineError> {
+        let sheet = match self.sheets.get_mut(sheet_name) {
+            Some(s) => s,
+            None => return Err(EngineError::Internal(format!("Sheet '{}' does not exist", sheet_name))),
+        };
+        
+        let mut changes = Vec::new();
+        for (cell_addr, cell) in sheet.take_range(range) {
+            self.dependency_graph.remove_dependencies(sheet_name, &cell_addr);
+            self.volatile_cells.remove(&(sheet_name.to_string(), cell_addr.clone()));
+            self.formula_asts.remove(&(sheet_name.to_string(), cell_addr.clone()));
+            self.mark_dirty(sheet_name, &cell_addr);
+            changes.push(Change::cell(sheet_name, cell_addr, Some(cell), None));
+        }
+        self.journal.record(changes);
+        
+        self.finish_edit()
+    }
+    
+    // Remove one cell and recalculate the formulas that referenced it. Returns
+    // whether there was a cell to remove.
+    pub fn delete_cell(&mut self, sheet_name: &str, row: RowId, col: ColumnId) -> Result<bool, EngineError> {
+        let existed = match self.sheets.get(sheet_name) {
+            Some(s) => s.contains_cell(row, col),
+            None => return Err(EngineError::Internal(format!("Sheet '{}' does not exist", sheet_name))),
+        };
+        if existed {
+            let cell_addr = CellAddress::new(row, col);
+            self.clear_range(sheet_name, &CellRange::new(cell_addr.clone(), cell_addr))?;
+        }
+        Ok(existed)
+    }
+    
+    // Hide or show a row and recalculate the formulas that read its cells, since
+    // SUBTOTAL can skip hidden rows
+    pub fn set_row_hidden(&mut self, sheet_name: &str, row: RowId, hidden: bool) -> Result<(), EngineError> {
+        let sheet = match self.sheets.get_mut(sheet_name) {
+            Some(s) => s,
+            None => return Err(EngineError::Internal(format!("Sheet '{}' does not exist", sheet_name))),
+        };
+        if sheet.is_row_hidden(row) == hidden {
+            return Ok(());
+        }
+        sheet.set_row_hidden(row, hidden);
+        
+        let cells: Vec<CellAddress> = sheet.get_row(row).map(|(&col, _)| CellAddress::new(row, col)).collect();
+        for cell_addr in &cells {
+            self.mark_dirty(sheet_name, cell_addr);
+        }
+        
+        self.finish_edit()
+    }
+    
+    // Remove every cell of a sheet, keeping the sheet itself
+    pub fn clear_sheet(&mut self, sheet_name: &str) -> Result<(), EngineError> {
+        let used = match self.sheets.get(sheet_name) {
+            Some(s) => s.used_bounds(),
+            None => return Err(EngineError::Internal(format!("Sheet '{}' does not exist", sheet_name))),
+        };
+        
+        match used {
+            Some((row, col)) => self.clear_range(sheet_name, &CellRange::new(CellAddress::new(0, 0), CellAddress::new(row, col))),
+            None => Ok(()),
+        }
+    }
+    
+    // Move a range of cells so its top-left corner lands on `dest` (cut and paste).
+    // Formulas anywhere in the workbook that referenced the moved cells follow them;
+    // references to destination cells that were overwritten become #REF!.
+    pub fn move_range(&mut self, sheet_name: &str, source: CellRange, dest: CellAddress) -> Result<(), EngineError> {
+        let source = source.normalized();
+        let target = source.moved_to(&dest)?;
+        let row_offset = dest.row as i64 - source.start.row as i64;
+        let col_offset = dest.col as i64 - source.start.col as i64;
+        
+        let sheet = match self.sheets.get_mut(sheet_name) {
+            Some(s) => s,
+            None => return Err(EngineError::Internal(format!("Sheet '{}' does not exist", sheet_name))),
+        };
+        let moved: Vec<CellAddress> = sheet.get_range_cells(&source).map(|(address, _)| address).collect();
+        
+        // Prior state of every cell the move touches, for the journal
+        let mut befores: HashMap<(String, CellAddress), Option<Cell>> = sheet.get_range_cells(&source)
+            .chain(sheet.get_range_cells(&target))
+            .map(|(address, cell)| ((sheet_name.to_string(), address), Some(cell.clone())))
+            .collect();
+        let merges_before = sheet.merged_ranges.clone();
+        
+        let overwritten = sheet.move_range(&source, &dest)?;
+        let merges_after = sheet.merged_ranges.clone();
+        
+        // The vacated and overwritten addresses no longer hold their formulas
+        for cell_addr in moved.iter().chain(&overwritten) {
+            self.dependency_graph.remove_dependencies(sheet_name, cell_addr);
+            self.volatile_cells.remove(&(sheet_name.to_string(), cell_addr.clone()));
+            self.formula_asts.remove(&(sheet_name.to_string(), cell_addr.clone()));
+        }
+        
+        // A reference follows the move only when it lies entirely inside the source;
+        // one entirely inside the overwritten part of the destination is lost
+        let repoint = |owner: &str, reference: &Reference| {
+            if reference.is_whole_line() || reference.sheet().unwrap_or(owner) != sheet_name {
+                return None;
+            }
+            let bounds = reference.bounds();
+            if source.contains(&bounds.start) && source.contains(&bounds.end) {
+                Some(AstNode::Reference(reference.offset(row_offset, col_offset)))
+            } else if target.contains(&bounds.start) && target.contains(&bounds.end) && !bounds.overlaps(&source) {
+                Some(AstNode::Literal(Literal::Error(CellError::InvalidReference)))
+            } else {
+                None
+            }
+        };
+        
+        let arrived: HashSet<CellAddress> = moved.iter()
+            .map(|address| CellAddress::new((address.row as i64 + row_offset) as RowId, (address.col as i64 + col_offset) as ColumnId))
+            .collect();
+        for address in &arrived {
+            befores.entry((sheet_name.to_string(), address.clone())).or_insert(None);
+        }
+        let mut updates = Vec::new();
+        for (owner, sheet) in &self.sheets {
+            for (&(row, col), cell) in sheet.get_all_cells() {
+                let CellValue::Formula(formula) = &cell.value else { continue };
+                let Ok(mut ast) = parse_formula(formula) else { continue };
+                let cell_addr = CellAddress::new(row, col);
+                let changed = rewrite_references(&mut ast, &mut |reference| repoint(owner, reference));
+                if changed {
+                    befores.entry((owner.clone(), cell_addr.clone())).or_insert_with(|| Some(cell.clone()));
+                }
+                if changed || (owner == sheet_name && arrived.contains(&cell_addr)) {
+                    updates.push((owner.clone(), cell_addr, ast, changed));
+                }
+            }
+        }
+        
+        // Store the rewritten formulas and register moved formulas at their new addresses
+        for (owner, cell_addr, ast, changed) in updates {
+            if changed {
+                let formula = format_ast(&ast);
+                if let Some(cell) = self.sheets.get_mut(&owner).and_then(|s| s.get_cell_mut(cell_addr.row, cell_addr.col)) {
+                    cell.value = CellValue::Formula(formula.clone());
+                    cell.formula = Some(formula);
+                }
+            }
+            self.dependency_graph.remove_dependencies(&owner, &cell_addr);
+            self.register_dependencies(&owner, &cell_addr, &ast);
+            self.mark_dirty(&owner, &cell_addr);
+        }
+        
+        for cell_addr in moved.iter().chain(&overwritten).chain(&arrived) {
+            self.mark_dirty(sheet_name, cell_addr);
+        }
+        
+        let mut changes = self.cell_changes(befores);
+        if merges_before != merges_after {
+            changes.push(Change::Merges { sheet: sheet_name.to_string(), before: merges_before, after: merges_after });
+        }
+        self.journal.record(changes);
+        
+        self.finish_edit()
+    }
+    
+    // Journal changes from the prior state of cells to their current state
+    fn cell_changes(&self, befores: HashMap<(String, CellAddress), Option<Cell>>) -> Vec<Change> {
+        befores.into_iter()
+            .map(|((sheet, address), before)| {
+                let after = self.sheets.get(&sheet).and_then(|s| s.get_cell(address.row, address.col)).cloned();
+                Change::cell(&sheet, address, before, after)
+            })
+            .collect()
+    }
+    
+    // Apply a batch of edits atomically. If `edits` returns an error, every change it
+    // made is rolled back and the error is returned; otherwise the edits form a single
+    // undo step. Recalculation runs once, when the outermost transaction commits.
+    pub fn transaction<T>(&mut self, edits: impl FnOnce(&mut Workbook) -> Result<T, EngineError>) -> Result<T, EngineError> {
+        let mark = self.journal.begin_group();
+        self.batch_depth += 1;
+        let result = edits(self);
+        self.batch_depth -= 1;
+        
+        let rolled_back = match &result {
+            Ok(_) => Vec::new(),
+            Err(_) => self.journal.rollback_group(mark),
+        };
+        self.journal.end_group();
+        
+        // Restore a failed batch; either way this recalculates if the outermost transaction ended
+        self.apply_changes(rolled_back.iter().rev(), true)?;
+        result
+    }
+    
+    // Recalculate after an edit, unless it is part of a transaction that will
+    pub(crate) fn finish_edit(&mut self) -> Result<(), EngineError> {
+        if self.batch_depth > 0 {
+            return Ok(());
+        }
+        self.recalculate()
+    }
+    
+    // Whether there is an edit to undo
+    pub fn can_undo(&self) -> bool {
+        self.journal.can_undo()
+    }
+    
+    // Whether there is an undone edit to redo
+    pub fn can_redo(&self) -> bool {
+        self.journal.can_redo()
+    }
+    
+    // Revert the most recent edit (or transaction) and recalculate.
+    // Returns false if there was nothing to undo.
+    pub fn undo(&mut self) -> Result<bool, EngineError> {
+        let step = match self.journal.pop_undo() {
+            Some(step) => step,
+            None => return Ok(false),
+        };
+        self.apply_changes(step.iter().rev(), true)?;
+        self.journal.push_redo(step);
+        Ok(true)
+    }
+    
+    // Reapply the most recently undone edit and recalculate.
+    // Returns false if there was nothing to redo.
+    pub fn redo(&mut self) -> Result<bool, EngineError> {
+        let step = match self.journal.pop_redo() {
+            Some(step) => step,
+            None => return Ok(false),
+        };
+        self.apply_changes(step.iter(), false)?;
+        self.journal.push_undo(step);
+        Ok(true)
+    }
+    
+    // Restore each change's prior (undo) or later (redo) state without journaling it,
+    // then rebuild the dependencies of the restored cells and recalculate
+    fn apply_changes<'a>(&mut self, changes: impl Iterator<Item = &'a Change>, undo: bool) -> Result<(), EngineError> {
+        let mut restored = Vec::new();
+        for change in changes {
+            match change {
+                Change::Cell { sheet, address, before, after } => {
+                    let state = if undo { before } else { after };
+                    if let Some(s) = self.sheets.get_mut(sheet) {
+                        match state {
+                            Some(cell) => s.cells.insert((address.row, address.col), Cell::clone(cell)),
+                            None => s.cells.remove(&(address.row, address.col)),
+                        };
+                        restored.push((sheet.clone(), address.clone()));
+                    }
+                },
+                Change::Merges { sheet, before, after } => {
+                    if let Some(s) = self.sheets.get_mut(sheet) {
+                        s.merged_ranges = if undo { before.clone() } else { after.clone() };
+                    }
+                },
+            }
+        }
+        
+        for (sheet_name, cell_addr) in &restored {
+            self.dependency_graph.remove_dependencies(sheet_name, cell_addr);
+            self.volatile_cells.remove(&(sheet_name.clone(), cell_addr.clone()));
+            let formula = self.sheets[sheet_name].get_cell(cell_addr.row, cell_addr.col)
+                .and_then(|cell| cell.formula.clone());
+            if let Some(Ok(ast)) = formula.map(|formula| parse_formula(&formula)) {
+                self.register_dependencies(sheet_name, cell_addr, &ast);
+            }
+        }
+        for (sheet_name, cell_addr) in &restored {
+            self.mark_dirty(sheet_name, cell_addr);
+        }
+        
+        self.finish_edit()
+    }
+    
     // Mark a cell and all its dependents as dirty (needs recalculation)
     fn mark_dirty(&mut self, sheet_name: &str, cell_addr: &CellAddress) {
         self.dirty_cells.insert((sheet_name.to_string(), cell_addr.clone()));
+        self.edited_cells.insert((sheet_name.to_string(), cell_addr.clone()));
         
         // Mark all dependent cells as dirty
         let dependents = self.dependency_graph.get_dependents(sheet_name, cell_addr);
@@ -564,57 +1985,1880 @@ impl Workbook {
     
     // Recalculate all dirty cells
     pub fn recalculate(&mut self) -> Result<(), EngineError> {
-        // Sort dirty cells in topological order 
-        // (In a full implementation, we'd do a proper topological sort of the dependency graph)
+        self.recalculate_with(|_, _| {}, &AtomicBool::new(false)).map(|_| ())
+    }
+    
+    // Number of formula cells recalculation has evaluated since the workbook was
+    // created, e.g. to see how much work an edit caused
+    pub fn evaluation_count(&self) -> u64 {
+        self.evaluations
+    }
+    
+    // Recalculate all dirty cells, reporting (cells done, cells in total) after each
+    // level of independent cells and checking `cancel` between levels. Returns false
+    // if cancelled: the results computed so far are kept and the remaining cells stay
+    // dirty, so the next recalculation finishes the job.
+    pub fn recalculate_with(&mut self, mut progress: impl FnMut(usize, usize), cancel: &AtomicBool) -> Result<bool, EngineError> {
+        // Volatile formulas (and everything depending on them) are always recomputed
+        let volatile_cells: Vec<_> = self.volatile_cells.iter().cloned().collect();
+        for (sheet_name, cell_addr) in volatile_cells {
+            self.mark_dirty(&sheet_name, &cell_addr);
+        }
+        
+        // Evaluate level by level so every precedent is computed before its dependents
         let dirty_cells = std::mem::take(&mut self.dirty_cells);
+        let order = self.dependency_graph.evaluation_order(&dirty_cells);
         
-        for (sheet_name, cell_addr) in dirty_cells {
-            // Get the sheet
-            let sheet = match self.sheets.get(&sheet_name) {
-                Some(s) => s,
-                None => continue, // Skip if sheet doesn't exist (shouldn't happen)
-            };
+        // A dependent is only recomputed once one of its precedents has actually
+        // changed value, so an edit absorbed by an intermediate cell stops there
+        let mut changed = std::mem::take(&mut self.edited_cells);
+        
+        let levels = self.dependency_graph.evaluation_levels(&dirty_cells, order);
+        let total: usize = levels.iter().flatten().map(|component| component.len()).sum();
+        let mut done = 0;
+        let mut levels = levels.into_iter();
+        
+        while let Some(level) = levels.next() {
+            if cancel.load(Ordering::Relaxed) {
+                // Leave what's left dirty, and recompute it outright next time since the
+                // precedents that changed in this run are no longer tracked
+                let remaining: Vec<_> = std::iter::once(level).chain(levels).flatten().flatten().collect();
+                self.edited_cells.extend(remaining.iter().cloned());
+                self.dirty_cells.extend(remaining);
+                return Ok(false);
+            }
             
-            // Get the cell
-            let cell = match sheet.get_cell(cell_addr.row, cell_addr.col) {
-                Some(c) => c,
-                None => continue, // Skip if cell doesn't exist (shouldn't happen)
-            };
+            done += level.iter().map(|component| component.len()).sum::<usize>();
+            let (cycles, cells): (Vec<_>, Vec<_>) = level.into_iter()
+                .partition(|component| self.dependency_graph.is_cyclic(component));
+            let cells: Vec<_> = cells.into_iter()
+                .flatten()
+                .filter(|cell| {
+                    changed.contains(cell)
+                        || self.dependency_graph.precedents_in(cell, &dirty_cells).iter().any(|p| changed.contains(p))
+                })
+                .collect();
+            changed.extend(self.recalculate_level(cells)?);
             
-            // Only evaluate formulas
-            if let CellValue::Formula(formula) = &cell.value {
-                // Clone the formula (because we need to pass ownership to evaluate)
-                let formula_clone = formula.clone();
-                
-                // Evaluate the formula
-                let result = self.evaluator.evaluate_formula(self, &sheet_name, &cell_addr, &formula_clone)?;
-                
-                // Update the calculated value
-                let sheet = self.sheets.get_mut(&sheet_name).unwrap();
-                let cell = sheet.get_cell_mut(cell_addr.row, cell_addr.col).unwrap();
-                cell.calculated_value = Some(result);
+            // Cycles are always recomputed and count as changed
+            for component in cycles {
+                changed.extend(component.iter().cloned());
+                match self.iterative_calc {
+                    Some(settings) => self.iterate_cycle(&component, settings)?,
+                    None => {
+                        // Cells on a cycle can't be computed; only they become errors
+                        for (sheet_name, cell_addr) in component {
+                            self.set_calculated_value(&sheet_name, &cell_addr, CellValue::Error(CellError::InvalidReference));
+                        }
+                    },
+                }
             }
+            progress(done, total);
         }
         
-        Ok(())
+        Ok(true)
     }
     
-    // Get a cell value (calculated value if formula, or direct value)
-    pub fn get_cell_value(&self, sheet_name: &str, row: RowId, col: ColumnId) -> Result<CellValue, EngineError> {
-        let sheet = match self.get_sheet(sheet_name) {
-            Some(s) => s,
-            None => return Err(EngineError::Internal(format!("Sheet '{}' does not exist", sheet_name))),
-        };
+    // Evaluate cells that don't depend on each other and return those whose value
+    // changed. Large levels are evaluated in parallel, each cell reading the workbook
+    // as it was before the level started.
+    fn recalculate_level(&mut self, cells: Vec<(String, CellAddress)>) -> Result<Vec<(String, CellAddress)>, EngineError> {
+        let mut changed = Vec::new();
         
-        let cell = match sheet.get_cell(row, col) {
-            Some(c) => c,
-            None => return Ok(CellValue::Blank), // Empty cell is treated as blank
-        };
+        // Volatile cells draw random numbers, so they are evaluated one at a time in
+        // address order to keep seeded results reproducible
+        let (mut sequential, mut cells): (Vec<_>, Vec<_>) = cells.into_iter()
+            .partition(|cell| self.volatile_cells.contains(cell));
+        sequential.sort_by(|(a_sheet, a), (b_sheet, b)| (a_sheet, a.row, a.col).cmp(&(b_sheet, b.row, b.col)));
+        if cells.len() < PARALLEL_RECALC_MIN_CELLS {
+            sequential.append(&mut cells);
+        }
         
-        // Return calculated value if available, otherwise the direct value
-        match &cell.calculated_value {
-            Some(cv) => Ok(cv.clone()),
-            None => Ok(cell.value.clone()),
+        for (sheet_name, cell_addr) in sequential {
+            let previous = self.calculated_value(&sheet_name, &cell_addr);
+            if let Some(value) = self.recalculate_cell(&sheet_name, &cell_addr)? {
+                if !previous.is_some_and(|previous| identical_values(&previous, &value.finite_or_error())) {
+                    changed.push((sheet_name, cell_addr));
+                }
+            }
         }
+        if cells.is_empty() {
+            return Ok(changed);
+        }
+        
+        // Look up the parsed formulas first; the cache can't be updated while evaluating
+        let mut jobs = Vec::with_capacity(cells.len());
+        for (sheet_name, cell_addr) in cells {
+            let formula = match self.sheets.get(&sheet_name).and_then(|s| s.get_cell(cell_addr.row, cell_addr.col)) {
+                Some(Cell { value: CellValue::Formula(formula), .. }) => formula.clone(),
+                _ => {
+                    self.formula_asts.remove(&(sheet_name, cell_addr));
+                    continue;
+                },
+            };
+            let ast = self.formula_ast(&sheet_name, &cell_addr, &formula)
+                .map_err(|e| e.in_cell(&sheet_name, cell_addr.to_a1()))?;
+            jobs.push((sheet_name, cell_addr, ast));
+        }
+        
+        let workbook = &*self;
+        let results: Vec<_> = jobs.par_iter()
+            .map(|(sheet_name, cell_addr, ast)| workbook.evaluator.evaluate_ast(workbook, sheet_name, cell_addr, ast))
+            .collect();
+        self.evaluations += results.len() as u64;
+        
+        for ((sheet_name, cell_addr, _), result) in jobs.into_iter().zip(results) {
+            let value = result.map_err(|e| e.in_cell(&sheet_name, cell_addr.to_a1()))?;
+            let previous = self.calculated_value(&sheet_name, &cell_addr);
+            if !previous.is_some_and(|previous| identical_values(&previous, &value.clone().finite_or_error())) {
+                changed.push((sheet_name.clone(), cell_addr.clone()));
+            }
+            self.set_calculated_value(&sheet_name, &cell_addr, value);
+        }
+        Ok(changed)
+    }
+    
+    // Evaluate the cells of a cycle repeatedly until the values settle or the
+    // iteration limit is reached
+    fn iterate_cycle(&mut self, component: &[(String, CellAddress)], settings: IterativeCalc) -> Result<(), EngineError> {
+        // Start from the previous results, or zero for cells without a usable one
+        for (sheet_name, cell_addr) in component {
+            let previous = self.get_cell_value(sheet_name, cell_addr.row, cell_addr.col)?;
+            if matches!(previous, CellValue::Formula(_) | CellValue::Error(_)) {
+                self.set_calculated_value(sheet_name, cell_addr, CellValue::Number(0.0));
+            }
+        }
+        
+        for _ in 0..settings.max_iterations {
+            let mut largest_change: f64 = 0.0;
+            
+            for (sheet_name, cell_addr) in component {
+                let previous = self.get_cell_value(sheet_name, cell_addr.row, cell_addr.col)?;
+                let current = self.recalculate_cell(sheet_name, cell_addr)?;
+                
+                let change = match (&previous, &current) {
+                    (CellValue::Number(a), Some(CellValue::Number(b))) => (a - b).abs(),
+                    _ => f64::INFINITY,
+                };
+                largest_change = largest_change.max(change);
+            }
+            
+            if largest_change < settings.max_change {
+                break;
+            }
+        }
+        
+        Ok(())
+    }
+    
+    // Evaluate a single formula cell and store its result. Returns the new value,
+    // or None if the cell does not hold a formula.
+    fn recalculate_cell(&mut self, sheet_name: &str, cell_addr: &CellAddress) -> Result<Option<CellValue>, EngineError> {
+        // Get the cell
+        let cell = match self.sheets.get(sheet_name).and_then(|s| s.get_cell(cell_addr.row, cell_addr.col)) {
+            Some(c) => c,
+            None => return Ok(None), // Skip if sheet or cell doesn't exist (shouldn't happen)
+        };
+        
+        // Only evaluate formulas
+        let formula = match &cell.value {
+            CellValue::Formula(formula) => formula.clone(),
+            _ => {
+                self.formula_asts.remove(&(sheet_name.to_string(), cell_addr.clone()));
+                return Ok(None);
+            },
+        };
+        
+        // Evaluate the formula and update the calculated value
+        let ast = self.formula_ast(sheet_name, cell_addr, &formula)
+            .map_err(|e| e.in_cell(sheet_name, cell_addr.to_a1()))?;
+        let result = self.evaluator.evaluate_ast(self, sheet_name, cell_addr, &ast)
+            .map_err(|e| e.in_cell(sheet_name, cell_addr.to_a1()))?;
+        self.evaluations += 1;
+        self.set_calculated_value(sheet_name, cell_addr, result.clone());
+        
+        Ok(Some(result))
+    }
+    
+    // The parsed and validated formula of a cell, parsed again only when its text has changed
+    fn formula_ast(&mut self, sheet_name: &str, cell_addr: &CellAddress, formula: &str) -> Result<Arc<AstNode>, EngineError> {
+        let key = (sheet_name.to_string(), cell_addr.clone());
+        if let Some((text, ast)) = self.formula_asts.get(&key) {
+            if text == formula {
+                return Ok(Arc::clone(ast));
+            }
+        }
+        
+        let ast = Arc::new(validate_formula(formula, self.evaluator.function_registry())?);
+        self.formula_asts.insert(key, (formula.to_string(), Arc::clone(&ast)));
+        Ok(ast)
+    }
+    
+    // The stored result of a formula cell, if it has been evaluated
+    fn calculated_value(&self, sheet_name: &str, cell_addr: &CellAddress) -> Option<CellValue> {
+        self.sheets.get(sheet_name)
+            .and_then(|s| s.get_cell(cell_addr.row, cell_addr.col))
+            .and_then(|cell| cell.calculated_value.clone())
+    }
+    
+    // Store the result of evaluating a formula cell
+    fn set_calculated_value(&mut self, sheet_name: &str, cell_addr: &CellAddress, value: CellValue) {
+        if let Some(cell) = self.sheets.get_mut(sheet_name).and_then(|s| s.get_cell_mut(cell_addr.row, cell_addr.col)) {
+            cell.calculated_value = Some(value.finite_or_error());
+        }
+    }
+    
+    // Evaluate a single cell on demand, reading precedents from their current values.
+    // Unlike recalculation, nothing is cached or marked dirty.
+    pub fn evaluate_cell(&self, sheet_name: &str, row: RowId, col: ColumnId) -> Result<CellValue, EngineError> {
+        let sheet = match self.get_sheet(sheet_name) {
+            Some(s) => s,
+            None => return Err(EngineError::Internal(format!("Sheet '{}' does not exist", sheet_name))),
+        };
+        
+        let cell = match sheet.get_cell(row, col) {
+            Some(c) => c,
+            None => return Ok(CellValue::Blank),
+        };
+        
+        let cell_addr = CellAddress::new(row, col);
+        match &cell.value {
+            CellValue::Formula(formula) => match self.formula_asts.get(&(sheet_name.to_string(), cell_addr.clone())) {
+                Some((text, ast)) if text == formula => self.evaluator.evaluate_ast(self, sheet_name, &cell_addr, ast),
+                _ => self.evaluator.evaluate_formula(self, sheet_name, &cell_addr, formula),
+            },
+            value => Ok(value.clone()),
+        }
+    }
+    
+    // The sheets and cells that differ in `other`: sheets added or removed, and cells
+    // added, removed or changed (by formula or value) on the sheets both have
+    pub fn diff(&self, other: &Workbook) -> WorkbookDiff {
+        diff_workbooks(self, other)
+    }
+    
+    // Flag likely modeling mistakes in every formula: unguarded divisions, hardcoded
+    // numbers, references to blank cells, formulas that break the pattern of their
+    // row or column, and circular references. Sorted by sheet and cell.
+    pub fn lint(&self) -> Vec<LintWarning> {
+        let mut warnings = lint_formulas(self);
+        for (sheet_name, sheet) in &self.sheets {
+            for (&(row, col), cell) in sheet.get_all_cells() {
+                let address = CellAddress::new(row, col);
+                if cell.formula.is_some() && self.dependency_graph.check_circular_reference(sheet_name, &address) {
+                    warnings.push(LintWarning {
+                        sheet: sheet_name.clone(),
+                        address,
+                        kind: LintKind::CircularReference,
+                        message: "Formula is part of a circular reference".to_string(),
+                    });
+                }
+            }
+        }
+        warnings.sort_by(|a, b| (&a.sheet, a.address.row, a.address.col).cmp(&(&b.sheet, b.address.row, b.address.col)));
+        warnings
+    }
+    
+    // Every cell a cell's formula depends on, directly or through other formulas.
+    // Whole-column and whole-row references contribute their populated cells.
+    pub fn trace_precedents(&self, sheet_name: &str, cell: &CellAddress) -> Result<HashSet<(String, CellAddress)>, EngineError> {
+        if self.get_sheet(sheet_name).is_none() {
+            return Err(EngineError::Internal(format!("Sheet '{}' does not exist", sheet_name)));
+        }
+        
+        let mut result = HashSet::new();
+        let mut queue = VecDeque::new();
+        queue.push_back((sheet_name.to_string(), cell.clone()));
+        while let Some((sheet, address)) = queue.pop_front() {
+            let mut direct: Vec<_> = self.dependency_graph.get_precedents(&sheet, &address)
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect();
+            for (ref_sheet, range) in self.dependency_graph.get_range_precedents(&sheet, &address) {
+                if let Some(s) = self.sheets.get(ref_sheet) {
+                    direct.extend(s.get_range_cells(range).map(|(address, _)| (ref_sheet.clone(), address)));
+                }
+            }
+            
+            for precedent in direct {
+                if result.insert(precedent.clone()) {
+                    queue.push_back(precedent);
+                }
+            }
+        }
+        
+        Ok(result)
+    }
+    
+    // Every formula cell that depends on a cell, directly or through other formulas
+    pub fn trace_dependents(&self, sheet_name: &str, cell: &CellAddress) -> Result<HashSet<(String, CellAddress)>, EngineError> {
+        if self.get_sheet(sheet_name).is_none() {
+            return Err(EngineError::Internal(format!("Sheet '{}' does not exist", sheet_name)));
+        }
+        Ok(self.dependency_graph.get_dependents(sheet_name, cell))
+    }
+    
+    // Evaluate a formula as if entered in A1 of a sheet, without storing it or
+    // changing any cell. Unknown functions and wrong argument counts are rejected
+    // as they would be by a strict workbook.
+    pub fn evaluate_formula(&self, sheet_name: &str, formula: &str) -> Result<CellValue, EngineError> {
+        if self.get_sheet(sheet_name).is_none() {
+            return Err(EngineError::Internal(format!("Sheet '{}' does not exist", sheet_name)));
+        }
+        let ast = validate_formula(formula, self.evaluator.function_registry())?;
+        self.evaluator.evaluate_ast(self, sheet_name, &CellAddress::new(0, 0), &ast)
+    }
+    
+    // Evaluate a formula as if entered in A1 of a sheet, recording every sub-expression's value
+    pub fn trace_formula(&self, sheet_name: &str, formula: &str) -> Result<TraceNode, EngineError> {
+        if self.get_sheet(sheet_name).is_none() {
+            return Err(EngineError::Internal(format!("Sheet '{}' does not exist", sheet_name)));
+        }
+        self.evaluator.evaluate_traced(self, sheet_name, &CellAddress::new(0, 0), formula)
+    }
+    
+    // The used range of a sheet, from A1 to the furthest populated row and column.
+    // None when the sheet is empty.
+    pub fn used_range(&self, sheet_name: &str) -> Result<Option<CellRange>, EngineError> {
+        let sheet = match self.get_sheet(sheet_name) {
+            Some(s) => s,
+            None => return Err(EngineError::Internal(format!("Sheet '{}' does not exist", sheet_name))),
+        };
+        
+        Ok(sheet.used_bounds().map(|(row, col)| CellRange::new(CellAddress::new(0, 0), CellAddress::new(row, col))))
+    }
+    
+    // Effective values of a sheet's used range as rows, with empty cells as Blank.
+    // An empty sheet gives an empty grid.
+    pub fn sheet_snapshot(&self, sheet_name: &str) -> Result<Vec<Vec<CellValue>>, EngineError> {
+        let range = match self.used_range(sheet_name)? {
+            Some(range) => range,
+            None => return Ok(Vec::new()),
+        };
+        
+        let sheet = &self.sheets[sheet_name];
+        let (rows, cols) = range.dimensions();
+        let mut grid = vec![vec![CellValue::Blank; cols as usize]; rows as usize];
+        for (&(row, col), cell) in sheet.get_all_cells() {
+            grid[row as usize][col as usize] = cell.effective_value().clone();
+        }
+        Ok(grid)
+    }
+    
+    // Find the value of `changing_cell` that makes the formula in `target_cell` evaluate
+    // to `target_value`, searching with the secant method and bisecting once a sign
+    // change brackets the answer. On success the changing cell keeps the value found
+    // (one undoable edit); otherwise it is left as it was.
+    pub fn goal_seek(&mut self, sheet_name: &str, target_cell: &CellAddress, target_value: f64, changing_cell: &CellAddress) -> Result<f64, EngineError> {
+        let sheet = match self.get_sheet(sheet_name) {
+            Some(s) => s,
+            None => return Err(EngineError::Internal(format!("Sheet '{}' does not exist", sheet_name))),
+        };
+        
+        let original = sheet.get_cell(changing_cell.row, changing_cell.col).cloned();
+        let start = match original.as_ref().map(|cell| &cell.value) {
+            None | Some(CellValue::Blank) => 0.0,
+            Some(CellValue::Number(n)) | Some(CellValue::Date(n)) => *n,
+            Some(_) => return Err(EngineError::EvaluationError(
+                format!("Goal seek needs a number in the changing cell {}", changing_cell.to_a1()))),
+        };
+        
+        let result = self.seek_root(sheet_name, target_cell, target_value, changing_cell, start);
+        self.restore_cell(sheet_name, changing_cell, original)?;
+        
+        match result? {
+            Some(solution) => {
+                self.set_cell_value(sheet_name, changing_cell.row, changing_cell.col, CellValue::Number(solution))?;
+                Ok(solution)
+            },
+            None => Err(EngineError::EvaluationError(format!(
+                "Goal seek did not converge: no value of {} found that makes {} equal {}",
+                changing_cell.to_a1(), target_cell.to_a1(), target_value))),
+        }
+    }
+    
+    // Search for the root of target - target_value by trying values in the changing
+    // cell. Returns None if the search stalls or runs out of iterations.
+    fn seek_root(&mut self, sheet_name: &str, target_cell: &CellAddress, target_value: f64, changing_cell: &CellAddress, start: f64) -> Result<Option<f64>, EngineError> {
+        let miss = |workbook: &mut Workbook, x: f64| -> Result<f64, EngineError> {
+            workbook.set_trial_value(sheet_name, changing_cell, CellValue::Number(x))?;
+            match workbook.get_cell_value(sheet_name, target_cell.row, target_cell.col)? {
+                CellValue::Number(n) | CellValue::Date(n) => Ok(n - target_value),
+                other => Err(EngineError::EvaluationError(
+                    format!("Goal seek needs a number in the target cell {}, found {:?}", target_cell.to_a1(), other))),
+            }
+        };
+        
+        let (mut x0, mut f0) = (start, miss(self, start)?);
+        if f0.abs() < GOAL_SEEK_TOLERANCE {
+            return Ok(Some(x0));
+        }
+        let mut x1 = if start == 0.0 { 1.0 } else { start * 1.1 };
+        
+        // Two points whose misses have opposite signs, once seen
+        let mut bracket: Option<(f64, f64, f64, f64)> = None;
+        for _ in 0..GOAL_SEEK_MAX_ITERATIONS {
+            let f1 = miss(self, x1)?;
+            if f1.abs() < GOAL_SEEK_TOLERANCE {
+                return Ok(Some(x1));
+            }
+            
+            bracket = match bracket {
+                Some((a, fa, _, _)) if fa.signum() != f1.signum() => Some((a, fa, x1, f1)),
+                Some((_, _, b, fb)) => Some((x1, f1, b, fb)),
+                None if f0.signum() != f1.signum() => Some((x0, f0, x1, f1)),
+                None => None,
+            };
+            
+            let secant = x1 - f1 * (x1 - x0) / (f1 - f0);
+            let next = match bracket {
+                Some((a, _, b, _)) if !(secant > a.min(b) && secant < a.max(b)) => (a + b) / 2.0,
+                _ if !secant.is_finite() => return Ok(None),
+                _ => secant,
+            };
+            
+            (x0, f0, x1) = (x1, f1, next);
+        }
+        
+        Ok(None)
+    }
+    
+    // Monte Carlo simulation: recalculate `iterations` times, drawing new values for
+    // every RAND and RANDBETWEEN, and collect the number in `output_cell` after each.
+    // Seed the generator with `set_random_seed` first for reproducible samples.
+    pub fn simulate(&mut self, sheet_name: &str, output_cell: &CellAddress, iterations: usize) -> Result<Vec<f64>, EngineError> {
+        if self.get_sheet(sheet_name).is_none() {
+            return Err(EngineError::Internal(format!("Sheet '{}' does not exist", sheet_name)));
+        }
+        
+        let mut samples = Vec::with_capacity(iterations);
+        for _ in 0..iterations {
+            self.recalculate()?;
+            match self.get_cell_value(sheet_name, output_cell.row, output_cell.col)? {
+                CellValue::Number(n) | CellValue::Date(n) => samples.push(n),
+                other => return Err(EngineError::EvaluationError(
+                    format!("Simulation needs a number in the output cell {}, found {:?}", output_cell.to_a1(), other))),
+            }
+        }
+        
+        Ok(samples)
+    }
+    
+    // Sensitivity table of a formula over one or two inputs, like Excel's data tables.
+    // Each row value is tried in `row_input_cell` and each column value in
+    // `col_input_cell`; the grid has a row per column value and a column per row
+    // value, holding the formula's result for that pair. A missing input gives a
+    // one-variable table. The inputs are restored afterwards.
+    pub fn data_table(
+        &mut self,
+        sheet_name: &str,
+        formula_cell: &CellAddress,
+        row_input_cell: Option<&CellAddress>,
+        row_values: &[CellValue],
+        col_input_cell: Option<&CellAddress>,
+        col_values: &[CellValue],
+    ) -> Result<CellValue, EngineError> {
+        let sheet = match self.get_sheet(sheet_name) {
+            Some(s) => s,
+            None => return Err(EngineError::Internal(format!("Sheet '{}' does not exist", sheet_name))),
+        };
+        if row_input_cell.is_none() && col_input_cell.is_none() {
+            return Err(EngineError::EvaluationError("A data table needs a row or column input cell".into()));
+        }
+        if row_input_cell == Some(formula_cell) || col_input_cell == Some(formula_cell) || (row_input_cell.is_some() && row_input_cell == col_input_cell) {
+            return Err(EngineError::InvalidReference(
+                format!("Data table input cells must differ from each other and from {}", formula_cell.to_a1())));
+        }
+        
+        let originals: Vec<(CellAddress, Option<Cell>)> = row_input_cell.into_iter().chain(col_input_cell)
+            .map(|cell_addr| (cell_addr.clone(), sheet.get_cell(cell_addr.row, cell_addr.col).cloned()))
+            .collect();
+        
+        // A missing input contributes a single pass that leaves nothing changed
+        let row_trials: Vec<Option<(&CellAddress, &CellValue)>> = match row_input_cell {
+            Some(cell_addr) => row_values.iter().map(|value| Some((cell_addr, value))).collect(),
+            None => vec![None],
+        };
+        let col_trials: Vec<Option<(&CellAddress, &CellValue)>> = match col_input_cell {
+            Some(cell_addr) => col_values.iter().map(|value| Some((cell_addr, value))).collect(),
+            None => vec![None],
+        };
+        
+        let run = |workbook: &mut Workbook| -> Result<Vec<Vec<CellValue>>, EngineError> {
+            let mut grid = Vec::with_capacity(col_trials.len());
+            for col_trial in &col_trials {
+                if let Some((cell_addr, value)) = col_trial {
+                    workbook.set_trial_value(sheet_name, cell_addr, (*value).clone())?;
+                }
+                let mut row = Vec::with_capacity(row_trials.len());
+                for row_trial in &row_trials {
+                    if let Some((cell_addr, value)) = row_trial {
+                        workbook.set_trial_value(sheet_name, cell_addr, (*value).clone())?;
+                    }
+                    row.push(workbook.get_cell_value(sheet_name, formula_cell.row, formula_cell.col)?);
+                }
+                grid.push(row);
+            }
+            Ok(grid)
+        };
+        let grid = run(self);
+        
+        for (cell_addr, original) in originals {
+            self.restore_cell(sheet_name, &cell_addr, original)?;
+        }
+        Ok(CellValue::Array(grid?))
+    }
+    
+    // Store a scenario, replacing any scenario of the same name
+    pub fn define_scenario(&mut self, name: &str, sheet_name: &str, cells: Vec<(CellAddress, CellValue)>) -> Result<(), EngineError> {
+        if self.get_sheet(sheet_name).is_none() {
+            return Err(EngineError::Internal(format!("Sheet '{}' does not exist", sheet_name)));
+        }
+        self.scenarios.insert(name.to_string(), Scenario { sheet: sheet_name.to_string(), cells });
+        Ok(())
+    }
+    
+    // Store the current contents of some cells as a scenario, so the live sheet can be
+    // saved as a case and restored later. Formulas are kept as formulas.
+    pub fn capture_scenario(&mut self, name: &str, sheet_name: &str, cells: &[CellAddress]) -> Result<(), EngineError> {
+        let values = self.current_values(sheet_name, cells)?;
+        self.define_scenario(name, sheet_name, values)
+    }
+    
+    // The contents of some cells, in the form a scenario stores them
+    pub fn current_values(&self, sheet_name: &str, cells: &[CellAddress]) -> Result<Vec<(CellAddress, CellValue)>, EngineError> {
+        let sheet = match self.get_sheet(sheet_name) {
+            Some(s) => s,
+            None => return Err(EngineError::Internal(format!("Sheet '{}' does not exist", sheet_name))),
+        };
+        
+        Ok(cells.iter()
+            .map(|cell_addr| {
+                let value = sheet.get_cell(cell_addr.row, cell_addr.col).map_or(CellValue::Blank, |cell| cell.value.clone());
+                (cell_addr.clone(), value)
+            })
+            .collect())
+    }
+    
+    // Write a scenario's values into its cells and recalculate. This is a single edit,
+    // so `undo()` brings back the values it replaced.
+    pub fn apply_scenario(&mut self, name: &str) -> Result<(), EngineError> {
+        let scenario = match self.scenarios.get(name) {
+            Some(scenario) => scenario.clone(),
+            None => return Err(EngineError::Internal(format!("Scenario '{}' does not exist", name))),
+        };
+        
+        let cells = scenario.cells.into_iter().map(|(cell_addr, value)| (cell_addr.row, cell_addr.col, value)).collect();
+        self.set_cells(&scenario.sheet, cells)
+    }
+    
+    // Get a scenario by name
+    pub fn scenario(&self, name: &str) -> Option<&Scenario> {
+        self.scenarios.get(name)
+    }
+    
+    // Names of all scenarios, in alphabetical order
+    pub fn scenario_names(&self) -> Vec<&String> {
+        self.scenarios.keys().collect()
+    }
+    
+    // Delete a scenario. Returns whether it existed.
+    pub fn remove_scenario(&mut self, name: &str) -> bool {
+        self.scenarios.remove(name).is_some()
+    }
+    
+    // Define a table over a range of a sheet. The first row of the range holds the
+    // column headers, which must be distinct and not blank; at least one data row
+    // must follow. Formulas already referring to the table by name are recalculated.
+    pub fn add_table(&mut self, name: &str, sheet_name: &str, range: &CellRange) -> Result<(), EngineError> {
+        let sheet = match self.sheets.get(sheet_name) {
+            Some(s) => s,
+            None => return Err(EngineError::Internal(format!("Sheet '{}' does not exist", sheet_name))),
+        };
+        
+        let mut chars = name.chars();
+        let valid_name = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+            && CellAddress::from_a1(name).is_err();
+        if !valid_name {
+            return Err(EngineError::InvalidReference(format!("'{}' is not a valid table name", name)));
+        }
+        if self.tables.contains_key(&name.to_uppercase()) {
+            return Err(EngineError::Internal(format!("Table '{}' already exists", name)));
+        }
+        
+        let range = range.normalized();
+        if range.start.row == range.end.row {
+            return Err(EngineError::InvalidReference(format!("Table '{}' needs a header row and at least one data row", name)));
+        }
+        if let Some(other) = self.tables.values().find(|table| table.sheet == sheet_name && table.range.overlaps(&range)) {
+            return Err(EngineError::InvalidReference(format!("{} overlaps table '{}'", range, other.name)));
+        }
+        
+        let mut columns: Vec<String> = Vec::new();
+        for col in range.start.col..=range.end.col {
+            let header = sheet.get_cell(range.start.row, col).map(|cell| cell.effective_value().to_string()).unwrap_or_default();
+            if header.trim().is_empty() {
+                return Err(EngineError::InvalidReference(format!("Table '{}' has no header in column {}", name, column_index_to_letter(col))));
+            }
+            if columns.iter().any(|seen| seen.eq_ignore_ascii_case(&header)) {
+                return Err(EngineError::InvalidReference(format!("Table '{}' has more than one '{}' column", name, header)));
+            }
+            columns.push(header);
+        }
+        
+        let table = Table { name: name.to_string(), sheet: sheet_name.to_string(), range, columns };
+        self.tables.insert(name.to_uppercase(), table);
+        self.refresh_table_formulas()
+    }
+    
+    // Delete a table; structured references to it become #NAME?. Returns whether it existed.
+    pub fn remove_table(&mut self, name: &str) -> Result<bool, EngineError> {
+        if self.tables.remove(&name.to_uppercase()).is_none() {
+            return Ok(false);
+        }
+        self.refresh_table_formulas()?;
+        Ok(true)
+    }
+    
+    // Get a table by (case-insensitive) name
+    pub fn table(&self, name: &str) -> Option<&Table> {
+        self.tables.get(&name.to_uppercase())
+    }
+    
+    // All tables, in alphabetical order of name
+    pub fn tables(&self) -> impl Iterator<Item = &Table> {
+        self.tables.values()
+    }
+    
+    // The plain reference a structured reference stands for in a given formula cell.
+    // #NAME? for an unknown table, #REF! for an unknown column or, without a table
+    // name, a formula outside any table, and #VALUE! for [@Column] outside the table's rows.
+    pub(crate) fn resolve_table_reference(&self, sheet_name: &str, cell_addr: &CellAddress, reference: &TableReference) -> Result<Reference, CellError> {
+        let table = match &reference.table {
+            Some(name) => self.table(name).ok_or(CellError::NameNotFound)?,
+            None => self.tables.values().find(|table| table.contains(sheet_name, cell_addr)).ok_or(CellError::InvalidReference)?,
+        };
+        let column = table.column_range(&reference.column).ok_or(CellError::InvalidReference)?;
+        
+        if reference.this_row {
+            if !(column.start.row..=column.end.row).contains(&cell_addr.row) {
+                return Err(CellError::InvalidValue);
+            }
+            Ok(Reference::SheetCell { sheet: table.sheet.clone(), address: CellAddress::new(cell_addr.row, column.start.col) })
+        } else {
+            Ok(Reference::SheetRange { sheet: table.sheet.clone(), start: column.start, end: column.end })
+        }
+    }
+    
+    // Re-register and recalculate the formulas with structured references, after
+    // the tables they may resolve to have changed
+    fn refresh_table_formulas(&mut self) -> Result<(), EngineError> {
+        let mut formulas = Vec::new();
+        for (sheet_name, sheet) in &self.sheets {
+            for (&(row, col), cell) in sheet.get_all_cells() {
+                let Some(Ok(ast)) = cell.formula.as_deref().map(parse_formula) else { continue };
+                if !extract_table_references(&ast).is_empty() {
+                    formulas.push((sheet_name.clone(), CellAddress::new(row, col), ast));
+                }
+            }
+        }
+        
+        for (sheet_name, cell_addr, ast) in formulas {
+            self.dependency_graph.remove_dependencies(&sheet_name, &cell_addr);
+            self.register_dependencies(&sheet_name, &cell_addr, &ast);
+            self.mark_dirty(&sheet_name, &cell_addr);
+        }
+        self.finish_edit()
+    }
+    
+    // Write a constant into an input cell and recalculate, outside the edit journal.
+    // What-if tools try values this way and put the original cell back afterwards.
+    fn set_trial_value(&mut self, sheet_name: &str, cell_addr: &CellAddress, value: CellValue) -> Result<(), EngineError> {
+        let mut cell = Cell::new(value);
+        cell.style = self.sheets.get(sheet_name)
+            .and_then(|s| s.get_cell(cell_addr.row, cell_addr.col))
+            .and_then(|old| old.style.clone());
+        let change = Change::cell(sheet_name, cell_addr.clone(), None, Some(cell));
+        self.apply_changes(std::iter::once(&change), false)
+    }
+    
+    // Put back the state a cell had before trial values were written into it
+    fn restore_cell(&mut self, sheet_name: &str, cell_addr: &CellAddress, original: Option<Cell>) -> Result<(), EngineError> {
+        let change = Change::cell(sheet_name, cell_addr.clone(), original, None);
+        self.apply_changes(std::iter::once(&change), true)
+    }
+    
+    // Summarize a table by the values of one column, like a one-field pivot table.
+    // Row 1 is the table's header; every row below it with a key joins that key's
+    // group (text keys ignore case). Returns a two-column array of each key, in order
+    // of first appearance, and the aggregate of its values.
+    pub fn group_by(&self, sheet_name: &str, key_col: ColumnId, value_col: ColumnId, agg: Aggregation) -> Result<CellValue, EngineError> {
+        let sheet = match self.get_sheet(sheet_name) {
+            Some(s) => s,
+            None => return Err(EngineError::Internal(format!("Sheet '{}' does not exist", sheet_name))),
+        };
+        
+        let mut rows: Vec<RowId> = sheet.get_column(key_col).map(|(&row, _)| row).filter(|&row| row > 0).collect();
+        rows.sort_unstable();
+        
+        let mut groups: Vec<(CellValue, Vec<CellValue>)> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        for row in rows {
+            let key = self.get_cell_value(sheet_name, row, key_col)?;
+            if matches!(key, CellValue::Blank) {
+                continue;
+            }
+            let group_key = match &key {
+                CellValue::Text(text) => format!("text:{}", text.to_lowercase()),
+                other => format!("{:?}", other),
+            };
+            
+            let value = self.get_cell_value(sheet_name, row, value_col)?;
+            let position = *positions.entry(group_key).or_insert_with(|| {
+                groups.push((key, Vec::new()));
+                groups.len() - 1
+            });
+            groups[position].1.push(value);
+        }
+        
+        // Each group's values are passed as one range, so text and blanks are skipped
+        // as they would be in SUM(B2:B10)
+        let mut table = Vec::with_capacity(groups.len());
+        for (key, values) in groups {
+            let aggregate = self.evaluator.call_function(agg.function_name(), &[CellValue::Array(vec![values])])?;
+            table.push(vec![key, aggregate]);
+        }
+        Ok(CellValue::Array(table))
+    }
+    
+    // Get a cell value (calculated value if formula, or direct value)
+    pub fn get_cell_value(&self, sheet_name: &str, row: RowId, col: ColumnId) -> Result<CellValue, EngineError> {
+        let sheet = match self.get_sheet(sheet_name) {
+            Some(s) => s,
+            None => return Err(EngineError::Internal(format!("Sheet '{}' does not exist", sheet_name))),
+        };
+        
+        let cell = match sheet.get_cell(row, col) {
+            Some(c) => c,
+            None => return Ok(CellValue::Blank), // Empty cell is treated as blank
+        };
+        
+        // Return calculated value if available, otherwise the direct value
+        match &cell.calculated_value {
+            Some(cv) => Ok(cv.clone()),
+            None => Ok(cell.value.clone()),
+        }
+    }
+    
+    // Get a cell's value as displayed: rendered through its number format, if it
+    // has one, in the workbook's locale
+    pub fn formatted_value(&self, sheet_name: &str, row: RowId, col: ColumnId) -> Result<String, EngineError> {
+        let value = self.get_cell_value(sheet_name, row, col)?;
+        let code = self.sheets[sheet_name].get_cell_style(row, col)
+            .and_then(|style| style.number_format.as_deref());
+        Ok(format_value(&value, code, &self.locale, self.date_system))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // A workbook with a single empty sheet, Sheet1
+    fn workbook() -> Workbook {
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Sheet1".to_string()).unwrap();
+        workbook
+    }
+    
+    fn set(workbook: &mut Workbook, reference: &str, value: impl Into<CellValue>) {
+        let address = CellAddress::from_a1(reference).unwrap();
+        workbook.set_cell_value("Sheet1", address.row, address.col, value).unwrap();
+    }
+    
+    fn value(workbook: &Workbook, reference: &str) -> CellValue {
+        let address = CellAddress::from_a1(reference).unwrap();
+        workbook.get_cell_value("Sheet1", address.row, address.col).unwrap()
+    }
+    
+    #[test]
+    fn cycles_become_errors_while_neighbors_compute() {
+        let mut workbook = workbook();
+        set(&mut workbook, "D1", 5.0);
+        set(&mut workbook, "E1", "=D1*2");
+        set(&mut workbook, "A1", "=B1+1");
+        set(&mut workbook, "B1", "=C1+1");
+        // Closing the cycle is accepted
+        set(&mut workbook, "C1", "=A1+D1");
+        set(&mut workbook, "F1", "=A1");
+        
+        for reference in ["A1", "B1", "C1"] {
+            assert_eq!(value(&workbook, reference), CellValue::Error(CellError::InvalidReference), "{}", reference);
+        }
+        assert_eq!(value(&workbook, "E1"), CellValue::Number(10.0));
+        set(&mut workbook, "D1", 6.0);
+        assert_eq!(value(&workbook, "E1"), CellValue::Number(12.0));
+        
+        // Breaking the cycle computes its cells again
+        set(&mut workbook, "C1", "=D1");
+        assert_eq!(value(&workbook, "A1"), CellValue::Number(8.0));
+        assert_eq!(value(&workbook, "F1"), CellValue::Number(8.0));
+    }
+    
+    #[test]
+    fn iterative_calculation_settles_a_convergent_cycle() {
+        let mut workbook = workbook();
+        workbook.set_iterative_calc(Some(IterativeCalc { max_iterations: 100, max_change: 1e-9 }));
+        set(&mut workbook, "A1", "=B1+1");
+        set(&mut workbook, "B1", "=A1*0.1");
+        
+        // A1 = A1 * 0.1 + 1
+        match (value(&workbook, "A1"), value(&workbook, "B1")) {
+            (CellValue::Number(a), CellValue::Number(b)) => {
+                assert!((a - 1.0 / 0.9).abs() < 1e-6, "{}", a);
+                assert!((b - 0.1 / 0.9).abs() < 1e-6, "{}", b);
+            },
+            other => panic!("{:?}", other),
+        }
+        
+        // Without iteration the cycle is an error again
+        workbook.set_iterative_calc(None);
+        set(&mut workbook, "B1", "=A1*0.2");
+        assert_eq!(value(&workbook, "A1"), CellValue::Error(CellError::InvalidReference));
+    }
+    
+    #[test]
+    fn iterative_calculation_stops_at_the_iteration_limit() {
+        let mut workbook = workbook();
+        workbook.set_iterative_calc(Some(IterativeCalc { max_iterations: 5, max_change: 0.0 }));
+        // Diverges by one each iteration
+        set(&mut workbook, "A1", "=A1+1");
+        assert!(matches!(value(&workbook, "A1"), CellValue::Number(n) if (1.0..=5.0).contains(&n)));
+    }
+    
+    #[test]
+    fn seeded_random_numbers_repeat() {
+        let random_column = |seed: u64| {
+            let mut workbook = workbook();
+            workbook.set_random_seed(seed);
+            for reference in ["A1", "A2", "A3"] {
+                set(&mut workbook, reference, "=RAND()");
+            }
+            set(&mut workbook, "B1", "=RANDBETWEEN(5,1)");
+            ["A1", "A2", "A3", "B1"].map(|reference| value(&workbook, reference))
+        };
+        
+        let values = random_column(42);
+        assert_eq!(values, [
+            CellValue::Number(0.21840519371218436),
+            CellValue::Number(0.3399310389170206),
+            CellValue::Number(0.6184820663561348),
+            CellValue::Number(5.0),
+        ]);
+        assert_eq!(values, random_column(42));
+        assert_ne!(values, random_column(7));
+    }
+    
+    #[test]
+    fn volatile_cells_are_recomputed_on_every_recalculation() {
+        let mut workbook = workbook();
+        workbook.set_random_seed(1);
+        set(&mut workbook, "A1", "=RAND()");
+        set(&mut workbook, "B1", 2.0);
+        set(&mut workbook, "C1", "=B1*2");
+        set(&mut workbook, "D1", "=A1+1");
+        
+        let mut previous = value(&workbook, "A1");
+        for _ in 0..2 {
+            let evaluations = workbook.evaluation_count();
+            workbook.recalculate().unwrap();
+            
+            // The RAND cell and its dependent, but not the pure formula
+            assert_eq!(workbook.evaluation_count() - evaluations, 2);
+            let current = value(&workbook, "A1");
+            assert_ne!(current, previous);
+            previous = current;
+        }
+        assert_eq!(value(&workbook, "C1"), CellValue::Number(4.0));
+    }
+    
+    #[test]
+    fn evaluate_cell_matches_the_recalculated_value() {
+        let mut workbook = workbook();
+        set(&mut workbook, "A1", 3.0);
+        set(&mut workbook, "A2", 4.0);
+        let formulas = ["=A1+A2", "=SUM(A1:A2)*2", "=IF(A1>A2,\"more\",\"less\")", "=A3+1", "=A1/0", "=CONCATENATE(A1,\"x\")"];
+        for (row, formula) in formulas.iter().enumerate() {
+            workbook.set_cell_value("Sheet1", row as RowId, 1, *formula).unwrap();
+        }
+        workbook.recalculate().unwrap();
+        
+        let evaluations = workbook.evaluation_count();
+        for row in 0..formulas.len() as RowId {
+            assert_eq!(workbook.evaluate_cell("Sheet1", row, 1).unwrap(), workbook.get_cell_value("Sheet1", row, 1).unwrap());
+        }
+        // Nothing was recalculated or cached
+        assert_eq!(workbook.evaluation_count(), evaluations);
+        
+        assert_eq!(workbook.evaluate_cell("Sheet1", 0, 0).unwrap(), CellValue::Number(3.0));
+        assert_eq!(workbook.evaluate_cell("Sheet1", 9, 9).unwrap(), CellValue::Blank);
+        assert!(workbook.evaluate_cell("Missing", 0, 0).is_err());
+    }
+    
+    #[test]
+    fn a1_references_stay_within_excel_limits() {
+        assert_eq!(CellAddress::from_a1("XFD1").unwrap(), CellAddress::new(0, 16383));
+        assert_eq!(CellAddress::from_a1("A1048576").unwrap(), CellAddress::new(1_048_575, 0));
+        
+        for reference in ["XFE1", "A1048577", "A0", "A99999999999999999999", "AAAAAAAA999999999999"] {
+            assert!(matches!(CellAddress::from_a1(reference), Err(EngineError::InvalidReference(_))), "{}", reference);
+        }
+        assert!(matches!(CellAddress::from_a1("1A"), Err(EngineError::ParseError(_))));
+        assert!(crate::parser::parse_formula("=XFE1+1").is_err());
+    }
+    
+    #[test]
+    fn whole_column_and_row_references_follow_scattered_values() {
+        let mut workbook = workbook();
+        set(&mut workbook, "A1", 1.0);
+        set(&mut workbook, "A500000", 2.0);
+        set(&mut workbook, "A1048576", 3.0);
+        set(&mut workbook, "C2", 5.0);
+        set(&mut workbook, "XFD2", 6.0);
+        set(&mut workbook, "B1", "=SUM(A:A)");
+        set(&mut workbook, "B3", "=SUM(2:2)");
+        set(&mut workbook, "B4", "=COUNT(A:A)");
+        
+        assert_eq!(value(&workbook, "B1"), CellValue::Number(6.0));
+        assert_eq!(value(&workbook, "B3"), CellValue::Number(11.0));
+        assert_eq!(value(&workbook, "B4"), CellValue::Number(3.0));
+        
+        // A change anywhere in the column or row recalculates the formulas
+        set(&mut workbook, "A70000", 4.0);
+        set(&mut workbook, "M2", 1.0);
+        assert_eq!(value(&workbook, "B1"), CellValue::Number(10.0));
+        assert_eq!(value(&workbook, "B3"), CellValue::Number(12.0));
+        assert_eq!(value(&workbook, "B4"), CellValue::Number(4.0));
+    }
+    
+    #[test]
+    fn sparse_ranges_only_visit_populated_cells() {
+        let mut workbook = workbook();
+        for address in ["A1", "A1000", "A1048576", "B5"] {
+            set(&mut workbook, address, 1.0);
+        }
+        
+        let range = CellRange::from_a1("A1:A1048576").unwrap();
+        assert_eq!(range.dimensions(), (1048576, 1));
+        let cells = workbook.get_sheet("Sheet1").unwrap().get_range_cells(&range);
+        // The iterator holds only the populated cells, not a million blanks
+        assert_eq!(cells.len(), 3);
+        assert_eq!(cells.map(|(address, _)| address.to_a1()).collect::<Vec<_>>(), ["A1", "A1000", "A1048576"]);
+        
+        // SUM(A:A) resolves one value per populated cell, with an allocation to match
+        let column = Reference::ColumnRange { sheet: None, start: 0, end: 0 }.bounds();
+        let mut context = crate::evaluator::EvaluationContext::new(&workbook, "Sheet1", CellAddress::new(0, 2));
+        match context.resolve_range_sparse("Sheet1", &column).unwrap() {
+            CellValue::Array(rows) => {
+                assert_eq!(rows.len(), 1);
+                assert_eq!(rows[0].len(), 3);
+                assert!(rows[0].capacity() < 16, "{}", rows[0].capacity());
+            },
+            other => panic!("expected an array, got {:?}", other),
+        }
+        set(&mut workbook, "C3", "=SUM(A:A)");
+        assert_eq!(value(&workbook, "C3"), CellValue::Number(3.0));
+        
+        set(&mut workbook, "C1", "=SUM(A1:B1048576)");
+        set(&mut workbook, "C2", "=COUNT(A1:A1048576)");
+        assert_eq!(value(&workbook, "C1"), CellValue::Number(4.0));
+        assert_eq!(value(&workbook, "C2"), CellValue::Number(3.0));
+        
+        // Large ranges are tracked whole, so an edit inside one still recalculates
+        set(&mut workbook, "A500000", 2.0);
+        assert_eq!(value(&workbook, "C1"), CellValue::Number(6.0));
+        assert_eq!(value(&workbook, "C2"), CellValue::Number(4.0));
+        set(&mut workbook, "D1", "=SUM(A1:XFD1048576)");
+        assert_eq!(value(&workbook, "D1"), CellValue::Error(CellError::InvalidReference));
+    }
+    
+    #[test]
+    fn values_display_as_excel_shows_them() {
+        let cases = [
+            (CellValue::Blank, ""),
+            (CellValue::Number(3.0), "3"),
+            (CellValue::Number(-0.0), "0"),
+            (CellValue::Number(2.5), "2.5"),
+            (CellValue::Number(0.1 + 0.2), "0.3"),
+            (CellValue::Number(1e15), "1E+15"),
+            (CellValue::Number(1.5e-10), "1.5E-10"),
+            (CellValue::Date(45292.0), "45292"),
+            (CellValue::Text("Hi".to_string()), "Hi"),
+            (CellValue::Boolean(true), "TRUE"),
+            (CellValue::Boolean(false), "FALSE"),
+            (CellValue::Error(CellError::DivisionByZero), "#DIV/0!"),
+            (CellValue::Error(CellError::NotAvailable), "#N/A"),
+            (CellValue::Formula("=A1+1".to_string()), "=A1+1"),
+            (CellValue::Array(vec![vec![CellValue::Number(7.0), CellValue::Number(8.0)]]), "7"),
+            (CellValue::Array(vec![]), ""),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(value.to_string(), expected, "{:?}", value);
+        }
+    }
+    
+    #[test]
+    fn value_equality_follows_the_equals_operator() {
+        use CellValue::*;
+        let text = |s: &str| Text(s.to_string());
+        
+        assert_eq!(Number(f64::NAN), Number(f64::NAN));
+        assert_eq!(Number(0.0), Number(-0.0));
+        assert_ne!(Number(1.0), Number(1.0 + 1e-12));
+        assert_eq!(text("Total"), text("TOTAL"));
+        assert_ne!(text("1"), Number(1.0));
+        assert_eq!(Boolean(true), Boolean(true));
+        assert_ne!(Boolean(true), Number(1.0));
+        assert_eq!(Error(CellError::NotAvailable), Error(CellError::NotAvailable));
+        assert_ne!(Error(CellError::NotAvailable), Error(CellError::InvalidValue));
+        
+        // `==` is structural; `equals` is the `=` operator, where blank is 0, "" or FALSE
+        assert_ne!(Blank, Number(0.0));
+        assert_ne!(Date(45292.0), Number(45292.0));
+        for other in [Blank, Number(0.0), text(""), Boolean(false)] {
+            assert!(Blank.equals(&other) && other.equals(&Blank), "{:?}", other);
+        }
+        assert!(!Blank.equals(&Number(1.0)));
+        assert!(Date(45292.0).equals(&Number(45292.0)));
+        assert!(text("a").equals(&text("A")));
+        
+        // Test comparisons can allow for floating point error
+        assert!(Number(0.1 + 0.2).approx_eq(&Number(0.3), 1e-9));
+        assert!(!Number(0.1 + 0.2).approx_eq(&Number(0.31), 1e-9));
+        assert!(Array(vec![vec![Number(1.0 / 3.0)]]).approx_eq(&Array(vec![vec![Number(0.333333333)]]), 1e-9));
+    }
+    
+    #[test]
+    fn clearing_a_range_updates_its_dependents() {
+        let mut workbook = workbook();
+        for (i, reference) in ["A1", "A2", "A3", "A4"].into_iter().enumerate() {
+            set(&mut workbook, reference, (i + 1) as f64);
+        }
+        set(&mut workbook, "B1", "=SUM(A1:A4)");
+        set(&mut workbook, "B2", "=A2*10");
+        assert_eq!(value(&workbook, "B1"), CellValue::Number(10.0));
+        
+        workbook.clear_range("Sheet1", &CellRange::from_a1("A2:A3").unwrap()).unwrap();
+        assert_eq!(value(&workbook, "A2"), CellValue::Blank);
+        assert_eq!(value(&workbook, "B1"), CellValue::Number(5.0));
+        assert_eq!(value(&workbook, "B2"), CellValue::Number(0.0));
+        
+        // Clearing a formula cell removes its dependencies too
+        workbook.clear_range("Sheet1", &CellRange::from_a1("B1:B1").unwrap()).unwrap();
+        set(&mut workbook, "A1", 100.0);
+        assert_eq!(value(&workbook, "B1"), CellValue::Blank);
+        assert!(workbook.clear_range("Missing", &CellRange::from_a1("A1:A2").unwrap()).is_err());
+    }
+    
+    #[test]
+    fn moved_cells_keep_their_references() {
+        let mut workbook = workbook();
+        workbook.add_sheet("Summary".to_string()).unwrap();
+        for (i, reference) in ["A1", "A2", "A3"].into_iter().enumerate() {
+            set(&mut workbook, reference, (i + 1) as f64);
+        }
+        set(&mut workbook, "F1", 9.0);
+        set(&mut workbook, "Z100", "=SUM(A1:A3)+A2");
+        workbook.set_cell_value("Summary", 0, 0, "=Sheet1!A3*2").unwrap();
+        set(&mut workbook, "B1", "=F1");
+        
+        // Move the column onto F (overwriting F1), far from the formulas using it
+        workbook.move_range("Sheet1", CellRange::from_a1("A1:A3").unwrap(), CellAddress::from_a1("F1").unwrap()).unwrap();
+        let formula = |workbook: &Workbook, sheet: &str, reference: &str| {
+            let address = CellAddress::from_a1(reference).unwrap();
+            workbook.get_sheet(sheet).unwrap().get_cell(address.row, address.col).unwrap().formula.clone().unwrap()
+        };
+        assert_eq!(formula(&workbook, "Sheet1", "Z100"), "=SUM(F1:F3)+F2");
+        assert_eq!(formula(&workbook, "Summary", "A1"), "=Sheet1!F3*2");
+        assert_eq!(value(&workbook, "Z100"), CellValue::Number(8.0));
+        assert_eq!(value(&workbook, "A1"), CellValue::Blank);
+        assert_eq!(value(&workbook, "B1"), CellValue::Error(CellError::InvalidReference));
+        
+        // Overlapping source and destination: shift the column down one row
+        workbook.move_range("Sheet1", CellRange::from_a1("F1:F3").unwrap(), CellAddress::from_a1("F2").unwrap()).unwrap();
+        assert_eq!(formula(&workbook, "Sheet1", "Z100"), "=SUM(F2:F4)+F3");
+        assert_eq!(value(&workbook, "F4"), CellValue::Number(3.0));
+        assert_eq!(value(&workbook, "Z100"), CellValue::Number(8.0));
+        assert_eq!(workbook.get_cell_value("Summary", 0, 0).unwrap(), CellValue::Number(6.0));
+    }
+    
+    #[test]
+    fn snapshots_hold_the_used_range_with_blanks_filled_in() {
+        // The income statement from examples/simple_model.rs
+        let mut workbook = workbook();
+        let rows: [[&str; 3]; 8] = [
+            ["Item", "2025", "2026"],
+            ["Revenue", "1000", "1150"],
+            ["Cost of Goods Sold", "=B2*0.6", "=C2*0.6"],
+            ["Gross Profit", "=B2-B3", "=C2-C3"],
+            ["Operating Expenses", "250", "275"],
+            ["Operating Income", "=B4-B5", "=C4-C5"],
+            ["Taxes (25%)", "=B6*0.25", "=C6*0.25"],
+            ["Net Income", "=B6-B7", "=C6-C7"],
+        ];
+        for (row, cells) in rows.iter().enumerate() {
+            for (col, text) in cells.iter().enumerate() {
+                let value = match text.parse::<f64>() {
+                    Ok(n) if row > 0 => CellValue::Number(n),
+                    _ => CellValue::from(*text),
+                };
+                workbook.set_cell_value("Sheet1", row as RowId, col as ColumnId, value).unwrap();
+            }
+        }
+        set(&mut workbook, "E10", "note");
+        
+        let grid = workbook.sheet_snapshot("Sheet1").unwrap();
+        assert_eq!((grid.len(), grid[0].len()), (10, 5));
+        assert_eq!(grid[0][..3], [CellValue::from("Item"), CellValue::from("2025"), CellValue::from("2026")]);
+        let column = |col: usize| grid[1..8].iter().map(|row| row[col].clone()).collect::<Vec<_>>();
+        let numbers = |values: [f64; 7]| values.map(CellValue::Number).to_vec();
+        assert_eq!(column(1), numbers([1000.0, 600.0, 400.0, 250.0, 150.0, 37.5, 112.5]));
+        assert_eq!(column(2), numbers([1150.0, 690.0, 460.0, 275.0, 185.0, 46.25, 138.75]));
+        assert_eq!(grid[7][0], CellValue::from("Net Income"));
+        assert!(grid[8].iter().all(|value| *value == CellValue::Blank));
+        assert_eq!(grid[9][4], CellValue::from("note"));
+        assert_eq!(grid[0][3], CellValue::Blank);
+        
+        workbook.add_sheet("Empty".to_string()).unwrap();
+        assert!(workbook.sheet_snapshot("Empty").unwrap().is_empty());
+        assert!(workbook.sheet_snapshot("Missing").is_err());
+    }
+    
+    #[test]
+    fn undo_and_redo_a_cell_edit() {
+        let mut workbook = workbook();
+        assert!(!workbook.can_undo());
+        set(&mut workbook, "A1", 1.0);
+        set(&mut workbook, "B1", "=A1*2");
+        set(&mut workbook, "A1", 5.0);
+        assert_eq!(value(&workbook, "B1"), CellValue::Number(10.0));
+        
+        assert!(workbook.undo().unwrap());
+        assert_eq!(value(&workbook, "A1"), CellValue::Number(1.0));
+        assert_eq!(value(&workbook, "B1"), CellValue::Number(2.0));
+        assert!(workbook.can_redo());
+        
+        assert!(workbook.redo().unwrap());
+        assert_eq!(value(&workbook, "B1"), CellValue::Number(10.0));
+        assert!(!workbook.redo().unwrap());
+        
+        // Undoing the formula's entry leaves the cell empty; a new edit clears the redo history
+        workbook.undo().unwrap();
+        workbook.undo().unwrap();
+        assert_eq!(value(&workbook, "B1"), CellValue::Blank);
+        set(&mut workbook, "C1", 3.0);
+        assert!(!workbook.can_redo());
+    }
+    
+    #[test]
+    fn a_filled_range_is_undone_in_one_step() {
+        let mut workbook = workbook();
+        set(&mut workbook, "B1", "=SUM(A1:A3)");
+        let fill = (0..3).map(|row| (row, 0, CellValue::Number(7.0))).collect();
+        workbook.set_cells("Sheet1", fill).unwrap();
+        assert_eq!(value(&workbook, "B1"), CellValue::Number(21.0));
+        
+        assert!(workbook.undo().unwrap());
+        for reference in ["A1", "A2", "A3"] {
+            assert_eq!(value(&workbook, reference), CellValue::Blank);
+        }
+        assert_eq!(value(&workbook, "B1"), CellValue::Number(0.0));
+        
+        workbook.redo().unwrap();
+        assert_eq!(value(&workbook, "A3"), CellValue::Number(7.0));
+        assert_eq!(value(&workbook, "B1"), CellValue::Number(21.0));
+    }
+    
+    #[test]
+    fn a_failed_transaction_rolls_back_its_earlier_edits() {
+        let mut workbook = workbook();
+        set(&mut workbook, "A1", 1.0);
+        set(&mut workbook, "B1", "=A1+1");
+        
+        // The second of three edits targets a missing sheet
+        let result = workbook.transaction(|tx| {
+            tx.set_cell_value("Sheet1", 0, 0, 10.0)?;
+            tx.set_cell_value("Missing", 0, 0, 20.0)?;
+            tx.set_cell_value("Sheet1", 2, 0, 30.0)
+        });
+        assert!(result.is_err());
+        assert_eq!(value(&workbook, "A1"), CellValue::Number(1.0));
+        assert_eq!(value(&workbook, "A3"), CellValue::Blank);
+        assert_eq!(value(&workbook, "B1"), CellValue::Number(2.0));
+        assert!(workbook.get_sheet("Missing").is_none());
+        
+        // A successful one recalculates once on commit and is a single undo step
+        let before = workbook.evaluation_count();
+        workbook.transaction(|tx| {
+            tx.set_cell_value("Sheet1", 0, 0, 10.0)?;
+            tx.set_cell_value("Sheet1", 0, 2, "=B1*2")?;
+            tx.set_cell_value("Sheet1", 2, 0, 30.0)
+        }).unwrap();
+        assert_eq!(value(&workbook, "C1"), CellValue::Number(22.0));
+        assert_eq!(workbook.evaluation_count() - before, 2);
+        workbook.undo().unwrap();
+        assert_eq!(value(&workbook, "A1"), CellValue::Number(1.0));
+        assert_eq!(value(&workbook, "C1"), CellValue::Blank);
+        assert_eq!(value(&workbook, "A3"), CellValue::Blank);
+    }
+    
+    #[test]
+    fn references_to_missing_sheets_are_ref_errors() {
+        let mut workbook = workbook();
+        set(&mut workbook, "A1", "=Missing!A1+1");
+        set(&mut workbook, "A2", "=SUM('No Such Sheet'!B1:B3)");
+        set(&mut workbook, "A3", "=Sheet1!A4*2");
+        set(&mut workbook, "A4", 5.0);
+        
+        assert_eq!(value(&workbook, "A1"), CellValue::Error(CellError::InvalidReference));
+        assert_eq!(value(&workbook, "A2"), CellValue::Error(CellError::InvalidReference));
+        assert_eq!(value(&workbook, "A3"), CellValue::Number(10.0));
+        
+        // No dangling dependency is registered for the missing sheet
+        let a1 = CellAddress::from_a1("A1").unwrap();
+        assert!(workbook.trace_precedents("Sheet1", &a1).unwrap().is_empty());
+    }
+    
+    #[test]
+    fn tracing_follows_a_multi_level_chain() {
+        let mut workbook = workbook();
+        workbook.add_sheet("Report".to_string()).unwrap();
+        set(&mut workbook, "A1", 1.0);
+        set(&mut workbook, "A2", 2.0);
+        set(&mut workbook, "B1", "=A1+A2");
+        set(&mut workbook, "C1", "=B1*2");
+        set(&mut workbook, "D1", "=SUM(C1:C2)");
+        workbook.set_cell_value("Report", 0, 0, "=Sheet1!D1").unwrap();
+        
+        let cells = |set: HashSet<(String, CellAddress)>| {
+            let mut cells: Vec<String> = set.into_iter().map(|(sheet, address)| format!("{}!{}", sheet, address.to_a1())).collect();
+            cells.sort();
+            cells
+        };
+        let address = |reference: &str| CellAddress::from_a1(reference).unwrap();
+        
+        assert_eq!(cells(workbook.trace_precedents("Report", &address("A1")).unwrap()),
+            ["Sheet1!A1", "Sheet1!A2", "Sheet1!B1", "Sheet1!C1", "Sheet1!C2", "Sheet1!D1"]);
+        assert_eq!(cells(workbook.trace_dependents("Sheet1", &address("A2")).unwrap()),
+            ["Report!A1", "Sheet1!B1", "Sheet1!C1", "Sheet1!D1"]);
+        assert_eq!(cells(workbook.trace_precedents("Sheet1", &address("C1")).unwrap()), ["Sheet1!A1", "Sheet1!A2", "Sheet1!B1"]);
+        assert!(workbook.trace_dependents("Report", &address("A1")).unwrap().is_empty());
+        assert!(workbook.trace_precedents("Missing", &address("A1")).is_err());
+    }
+    
+    #[test]
+    fn group_by_sums_a_sales_table_by_region() {
+        let mut workbook = workbook();
+        let rows = [("Region", "Amount"), ("West", "100"), ("East", "50"), ("west", "25"), ("North", "x"), ("East", "=B3*2")];
+        for (row, (region, amount)) in rows.iter().enumerate() {
+            let amount = amount.parse::<f64>().map(CellValue::Number).unwrap_or_else(|_| CellValue::from(*amount));
+            workbook.set_cell_value("Sheet1", row as RowId, 0, *region).unwrap();
+            workbook.set_cell_value("Sheet1", row as RowId, 1, amount).unwrap();
+        }
+        
+        // Groups come in order of first appearance; keys ignore case and text amounts are skipped
+        let text = |s: &str| CellValue::Text(s.to_string());
+        assert_eq!(workbook.group_by("Sheet1", 0, 1, Aggregation::Sum).unwrap(), CellValue::Array(vec![
+            vec![text("West"), CellValue::Number(125.0)],
+            vec![text("East"), CellValue::Number(150.0)],
+            vec![text("North"), CellValue::Number(0.0)],
+        ]));
+        let counts = workbook.group_by("Sheet1", 0, 1, Aggregation::Count).unwrap();
+        assert_eq!(counts, CellValue::Array(vec![
+            vec![text("West"), CellValue::Number(2.0)],
+            vec![text("East"), CellValue::Number(2.0)],
+            vec![text("North"), CellValue::Number(0.0)],
+        ]));
+        let CellValue::Array(maxima) = workbook.group_by("Sheet1", 0, 1, Aggregation::Max).unwrap() else { panic!() };
+        assert_eq!(maxima[1][1], CellValue::Number(100.0));
+        assert!(workbook.group_by("Missing", 0, 1, Aggregation::Sum).is_err());
+    }
+    
+    #[test]
+    fn goal_seek_finds_break_even_revenue() {
+        let mut workbook = workbook();
+        set(&mut workbook, "A1", 500.0); // Revenue
+        set(&mut workbook, "A2", "=A1*0.6+400"); // Variable and fixed costs
+        set(&mut workbook, "A3", "=A1-A2"); // Profit
+        let (revenue, profit) = (CellAddress::from_a1("A1").unwrap(), CellAddress::from_a1("A3").unwrap());
+        
+        let solution = workbook.goal_seek("Sheet1", &profit, 0.0, &revenue).unwrap();
+        assert!((solution - 1000.0).abs() < 1e-6, "{}", solution);
+        assert_eq!(value(&workbook, "A1"), CellValue::Number(solution));
+        let CellValue::Number(n) = value(&workbook, "A3") else { panic!() };
+        assert!(n.abs() < 1e-6);
+        
+        // The result is one undoable edit
+        workbook.undo().unwrap();
+        assert_eq!(value(&workbook, "A1"), CellValue::Number(500.0));
+        
+        // No solution, or a changing cell that isn't a number, leaves the input as it was
+        set(&mut workbook, "B1", "=A1*A1");
+        assert!(workbook.goal_seek("Sheet1", &CellAddress::from_a1("B1").unwrap(), -1.0, &revenue).is_err());
+        assert_eq!(value(&workbook, "A1"), CellValue::Number(500.0));
+        set(&mut workbook, "C1", "text");
+        assert!(workbook.goal_seek("Sheet1", &profit, 0.0, &CellAddress::from_a1("C1").unwrap()).is_err());
+        assert_eq!(value(&workbook, "C1"), CellValue::from("text"));
+    }
+    
+    #[test]
+    fn data_table_builds_an_npv_sensitivity_grid() {
+        let mut workbook = workbook();
+        set(&mut workbook, "B1", 0.1); // WACC
+        set(&mut workbook, "B2", 0.02); // Growth
+        set(&mut workbook, "B3", "=NPV(B1,100*(1+B2),100*(1+B2)^2,100*(1+B2)^3)");
+        let address = |reference: &str| CellAddress::from_a1(reference).unwrap();
+        
+        let waccs = [0.08, 0.1, 0.12];
+        let growths = [0.0, 0.02, 0.04];
+        let numbers = |values: &[f64]| values.iter().map(|&v| CellValue::Number(v)).collect::<Vec<_>>();
+        let grid = workbook.data_table("Sheet1", &address("B3"), Some(&address("B1")), &numbers(&waccs), Some(&address("B2")), &numbers(&growths)).unwrap();
+        
+        // A row per growth rate and a column per WACC
+        let npv = |wacc: f64, growth: f64| (1..=3).map(|t| 100.0 * (1.0 + growth).powi(t) / (1.0 + wacc).powi(t)).sum::<f64>();
+        let CellValue::Array(rows) = grid else { panic!("expected an array, got {:?}", grid) };
+        assert_eq!(rows.len(), 3);
+        for (row, growth) in rows.iter().zip(growths) {
+            assert_eq!(row.len(), 3);
+            for (cell, wacc) in row.iter().zip(waccs) {
+                assert!(cell.approx_eq(&CellValue::Number(npv(wacc, growth)), 1e-9), "{} {}: {:?}", wacc, growth, cell);
+            }
+        }
+        
+        // The inputs and the formula's value are restored
+        assert_eq!(value(&workbook, "B1"), CellValue::Number(0.1));
+        assert_eq!(value(&workbook, "B2"), CellValue::Number(0.02));
+        assert!(value(&workbook, "B3").approx_eq(&CellValue::Number(npv(0.1, 0.02)), 1e-9));
+        
+        // One input gives a single row
+        let CellValue::Array(rows) = workbook.data_table("Sheet1", &address("B3"), Some(&address("B1")), &numbers(&waccs), None, &[]).unwrap() else { panic!() };
+        assert_eq!((rows.len(), rows[0].len()), (1, 3));
+    }
+    
+    #[test]
+    fn scenarios_swap_inputs_and_can_be_undone() {
+        let mut workbook = workbook();
+        set(&mut workbook, "A1", 100.0); // Units
+        set(&mut workbook, "A2", 5.0); // Price
+        set(&mut workbook, "A3", "=A1*A2"); // Revenue
+        let inputs = [CellAddress::from_a1("A1").unwrap(), CellAddress::from_a1("A2").unwrap()];
+        
+        workbook.capture_scenario("Base", "Sheet1", &inputs).unwrap();
+        workbook.define_scenario("Bull", "Sheet1", vec![(inputs[0].clone(), CellValue::Number(150.0)), (inputs[1].clone(), CellValue::Number(6.0))]).unwrap();
+        workbook.define_scenario("Bear", "Sheet1", vec![(inputs[0].clone(), CellValue::Number(80.0)), (inputs[1].clone(), "=3+1".into())]).unwrap();
+        
+        workbook.apply_scenario("Bull").unwrap();
+        assert_eq!(value(&workbook, "A3"), CellValue::Number(900.0));
+        workbook.apply_scenario("Bear").unwrap();
+        assert_eq!(value(&workbook, "A3"), CellValue::Number(320.0));
+        workbook.apply_scenario("Base").unwrap();
+        assert_eq!(value(&workbook, "A3"), CellValue::Number(500.0));
+        
+        // Applying a scenario is a single edit
+        workbook.undo().unwrap();
+        assert_eq!(value(&workbook, "A3"), CellValue::Number(320.0));
+        assert_eq!(workbook.current_values("Sheet1", &inputs[1..]).unwrap(), vec![(inputs[1].clone(), "=3+1".into())]);
+        assert!(workbook.apply_scenario("Missing").is_err());
+    }
+    
+    #[test]
+    fn recalculation_reuses_parsed_formulas() {
+        let mut workbook = workbook();
+        set(&mut workbook, "A1", 2.0);
+        set(&mut workbook, "B1", "=A1*3+SUM(A1:A2)");
+        set(&mut workbook, "C1", "=B1&\"!\"");
+        let cached = |workbook: &Workbook, reference: &str| {
+            let key = ("Sheet1".to_string(), CellAddress::from_a1(reference).unwrap());
+            Arc::clone(&workbook.formula_asts[&key].1)
+        };
+        let before = (cached(&workbook, "B1"), cached(&workbook, "C1"));
+        
+        // Repeated recalculations evaluate the same parsed trees
+        for i in 0..100 {
+            set(&mut workbook, "A2", i as f64);
+            workbook.recalculate().unwrap();
+        }
+        assert!(Arc::ptr_eq(&before.0, &cached(&workbook, "B1")));
+        assert!(Arc::ptr_eq(&before.1, &cached(&workbook, "C1")));
+        assert_eq!(value(&workbook, "B1"), CellValue::Number(107.0));
+        assert_eq!(value(&workbook, "C1"), CellValue::from("107!"));
+        
+        // Changing the formula text replaces its tree; removing the cell drops it
+        set(&mut workbook, "B1", "=A1*4");
+        assert!(!Arc::ptr_eq(&before.0, &cached(&workbook, "B1")));
+        assert_eq!(value(&workbook, "C1"), CellValue::from("8!"));
+        workbook.delete_cell("Sheet1", 0, 2).unwrap();
+        assert!(!workbook.formula_asts.contains_key(&("Sheet1".to_string(), CellAddress::from_a1("C1").unwrap())));
+    }
+    
+    #[test]
+    fn a_wide_fan_out_recalculates_in_parallel_correctly() {
+        // Two levels of independent formulas, each level large enough to run in parallel
+        let width = PARALLEL_RECALC_MIN_CELLS as RowId * 4;
+        let mut workbook = workbook();
+        let mut cells = vec![(0, 0, CellValue::Number(1.0))];
+        for row in 0..width {
+            cells.push((row, 1, CellValue::Formula(format!("=A1*{}+IF(MOD({},2)=0,1,0)", row, row))));
+            cells.push((row, 2, CellValue::Formula(format!("=B{}*2", row + 1))));
+        }
+        cells.push((0, 3, CellValue::Formula(format!("=SUM(C1:C{})", width))));
+        workbook.set_cells("Sheet1", cells).unwrap();
+        
+        let expected = |a: f64| (0..width).map(|row| 2.0 * (a * row as f64 + if row % 2 == 0 { 1.0 } else { 0.0 })).sum::<f64>();
+        assert_eq!(value(&workbook, "D1"), CellValue::Number(expected(1.0)));
+        
+        let before = workbook.evaluation_count();
+        set(&mut workbook, "A1", 3.0);
+        // B1 is A1*0+1, unchanged, so C1 isn't recomputed
+        assert_eq!(workbook.evaluation_count() - before, 2 * width as u64);
+        assert_eq!(value(&workbook, &format!("C{}", width)), CellValue::Number(2.0 * 3.0 * (width - 1) as f64));
+        assert_eq!(value(&workbook, "D1"), CellValue::Number(expected(3.0)));
+    }
+    
+    #[test]
+    fn unchanged_intermediates_stop_recalculation() {
+        let mut workbook = workbook();
+        set(&mut workbook, "A1", 5.0);
+        set(&mut workbook, "B1", "=IF(A1>0,\"pos\",\"neg\")");
+        set(&mut workbook, "C1", "=B1&\"!\"");
+        set(&mut workbook, "D1", "=LEN(C1)*A2");
+        set(&mut workbook, "A2", 1.0);
+        
+        // The IF absorbs the edit, so only B1 is evaluated
+        let before = workbook.evaluation_count();
+        set(&mut workbook, "A1", 7.0);
+        assert_eq!(workbook.evaluation_count() - before, 1);
+        
+        // A change that reaches B1's value flows all the way down
+        let before = workbook.evaluation_count();
+        set(&mut workbook, "A1", -1.0);
+        assert_eq!(workbook.evaluation_count() - before, 3);
+        assert_eq!(value(&workbook, "C1"), CellValue::from("neg!"));
+        
+        // A direct input of D1 still recomputes it
+        let before = workbook.evaluation_count();
+        set(&mut workbook, "A2", 2.0);
+        assert_eq!(workbook.evaluation_count() - before, 1);
+        assert_eq!(value(&workbook, "D1"), CellValue::Number(8.0));
+    }
+    
+    #[test]
+    fn simulating_two_dice_averages_seven() {
+        let mut workbook = workbook();
+        set(&mut workbook, "A1", "=RANDBETWEEN(1,6)+RANDBETWEEN(1,6)");
+        let output = CellAddress::from_a1("A1").unwrap();
+        
+        workbook.set_random_seed(42);
+        let samples = workbook.simulate("Sheet1", &output, 5000).unwrap();
+        assert_eq!(samples.len(), 5000);
+        assert!(samples.iter().all(|&sample| (2.0..=12.0).contains(&sample) && sample.fract() == 0.0));
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+        assert!((mean - 7.0).abs() < 0.15, "{}", mean);
+        
+        // The same seed gives the same samples
+        workbook.set_random_seed(42);
+        assert_eq!(workbook.simulate("Sheet1", &output, 5000).unwrap(), samples);
+        
+        set(&mut workbook, "B1", "text");
+        assert!(workbook.simulate("Sheet1", &CellAddress::from_a1("B1").unwrap(), 3).is_err());
+    }
+    
+    #[test]
+    fn recalculation_errors_name_their_cell() {
+        let mut workbook = workbook();
+        workbook.add_sheet("Projections".to_string()).unwrap();
+        workbook.set_cell_value("Projections", 0, 6, "x").unwrap();
+        
+        // A text year is an error value, not an engine error
+        workbook.set_cell_value("Projections", 1, 6, "=DATE(G1,1,1)").unwrap();
+        assert_eq!(workbook.get_cell_value("Projections", 1, 6).unwrap(), CellValue::Error(CellError::InvalidValue));
+        
+        // An unknown function aborts the evaluation, and the error names the cell
+        let error = workbook.set_cell_value("Projections", 9, 6, "=FOO(G1)").unwrap_err();
+        assert!(matches!(&error, EngineError::CellEvaluation { sheet, cell, .. } if sheet == "Projections" && cell == "G10"), "{:?}", error);
+        assert!(error.to_string().contains("Projections!G10"), "{}", error);
+    }
+    
+    #[test]
+    fn implicit_intersection_picks_the_value_in_line() {
+        let mut workbook = workbook();
+        set(&mut workbook, "A1", "=SEQUENCE(3,1,10,10)");
+        for (reference, value) in [("C1", 1.0), ("C2", 2.0), ("C3", 3.0)] {
+            set(&mut workbook, reference, value);
+        }
+        set(&mut workbook, "B2", "=@A1#");
+        set(&mut workbook, "B3", "=@A1#+1");
+        set(&mut workbook, "B5", "=@A1#");
+        set(&mut workbook, "D2", "=@C1:C3");
+        set(&mut workbook, "D7", "=@C1:C3");
+        set(&mut workbook, "E1", "=@SEQUENCE(1,3)");
+        
+        assert_eq!(value(&workbook, "B2"), CellValue::Number(20.0));
+        assert_eq!(value(&workbook, "B3"), CellValue::Number(31.0));
+        // Outside the spilled column there is no single value
+        assert_eq!(value(&workbook, "B5"), CellValue::Error(CellError::InvalidValue));
+        assert_eq!(value(&workbook, "D2"), CellValue::Number(2.0));
+        assert_eq!(value(&workbook, "D7"), CellValue::Error(CellError::InvalidValue));
+        // An array that isn't a spill range gives its first value
+        assert_eq!(value(&workbook, "E1"), CellValue::Number(1.0));
+    }
+    
+    #[test]
+    fn spill_reference_follows_the_spill_size() {
+        let mut workbook = workbook();
+        set(&mut workbook, "A1", "=SEQUENCE(3)");
+        set(&mut workbook, "B1", "=SUM(A1#)");
+        assert_eq!(value(&workbook, "B1"), CellValue::Number(6.0));
+        
+        set(&mut workbook, "A1", "=SEQUENCE(4)");
+        assert_eq!(value(&workbook, "B1"), CellValue::Number(10.0));
+        
+        // A cell that doesn't hold an array has no spill range
+        set(&mut workbook, "A1", 5.0);
+        assert_eq!(value(&workbook, "B1"), CellValue::Error(CellError::InvalidReference));
+    }
+    
+    #[test]
+    fn text_follows_the_workbook_locale() {
+        let mut workbook = workbook();
+        set(&mut workbook, "A1", 1234.5);
+        set(&mut workbook, "B1", "=TEXT(A1,\"#,##0.00\")");
+        assert_eq!(value(&workbook, "B1"), CellValue::Text("1,234.50".to_string()));
+        
+        workbook.set_locale(Locale::de_de()).unwrap();
+        assert_eq!(value(&workbook, "B1"), CellValue::Text("1.234,50".to_string()));
+        assert_eq!(workbook.formatted_value("Sheet1", 0, 0).unwrap(), "1234,5");
+    }
+    
+    #[test]
+    fn strict_mode_rejects_formulas_that_cannot_evaluate() {
+        let mut workbook = workbook();
+        set(&mut workbook, "A1", 1.0);
+        
+        // Off by default: the formula is stored and fails when evaluated
+        assert!(!workbook.is_strict());
+        let error = workbook.set_cell_value("Sheet1", 0, 1, "=SUMM(A1)").unwrap_err();
+        assert!(matches!(&error, EngineError::CellEvaluation { cell, source, .. }
+            if cell == "B1" && matches!(**source, EngineError::InvalidFormula { position: 2, .. })), "{:?}", error);
+        let sheet = workbook.get_sheet("Sheet1").unwrap();
+        assert_eq!(sheet.get_cell(0, 1).unwrap().formula.as_deref(), Some("=SUMM(A1)"));
+        
+        workbook.set_strict(true);
+        let error = workbook.set_cell_value("Sheet1", 1, 1, "=ROUND(A1)").unwrap_err();
+        assert!(matches!(&error, EngineError::InvalidFormula { message, .. } if message == "ROUND takes 2 arguments, got 1"), "{:?}", error);
+        assert!(workbook.get_sheet("Sheet1").unwrap().get_cell(1, 1).is_none());
+        
+        // A rejected cell in a batch keeps the whole batch out
+        let cells = vec![(2, 1, CellValue::Number(2.0)), (3, 1, CellValue::Formula("=SUMM(A1)".to_string()))];
+        assert!(workbook.set_cells("Sheet1", cells).is_err());
+        assert_eq!(value(&workbook, "B3"), CellValue::Blank);
+        
+        set(&mut workbook, "B2", "=ROUND(A1,0)");
+        assert_eq!(value(&workbook, "B2"), CellValue::Number(1.0));
+    }
+    
+    #[test]
+    fn detect_pattern_finds_the_cell_off_the_row_pattern() {
+        let mut workbook = workbook();
+        set(&mut workbook, "A1", 100.0);
+        for (reference, formula) in [("B1", "=A1*1.1"), ("C1", "=B1*1.1"), ("D1", "=C1*1.2"), ("E1", "=D1*1.1")] {
+            set(&mut workbook, reference, formula);
+        }
+        let sheet = workbook.get_sheet("Sheet1").unwrap();
+        
+        let pattern = sheet.detect_pattern(&CellRange::from_a1("B1:E1").unwrap()).unwrap();
+        assert_eq!(pattern.template.as_deref(), Some("=RC[-1]*1.1"));
+        assert_eq!(pattern.breaks, vec![CellAddress::from_a1("D1").unwrap()]);
+        assert!(!pattern.is_consistent());
+        
+        // The input cell has no formula, so it breaks the pattern too
+        let pattern = sheet.detect_pattern(&CellRange::from_a1("A1:C1").unwrap()).unwrap();
+        assert_eq!(pattern.breaks, vec![CellAddress::from_a1("A1").unwrap()]);
+        assert!(sheet.detect_pattern(&CellRange::from_a1("B1:C1").unwrap()).unwrap().is_consistent());
+        assert!(sheet.detect_pattern(&CellRange::from_a1("A1:B2").unwrap()).is_err());
+    }
+    
+    #[test]
+    fn structured_references_read_table_columns() {
+        let mut workbook = workbook();
+        let rows = [("Region", "Amount"), ("North", "10"), ("South", "20"), ("East", "30")];
+        for (row, (region, amount)) in rows.into_iter().enumerate() {
+            let amount = amount.parse::<f64>().map_or(CellValue::Text(amount.to_string()), CellValue::Number);
+            workbook.set_cell_value("Sheet1", row as RowId, 0, region).unwrap();
+            workbook.set_cell_value("Sheet1", row as RowId, 1, amount).unwrap();
+        }
+        set(&mut workbook, "C1", "Tax");
+        for reference in ["C2", "C3", "C4"] {
+            set(&mut workbook, reference, "=[@Amount]/10");
+        }
+        set(&mut workbook, "E1", "=SUM(Sales[Amount])");
+        // Before the table exists its name is unknown
+        assert_eq!(value(&workbook, "E1"), CellValue::Error(CellError::NameNotFound));
+        
+        workbook.add_table("Sales", "Sheet1", &CellRange::from_a1("A1:C4").unwrap()).unwrap();
+        assert_eq!(workbook.table("sales").unwrap().columns, vec!["Region", "Amount", "Tax"]);
+        assert_eq!(value(&workbook, "E1"), CellValue::Number(60.0));
+        assert_eq!(value(&workbook, "C3"), CellValue::Number(2.0));
+        
+        set(&mut workbook, "B3", 50.0);
+        assert_eq!(value(&workbook, "E1"), CellValue::Number(90.0));
+        assert_eq!(value(&workbook, "C3"), CellValue::Number(5.0));
+        
+        set(&mut workbook, "E2", "=Sales[Profit]");
+        assert_eq!(value(&workbook, "E2"), CellValue::Error(CellError::InvalidReference));
+        assert!(workbook.add_table("Overlap", "Sheet1", &CellRange::from_a1("C1:D3").unwrap()).is_err());
+        
+        assert!(workbook.remove_table("Sales").unwrap());
+        assert_eq!(value(&workbook, "E1"), CellValue::Error(CellError::NameNotFound));
+    }
+    
+    #[test]
+    fn indirect_reads_a1_and_r1c1_reference_text() {
+        let mut workbook = workbook();
+        workbook.add_sheet("My Sheet".to_string()).unwrap();
+        workbook.add_sheet("Sheet2".to_string()).unwrap();
+        workbook.set_cell_value("My Sheet", 0, 0, 7.0).unwrap();
+        workbook.set_cell_value("Sheet2", 2, 1, 11.0).unwrap();
+        set(&mut workbook, "A1", 3.0);
+        set(&mut workbook, "A2", 4.0);
+        set(&mut workbook, "B1", 5.0);
+        
+        set(&mut workbook, "C1", "=INDIRECT(\"B1\")");
+        set(&mut workbook, "C2", "=INDIRECT(\"Sheet2!B3\")");
+        set(&mut workbook, "C3", "=INDIRECT(\"'My Sheet'!A1\")");
+        set(&mut workbook, "C4", "=SUM(INDIRECT(\"A1:B1\"))");
+        // R1C1: absolute, and relative to the formula's cell (C5)
+        set(&mut workbook, "C5", "=INDIRECT(\"R2C1\",FALSE)");
+        set(&mut workbook, "C6", "=INDIRECT(\"R[-5]C[-1]\",FALSE)");
+        set(&mut workbook, "C7", "=INDIRECT(\"Sheet2!R3C2\",FALSE)");
+        set(&mut workbook, "C8", "=INDIRECT(\"not a reference\")");
+        
+        assert_eq!(value(&workbook, "C1"), CellValue::Number(5.0));
+        assert_eq!(value(&workbook, "C2"), CellValue::Number(11.0));
+        assert_eq!(value(&workbook, "C3"), CellValue::Number(7.0));
+        assert_eq!(value(&workbook, "C4"), CellValue::Number(8.0));
+        assert_eq!(value(&workbook, "C5"), CellValue::Number(4.0));
+        assert_eq!(value(&workbook, "C6"), CellValue::Number(5.0));
+        assert_eq!(value(&workbook, "C7"), CellValue::Number(11.0));
+        assert_eq!(value(&workbook, "C8"), CellValue::Error(CellError::InvalidReference));
+    }
+    
+    #[test]
+    fn quoted_sheet_names_are_referenced() {
+        let mut workbook = workbook();
+        workbook.add_sheet("DCF Valuation".to_string()).unwrap();
+        workbook.add_sheet("Bob's Data".to_string()).unwrap();
+        workbook.set_cell_value("DCF Valuation", 2, 1, 1500.0).unwrap();
+        workbook.set_cell_value("Bob's Data", 0, 0, 2.0).unwrap();
+        
+        set(&mut workbook, "A1", "='DCF Valuation'!B3*'Bob''s Data'!A1");
+        assert_eq!(value(&workbook, "A1"), CellValue::Number(3000.0));
+        workbook.set_cell_value("DCF Valuation", 2, 1, 1000.0).unwrap();
+        assert_eq!(value(&workbook, "A1"), CellValue::Number(2000.0));
+        
+        let reference = CellReference::from_a1("'DCF Valuation'!B3").unwrap();
+        assert_eq!(reference.sheet.as_deref(), Some("DCF Valuation"));
+        assert_eq!(reference.address, CellAddress::from_a1("B3").unwrap());
+        assert_eq!(CellReference::from_a1("'Bob''s Data'!A1").unwrap().to_string(), "'Bob''s Data'!A1");
+        assert_eq!(CellReference::from_a1("Sheet1!A1").unwrap().to_string(), "Sheet1!A1");
+        assert!(CellReference::from_a1("'Bob's Data'!A1").is_err());
+        assert!(CellReference::from_a1("''!A1").is_err());
+    }
+    
+    #[test]
+    fn ranges_parse_from_a1_notation() {
+        let a1 = CellAddress::new(0, 0);
+        let b2 = CellAddress::new(1, 1);
+        assert_eq!(CellRange::from_a1("A1:B2").unwrap(), CellRange::new(a1.clone(), b2.clone()));
+        assert_eq!(CellRange::from_a1("B2:A1").unwrap(), CellRange::new(a1.clone(), b2.clone()));
+        // Corners given bottom-left to top-right are normalized too
+        assert_eq!(CellRange::from_a1("A2:B1").unwrap(), CellRange::new(a1.clone(), b2.clone()));
+        assert_eq!(CellRange::from_a1("A1:B2").unwrap().dimensions(), (2, 2));
+        
+        assert!(matches!(CellRange::from_a1("A1"), Err(EngineError::ParseError(_))));
+        assert!(CellRange::from_a1("A1:B").is_err());
+        assert!(CellRange::from_a1("not a range").is_err());
+        
+        assert_eq!(CellOrRange::from_a1("B2").unwrap(), CellOrRange::Cell(b2.clone()));
+        assert_eq!(CellOrRange::from_a1("B2:A1").unwrap(), CellOrRange::Range(CellRange::new(a1, b2.clone())));
+        assert_eq!(CellOrRange::from_a1("B2").unwrap().to_range(), CellRange::new(b2.clone(), b2));
+        assert!(CellOrRange::from_a1("A1:").is_err());
+    }
+    
+    #[test]
+    fn set_cells_recalculates_a_block_once() {
+        let mut workbook = workbook();
+        set(&mut workbook, "D1", "=SUM(A1:C3)");
+        set(&mut workbook, "D2", "=D1*2");
+        let before = workbook.evaluation_count();
+        
+        let block = (0..3).flat_map(|row| (0..3).map(move |col| (row, col, CellValue::Number((row * 3 + col + 1) as f64)))).collect();
+        workbook.set_cells("Sheet1", block).unwrap();
+        
+        // Nine edits, but each dependent formula is evaluated once
+        assert_eq!(workbook.evaluation_count() - before, 2);
+        assert_eq!(value(&workbook, "D1"), CellValue::Number(45.0));
+        assert_eq!(value(&workbook, "D2"), CellValue::Number(90.0));
+        
+        // Formulas in the block see the values set alongside them
+        workbook.set_cells("Sheet1", vec![
+            (4, 0, CellValue::Formula("=B5+1".to_string())),
+            (4, 1, CellValue::Number(10.0)),
+        ]).unwrap();
+        assert_eq!(value(&workbook, "A5"), CellValue::Number(11.0));
+    }
+    
+    #[test]
+    fn cancelled_recalculation_stops_early_and_resumes() {
+        let mut workbook = workbook();
+        set(&mut workbook, "A1", 1.0);
+        for row in 2..=6 {
+            set(&mut workbook, &format!("A{}", row), format!("=A{}+1", row - 1).as_str());
+        }
+        assert_eq!(value(&workbook, "A6"), CellValue::Number(6.0));
+        
+        // Change the input without recalculating, then cancel after the first level
+        workbook.sheets.get_mut("Sheet1").unwrap().set_cell(0, 0, CellValue::Number(10.0)).unwrap();
+        workbook.mark_dirty("Sheet1", &CellAddress::new(0, 0));
+        let cancel = AtomicBool::new(false);
+        let mut reports = Vec::new();
+        let finished = workbook.recalculate_with(|done, total| {
+            reports.push((done, total));
+            cancel.store(true, Ordering::Relaxed);
+        }, &cancel).unwrap();
+        
+        assert!(!finished);
+        assert_eq!(reports, vec![(1, 6)]);
+        assert_eq!(value(&workbook, "A6"), CellValue::Number(6.0));
+        
+        // The next recalculation picks up the cells left dirty
+        workbook.recalculate().unwrap();
+        assert_eq!(value(&workbook, "A2"), CellValue::Number(11.0));
+        assert_eq!(value(&workbook, "A6"), CellValue::Number(15.0));
+    }
+    
+    #[test]
+    fn column_letters_convert_both_ways() {
+        for (letters, index) in [("A", 0), ("Z", 25), ("AA", 26), ("AZ", 51), ("BA", 52), ("ZZ", 701), ("AAA", 702), ("XFD", 16383)] {
+            assert_eq!(column_letter_to_index(letters).unwrap(), index, "{}", letters);
+            assert_eq!(column_index_to_letter(index), letters);
+        }
+        assert_eq!(column_letter_to_index("xfd").unwrap(), 16383);
+        
+        assert!(matches!(column_letter_to_index(""), Err(EngineError::ParseError(_))));
+        assert!(matches!(column_letter_to_index("A1"), Err(EngineError::ParseError(_))));
+        assert!(matches!(column_letter_to_index("A-"), Err(EngineError::ParseError(_))));
+        assert!(matches!(column_letter_to_index("XFE"), Err(EngineError::InvalidReference(_))));
+        assert!(matches!(column_letter_to_index("AAAAAAAAAAAAAAA"), Err(EngineError::InvalidReference(_))));
+        
+        // Cell addresses use the same conversion
+        assert_eq!(CellAddress::from_a1("XFD1048576").unwrap(), CellAddress::new(1_048_575, 16383));
+        assert!(CellAddress::from_a1("XFE1").is_err());
+    }
+    
+    #[test]
+    fn sheets_with_a_capacity_hint_hold_the_same_cells() {
+        let mut hinted = Sheet::with_capacity("Data".to_string(), 2_000);
+        assert!(hinted.cells.capacity() >= 2_000);
+        let mut plain = Sheet::new("Data".to_string());
+        for row in 0..1_000 {
+            for sheet in [&mut hinted, &mut plain] {
+                sheet.set_cell(row, 0, CellValue::Number(row as f64)).unwrap();
+                sheet.set_cell(row, 1, CellValue::Text(format!("item {}", row))).unwrap();
+            }
+        }
+        
+        hinted.shrink_to_fit();
+        plain.reserve(500);
+        assert!(plain.cells.capacity() >= 2_500);
+        for (row, col) in [(0, 0), (999, 0), (500, 1), (999, 1)] {
+            assert_eq!(hinted.get_cell(row, col).unwrap().value, plain.get_cell(row, col).unwrap().value);
+        }
+        assert_eq!(hinted.get_all_cells().count(), 2_000);
+        assert_eq!(hinted.get_cell(999, 1).unwrap().value, CellValue::Text("item 999".to_string()));
+    }
+    
+    #[test]
+    fn blanks_follow_one_set_of_rules() {
+        let mut workbook = workbook();
+        set(&mut workbook, "A2", 5.0);
+        set(&mut workbook, "A3", 2.0);
+        // A1 is empty throughout
+        let cases = [
+            ("=A1*5", CellValue::Number(0.0)),
+            ("=A1+5", CellValue::Number(5.0)),
+            ("=A1+A4", CellValue::Number(0.0)),
+            ("=A2/A1", CellValue::Error(CellError::DivisionByZero)),
+            ("=A1=0", CellValue::Boolean(true)),
+            ("=A1=\"\"", CellValue::Boolean(true)),
+            ("=A1=FALSE", CellValue::Boolean(true)),
+            ("=A1<1", CellValue::Boolean(true)),
+            ("=SUM(A1,A3)", CellValue::Number(2.0)),
+            ("=AVERAGE(A1:A3)", CellValue::Number(3.5)),
+            ("=PRODUCT(A1:A3)", CellValue::Number(10.0)),
+            ("=MIN(A1:A3)", CellValue::Number(2.0)),
+        ];
+        for (row, (formula, expected)) in cases.into_iter().enumerate() {
+            workbook.set_cell_value("Sheet1", row as RowId, 1, formula).unwrap();
+            assert_eq!(workbook.get_cell_value("Sheet1", row as RowId, 1).unwrap(), expected, "{}", formula);
+        }
+    }
+    
+    #[test]
+    fn non_finite_numbers_become_num_errors() {
+        let mut workbook = workbook();
+        let num = CellValue::Error(CellError::InvalidNumber);
+        
+        set(&mut workbook, "A1", f64::NAN);
+        set(&mut workbook, "A2", f64::INFINITY);
+        set(&mut workbook, "A3", CellValue::Date(f64::NEG_INFINITY));
+        for reference in ["A1", "A2", "A3"] {
+            assert_eq!(value(&workbook, reference), num, "{}", reference);
+        }
+        
+        set(&mut workbook, "B1", "=1E308*10");
+        set(&mut workbook, "B2", "=1E308+1E308");
+        set(&mut workbook, "B3", "=EXP(1000)");
+        set(&mut workbook, "B4", "=-1E308/1E-10");
+        set(&mut workbook, "B5", "=SEQUENCE(2,1,1E308,1E308)");
+        for reference in ["B1", "B2", "B3", "B4"] {
+            assert_eq!(value(&workbook, reference), num, "{}", reference);
+        }
+        assert_eq!(value(&workbook, "B5"), CellValue::Array(vec![vec![CellValue::Number(1E308)], vec![num.clone()]]));
+        
+        // 0/0 is still a division by zero, not NaN
+        set(&mut workbook, "C1", "=0/0");
+        assert_eq!(value(&workbook, "C1"), CellValue::Error(CellError::DivisionByZero));
+    }
+    
+    #[test]
+    fn switching_the_date_system_recalculates_dates() {
+        let mut workbook = workbook();
+        set(&mut workbook, "A1", "=DATE(2024,1,1)");
+        set(&mut workbook, "A2", "=EDATE(A1,1)");
+        set(&mut workbook, "A3", "=YEAR(45292)");
+        assert_eq!(value(&workbook, "A1"), CellValue::Date(45292.0));
+        assert_eq!(value(&workbook, "A3"), CellValue::Number(2024.0));
+        
+        workbook.set_date_system(DateSystem::Excel1904).unwrap();
+        assert_eq!(value(&workbook, "A1"), CellValue::Date(43830.0));
+        assert_eq!(value(&workbook, "A2"), CellValue::Date(43861.0));
+        // The same serial is four years later in the 1904 system
+        assert_eq!(value(&workbook, "A3"), CellValue::Number(2028.0));
+        
+        workbook.set_date_system(DateSystem::Excel1900).unwrap();
+        assert_eq!(value(&workbook, "A1"), CellValue::Date(45292.0));
     }
 }
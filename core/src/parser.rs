@@ -1,54 +1,510 @@
 // ssengine-core/src/parser.rs
 // Formula parsing using pest
 
+use pest::iterators::Pair;
 use pest::Parser;
 use pest_derive::Parser;
 
-use crate::ast::{AstNode, Literal, Reference, BinaryOperator, UnaryOperator};
-use crate::error::EngineError;
-use crate::model::{CellAddress, RowId, ColumnId};
+use crate::ast::{AstNode, Literal, Reference, TableReference, BinaryOperator, UnaryOperator, unquote_sheet_name};
+use crate::error::{EngineError, CellError};
+use crate::functions::FunctionRegistry;
+use crate::model::{CellAddress, CellRange, CellReference, MAX_ROWS, MAX_COLUMNS, column_index, row_index};
 
 // The pest grammar will be defined here
 #[derive(Parser)]
 #[grammar = "grammar/excel.pest"] // This file doesn't exist yet, will need to be created
 pub struct FormulaParser;
 
-// Placeholder for the parse function
+// Parse a formula (with or without the leading '=') into an AST
 pub fn parse_formula(input: &str) -> Result<AstNode, EngineError> {
-    // To be implemented once we create the grammar file
-    Err(EngineError::NotImplemented("Formula parsing".to_string()))
+    build_ast(parse_expression(input)?)
 }
 
-// Helper function to convert cell references like "A1" to (row, col) coordinates
-pub fn parse_cell_reference(reference: &str) -> Result<CellAddress, EngineError> {
-    // Simple implementation - will be replaced with proper parsing from the grammar
-    let mut col_str = String::new();
-    let mut row_str = String::new();
+// Parse a formula and check every function call against the registry: the function
+// must exist and, where its metadata is known, accept the number of arguments given.
+// Problems are reported as `InvalidFormula` with the position of the call.
+pub fn validate_formula(input: &str, functions: &FunctionRegistry) -> Result<AstNode, EngineError> {
+    let expression = parse_expression(input)?;
+    
+    // Positions count characters of the input as given, leading whitespace included
+    let indent = input.chars().take_while(|c| c.is_whitespace()).count();
+    for call in expression.clone().into_inner().flatten().filter(|pair| pair.as_rule() == Rule::function_call) {
+        let position = indent + call.as_span().start_pos().line_col().1;
+        let mut inner = call.into_inner();
+        let name = inner.next().map(|name| name.as_str().to_uppercase()).unwrap_or_default();
+        let arg_count = inner.next().map_or(0, |argument_list| argument_list.into_inner().count());
+        
+        if functions.get(&name).is_none() && functions.get_contextual(&name).is_none() {
+            return Err(EngineError::InvalidFormula { position, message: format!("Unknown function {}", name) });
+        }
+        if let Some(meta) = functions.metadata(&name).filter(|meta| !meta.accepts(arg_count)) {
+            let (expected, last) = match meta.max_args {
+                Some(max) if max == meta.min_args => (max.to_string(), max),
+                Some(max) => (format!("{} to {}", meta.min_args, max), max),
+                None => (format!("at least {}", meta.min_args), meta.min_args),
+            };
+            let message = format!("{} takes {} argument{}, got {}", name, expected, if last == 1 { "" } else { "s" }, arg_count);
+            return Err(EngineError::InvalidFormula { position, message });
+        }
+    }
+    
+    build_ast(expression)
+}
+
+// Parse a formula down to its top-level expression
+fn parse_expression(input: &str) -> Result<Pair<'_, Rule>, EngineError> {
+    let mut pairs = FormulaParser::parse(Rule::formula, input.trim())
+        .map_err(|e| EngineError::ParseError(e.to_string()))?;
+    
+    pairs
+        .next()
+        .and_then(|formula| formula.into_inner().next())
+        .ok_or_else(|| EngineError::ParseError(format!("Empty formula: {}", input)))
+}
+
+// Parse reference text in A1 style, e.g. "B3", "Sheet2!A1:B2" or "'My Sheet'!A1"
+pub fn parse_reference(text: &str) -> Result<Reference, EngineError> {
+    let reference = FormulaParser::parse(Rule::reference_text, text)
+        .map_err(|e| EngineError::InvalidReference(e.to_string()))?
+        .next()
+        .and_then(|pair| pair.into_inner().next())
+        .ok_or_else(|| EngineError::InvalidReference(format!("Not a reference: {}", text)))?;
+    
+    match build_reference(reference)? {
+        AstNode::Reference(reference) => Ok(reference),
+        _ => Err(EngineError::InvalidReference(format!("Not a reference: {}", text))),
+    }
+}
+
+// Parse reference text in R1C1 style, with relative parts (R[1]C[-1]) counted from
+// `origin`, e.g. "R2C3", "Sheet2!R[-1]C" or "R1C1:R[2]C[2]"
+pub fn parse_r1c1_reference(text: &str, origin: &CellAddress) -> Result<Reference, EngineError> {
+    let pairs = FormulaParser::parse(Rule::r1c1_text, text.trim())
+        .map_err(|e| EngineError::InvalidReference(e.to_string()))?
+        .next()
+        .map(|pair| pair.into_inner())
+        .ok_or_else(|| EngineError::InvalidReference(format!("Not a reference: {}", text)))?;
+    
+    let mut sheet = None;
+    let mut cells = Vec::new();
+    for pair in pairs {
+        match pair.as_rule() {
+            Rule::sheet_prefix => sheet = Some(sheet_name(pair)?),
+            Rule::r1c1_cell => {
+                let mut parts = pair.into_inner();
+                let row = r1c1_index(parts.next(), origin.row, MAX_ROWS, text)?;
+                let col = r1c1_index(parts.next(), origin.col, MAX_COLUMNS, text)?;
+                cells.push(CellAddress::new(row, col));
+            },
+            _ => {},
+        }
+    }
+    
+    let mut cells = cells.into_iter();
+    let start = cells.next().ok_or_else(|| EngineError::InvalidReference(format!("Not a reference: {}", text)))?;
+    Ok(match (sheet, cells.next()) {
+        (None, None) => Reference::Cell(start),
+        (None, Some(end)) => Reference::Range { start, end },
+        (Some(sheet), None) => Reference::SheetCell { sheet, address: start },
+        (Some(sheet), Some(end)) => Reference::SheetRange { sheet, start, end },
+    })
+}
+
+// The zero-based row or column an R1C1 part names: absolute (R3), relative to the
+// origin (R[-1]) or the origin itself (R)
+fn r1c1_index(part: Option<Pair<Rule>>, origin: u32, limit: u32, text: &str) -> Result<u32, EngineError> {
+    let index = match part.and_then(|part| part.into_inner().next()) {
+        None => origin as i64,
+        Some(offset) if offset.as_rule() == Rule::relative_offset => {
+            let delta: i64 = offset.as_str().trim_matches(|c| c == '[' || c == ']').parse()
+                .map_err(|_| EngineError::InvalidReference(format!("Reference out of range: {}", text)))?;
+            origin as i64 + delta
+        },
+        Some(index) => index.as_str().parse::<i64>().map_or(-1, |index| index - 1),
+    };
+    
+    if (0..limit as i64).contains(&index) {
+        Ok(index as u32)
+    } else {
+        Err(EngineError::InvalidReference(format!("Reference out of range: {}", text)))
+    }
+}
+
+// Render an AST back to canonical formula text (with the leading '=')
+pub fn format_ast(node: &AstNode) -> String {
+    format!("={}", node)
+}
+
+// Ranges with more cells than this are kept whole rather than expanded cell by cell
+const MAX_EXPANDED_CELLS: u64 = 4096;
+
+// Whether a reference is tracked as a range: whole columns and rows, and large ranges
+fn is_kept_whole(reference: &Reference) -> bool {
+    let (rows, cols) = reference.bounds().dimensions();
+    reference.is_whole_line() || rows as u64 * cols as u64 > MAX_EXPANDED_CELLS
+}
+
+// Collect every cell referenced by an AST (ranges are expanded to their cells).
+// Whole columns and rows and large ranges are left out; see `extract_range_references`.
+pub fn extract_cell_references(node: &AstNode) -> Vec<CellReference> {
+    let mut references = Vec::new();
+    visit_references(node, &mut |reference| {
+        if is_kept_whole(reference) {
+            return;
+        }
+        let bounds = reference.bounds();
+        let (start, end) = (&bounds.start, &bounds.end);
+        for row in start.row.min(end.row)..=start.row.max(end.row) {
+            for col in start.col.min(end.col)..=start.col.max(end.col) {
+                references.push(CellReference {
+                    sheet: reference.sheet().map(str::to_string),
+                    address: CellAddress::new(row, col),
+                });
+            }
+        }
+    });
+    references
+}
+
+// Collect the whole-column, whole-row and large range references of an AST as
+// unexpanded ranges
+pub fn extract_range_references(node: &AstNode) -> Vec<(Option<String>, CellRange)> {
+    let mut references = Vec::new();
+    visit_references(node, &mut |reference| {
+        if is_kept_whole(reference) {
+            references.push((reference.sheet().map(str::to_string), reference.bounds()));
+        }
+    });
+    references
+}
+
+// Collect the structured references of an AST, which only the workbook can resolve
+pub fn extract_table_references(node: &AstNode) -> Vec<TableReference> {
+    let mut references = Vec::new();
+    visit_table_references(node, &mut references);
+    references
+}
+
+fn visit_table_references(node: &AstNode, references: &mut Vec<TableReference>) {
+    match node {
+        AstNode::Literal(_) | AstNode::Reference(_) => {},
+        AstNode::TableReference(reference) => references.push(reference.clone()),
+        AstNode::BinaryOp { left, right, .. } => {
+            visit_table_references(left, references);
+            visit_table_references(right, references);
+        },
+        AstNode::UnaryOp { operand, .. } => visit_table_references(operand, references),
+        AstNode::FunctionCall { args, .. } | AstNode::Union(args) => {
+            for arg in args {
+                visit_table_references(arg, references);
+            }
+        },
+    }
+}
+
+fn visit_references(node: &AstNode, visit: &mut impl FnMut(&Reference)) {
+    match node {
+        AstNode::Literal(_) | AstNode::TableReference(_) => {},
+        AstNode::Reference(reference) => visit(reference),
+        AstNode::BinaryOp { left, right, .. } => {
+            visit_references(left, visit);
+            visit_references(right, visit);
+        },
+        AstNode::UnaryOp { operand, .. } => visit_references(operand, visit),
+        AstNode::FunctionCall { args, .. } | AstNode::Union(args) => {
+            for arg in args {
+                visit_references(arg, visit);
+            }
+        },
+    }
+}
+
+// Replace every reference for which `rewrite` returns a node. Returns whether
+// anything was replaced.
+pub fn rewrite_references(node: &mut AstNode, rewrite: &mut impl FnMut(&Reference) -> Option<AstNode>) -> bool {
+    match node {
+        AstNode::Literal(_) | AstNode::TableReference(_) => false,
+        AstNode::Reference(reference) => match rewrite(reference) {
+            Some(replacement) => {
+                *node = replacement;
+                true
+            },
+            None => false,
+        },
+        AstNode::BinaryOp { left, right, .. } => {
+            let left_changed = rewrite_references(left, rewrite);
+            rewrite_references(right, rewrite) || left_changed
+        },
+        AstNode::UnaryOp { operand, .. } => rewrite_references(operand, rewrite),
+        AstNode::FunctionCall { args, .. } | AstNode::Union(args) => {
+            let mut changed = false;
+            for arg in args {
+                changed |= rewrite_references(arg, rewrite);
+            }
+            changed
+        },
+    }
+}
+
+// Convert a pest pair into an AST node
+fn build_ast(pair: Pair<Rule>) -> Result<AstNode, EngineError> {
+    match pair.as_rule() {
+        Rule::expression => {
+            let inner = pair.into_inner().next()
+                .ok_or_else(|| EngineError::ParseError("Empty expression".to_string()))?;
+            build_ast(inner)
+        },
+        Rule::comparison | Rule::addition | Rule::multiplication => build_binary_chain(pair, None),
+        Rule::concatenation => build_binary_chain(pair, Some(BinaryOperator::Concat)),
+        Rule::power => build_binary_chain(pair, Some(BinaryOperator::Power)),
+        Rule::intersection => build_binary_chain(pair, None),
+        Rule::union => Ok(AstNode::Union(pair.into_inner().map(build_ast).collect::<Result<_, _>>()?)),
+        Rule::unary => {
+            let mut signs = Vec::new();
+            let mut operand = None;
+            let mut percents = 0;
+            let mut intersect = false;
+            for inner in pair.into_inner() {
+                match inner.as_rule() {
+                    Rule::plus => signs.push(UnaryOperator::Positive),
+                    Rule::minus => signs.push(UnaryOperator::Negative),
+                    Rule::at => intersect = true,
+                    Rule::percent => percents += 1,
+                    _ => operand = Some(build_ast(inner)?),
+                }
+            }
+            
+            let mut node = operand
+                .ok_or_else(|| EngineError::ParseError("Missing operand".to_string()))?;
+            if intersect {
+                node = AstNode::UnaryOp { op: UnaryOperator::ImplicitIntersection, operand: Box::new(node) };
+            }
+            // Percents apply to the operand before any sign: -5% is -(5%)
+            for _ in 0..percents {
+                node = AstNode::UnaryOp { op: UnaryOperator::Percent, operand: Box::new(node) };
+            }
+            // Signs apply right to left: --A1 is -(-(A1))
+            for op in signs.into_iter().rev() {
+                node = AstNode::UnaryOp { op, operand: Box::new(node) };
+            }
+            Ok(node)
+        },
+        Rule::number => pair.as_str().parse::<f64>()
+            .map(|n| AstNode::Literal(Literal::Number(n)))
+            .map_err(|_| EngineError::ParseError(format!("Invalid number: {}", pair.as_str()))),
+        Rule::string => {
+            let raw = pair.as_str();
+            let text = raw[1..raw.len() - 1].replace("\"\"", "\"");
+            Ok(AstNode::Literal(Literal::Text(text)))
+        },
+        Rule::boolean => Ok(AstNode::Literal(Literal::Boolean(pair.as_str().eq_ignore_ascii_case("TRUE")))),
+        Rule::error => {
+            let error = match pair.as_str() {
+                "#NULL!" => CellError::Null,
+                "#DIV/0!" => CellError::DivisionByZero,
+                "#VALUE!" => CellError::InvalidValue,
+                "#REF!" => CellError::InvalidReference,
+                "#NAME?" => CellError::NameNotFound,
+                "#NUM!" => CellError::InvalidNumber,
+                "#N/A" => CellError::NotAvailable,
+                other => return Err(EngineError::ParseError(format!("Unknown error literal: {}", other))),
+            };
+            Ok(AstNode::Literal(Literal::Error(error)))
+        },
+        Rule::reference => build_reference(pair),
+        Rule::table_reference => {
+            let mut reference = TableReference { table: None, column: String::new(), this_row: false };
+            for inner in pair.into_inner() {
+                match inner.as_rule() {
+                    Rule::table_name => reference.table = Some(inner.as_str().to_string()),
+                    Rule::this_row => reference.this_row = true,
+                    Rule::table_column => reference.column = inner.as_str().to_string(),
+                    _ => {},
+                }
+            }
+            Ok(AstNode::TableReference(reference))
+        },
+        Rule::function_call => {
+            let mut inner = pair.into_inner();
+            let name = inner.next()
+                .ok_or_else(|| EngineError::ParseError("Missing function name".to_string()))?
+                .as_str()
+                .to_uppercase();
+            
+            let mut args = Vec::new();
+            if let Some(argument_list) = inner.next() {
+                for arg in argument_list.into_inner() {
+                    args.push(build_ast(arg)?);
+                }
+            }
+            Ok(AstNode::FunctionCall { name, args })
+        },
+        rule => Err(EngineError::ParseError(format!("Unexpected token: {:?}", rule))),
+    }
+}
+
+// Fold a left-associative chain of operands. Operator tokens between operands
+// select the operator; `default_op` is used where the grammar keeps it silent.
+fn build_binary_chain(pair: Pair<Rule>, default_op: Option<BinaryOperator>) -> Result<AstNode, EngineError> {
+    let mut left: Option<AstNode> = None;
+    let mut pending_op = None;
     
-    for c in reference.chars() {
-        if c.is_alphabetic() {
-            col_str.push(c.to_ascii_uppercase());
-        } else if c.is_numeric() {
-            row_str.push(c);
-        } else {
-            return Err(EngineError::ParseError(format!("Invalid character in cell reference: {}", c)));
+    for inner in pair.into_inner() {
+        if let Some(op) = binary_operator(inner.as_rule()) {
+            pending_op = Some(op);
+            continue;
         }
+        
+        let right = build_ast(inner)?;
+        left = Some(match left {
+            None => right,
+            Some(left) => {
+                let op = pending_op.take().or_else(|| default_op.clone())
+                    .ok_or_else(|| EngineError::ParseError("Missing operator".to_string()))?;
+                AstNode::BinaryOp { op, left: Box::new(left), right: Box::new(right) }
+            },
+        });
+    }
+    
+    left.ok_or_else(|| EngineError::ParseError("Missing operand".to_string()))
+}
+
+fn binary_operator(rule: Rule) -> Option<BinaryOperator> {
+    match rule {
+        Rule::plus => Some(BinaryOperator::Add),
+        Rule::minus => Some(BinaryOperator::Subtract),
+        Rule::multiply => Some(BinaryOperator::Multiply),
+        Rule::divide => Some(BinaryOperator::Divide),
+        Rule::equal => Some(BinaryOperator::Equal),
+        Rule::not_equal => Some(BinaryOperator::NotEqual),
+        Rule::less_than => Some(BinaryOperator::LessThan),
+        Rule::less_than_eq => Some(BinaryOperator::LessThanOrEqual),
+        Rule::greater_than => Some(BinaryOperator::GreaterThan),
+        Rule::greater_than_eq => Some(BinaryOperator::GreaterThanOrEqual),
+        Rule::intersect => Some(BinaryOperator::Intersect),
+        _ => None,
     }
+}
+
+// Build a cell or range reference, optionally qualified by a sheet name
+fn build_reference(pair: Pair<Rule>) -> Result<AstNode, EngineError> {
+    let mut sheet = None;
+    let mut start = None;
+    let mut end = None;
+    let mut columns = None;
+    let mut rows = None;
+    let mut spill = false;
     
-    if col_str.is_empty() || row_str.is_empty() {
-        return Err(EngineError::ParseError(format!("Invalid cell reference format: {}", reference)));
+    for inner in pair.into_inner() {
+        match inner.as_rule() {
+            Rule::sheet_prefix => sheet = Some(sheet_name(inner)?),
+            Rule::cell_address => start = Some(CellAddress::from_a1(inner.as_str())?),
+            Rule::range_suffix => {
+                let address = inner.into_inner().next()
+                    .ok_or_else(|| EngineError::ParseError("Missing range end".to_string()))?;
+                end = Some(CellAddress::from_a1(address.as_str())?);
+            },
+            Rule::spill => spill = true,
+            Rule::column_range => columns = Some(build_line_range(inner, column_index)?),
+            Rule::row_range => rows = Some(build_line_range(inner, row_index)?),
+            rule => return Err(EngineError::ParseError(format!("Unexpected token in reference: {:?}", rule))),
+        }
     }
     
-    // Convert column letters to 0-based index
-    let col: ColumnId = col_str.chars().fold(0, |acc, c| {
-        acc * 26 + (c as u32 - 'A' as u32 + 1)
-    }) - 1;
+    if let Some((start, end)) = columns {
+        return Ok(AstNode::Reference(Reference::ColumnRange { sheet, start, end }));
+    }
+    if let Some((start, end)) = rows {
+        return Ok(AstNode::Reference(Reference::RowRange { sheet, start, end }));
+    }
     
-    // Convert row to 0-based index
-    let row: RowId = match row_str.parse::<RowId>() {
-        Ok(r) => r - 1, // Excel rows are 1-based
-        Err(_) => return Err(EngineError::ParseError(format!("Invalid row in cell reference: {}", row_str))),
+    let start = start.ok_or_else(|| EngineError::ParseError("Missing cell address".to_string()))?;
+    if spill {
+        return Ok(AstNode::Reference(Reference::Spill { sheet, anchor: start }));
+    }
+    let reference = match (sheet, end) {
+        (None, None) => Reference::Cell(start),
+        (None, Some(end)) => Reference::Range { start, end },
+        (Some(sheet), None) => Reference::SheetCell { sheet, address: start },
+        (Some(sheet), Some(end)) => Reference::SheetRange { sheet, start, end },
     };
+    Ok(AstNode::Reference(reference))
+}
+
+// The sheet a sheet prefix names, without its quotes and with doubled quotes undone
+fn sheet_name(prefix: Pair<Rule>) -> Result<String, EngineError> {
+    let name = prefix.into_inner().next()
+        .ok_or_else(|| EngineError::ParseError("Missing sheet name".to_string()))?
+        .as_str();
+    unquote_sheet_name(name).ok_or_else(|| EngineError::ParseError(format!("Invalid sheet name: {}", name)))
+}
+
+// Parse both ends of a whole-column or whole-row range, lowest first (B:A is A:B)
+fn build_line_range(pair: Pair<Rule>, index: fn(&str) -> Option<u32>) -> Result<(u32, u32), EngineError> {
+    let text = pair.as_str();
+    let mut ends = pair.into_inner().map(|end| index(end.as_str()));
+    match (ends.next().flatten(), ends.next().flatten()) {
+        (Some(start), Some(end)) => Ok((start.min(end), start.max(end))),
+        _ => Err(EngineError::InvalidReference(format!("Reference out of range: {}", text))),
+    }
+}
+
+// Helper function to convert cell references like "A1" to (row, col) coordinates
+pub fn parse_cell_reference(reference: &str) -> Result<CellAddress, EngineError> {
+    CellAddress::from_a1(reference)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn formatted_formulas_parse_back_to_the_same_ast() {
+        let formulas = [
+            "=1+2*3",
+            "=(1+2)*3",
+            "=1-(2-3)",
+            "=2^3^2",
+            "=(2^3)^2",
+            "=-A1^2",
+            "=(-A1)^2",
+            "=A1&\" and \"&B1",
+            "=A1>=B1",
+            "=(A1=B1)=FALSE",
+            "=SUM(A1:B10,Sheet2!C3,'My Sheet'!D4)",
+            "=IF(AND(A1>0,B1<>\"\"),A1/B1,#DIV/0!)",
+            "=50%*A1",
+            "=ROUND(PI()*2,3)",
+            "=\"say \"\"hi\"\"\"",
+        ];
+        for formula in formulas {
+            let ast = parse_formula(formula).unwrap();
+            let text = format_ast(&ast);
+            assert_eq!(parse_formula(&text).unwrap(), ast, "{} became {}", formula, text);
+            // The canonical text is a fixed point
+            assert_eq!(format_ast(&parse_formula(&text).unwrap()), text);
+        }
+    }
     
-    Ok(CellAddress { row, col })
+    #[test]
+    fn formatting_drops_redundant_parentheses() {
+        assert_eq!(format_ast(&parse_formula("=((1+2))+(3*4)").unwrap()), "=1+2+3*4");
+        assert_eq!(format_ast(&parse_formula("=1-(2+3)").unwrap()), "=1-(2+3)");
+    }
+    
+    #[test]
+    fn validation_reports_bad_calls_with_their_position() {
+        let workbook = crate::model::Workbook::new();
+        let functions = workbook.functions();
+        let invalid = |formula: &str| match validate_formula(formula, functions) {
+            Err(EngineError::InvalidFormula { position, message }) => (position, message),
+            other => panic!("{} validated to {:?}", formula, other),
+        };
+        
+        assert_eq!(invalid("=SUM()"), (2, "SUM takes at least 1 argument, got 0".to_string()));
+        assert_eq!(invalid("=1+ROUND(A1)"), (4, "ROUND takes 2 arguments, got 1".to_string()));
+        assert_eq!(invalid("=SUM(A1,SUMM(B1))"), (9, "Unknown function SUMM".to_string()));
+        assert!(matches!(validate_formula("=SU M(A1)", functions), Err(EngineError::ParseError(_))));
+        assert_eq!(validate_formula("=sum(A1,2)", functions).unwrap(), parse_formula("=sum(A1,2)").unwrap());
+    }
 }
@@ -1,23 +1,151 @@
 // ssengine-io/src/csv.rs
 // CSV file reading and writing
 
-use ssengine_core::{Workbook, Sheet, Cell, CellValue, EngineError};
-use csv::{Reader, Writer, ReaderBuilder, WriterBuilder};
-use std::fs::File;
+use ssengine_core::{Workbook, CellValue, CellStyle, EngineError, RowId, ColumnId};
+use ssengine_core::date::{DateSystem, serial_to_date};
+use csv::{ReaderBuilder, WriterBuilder, Trim};
 use std::path::Path;
 
+/// CSV dialect used for reading and writing
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvOptions {
+    /// Field separator, e.g. `b','`, `b'\t'` or `b';'`
+    pub delimiter: u8,
+    /// Character used to quote fields containing the delimiter
+    pub quote: u8,
+    /// Whether the first record is a header row. Header fields are kept as bold
+    /// text labels instead of being parsed as numbers, booleans or formulas, and
+    /// the header row is frozen.
+    pub has_headers: bool,
+    /// Strip leading and trailing whitespace from fields when reading
+    pub trim: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: b',',
+            quote: b'"',
+            has_headers: false,
+            trim: false,
+        }
+    }
+}
+
 /// Read a CSV file into a workbook with a single sheet
+///
+/// Every record becomes a row. Fields that parse as numbers or booleans are
+/// stored as such, fields starting with '=' as formulas, and the rest as text.
 pub fn read_csv<P: AsRef<Path>>(path: P, sheet_name: Option<String>) -> Result<Workbook, EngineError> {
-    // Placeholder implementation
-    // This would use the csv crate to read the CSV file
-    Err(EngineError::NotImplemented("CSV reading".into()))
+    read_csv_with(path, sheet_name, &CsvOptions::default())
+}
+
+/// Read a CSV file into a workbook with a single sheet, using the given dialect
+pub fn read_csv_with<P: AsRef<Path>>(path: P, sheet_name: Option<String>, options: &CsvOptions) -> Result<Workbook, EngineError> {
+    // Headers are handled here rather than by the reader so the header row is kept
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(options.delimiter)
+        .quote(options.quote)
+        .trim(if options.trim { Trim::All } else { Trim::None })
+        .from_path(path)
+        .map_err(|e| EngineError::IoError(e.to_string()))?;
+    
+    let mut workbook = Workbook::new();
+    let sheet_name = workbook.add_sheet(sheet_name.unwrap_or_else(|| "Sheet1".to_string()))?;
+    let sheet = workbook.get_sheet_mut(&sheet_name).unwrap();
+    
+    if options.has_headers {
+        sheet.set_freeze_panes(1, 0);
+    }
+    
+    for (row, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| EngineError::IoError(e.to_string()))?;
+        for (col, field) in record.iter().enumerate() {
+            if field.is_empty() {
+                continue;
+            }
+            if options.has_headers && row == 0 {
+                sheet.set_cell(row as RowId, col as ColumnId, CellValue::Text(field.to_string()))?;
+                sheet.set_cell_style(row as RowId, col as ColumnId, CellStyle { bold: true, ..CellStyle::default() });
+            } else {
+                sheet.set_cell(row as RowId, col as ColumnId, parse_field(field))?;
+            }
+        }
+    }
+    // The row count isn't known up front; drop the slack left by the cell map's growth
+    sheet.shrink_to_fit();
+    
+    Ok(workbook)
+}
+
+// Infer the cell value for a single CSV field
+fn parse_field(field: &str) -> CellValue {
+    // Rust also accepts "inf"/"NaN", which are text in a spreadsheet
+    if let Some(n) = field.trim().parse::<f64>().ok().filter(|n| n.is_finite()) {
+        return CellValue::Number(n);
+    }
+    
+    match field.trim().to_uppercase().as_str() {
+        "TRUE" => CellValue::Boolean(true),
+        "FALSE" => CellValue::Boolean(false),
+        _ => CellValue::from(field),
+    }
 }
 
 /// Write a single sheet from a workbook to a CSV file
+///
+/// The used range of the sheet is written row by row. Formula cells are written
+/// as their calculated values, and hyperlinked cells as their URLs.
 pub fn write_csv<P: AsRef<Path>>(workbook: &Workbook, sheet_name: &str, path: P) -> Result<(), EngineError> {
-    // Placeholder implementation
-    // This would use the csv crate to write the CSV file
-    Err(EngineError::NotImplemented("CSV writing".into()))
+    write_csv_with(workbook, sheet_name, path, &CsvOptions::default())
+}
+
+/// Write a single sheet from a workbook to a CSV file, using the given dialect
+pub fn write_csv_with<P: AsRef<Path>>(workbook: &Workbook, sheet_name: &str, path: P, options: &CsvOptions) -> Result<(), EngineError> {
+    let sheet = workbook.get_sheet(sheet_name)
+        .ok_or_else(|| EngineError::Internal(format!("Sheet '{}' does not exist", sheet_name)))?;
+    
+    let mut writer = WriterBuilder::new()
+        .delimiter(options.delimiter)
+        .quote(options.quote)
+        .from_path(path)
+        .map_err(|e| EngineError::IoError(e.to_string()))?;
+    
+    if let Some((max_row, max_col)) = sheet.used_bounds() {
+        for row in 0..=max_row {
+            let record: Vec<String> = (0..=max_col)
+                .map(|col| sheet.get_cell(row, col)
+                    .map(|cell| match &cell.hyperlink {
+                        Some(url) => url.clone(),
+                        None => format_field(cell.effective_value(), workbook.date_system(), options.trim),
+                    })
+                    .unwrap_or_default())
+                .collect();
+            writer.write_record(&record).map_err(|e| EngineError::IoError(e.to_string()))?;
+        }
+    }
+    
+    writer.flush().map_err(|e| EngineError::IoError(e.to_string()))
+}
+
+// Text written to a CSV field for a cell's value
+fn format_field(value: &CellValue, date_system: DateSystem, trim: bool) -> String {
+    let text = match value {
+        CellValue::Date(serial) => match serial_to_date(*serial, date_system) {
+            Some(date) => date.format("%Y-%m-%d").to_string(),
+            None => value.to_string(),
+        },
+        // A single field can only hold the array's top-left value
+        CellValue::Array(rows) => rows.first()
+            .and_then(|row| row.first())
+            .map(|first| format_field(first, date_system, trim))
+            .unwrap_or_default(),
+        other => other.to_string(),
+    };
+    
+    if trim { text.trim().to_string() } else { text }
 }
 
 // Helper function to detect CSV delimiter from content
@@ -25,3 +153,96 @@ fn _detect_delimiter(_content: &str) -> char {
     // Default to comma, but could detect tab, semicolon, etc.
     ','
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // A path in the temp directory that is removed when dropped
+    struct TempFile(std::path::PathBuf);
+    
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            TempFile(std::env::temp_dir().join(format!("ssengine-{}-{}", std::process::id(), name)))
+        }
+    }
+    
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+    
+    #[test]
+    fn tab_separated_files_with_headers() {
+        let file = TempFile::new("dialect.tsv");
+        std::fs::write(&file.0, "name\tamount\n  apples \t1.5\npears\t2\n").unwrap();
+        let options = CsvOptions { delimiter: b'\t', has_headers: true, trim: true, ..CsvOptions::default() };
+        let workbook = read_csv_with(&file.0, None, &options).unwrap();
+        let sheet = workbook.get_sheet("Sheet1").unwrap();
+        
+        // The header stays text, is bold and frozen; the data is parsed and trimmed
+        assert_eq!(sheet.get_cell(0, 1).unwrap().value, CellValue::Text("amount".to_string()));
+        assert!(sheet.get_cell_style(0, 0).is_some_and(|style| style.bold));
+        assert_eq!(sheet.freeze_panes().map(|address| (address.row, address.col)), Some((1, 0)));
+        assert_eq!(sheet.get_cell(1, 0).unwrap().value, CellValue::Text("apples".to_string()));
+        assert_eq!(sheet.get_cell(1, 1).unwrap().value, CellValue::Number(1.5));
+        assert_eq!(sheet.get_cell(2, 1).unwrap().value, CellValue::Number(2.0));
+        
+        // Without headers the first row is ordinary data
+        let workbook = read_csv_with(&file.0, None, &CsvOptions { delimiter: b'\t', ..CsvOptions::default() }).unwrap();
+        let sheet = workbook.get_sheet("Sheet1").unwrap();
+        assert!(sheet.freeze_panes().is_none());
+        assert!(sheet.get_cell_style(0, 0).is_none());
+        assert_eq!(sheet.get_cell(1, 0).unwrap().value, CellValue::Text("  apples ".to_string()));
+    }
+    
+    #[test]
+    fn semicolon_files_round_trip() {
+        let file = TempFile::new("dialect-semicolon.csv");
+        let options = CsvOptions { delimiter: b';', quote: b'\'', ..CsvOptions::default() };
+        
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Sheet1".to_string()).unwrap();
+        workbook.set_cell_value("Sheet1", 0, 0, "a;b").unwrap();
+        workbook.set_cell_value("Sheet1", 0, 1, 3.0).unwrap();
+        workbook.set_cell_value("Sheet1", 1, 1, true).unwrap();
+        write_csv_with(&workbook, "Sheet1", &file.0, &options).unwrap();
+        
+        // A field holding the delimiter is quoted with the configured quote
+        assert_eq!(std::fs::read_to_string(&file.0).unwrap(), "'a;b';3\n;TRUE\n");
+        
+        let workbook = read_csv_with(&file.0, Some("Data".to_string()), &options).unwrap();
+        let sheet = workbook.get_sheet("Data").unwrap();
+        assert_eq!(sheet.get_cell(0, 0).unwrap().value, CellValue::Text("a;b".to_string()));
+        assert_eq!(sheet.get_cell(0, 1).unwrap().value, CellValue::Number(3.0));
+        assert!(sheet.get_cell(1, 0).is_none());
+        assert_eq!(sheet.get_cell(1, 1).unwrap().value, CellValue::Boolean(true));
+    }
+    
+    #[test]
+    fn hyperlinked_cells_are_written_as_their_urls() {
+        let file = TempFile::new("links.csv");
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Sheet1".to_string()).unwrap();
+        let sheet = workbook.get_sheet_mut("Sheet1").unwrap();
+        sheet.set_cell_hyperlink(0, 0, "https://example.com/a", Some("Source")).unwrap();
+        sheet.set_cell(0, 1, CellValue::Number(1.0)).unwrap();
+        write_csv(&workbook, "Sheet1", &file.0).unwrap();
+        assert_eq!(std::fs::read_to_string(&file.0).unwrap(), "https://example.com/a,1\n");
+    }
+    
+    #[test]
+    fn large_files_import_every_cell() {
+        let file = TempFile::new("bulk.csv");
+        let rows: String = (0..5_000).map(|row| format!("{},row {},{}\n", row, row, row as f64 / 4.0)).collect();
+        std::fs::write(&file.0, rows).unwrap();
+        
+        let workbook = read_csv(&file.0, None).unwrap();
+        let sheet = workbook.get_sheet("Sheet1").unwrap();
+        assert_eq!(sheet.get_all_cells().count(), 15_000);
+        assert_eq!(sheet.get_cell(0, 1).unwrap().value, CellValue::Text("row 0".to_string()));
+        assert_eq!(sheet.get_cell(4_999, 0).unwrap().value, CellValue::Number(4_999.0));
+        assert_eq!(sheet.get_cell(4_999, 2).unwrap().value, CellValue::Number(1_249.75));
+    }
+}
@@ -4,8 +4,8 @@ pub mod xlsx;
 pub mod csv;
 
 // Re-export key functionality
-pub use xlsx::{read_xlsx, write_xlsx};
-pub use csv::{read_csv, write_csv};
+pub use xlsx::{read_xlsx, read_xlsx_bytes, read_xlsx_lazy, write_xlsx, write_xlsx_bytes, LazyXlsx, PartialWorkbook};
+pub use csv::{read_csv, read_csv_with, write_csv, write_csv_with, CsvOptions};
 
 #[cfg(test)]
 mod tests {
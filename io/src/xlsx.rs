@@ -1,29 +1,461 @@
 // ssengine-io/src/xlsx.rs
 // XLSX file reading and writing
 
-use ssengine_core::{Workbook, Sheet, Cell, CellValue, EngineError, RowId, ColumnId};
-use rust_xlsxwriter::{Workbook as XlsxWorkbook, Worksheet, Format, FormatBorder};
-use calamine::{Reader, RangeDeserializerBuilder, Xlsx, open_workbook};
+use ssengine_core::{Workbook, WorkbookMetadata, Sheet, Cell, CellAddress, CellValue, CellStyle, HorizontalAlignment, VerticalAlignment, BorderStyle, EngineError, RowId, ColumnId};
+use ssengine_core::error::CellError;
+use ssengine_core::date::DateSystem;
+use rust_xlsxwriter::{Workbook as XlsxWorkbook, Worksheet, ColNum, Color, DocProperties, ExcelDateTime, Format, FormatAlign, FormatBorder, FormatPattern, Formula, Note, Url, XlsxError};
+use calamine::{Reader, Xlsx, Data, CellErrorType, open_workbook};
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use zip::ZipArchive;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek};
 use std::path::Path;
 
 /// Read a workbook from an XLSX file
-pub fn read_xlsx<P: AsRef<Path>>(_path: P) -> Result<Workbook, EngineError> {
-    // Placeholder implementation - will be implemented in a later phase
-    // This would use the calamine crate to read the XLSX
-    Err(EngineError::NotImplemented("XLSX reading".into()))
+///
+/// Formula cells keep their formula text with the cached result from the file
+/// as the calculated value; they are not recalculated on load.
+pub fn read_xlsx<P: AsRef<Path>>(path: P) -> Result<Workbook, EngineError> {
+    let path = path.as_ref();
+    let excel: Xlsx<BufReader<File>> = open_workbook(path)
+        .map_err(|e: calamine::XlsxError| EngineError::IoError(e.to_string()))?;
+    let file = File::open(path).map_err(|e| EngineError::IoError(e.to_string()))?;
+    convert_workbook(excel, BufReader::new(file))
+}
+
+/// Read a workbook from the bytes of an XLSX file
+pub fn read_xlsx_bytes(bytes: &[u8]) -> Result<Workbook, EngineError> {
+    let excel = Xlsx::new(Cursor::new(bytes))
+        .map_err(|e| EngineError::IoError(e.to_string()))?;
+    convert_workbook(excel, Cursor::new(bytes))
+}
+
+/// Open an XLSX file for reading one sheet at a time
+///
+/// Only the sheet names, document properties and date system are read up front;
+/// see `LazyXlsx`.
+pub fn read_xlsx_lazy<P: AsRef<Path>>(path: P) -> Result<LazyXlsx, EngineError> {
+    let path = path.as_ref();
+    let excel: Xlsx<BufReader<File>> = open_workbook(path)
+        .map_err(|e: calamine::XlsxError| EngineError::IoError(e.to_string()))?;
+    let file = File::open(path).map_err(|e| EngineError::IoError(e.to_string()))?;
+    let mut archive = ZipArchive::new(BufReader::new(file)).map_err(|e| EngineError::IoError(e.to_string()))?;
+    
+    let workbook = empty_workbook(&mut archive)?;
+    let sheet_parts = sheet_parts(&mut archive);
+    Ok(LazyXlsx { excel, archive, sheet_parts, workbook })
+}
+
+/// An XLSX file whose sheets are read on first access
+///
+/// Reading a whole workbook materializes every cell of every sheet. For large files
+/// where only some sheets are needed, this reads a sheet's cells, notes and
+/// hyperlinks the first time it is asked for, so converting one sheet of a
+/// 100-sheet file doesn't load the other 99.
+pub struct LazyXlsx {
+    excel: Xlsx<BufReader<File>>,
+    archive: ZipArchive<BufReader<File>>,
+    sheet_parts: Vec<(String, String)>, // Sheet name and worksheet part, for annotations
+    workbook: Workbook, // Holds the sheets loaded so far
+}
+
+impl LazyXlsx {
+    /// The names of all sheets in the file, in file order
+    pub fn sheet_names(&self) -> Vec<String> {
+        self.excel.sheet_names()
+    }
+    
+    /// Whether a sheet has been read from the file yet
+    pub fn is_loaded(&self, name: &str) -> bool {
+        self.workbook.get_sheet(name).is_some()
+    }
+    
+    /// A sheet, read from the file on first access
+    pub fn sheet(&mut self, name: &str) -> Result<&Sheet, EngineError> {
+        if !self.is_loaded(name) {
+            if !self.excel.sheet_names().iter().any(|sheet_name| sheet_name == name) {
+                return Err(EngineError::Internal(format!("Sheet '{}' does not exist", name)));
+            }
+            
+            self.workbook.add_sheet(name.to_string())?;
+            let sheet = self.workbook.get_sheet_mut(name).unwrap();
+            convert_sheet(&mut self.excel, name, sheet)?;
+            if let Some((_, sheet_part)) = self.sheet_parts.iter().find(|(sheet_name, _)| sheet_name == name) {
+                read_annotations(sheet, &mut self.archive, sheet_part)?;
+            }
+        }
+        Ok(self.workbook.get_sheet(name).unwrap())
+    }
+    
+    /// The whole workbook, reading the sheets not loaded yet in file order
+    pub fn into_workbook(mut self) -> Result<Workbook, EngineError> {
+        let names = self.sheet_names();
+        for name in &names {
+            self.sheet(name)?;
+        }
+        if let Some(first) = names.first() {
+            self.workbook.set_active_sheet(first)?;
+        }
+        Ok(self.workbook)
+    }
+    
+    /// A workbook of only the sheets loaded so far, without reading the others
+    pub fn into_partial(mut self) -> PartialWorkbook {
+        let (loaded, missing): (Vec<_>, Vec<_>) = self.sheet_names().into_iter()
+            .partition(|name| self.is_loaded(name));
+        if let Some(first) = loaded.first() {
+            // Can't fail, the sheet is in the workbook
+            let _ = self.workbook.set_active_sheet(first);
+        }
+        PartialWorkbook { workbook: self.workbook, loaded, missing }
+    }
+}
+
+/// Some of the sheets of an XLSX file, from `LazyXlsx::into_partial`
+///
+/// Formula cells keep the cached results from the file, but recalculating a formula
+/// that refers to a missing sheet gives #REF!.
+pub struct PartialWorkbook {
+    pub workbook: Workbook,
+    /// The sheets that were read, in file order; the first one is active
+    pub loaded: Vec<String>,
+    /// The sheets of the file that were not read, in file order
+    pub missing: Vec<String>,
+}
+
+// Convert every sheet of an opened XLSX file into an engine workbook. `reader` reads
+// the same file as a zip archive, for the parts calamine doesn't read.
+fn convert_workbook<RS: Read + Seek, R: Read + Seek>(mut excel: Xlsx<RS>, reader: R) -> Result<Workbook, EngineError> {
+    let mut archive = ZipArchive::new(reader).map_err(|e| EngineError::IoError(e.to_string()))?;
+    let mut workbook = empty_workbook(&mut archive)?;
+    
+    for sheet_name in excel.sheet_names() {
+        workbook.add_sheet(sheet_name.clone())?;
+        let sheet = workbook.get_sheet_mut(&sheet_name).unwrap();
+        convert_sheet(&mut excel, &sheet_name, sheet)?;
+    }
+    
+    read_sheet_annotations(&mut workbook, &mut archive)?;
+    Ok(workbook)
+}
+
+// A workbook with the file's document properties and date system, before any sheet
+// is read. The date system is set first so it applies to the cells as they load.
+fn empty_workbook<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<Workbook, EngineError> {
+    let mut workbook = Workbook::new();
+    workbook.set_metadata(read_metadata(archive));
+    workbook.set_date_system(read_date_system(archive))?;
+    Ok(workbook)
+}
+
+// Read the cells of one sheet
+fn convert_sheet<RS: Read + Seek>(excel: &mut Xlsx<RS>, sheet_name: &str, sheet: &mut Sheet) -> Result<(), EngineError> {
+    // Literal values (and cached results of formula cells)
+    let values = excel.worksheet_range(sheet_name)
+        .map_err(|e| EngineError::IoError(e.to_string()))?;
+    let (start_row, start_col) = values.start().unwrap_or((0, 0));
+    sheet.reserve(values.used_cells().count());
+    for (row, col, data) in values.used_cells() {
+        let (row, col) = (start_row + row as RowId, start_col + col as ColumnId);
+        sheet.set_cell(row, col, convert_data(data))?;
+    }
+    
+    // Formulas, with the value read above kept as the calculated value
+    let formulas = excel.worksheet_formula(sheet_name)
+        .map_err(|e| EngineError::IoError(e.to_string()))?;
+    let (start_row, start_col) = formulas.start().unwrap_or((0, 0));
+    for (row, col, formula) in formulas.used_cells() {
+        let (row, col) = (start_row + row as RowId, start_col + col as ColumnId);
+        let cached = sheet.get_cell(row, col).map(|cell| cell.value.clone());
+        
+        sheet.set_cell(row, col, CellValue::Formula(format!("={}", formula)))?;
+        if let Some(cell) = sheet.get_cell_mut(row, col) {
+            cell.calculated_value = cached;
+        }
+    }
+    
+    Ok(())
+}
+
+// The date system from the workbook properties: <workbookPr date1904="1"/> for 1904.
+// Serials are read as stored, so they keep meaning the same dates.
+fn read_date_system<R: Read + Seek>(archive: &mut ZipArchive<R>) -> DateSystem {
+    let date1904 = read_part(archive, "xl/workbook.xml")
+        .and_then(|workbook_xml| workbook_xml.split("<workbookPr ").nth(1).and_then(|tag| attribute(tag, "date1904")));
+    match date1904.as_deref() {
+        Some("1") | Some("true") => DateSystem::Excel1904,
+        _ => DateSystem::Excel1900,
+    }
+}
+
+// Document properties from the docProps parts
+fn read_metadata<R: Read + Seek>(archive: &mut ZipArchive<R>) -> WorkbookMetadata {
+    let mut metadata = WorkbookMetadata::default();
+    
+    if let Some(core) = read_part(archive, "docProps/core.xml") {
+        metadata.title = element_text(&core, "dc:title");
+        metadata.subject = element_text(&core, "dc:subject");
+        metadata.author = element_text(&core, "dc:creator");
+        metadata.keywords = element_text(&core, "cp:keywords");
+        metadata.comments = element_text(&core, "dc:description");
+        metadata.created = element_text(&core, "dcterms:created").and_then(|text| parse_w3c_datetime(&text));
+        metadata.modified = element_text(&core, "dcterms:modified").and_then(|text| parse_w3c_datetime(&text));
+    }
+    if let Some(app) = read_part(archive, "docProps/app.xml") {
+        metadata.company = element_text(&app, "Company");
+    }
+    
+    // <property name="..."><vt:lpwstr>value</vt:lpwstr></property>, whatever the value type
+    if let Some(custom) = read_part(archive, "docProps/custom.xml") {
+        for property in custom.split("<property ").skip(1) {
+            let Some((name, _)) = property.split_once("name=\"").and_then(|(_, rest)| rest.split_once('"')) else { continue };
+            let value = property.split_once('>')
+                .and_then(|(_, rest)| rest.split_once('>'))
+                .and_then(|(_, rest)| rest.split_once('<'))
+                .map(|(value, _)| xml_unescape(value))
+                .unwrap_or_default();
+            metadata.custom.insert(xml_unescape(name), value);
+        }
+    }
+    
+    metadata
+}
+
+// Cell notes and hyperlinks of each worksheet
+fn read_sheet_annotations<R: Read + Seek>(workbook: &mut Workbook, archive: &mut ZipArchive<R>) -> Result<(), EngineError> {
+    for (name, sheet_part) in sheet_parts(archive) {
+        if let Some(sheet) = workbook.get_sheet_mut(&name) {
+            read_annotations(sheet, archive, &sheet_part)?;
+        }
+    }
+    Ok(())
+}
+
+// The name and worksheet part of each sheet. The workbook part lists the sheets with
+// relationship ids, and the workbook's relationships lead to the worksheet parts.
+fn sheet_parts<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Vec<(String, String)> {
+    let (Some(workbook_xml), Some(workbook_rels)) = (read_part(archive, "xl/workbook.xml"), read_part(archive, "xl/_rels/workbook.xml.rels")) else {
+        return Vec::new();
+    };
+    
+    workbook_xml.split("<sheet ").skip(1)
+        .filter_map(|sheet_tag| {
+            let (name, id) = (attribute(sheet_tag, "name")?, attribute(sheet_tag, "r:id")?);
+            let sheet_part = relationship_target(&workbook_rels, "xl", |tag| attribute(tag, "Id").as_deref() == Some(id.as_str()))?;
+            Some((name, sheet_part))
+        })
+        .collect()
+}
+
+// Cell notes and hyperlinks of one worksheet, found through the worksheet's own
+// relationships to its comments part and link targets
+fn read_annotations<R: Read + Seek>(sheet: &mut Sheet, archive: &mut ZipArchive<R>, sheet_part: &str) -> Result<(), EngineError> {
+    // xl/worksheets/sheet1.xml has its relationships in xl/worksheets/_rels/sheet1.xml.rels
+    let Some((sheet_dir, sheet_file)) = sheet_part.rsplit_once('/') else { return Ok(()) };
+    let Some(sheet_rels) = read_part(archive, &format!("{}/_rels/{}.rels", sheet_dir, sheet_file)) else { return Ok(()) };
+    let is_comments = |tag: &str| attribute(tag, "Type").is_some_and(|kind| kind.ends_with("/comments"));
+    let comments = relationship_target(&sheet_rels, sheet_dir, is_comments)
+        .and_then(|comments_part| read_part(archive, &comments_part));
+    let worksheet = read_part(archive, sheet_part);
+    
+    for comment in comments.as_deref().unwrap_or_default().split("<comment ").skip(1) {
+        let Some(address) = attribute(comment, "ref").and_then(|a1| CellAddress::from_a1(&a1).ok()) else { continue };
+        // The note's text may be split into runs, each in its own <t> element
+        let text: String = comment.split("<t").skip(1)
+            .filter(|run| run.starts_with('>') || run.starts_with(' '))
+            .filter_map(|run| run.split_once('>').and_then(|(_, rest)| rest.split_once("</t>")))
+            .map(|(text, _)| xml_unescape(text))
+            .collect();
+        sheet.set_cell_note(address.row, address.col, text);
+    }
+    
+    // External links only: <hyperlink ref="A1" r:id="rId2"/> with the URL as the
+    // relationship's target. Links within the workbook are skipped.
+    for link in worksheet.as_deref().unwrap_or_default().split("<hyperlink ").skip(1) {
+        let (Some(range), Some(id)) = (attribute(link, "ref"), attribute(link, "r:id")) else { continue };
+        let Some(address) = range.split(':').next().and_then(|a1| CellAddress::from_a1(a1).ok()) else { continue };
+        let url = sheet_rels.split("<Relationship ").skip(1)
+            .find(|tag| attribute(tag, "Id").as_deref() == Some(id.as_str()))
+            .and_then(|tag| attribute(tag, "Target"));
+        if let Some(url) = url {
+            sheet.set_cell_hyperlink(address.row, address.col, url, None)?;
+        }
+    }
+    
+    Ok(())
+}
+
+// The value of an attribute in the text following a tag name
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let tag = &tag[..tag.find('>')?];
+    let pattern = format!("{}=\"", name);
+    // Only whole attribute names match, so Id doesn't match sheetId
+    let start = tag.match_indices(&pattern)
+        .map(|(index, _)| index)
+        .find(|&index| index == 0 || tag[..index].ends_with(char::is_whitespace))? + pattern.len();
+    let value = &tag[start..];
+    Some(xml_unescape(&value[..value.find('"')?]))
+}
+
+// The part that the first matching relationship of a .rels part points to. Targets
+// are relative to `base_dir` unless they start with '/'.
+fn relationship_target(rels: &str, base_dir: &str, matches: impl Fn(&str) -> bool) -> Option<String> {
+    let tag = rels.split("<Relationship ").skip(1).find(|tag| matches(tag))?;
+    let target = attribute(tag, "Target")?;
+    if let Some(absolute) = target.strip_prefix('/') {
+        return Some(absolute.to_string());
+    }
+    
+    let mut parts: Vec<&str> = base_dir.split('/').filter(|part| !part.is_empty()).collect();
+    for part in target.split('/') {
+        match part {
+            ".." => { parts.pop(); },
+            "." | "" => {},
+            part => parts.push(part),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+// The text of a part of the zip archive, if it exists
+fn read_part<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> Option<String> {
+    let mut text = String::new();
+    archive.by_name(name).ok()?.read_to_string(&mut text).ok()?;
+    Some(text)
+}
+
+// The text content of the first `tag` element of an XML document
+fn element_text(xml: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}", tag);
+    let mut rest = xml;
+    let element = loop {
+        let after = &rest[rest.find(&open)? + open.len()..];
+        if after.starts_with('>') || after.starts_with(' ') {
+            break after;
+        }
+        rest = after;
+    };
+    
+    let tag_end = element.find('>')?;
+    if element[..tag_end].ends_with('/') {
+        return None; // Empty element
+    }
+    let content = &element[tag_end + 1..];
+    let end = content.find(&format!("</{}>", tag))?;
+    Some(xml_unescape(&content[..end]))
+}
+
+fn xml_unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+// Timestamps in docProps are W3C date-times in UTC, e.g. 2024-01-31T09:30:00Z
+fn parse_w3c_datetime(text: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(text.trim().trim_end_matches('Z'), "%Y-%m-%dT%H:%M:%S").ok()
+}
+
+// Convert a calamine cell value into an engine cell value
+fn convert_data(data: &Data) -> CellValue {
+    match data {
+        Data::Int(i) => CellValue::Number(*i as f64),
+        Data::Float(f) => CellValue::Number(*f),
+        Data::String(s) => CellValue::Text(s.clone()),
+        Data::Bool(b) => CellValue::Boolean(*b),
+        Data::DateTime(dt) if dt.is_datetime() => CellValue::Date(dt.as_f64()),
+        Data::DateTime(dt) => CellValue::Number(dt.as_f64()), // Durations stay plain numbers
+        Data::DateTimeIso(s) | Data::DurationIso(s) => CellValue::Text(s.clone()),
+        Data::Error(e) => CellValue::Error(match e {
+            CellErrorType::Div0 => CellError::DivisionByZero,
+            CellErrorType::NA => CellError::NotAvailable,
+            CellErrorType::Name => CellError::NameNotFound,
+            CellErrorType::Num => CellError::InvalidNumber,
+            CellErrorType::Ref => CellError::InvalidReference,
+            CellErrorType::Null => CellError::Null,
+            CellErrorType::Value | CellErrorType::GettingData => CellError::InvalidValue,
+        }),
+        Data::Empty => CellValue::Blank,
+    }
 }
 
 /// Write a workbook to an XLSX file
 pub fn write_xlsx<P: AsRef<Path>>(workbook: &Workbook, path: P) -> Result<(), EngineError> {
+    let bytes = write_xlsx_bytes(workbook)?;
+    std::fs::write(path, bytes).map_err(|e| EngineError::IoError(e.to_string()))
+}
+
+/// Write a workbook as the bytes of an XLSX file
+pub fn write_xlsx_bytes(workbook: &Workbook) -> Result<Vec<u8>, EngineError> {
+    let bytes = build_xlsx(workbook)?.save_to_buffer().map_err(xlsx_error)?;
+    match workbook.date_system() {
+        DateSystem::Excel1900 => Ok(bytes),
+        DateSystem::Excel1904 => set_date1904(&bytes),
+    }
+}
+
+// rust_xlsxwriter always writes the 1900 date system, so the 1904 flag is added to
+// the workbook properties of the finished package. Every other part is copied as is.
+fn set_date1904(bytes: &[u8]) -> Result<Vec<u8>, EngineError> {
+    let io_error = |e: zip::result::ZipError| EngineError::IoError(e.to_string());
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).map_err(io_error)?;
+    let workbook_xml = read_part(&mut archive, "xl/workbook.xml")
+        .ok_or_else(|| EngineError::Internal("Written package has no workbook part".into()))?;
+    let workbook_xml = workbook_xml.replacen("<workbookPr", "<workbookPr date1904=\"1\"", 1);
+    
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for index in 0..archive.len() {
+        let part = archive.by_index_raw(index).map_err(io_error)?;
+        if part.name() == "xl/workbook.xml" {
+            let options = zip::write::FileOptions::default().compression_method(part.compression());
+            writer.start_file(part.name(), options).map_err(io_error)?;
+            std::io::Write::write_all(&mut writer, workbook_xml.as_bytes()).map_err(|e| EngineError::IoError(e.to_string()))?;
+        } else {
+            writer.raw_copy_file(part).map_err(io_error)?;
+        }
+    }
+    Ok(writer.finish().map_err(io_error)?.into_inner())
+}
+
+// Convert an engine workbook into a rust_xlsxwriter workbook ready to save
+fn build_xlsx(workbook: &Workbook) -> Result<XlsxWorkbook, EngineError> {
     // Create a new XLSX workbook
     let mut xlsx_wb = XlsxWorkbook::new();
+    xlsx_wb.set_properties(&doc_properties(workbook.metadata())?);
     
     // Convert each sheet in our workbook
     for sheet_name in workbook.sheet_names() {
         let sheet = workbook.get_sheet(sheet_name).unwrap();
         
         // Create a new worksheet in the XLSX workbook
-        let mut xlsx_sheet = xlsx_wb.add_worksheet().set_name(sheet_name)?;
+        let xlsx_sheet = xlsx_wb.add_worksheet().set_name(sheet_name).map_err(xlsx_error)?;
+        
+        // Layout: frozen panes, explicit column widths and row heights, hidden rows
+        if let Some(freeze) = sheet.freeze_panes() {
+            xlsx_sheet.set_freeze_panes(freeze.row, xlsx_col(freeze.col)).map_err(xlsx_error)?;
+        }
+        for (&col, &width) in sheet.column_widths() {
+            xlsx_sheet.set_column_width(xlsx_col(col), width).map_err(xlsx_error)?;
+        }
+        for (&row, &height) in sheet.row_heights() {
+            xlsx_sheet.set_row_height(row, height).map_err(xlsx_error)?;
+        }
+        for &row in sheet.hidden_rows() {
+            xlsx_sheet.set_row_hidden(row).map_err(xlsx_error)?;
+        }
+        
+        // Merges go first: merge_range writes the top-left cell, which the cell
+        // loop below then overwrites with its actual value
+        for merged in sheet.merged_ranges() {
+            let format = sheet.get_cell_style(merged.start.row, merged.start.col)
+                .map(style_format)
+                .unwrap_or_default();
+            xlsx_sheet.merge_range(merged.start.row, xlsx_col(merged.start.col), merged.end.row, xlsx_col(merged.end.col), "", &format)
+                .map_err(xlsx_error)?;
+        }
         
         // Find the bounds of data in the sheet to avoid iterating over the entire sparse matrix
         let (max_row, max_col) = find_bounds(sheet);
@@ -32,65 +464,502 @@ pub fn write_xlsx<P: AsRef<Path>>(workbook: &Workbook, path: P) -> Result<(), En
         for row in 0..=max_row {
             for col in 0..=max_col {
                 if let Some(cell) = sheet.get_cell(row, col) {
-                    // Write the cell value to the XLSX worksheet
-                    write_cell_to_xlsx(&mut xlsx_sheet, row, col, cell)?;
+                    let col = xlsx_col(col);
+                    // Write the cell value to the XLSX worksheet, as a link if it has one
+                    match &cell.hyperlink {
+                        Some(url) if cell.formula.is_none() => write_hyperlink_to_xlsx(xlsx_sheet, row, col, cell, url)?,
+                        _ => write_cell_to_xlsx(xlsx_sheet, row, col, cell)?,
+                    }
+                    if let Some(note) = &cell.note {
+                        xlsx_sheet.insert_note(row, col, &Note::new(note).add_author_prefix(false)).map_err(xlsx_error)?;
+                    }
                 }
             }
         }
     }
     
-    // Save the XLSX workbook to file
-    match xlsx_wb.save(path) {
-        Ok(_) => Ok(()),
-        Err(e) => Err(EngineError::IoError(e.to_string())),
+    Ok(xlsx_wb)
+}
+
+// Convert workbook metadata into XLSX document properties. The modified time is
+// not carried over; rust_xlsxwriter stamps it when saving.
+fn doc_properties(metadata: &WorkbookMetadata) -> Result<DocProperties, EngineError> {
+    let mut properties = DocProperties::new();
+    
+    if let Some(title) = &metadata.title {
+        properties = properties.set_title(title);
+    }
+    if let Some(subject) = &metadata.subject {
+        properties = properties.set_subject(subject);
+    }
+    if let Some(author) = &metadata.author {
+        properties = properties.set_author(author);
+    }
+    if let Some(company) = &metadata.company {
+        properties = properties.set_company(company);
+    }
+    if let Some(keywords) = &metadata.keywords {
+        properties = properties.set_keywords(keywords);
+    }
+    if let Some(comments) = &metadata.comments {
+        properties = properties.set_comment(comments);
+    }
+    if let Some(created) = metadata.created {
+        let datetime = ExcelDateTime::from_ymd(created.year() as u16, created.month() as u8, created.day() as u8)
+            .and_then(|date| date.and_hms(created.hour() as u16, created.minute() as u8, created.second()))
+            .map_err(xlsx_error)?;
+        properties = properties.set_creation_datetime(&datetime);
+    }
+    for (name, value) in &metadata.custom {
+        properties = properties.set_custom_property(name, value.as_str());
     }
+    
+    Ok(properties)
+}
+
+// Convert a rust_xlsxwriter error into an engine error
+fn xlsx_error(e: XlsxError) -> EngineError {
+    EngineError::IoError(e.to_string())
+}
+
+// rust_xlsxwriter numbers columns with u16, which holds every column up to XFD
+fn xlsx_col(col: ColumnId) -> ColNum {
+    col as ColNum
 }
 
 // Find the maximum used row and column in a sheet
 fn find_bounds(sheet: &Sheet) -> (RowId, ColumnId) {
-    // This is a placeholder - in a real implementation we'd check the actual used cells
-    // In a sparse representation we'd need to track max row/col or iterate
-    (100, 20)  // Arbitrary limit for now
+    sheet.used_bounds().unwrap_or((0, 0))
 }
 
 // Write a single cell to an XLSX worksheet
 fn write_cell_to_xlsx(
     xlsx_sheet: &mut Worksheet,
     row: RowId, 
-    col: ColumnId, 
-    cell: &Cell
+    col: ColNum, 
+    cell: &Cell,
 ) -> Result<(), EngineError> {
+    // Cells without a style are written with the default format
+    let format = cell.style.as_ref().map(style_format).unwrap_or_default();
+    
+    // Formula cells keep their formula text, with the last calculated value
+    // stored as the cached result so readers that don't recalculate see it
+    if let Some(formula) = &cell.formula {
+        let mut xlsx_formula = Formula::new(formula);
+        if let Some(result) = cell.calculated_value.as_ref().and_then(formula_result) {
+            xlsx_formula = xlsx_formula.set_result(result);
+        }
+        // A formula that computes a date is shown as one, like a date value
+        let has_number_format = cell.style.as_ref().is_some_and(|style| style.number_format.is_some());
+        let format = match cell.calculated_value {
+            Some(CellValue::Date(_)) if !has_number_format => format.set_num_format("yyyy-mm-dd"),
+            _ => format,
+        };
+        xlsx_sheet.write_formula_with_format(row, col, xlsx_formula, &format).map_err(xlsx_error)?;
+        return Ok(());
+    }
+    
     // For the actual value (calculated or raw), write to XLSX
     match cell.effective_value() {
-        CellValue::Blank => Ok(()),
+        CellValue::Blank => {
+            // Empty cells are only written to keep their formatting
+            if cell.style.is_some() {
+                xlsx_sheet.write_blank(row, col, &format).map_err(xlsx_error)?;
+            }
+            Ok(())
+        },
         CellValue::Number(n) => {
-            xlsx_sheet.write_number(row, col, *n)?;
+            xlsx_sheet.write_number_with_format(row, col, *n, &format).map_err(xlsx_error)?;
+            Ok(())
+        },
+        CellValue::Date(serial) => {
+            // Dates are serial numbers with a date number format, in the workbook's
+            // date system (which is written to the file's properties)
+            let has_number_format = cell.style.as_ref().is_some_and(|style| style.number_format.is_some());
+            let date_format = if has_number_format { format } else { format.set_num_format("yyyy-mm-dd") };
+            xlsx_sheet.write_number_with_format(row, col, *serial, &date_format).map_err(xlsx_error)?;
             Ok(())
         },
         CellValue::Text(s) => {
-            xlsx_sheet.write_string(row, col, s)?;
+            xlsx_sheet.write_string_with_format(row, col, s, &format).map_err(xlsx_error)?;
             Ok(())
         },
         CellValue::Boolean(b) => {
-            xlsx_sheet.write_boolean(row, col, *b)?;
+            xlsx_sheet.write_boolean_with_format(row, col, *b, &format).map_err(xlsx_error)?;
             Ok(())
         },
         CellValue::Error(_) => {
-            xlsx_sheet.write_string(row, col, "#ERROR")?;
+            xlsx_sheet.write_string_with_format(row, col, "#ERROR", &format).map_err(xlsx_error)?;
+            Ok(())
+        },
+        CellValue::Array(_) => {
+            // Arrays only exist as formula results and are not written as literal values
+            xlsx_sheet.write_string_with_format(row, col, "#VALUE!", &format).map_err(xlsx_error)?;
             Ok(())
         },
         CellValue::Formula(f) => {
             // Write the formula string directly
-            xlsx_sheet.write_formula(row, col, f)?;
+            xlsx_sheet.write_formula_with_format(row, col, f.as_str(), &format).map_err(xlsx_error)?;
             Ok(())
         },
     }
 }
 
-// Helper function to convert cell formats to rust_xlsxwriter Format objects
-fn _convert_format(_format: &str) -> Format {
-    // Basic format conversion
-    let mut fmt = Format::new();
-    fmt.set_border(FormatBorder::Thin);
-    fmt
+// Write a linked cell to an XLSX worksheet, with its value as the link text. An
+// empty cell shows the URL. Formula cells are written as formulas, without the link.
+fn write_hyperlink_to_xlsx(xlsx_sheet: &mut Worksheet, row: RowId, col: ColNum, cell: &Cell, url: &str) -> Result<(), EngineError> {
+    let mut link = Url::new(url);
+    if !matches!(cell.value, CellValue::Blank) {
+        link = link.set_text(cell.value.to_string());
+    }
+    
+    // Without a style of its own the cell gets Excel's hyperlink look
+    match &cell.style {
+        Some(style) => xlsx_sheet.write_url_with_format(row, col, link, &style_format(style)).map_err(xlsx_error)?,
+        None => xlsx_sheet.write_url(row, col, link).map_err(xlsx_error)?,
+    };
+    Ok(())
+}
+
+// Text of a calculated value as stored in the cached result of a formula cell
+fn formula_result(value: &CellValue) -> Option<String> {
+    match value {
+        // Cached numbers keep full precision rather than the 15 digits shown in cells
+        CellValue::Number(n) | CellValue::Date(n) => Some(n.to_string()),
+        CellValue::Text(_) | CellValue::Boolean(_) | CellValue::Error(_) => Some(value.to_string()),
+        CellValue::Blank | CellValue::Formula(_) | CellValue::Array(_) => None,
+    }
+}
+
+// Convert a cell style into a rust_xlsxwriter Format
+fn style_format(style: &CellStyle) -> Format {
+    let mut format = Format::new();
+    
+    if style.bold {
+        format = format.set_bold();
+    }
+    if style.italic {
+        format = format.set_italic();
+    }
+    if let Some(size) = style.font_size {
+        format = format.set_font_size(size);
+    }
+    if let Some(color) = style.font_color {
+        format = format.set_font_color(Color::RGB(color));
+    }
+    if let Some(color) = style.fill_color {
+        format = format.set_background_color(Color::RGB(color)).set_pattern(FormatPattern::Solid);
+    }
+    if let Some(number_format) = &style.number_format {
+        format = format.set_num_format(number_format);
+    }
+    
+    format = format.set_border(match style.border {
+        BorderStyle::None => FormatBorder::None,
+        BorderStyle::Thin => FormatBorder::Thin,
+        BorderStyle::Medium => FormatBorder::Medium,
+        BorderStyle::Thick => FormatBorder::Thick,
+        BorderStyle::Dashed => FormatBorder::Dashed,
+        BorderStyle::Dotted => FormatBorder::Dotted,
+        BorderStyle::Double => FormatBorder::Double,
+    });
+    
+    format = match style.horizontal_alignment {
+        HorizontalAlignment::General => format,
+        HorizontalAlignment::Left => format.set_align(FormatAlign::Left),
+        HorizontalAlignment::Center => format.set_align(FormatAlign::Center),
+        HorizontalAlignment::Right => format.set_align(FormatAlign::Right),
+    };
+    match style.vertical_alignment {
+        VerticalAlignment::Bottom => format,
+        VerticalAlignment::Center => format.set_align(FormatAlign::VerticalCenter),
+        VerticalAlignment::Top => format.set_align(FormatAlign::Top),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ssengine_core::CellRange;
+    
+    // A file in the temp directory, removed when dropped
+    struct TempFile(std::path::PathBuf);
+    
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            TempFile(std::env::temp_dir().join(format!("ssengine-{}-{}", std::process::id(), name)))
+        }
+    }
+    
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+    
+    fn three_sheets() -> Workbook {
+        let mut workbook = Workbook::new();
+        for (i, name) in ["First", "Second", "Third"].into_iter().enumerate() {
+            workbook.add_sheet(name.to_string()).unwrap();
+            workbook.set_cell_value(name, 0, 0, i as f64 + 1.0).unwrap();
+        }
+        workbook
+    }
+    
+    #[test]
+    fn formula_results_are_written_as_cached_values() {
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Model".to_string()).unwrap();
+        workbook.set_cell_value("Model", 0, 0, 2.0).unwrap();
+        workbook.set_cell_value("Model", 1, 0, 3.0).unwrap();
+        workbook.set_cell_value("Model", 2, 0, "=A1*A2").unwrap();
+        workbook.set_cell_value("Model", 3, 0, "=A3&\" units\"").unwrap();
+        let bytes = write_xlsx_bytes(&workbook).unwrap();
+        
+        // The worksheet part holds each result next to its formula
+        let mut archive = ZipArchive::new(Cursor::new(&bytes)).unwrap();
+        let worksheet = read_part(&mut archive, "xl/worksheets/sheet1.xml").unwrap();
+        assert!(worksheet.contains("<f>A1*A2</f><v>6</v>"), "{}", worksheet);
+        assert!(worksheet.contains("<v>6 units</v>"), "{}", worksheet);
+        
+        // Readers that don't recalculate see the results
+        let read = read_xlsx_bytes(&bytes).unwrap();
+        let sheet = read.get_sheet("Model").unwrap();
+        assert_eq!(sheet.get_cell(2, 0).unwrap().calculated_value, Some(CellValue::Number(6.0)));
+        assert_eq!(sheet.get_cell(3, 0).unwrap().calculated_value, Some(CellValue::Text("6 units".to_string())));
+    }
+    
+    #[test]
+    fn dates_are_exported_as_date_cells() {
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Dates".to_string()).unwrap();
+        workbook.set_cell_value("Dates", 0, 0, "=DATE(2024,1,1)").unwrap();
+        workbook.set_cell_value("Dates", 1, 0, CellValue::Date(45293.0)).unwrap();
+        workbook.set_cell_value("Dates", 2, 0, 45294.0).unwrap();
+        let bytes = write_xlsx_bytes(&workbook).unwrap();
+        
+        let read = read_xlsx_bytes(&bytes).unwrap();
+        let sheet = read.get_sheet("Dates").unwrap();
+        assert_eq!(sheet.get_cell(0, 0).unwrap().calculated_value, Some(CellValue::Date(45292.0)));
+        assert_eq!(sheet.get_cell(1, 0).unwrap().value, CellValue::Date(45293.0));
+        // A plain number stays a number
+        assert_eq!(sheet.get_cell(2, 0).unwrap().value, CellValue::Number(45294.0));
+    }
+    
+    #[test]
+    fn lazy_reading_materializes_only_accessed_sheets() {
+        let file = TempFile::new("lazy.xlsx");
+        write_xlsx(&three_sheets(), &file.0).unwrap();
+        
+        let mut lazy = read_xlsx_lazy(&file.0).unwrap();
+        let names = lazy.sheet_names();
+        assert_eq!(names.len(), 3);
+        assert!(names.iter().all(|name| !lazy.is_loaded(name)));
+        
+        // Access the last sheet of the file before the first
+        let (first, last) = (names[0].clone(), names[2].clone());
+        let value = lazy.sheet(&last).unwrap().get_cell(0, 0).unwrap().value.clone();
+        assert!(matches!(value, CellValue::Number(_)));
+        lazy.sheet(&first).unwrap();
+        assert!(!lazy.is_loaded(&names[1]));
+        assert!(lazy.sheet("Missing").is_err());
+        
+        let partial = lazy.into_partial();
+        assert_eq!(partial.loaded, vec![first.clone(), last]);
+        assert_eq!(partial.missing, vec![names[1].clone()]);
+        assert_eq!(partial.workbook.sheet_count(), 2);
+        assert_eq!(partial.workbook.active_sheet_name(), Some(&first));
+    }
+    
+    #[test]
+    fn lazy_into_workbook_reads_the_remaining_sheets() {
+        let file = TempFile::new("lazy-whole.xlsx");
+        write_xlsx(&three_sheets(), &file.0).unwrap();
+        
+        let mut lazy = read_xlsx_lazy(&file.0).unwrap();
+        let names = lazy.sheet_names();
+        lazy.sheet(&names[2]).unwrap();
+        
+        let workbook = lazy.into_workbook().unwrap();
+        assert_eq!(workbook.sheet_count(), 3);
+        assert_eq!(workbook.active_sheet_name(), Some(&names[0]));
+        for name in ["First", "Second", "Third"] {
+            assert!(workbook.get_sheet(name).unwrap().get_cell(0, 0).is_some());
+        }
+    }
+    
+    #[test]
+    fn cell_styles_are_exported() {
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Styled".to_string()).unwrap();
+        workbook.set_cell_value("Styled", 0, 0, 1234.5).unwrap();
+        let style = CellStyle {
+            bold: true,
+            horizontal_alignment: HorizontalAlignment::Right,
+            number_format: Some("$#,##0.00".to_string()),
+            ..CellStyle::default()
+        };
+        workbook.get_sheet_mut("Styled").unwrap().set_cell_style(0, 0, style);
+        let bytes = write_xlsx_bytes(&workbook).unwrap();
+        
+        // The cell points at a cell format (s="1") that is bold, right-aligned and currency
+        let mut archive = ZipArchive::new(Cursor::new(&bytes)).unwrap();
+        let worksheet = read_part(&mut archive, "xl/worksheets/sheet1.xml").unwrap();
+        assert!(worksheet.contains(r#"<c r="A1" s="1"><v>1234.5</v></c>"#), "{}", worksheet);
+        let styles = read_part(&mut archive, "xl/styles.xml").unwrap();
+        assert!(styles.contains(r#"formatCode="$#,##0.00""#), "{}", styles);
+        assert!(styles.contains("<b/>"), "{}", styles);
+        assert!(styles.contains(r#"<alignment horizontal="right"/>"#), "{}", styles);
+        
+        // The value itself reads back unchanged
+        let read = read_xlsx_bytes(&bytes).unwrap();
+        assert_eq!(read.get_sheet("Styled").unwrap().get_cell(0, 0).unwrap().value, CellValue::Number(1234.5));
+    }
+    
+    #[test]
+    fn merged_title_cells_are_exported() {
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Report".to_string()).unwrap();
+        workbook.set_cell_value("Report", 0, 1, "stale").unwrap();
+        let sheet = workbook.get_sheet_mut("Report").unwrap();
+        sheet.merge_cells(CellRange::from_a1("A1:C1").unwrap()).unwrap();
+        assert!(sheet.merge_cells(CellRange::from_a1("C1:D2").unwrap()).is_err());
+        workbook.set_cell_value("Report", 0, 0, "Quarterly report").unwrap();
+        
+        // Cells covered by the merge read as blank and can't be written
+        assert_eq!(workbook.get_cell_value("Report", 0, 1).unwrap(), CellValue::Blank);
+        assert_eq!(workbook.get_cell_value("Report", 0, 2).unwrap(), CellValue::Blank);
+        assert!(workbook.set_cell_value("Report", 0, 2, "x").is_err());
+        
+        let bytes = write_xlsx_bytes(&workbook).unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(&bytes)).unwrap();
+        let worksheet = read_part(&mut archive, "xl/worksheets/sheet1.xml").unwrap();
+        assert!(worksheet.contains(r#"<mergeCell ref="A1:C1"/>"#), "{}", worksheet);
+        
+        let read = read_xlsx_bytes(&bytes).unwrap();
+        let sheet = read.get_sheet("Report").unwrap();
+        assert_eq!(sheet.get_cell(0, 0).unwrap().value, CellValue::Text("Quarterly report".to_string()));
+        assert!(sheet.get_cell(0, 1).is_none());
+    }
+    
+    #[test]
+    fn frozen_panes_and_sizes_are_exported() {
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Layout".to_string()).unwrap();
+        workbook.set_cell_value("Layout", 0, 0, "Account").unwrap();
+        let sheet = workbook.get_sheet_mut("Layout").unwrap();
+        sheet.set_freeze_panes(1, 0);
+        sheet.set_column_width(0, 30.0);
+        sheet.set_row_height(0, 24.0);
+        let bytes = write_xlsx_bytes(&workbook).unwrap();
+        
+        let mut archive = ZipArchive::new(Cursor::new(&bytes)).unwrap();
+        let layout = read_part(&mut archive, "xl/worksheets/sheet1.xml").unwrap();
+        assert!(layout.contains(r#"<pane ySplit="1" topLeftCell="A2" activePane="bottomLeft" state="frozen"/>"#), "{}", layout);
+        assert!(layout.contains(r#"<col min="1" max="1" width="30.7109375" customWidth="1"/>"#), "{}", layout);
+        assert!(layout.contains(r#"ht="24" customHeight="1""#), "{}", layout);
+        
+        // By default nothing is frozen or sized
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Plain".to_string()).unwrap();
+        let bytes = write_xlsx_bytes(&workbook).unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(&bytes)).unwrap();
+        let plain = read_part(&mut archive, "xl/worksheets/sheet1.xml").unwrap();
+        assert!(!plain.contains("<pane") && !plain.contains("<cols>") && !plain.contains("customHeight"), "{}", plain);
+    }
+    
+    #[test]
+    fn document_properties_survive_a_round_trip() {
+        let mut workbook = three_sheets();
+        let created = chrono::NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(9, 30, 0).unwrap();
+        let metadata = workbook.metadata_mut();
+        metadata.author = Some("Dana Ruiz".to_string());
+        metadata.title = Some("Q1 <Forecast> & plan".to_string());
+        metadata.company = Some("Acme".to_string());
+        metadata.created = Some(created);
+        metadata.custom.insert("Reviewed".to_string(), "yes".to_string());
+        let bytes = write_xlsx_bytes(&workbook).unwrap();
+        
+        let read = read_xlsx_bytes(&bytes).unwrap();
+        let metadata = read.metadata();
+        assert_eq!(metadata.author.as_deref(), Some("Dana Ruiz"));
+        assert_eq!(metadata.title.as_deref(), Some("Q1 <Forecast> & plan"));
+        assert_eq!(metadata.company.as_deref(), Some("Acme"));
+        assert_eq!(metadata.created, Some(created));
+        assert!(metadata.modified.is_some());
+        assert_eq!(metadata.custom.get("Reviewed").map(String::as_str), Some("yes"));
+        assert_eq!(metadata.subject, None);
+    }
+    
+    #[test]
+    fn cell_notes_round_trip() {
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Inputs".to_string()).unwrap();
+        workbook.set_cell_value("Inputs", 0, 0, 0.08).unwrap();
+        let sheet = workbook.get_sheet_mut("Inputs").unwrap();
+        sheet.set_cell_note(0, 0, "WACC per <treasury> & desk\nreviewed Q1");
+        sheet.set_cell_note(3, 2, "Empty cell note");
+        let bytes = write_xlsx_bytes(&workbook).unwrap();
+        
+        let read = read_xlsx_bytes(&bytes).unwrap();
+        let sheet = read.get_sheet("Inputs").unwrap();
+        assert_eq!(sheet.get_cell_note(0, 0), Some("WACC per <treasury> & desk\nreviewed Q1"));
+        assert_eq!(sheet.get_cell(0, 0).unwrap().value, CellValue::Number(0.08));
+        assert_eq!(sheet.get_cell_note(3, 2), Some("Empty cell note"));
+        assert_eq!(sheet.get_cell_note(1, 0), None);
+    }
+    
+    #[test]
+    fn hyperlinks_are_exported() {
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Sources".to_string()).unwrap();
+        let sheet = workbook.get_sheet_mut("Sources").unwrap();
+        sheet.set_cell_hyperlink(0, 0, "https://example.com/rates?q=1&r=2", Some("Rate source")).unwrap();
+        sheet.set_cell_hyperlink(1, 0, "https://example.com/plain", None).unwrap();
+        let bytes = write_xlsx_bytes(&workbook).unwrap();
+        
+        let mut archive = ZipArchive::new(Cursor::new(&bytes)).unwrap();
+        let worksheet = read_part(&mut archive, "xl/worksheets/sheet1.xml").unwrap();
+        assert!(worksheet.contains(r#"<hyperlink ref="A1" r:id="rId1"/>"#), "{}", worksheet);
+        let rels = read_part(&mut archive, "xl/worksheets/_rels/sheet1.xml.rels").unwrap();
+        assert!(rels.contains(r#"Target="https://example.com/rates?q=1&amp;r=2""#), "{}", rels);
+        
+        let read = read_xlsx_bytes(&bytes).unwrap();
+        let sheet = read.get_sheet("Sources").unwrap();
+        assert_eq!(sheet.get_cell_hyperlink(0, 0), Some("https://example.com/rates?q=1&r=2"));
+        assert_eq!(sheet.get_cell(0, 0).unwrap().value, CellValue::Text("Rate source".to_string()));
+        // A link without text shows its URL
+        assert_eq!(sheet.get_cell_hyperlink(1, 0), Some("https://example.com/plain"));
+        assert_eq!(sheet.get_cell(1, 0).unwrap().value, CellValue::Text("https://example.com/plain".to_string()));
+    }
+    
+    #[test]
+    fn the_1904_date_system_round_trips() {
+        // No date cells: the system can only come from the workbook properties
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Plan".to_string()).unwrap();
+        workbook.set_cell_value("Plan", 0, 0, 100.0).unwrap();
+        workbook.set_date_system(DateSystem::Excel1904).unwrap();
+        let bytes = write_xlsx_bytes(&workbook).unwrap();
+        
+        let mut archive = ZipArchive::new(Cursor::new(bytes.as_slice())).unwrap();
+        assert!(read_part(&mut archive, "xl/workbook.xml").unwrap().contains("<workbookPr date1904=\"1\""));
+        let read = read_xlsx_bytes(&bytes).unwrap();
+        assert_eq!(read.date_system(), DateSystem::Excel1904);
+        assert_eq!(read.get_sheet("Plan").unwrap().get_cell(0, 0).unwrap().value, CellValue::Number(100.0));
+        
+        // Serials are written as stored, so they keep their dates
+        workbook.set_cell_value("Plan", 1, 0, CellValue::Date(43830.0)).unwrap();
+        let file = TempFile::new("date1904.xlsx");
+        write_xlsx(&workbook, &file.0).unwrap();
+        let mut lazy = read_xlsx_lazy(&file.0).unwrap();
+        assert_eq!(lazy.sheet("Plan").unwrap().get_cell(1, 0).unwrap().value, CellValue::Date(43830.0));
+        let read = lazy.into_workbook().unwrap();
+        assert_eq!(read.date_system(), DateSystem::Excel1904);
+        assert_eq!(read.evaluate_formula("Plan", "=YEAR(A2)").unwrap(), CellValue::Number(2024.0));
+        
+        // The default system writes no flag
+        let read = read_xlsx_bytes(&write_xlsx_bytes(&Workbook::new()).unwrap()).unwrap();
+        assert_eq!(read.date_system(), DateSystem::Excel1900);
+    }
 }
@@ -1,60 +1,276 @@
 // ssengine-sdk/src/api.rs
 // API implementation for workbook operations
 
-use ssengine_core::{Workbook, Sheet, Cell, CellValue, EngineError, CellAddress};
-use ssengine_io::{read_xlsx, write_xlsx};
+use ssengine_core::{Workbook, CellValue, EngineError, CellAddress, CellOrRange};
+use ssengine_io::{read_xlsx, read_xlsx_bytes, write_xlsx, write_xlsx_bytes};
 use serde::{Serialize, Deserialize};
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
 use std::sync::{Arc, RwLock};
 
-// Workbook API - thread-safe wrapper around a workbook
+// Cells returned by one get_range call when the caller sets no cap
+pub const DEFAULT_MAX_CELLS: usize = 10_000;
+
+// Workbook API - thread-safe wrapper around a workbook. Every operation runs on
+// tokio's blocking pool, so a long recalculation never stalls the async runtime.
+#[derive(Clone)]
 pub struct WorkbookApi {
     workbook: Arc<RwLock<Workbook>>,
+    base_dir: PathBuf, // Import and export paths are resolved in, and confined to, this directory
+}
+
+impl Default for WorkbookApi {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl WorkbookApi {
     pub fn new() -> Self {
-        WorkbookApi {
-            workbook: Arc::new(RwLock::new(Workbook::new())),
-        }
+        Self::from_workbook(Workbook::new())
     }
     
     pub fn from_workbook(workbook: Workbook) -> Self {
         WorkbookApi {
             workbook: Arc::new(RwLock::new(workbook)),
+            base_dir: PathBuf::from("."),
         }
     }
     
+    // Confine import and export paths to a directory (the current one by default)
+    pub fn with_base_dir(mut self, base_dir: impl Into<PathBuf>) -> Self {
+        self.base_dir = base_dir.into();
+        self
+    }
+    
+    // Resolve a client-supplied path inside the base directory. An empty path is
+    // invalid; one that would leave the directory, through `..`, an absolute path or
+    // a symlink, is forbidden. The file itself need not exist yet.
+    fn resolve_path(&self, path: &Path) -> Result<PathBuf, ApiError> {
+        if path.as_os_str().is_empty() {
+            return Err(ApiError::InvalidRequest("Path must not be empty".into()));
+        }
+        if path.components().any(|component| !matches!(component, Component::Normal(_) | Component::CurDir)) {
+            return Err(ApiError::Forbidden(format!("Path must stay inside the base directory: {}", path.display())));
+        }
+        
+        let base = self.base_dir.canonicalize()
+            .map_err(|e| ApiError::InvalidRequest(format!("Base directory {} is unavailable: {}", self.base_dir.display(), e)))?;
+        let joined = base.join(path);
+        let resolved = match joined.canonicalize() {
+            Ok(resolved) => resolved,
+            Err(_) => {
+                let parent = joined.parent().unwrap_or(&base).canonicalize()
+                    .map_err(|e| ApiError::InvalidRequest(format!("Invalid path {}: {}", path.display(), e)))?;
+                parent.join(joined.file_name().unwrap_or_default())
+            },
+        };
+        
+        if !resolved.starts_with(&base) {
+            return Err(ApiError::Forbidden(format!("Path leaves the base directory: {}", path.display())));
+        }
+        Ok(resolved)
+    }
+    
+    // Run `op` with shared access to the workbook on a blocking thread
+    async fn read<T, F>(&self, op: F) -> Result<T, ApiError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Workbook) -> Result<T, ApiError> + Send + 'static,
+    {
+        let workbook = Arc::clone(&self.workbook);
+        tokio::task::spawn_blocking(move || {
+            let wb = workbook.read().map_err(|_| ApiError::LockError)?;
+            op(&wb)
+        })
+        .await
+        .map_err(|e| ApiError::TaskError(e.to_string()))?
+    }
+    
+    // Run `op` with exclusive access to the workbook on a blocking thread
+    async fn write<T, F>(&self, op: F) -> Result<T, ApiError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Workbook) -> Result<T, ApiError> + Send + 'static,
+    {
+        let workbook = Arc::clone(&self.workbook);
+        tokio::task::spawn_blocking(move || {
+            let mut wb = workbook.write().map_err(|_| ApiError::LockError)?;
+            op(&mut wb)
+        })
+        .await
+        .map_err(|e| ApiError::TaskError(e.to_string()))?
+    }
+    
     // Create a new sheet
-    pub fn add_sheet(&self, name: String) -> Result<String, ApiError> {
-        let mut wb = self.workbook.write().map_err(|_| ApiError::LockError)?
-        wb.add_sheet(name).map_err(ApiError::EngineError)
+    pub async fn add_sheet(&self, name: String) -> Result<String, ApiError> {
+        self.write(move |wb| wb.add_sheet(name).map_err(ApiError::EngineError)).await
+    }
+    
+    // Whether the workbook has a sheet of this name
+    pub async fn has_sheet(&self, name: String) -> Result<bool, ApiError> {
+        self.read(move |wb| Ok(wb.get_sheet(&name).is_some())).await
+    }
+    
+    // Set a cell from text as typed into it (see `parse_input`) and recalculate
+    // its dependents
+    pub async fn set_cell(&self, sheet: String, row: u32, col: u32, value: String) -> Result<(), ApiError> {
+        self.write(move |wb| wb.set_cell_value(&sheet, row, col, parse_input(&value)).map_err(ApiError::EngineError)).await
+    }
+    
+    // Remove a cell, so it reads as absent rather than blank, and recalculate its
+    // dependents. Returns whether there was a cell to remove.
+    pub async fn delete_cell(&self, sheet: String, row: u32, col: u32) -> Result<bool, ApiError> {
+        self.write(move |wb| wb.delete_cell(&sheet, row, col).map_err(ApiError::EngineError)).await
+    }
+    
+    // Get a cell's value, formula, note and hyperlink
+    pub async fn get_cell(&self, sheet: String, row: u32, col: u32) -> Result<CellResponse, ApiError> {
+        self.read(move |wb| {
+            let value = wb.get_cell_value(&sheet, row, col)?;
+            let cell = wb.get_sheet(&sheet).and_then(|s| s.get_cell(row, col));
+            
+            Ok(CellResponse {
+                value: value_to_json(&value),
+                formula: cell.and_then(|cell| cell.formula.clone()),
+                formatted: wb.formatted_value(&sheet, row, col)?,
+                note: cell.and_then(|cell| cell.note.clone()),
+                hyperlink: cell.and_then(|cell| cell.hyperlink.clone()),
+            })
+        }).await
+    }
+    
+    // Read a window of a range's rows. Reading starts `offset` rows below the top of the
+    // range (or where `continuation` says) and returns at most `limit` rows and about
+    // `max_cells` cells, always at least one row. Rows past the sheet's used range are
+    // not returned. While rows remain, the page carries a continuation token.
+    pub async fn get_range(
+        &self,
+        sheet: String,
+        range: String,
+        offset: u32,
+        limit: Option<u32>,
+        max_cells: Option<usize>,
+        continuation: Option<String>,
+    ) -> Result<RangePage, ApiError> {
+        let offset = match continuation {
+            Some(token) => token.parse::<u32>()
+                .map_err(|_| ApiError::InvalidRequest(format!("Invalid continuation token: {}", token)))?,
+            None => offset,
+        };
+        
+        self.read(move |wb| {
+            let range = CellOrRange::from_a1(&range)?.to_range();
+            let (top, bottom) = (range.start.row, range.end.row);
+            let (left, right) = (range.start.col, range.end.col);
+            
+            let used_bottom = match wb.used_range(&sheet)? {
+                Some(used) => bottom.min(used.end.row),
+                None => return Ok(RangePage { range: String::new(), values: Vec::new(), continuation: None }),
+            };
+            
+            let first = top.saturating_add(offset);
+            if first > used_bottom {
+                return Ok(RangePage { range: String::new(), values: Vec::new(), continuation: None });
+            }
+            let columns = (right - left + 1) as usize;
+            let mut rows = (max_cells.unwrap_or(DEFAULT_MAX_CELLS) / columns).max(1) as u32;
+            if let Some(limit) = limit {
+                rows = rows.min(limit.max(1));
+            }
+            let last = used_bottom.min(first.saturating_add(rows - 1));
+            
+            let mut values = Vec::new();
+            for row in first..=last {
+                let mut line = Vec::with_capacity(columns);
+                for col in left..=right {
+                    line.push(value_to_json(&wb.get_cell_value(&sheet, row, col)?));
+                }
+                values.push(line);
+            }
+            
+            Ok(RangePage {
+                range: format!("{}:{}", CellAddress::new(first, left).to_a1(), CellAddress::new(last, right).to_a1()),
+                values,
+                continuation: (last < used_bottom).then(|| (last + 1 - top).to_string()),
+            })
+        }).await
+    }
+    
+    // Evaluate a formula against the sheet's current data without storing it
+    pub async fn evaluate(&self, sheet: String, formula: String) -> Result<CellValue, ApiError> {
+        self.read(move |wb| wb.evaluate_formula(&sheet, &formula).map_err(ApiError::EngineError)).await
+    }
+    
+    // The transitive precedents and dependents of a cell, as sorted Sheet!A1 references
+    pub async fn trace(&self, sheet: String, row: u32, col: u32) -> Result<CellTrace, ApiError> {
+        self.read(move |wb| {
+            let cell = CellAddress::new(row, col);
+            let to_references = |cells: std::collections::HashSet<(String, CellAddress)>| {
+                let mut references: Vec<String> = cells.into_iter()
+                    .map(|(sheet, address)| format!("{}!{}", sheet, address.to_a1()))
+                    .collect();
+                references.sort();
+                references
+            };
+            
+            Ok(CellTrace {
+                precedents: to_references(wb.trace_precedents(&sheet, &cell)?),
+                dependents: to_references(wb.trace_dependents(&sheet, &cell)?),
+            })
+        }).await
+    }
+    
+    // The spreadsheet functions formulas can call, by name
+    pub async fn list_functions(&self) -> Result<Vec<FunctionInfo>, ApiError> {
+        self.read(|wb| {
+            Ok(wb.functions().list().into_iter()
+                .map(|meta| FunctionInfo {
+                    name: meta.name,
+                    min_args: meta.min_args,
+                    max_args: meta.max_args,
+                    category: meta.category.name().to_string(),
+                    description: meta.description,
+                })
+                .collect())
+        }).await
     }
     
-    // Set a cell value
-    pub fn set_cell(&self, sheet: String, row: u32, col: u32, value: String) -> Result<(), ApiError> {
-        // To be implemented
-        Err(ApiError::NotImplemented("set_cell".into()))
+    // Export the workbook to XLSX at a path relative to the base directory
+    pub async fn export_xlsx(&self, path: PathBuf) -> Result<(), ApiError> {
+        let path = self.resolve_path(&path)?;
+        self.read(move |wb| write_xlsx(wb, path).map_err(ApiError::EngineError)).await
     }
     
-    // Get a cell value
-    pub fn get_cell(&self, sheet: String, row: u32, col: u32) -> Result<CellResponse, ApiError> {
-        // To be implemented
-        Err(ApiError::NotImplemented("get_cell".into()))
+    // Import a workbook from XLSX at a path relative to the base directory.
+    // The file is read before the workbook is locked.
+    pub async fn import_xlsx(&self, path: PathBuf) -> Result<(), ApiError> {
+        let path = self.resolve_path(&path)?;
+        let imported = tokio::task::spawn_blocking(move || read_xlsx(path))
+            .await
+            .map_err(|e| ApiError::TaskError(e.to_string()))??;
+        self.write(move |wb| {
+            *wb = imported;
+            Ok(())
+        }).await
     }
     
-    // Export the workbook to XLSX
-    pub fn export_xlsx(&self, path: PathBuf) -> Result<(), ApiError> {
-        let wb = self.workbook.read().map_err(|_| ApiError::LockError)?;
-        write_xlsx(&wb, path).map_err(ApiError::EngineError)
+    // Export the workbook as the bytes of an XLSX file
+    pub async fn export_xlsx_bytes(&self) -> Result<Vec<u8>, ApiError> {
+        self.read(|wb| write_xlsx_bytes(wb).map_err(ApiError::EngineError)).await
     }
     
-    // Import a workbook from XLSX
-    pub fn import_xlsx(&self, path: PathBuf) -> Result<(), ApiError> {
-        let imported = read_xlsx(path).map_err(ApiError::EngineError)?;
-        let mut wb = self.workbook.write().map_err(|_| ApiError::LockError)?;
-        *wb = imported;
-        Ok(())
+    // Replace the workbook with one read from the bytes of an XLSX file. Bytes that
+    // aren't a readable XLSX file are an invalid request.
+    pub async fn import_xlsx_bytes(&self, bytes: &[u8]) -> Result<(), ApiError> {
+        let bytes = bytes.to_vec();
+        let imported = tokio::task::spawn_blocking(move || read_xlsx_bytes(&bytes))
+            .await
+            .map_err(|e| ApiError::TaskError(e.to_string()))?
+            .map_err(|e| ApiError::InvalidRequest(format!("Not a readable XLSX file: {}", e)))?;
+        self.write(move |wb| {
+            *wb = imported;
+            Ok(())
+        }).await
     }
 }
 
@@ -72,6 +288,15 @@ pub enum ApiError {
     
     #[error("Invalid request: {0}")]
     InvalidRequest(String),
+    
+    #[error("Workbook task failed: {0}")]
+    TaskError(String),
+    
+    #[error("Missing or invalid bearer token")]
+    Unauthorized,
+    
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
 }
 
 // Response types
@@ -80,29 +305,196 @@ pub struct CellResponse {
     pub value: serde_json::Value,
     pub formula: Option<String>,
     pub formatted: String,
+    pub note: Option<String>,
+    pub hyperlink: Option<String>,
+}
+
+// One window of a range's rows
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RangePage {
+    pub range: String, // The rows returned, in A1 notation (empty when there are none)
+    pub values: Vec<Vec<serde_json::Value>>,
+    pub continuation: Option<String>, // Pass back to read the following rows
+}
+
+// Cells a cell depends on and cells that depend on it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CellTrace {
+    pub precedents: Vec<String>,
+    pub dependents: Vec<String>,
+}
+
+// A spreadsheet function's argument counts and help text
+#[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct FunctionInfo {
+    pub name: String,
+    pub min_args: usize,
+    pub max_args: Option<usize>, // None: any number of arguments
+    pub category: String,
+    pub description: String,
+}
+
+// The value of text typed into a cell: a formula if it starts with '=', a number,
+// TRUE or FALSE (any case), blank if empty, and otherwise the text itself
+pub fn parse_input(input: &str) -> CellValue {
+    if input.is_empty() {
+        return CellValue::Blank;
+    }
+    if input.starts_with('=') {
+        return CellValue::Formula(input.to_string());
+    }
+    if let Some(n) = input.trim().parse::<f64>().ok().filter(|n| n.is_finite()) {
+        return CellValue::Number(n);
+    }
+    match input.to_uppercase().as_str() {
+        "TRUE" => CellValue::Boolean(true),
+        "FALSE" => CellValue::Boolean(false),
+        _ => CellValue::Text(input.to_string()),
+    }
+}
+
+// JSON form of a cell value: numbers and booleans as themselves, blanks as null,
+// errors as their code
+pub fn value_to_json(value: &CellValue) -> serde_json::Value {
+    match value {
+        CellValue::Blank => serde_json::Value::Null,
+        CellValue::Number(n) | CellValue::Date(n) => serde_json::Number::from_f64(*n)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
+        CellValue::Boolean(b) => serde_json::Value::Bool(*b),
+        CellValue::Array(rows) => serde_json::Value::Array(rows.iter()
+            .map(|row| serde_json::Value::Array(row.iter().map(value_to_json).collect()))
+            .collect()),
+        other => serde_json::Value::String(other.to_string()),
+    }
 }
 
 // Convert ApiError to HTTP response
-impl From<ApiError> for axum::http::StatusCode {
-    fn from(err: ApiError) -> Self {
+impl From<&ApiError> for axum::http::StatusCode {
+    fn from(err: &ApiError) -> Self {
         match err {
             ApiError::EngineError(_) => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
             ApiError::LockError => axum::http::StatusCode::SERVICE_UNAVAILABLE,
             ApiError::NotImplemented(_) => axum::http::StatusCode::NOT_IMPLEMENTED,
             ApiError::InvalidRequest(_) => axum::http::StatusCode::BAD_REQUEST,
+            ApiError::TaskError(_) => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Unauthorized => axum::http::StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => axum::http::StatusCode::FORBIDDEN,
         }
     }
 }
 
-// Helper function to convert ApiError to JSON response
+// Helper function to convert ApiError to JSON response. Errors raised while
+// recalculating a cell name it, e.g. "cell": "Projections!G10".
 pub fn error_to_json(err: ApiError) -> serde_json::Value {
+    let cell = match &err {
+        ApiError::EngineError(EngineError::CellEvaluation { sheet, cell, .. }) => Some(format!("{}!{}", sheet, cell)),
+        _ => None,
+    };
     serde_json::json!({
         "error": err.to_string(),
+        "cell": cell,
         "code": match err {
             ApiError::EngineError(_) => "ENGINE_ERROR",
             ApiError::LockError => "LOCK_ERROR",
             ApiError::NotImplemented(_) => "NOT_IMPLEMENTED",
             ApiError::InvalidRequest(_) => "INVALID_REQUEST",
+            ApiError::TaskError(_) => "TASK_ERROR",
+            ApiError::Unauthorized => "UNAUTHORIZED",
+            ApiError::Forbidden(_) => "FORBIDDEN",
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // A sheet with `rows` rows of three numbered columns
+    fn tall_sheet(rows: u32) -> WorkbookApi {
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Sheet1".into()).unwrap();
+        let cells = (0..rows).flat_map(|row| (0..3).map(move |col| (row, col, CellValue::Number((row * 10 + col) as f64))));
+        workbook.set_cells("Sheet1", cells.collect()).unwrap();
+        WorkbookApi::from_workbook(workbook)
+    }
+    
+    #[tokio::test]
+    async fn large_ranges_are_read_in_pages() {
+        let api = tall_sheet(1000);
+        
+        // An explicit window of rows
+        let page = api.get_range("Sheet1".into(), "A1:C1000".into(), 10, Some(5), None, None).await.unwrap();
+        assert_eq!(page.range, "A11:C15");
+        assert_eq!(page.values.len(), 5);
+        assert_eq!(page.values[0], vec![serde_json::json!(100.0), serde_json::json!(101.0), serde_json::json!(102.0)]);
+        assert_eq!(page.continuation.as_deref(), Some("15"));
+        
+        // Paging through the whole range 300 rows (900 cells) at a time
+        let (mut rows, mut pages, mut continuation) = (Vec::new(), 0, None);
+        loop {
+            let page = api.get_range("Sheet1".into(), "A1:C1000".into(), 0, None, Some(900), continuation).await.unwrap();
+            assert!(page.values.len() <= 300);
+            rows.extend(page.values);
+            pages += 1;
+            match page.continuation {
+                Some(token) => continuation = Some(token),
+                None => break,
+            }
+        }
+        assert_eq!(pages, 4);
+        assert_eq!(rows.len(), 1000);
+        assert_eq!(rows[999][2], serde_json::json!(9992.0));
+        
+        // Rows past the used range aren't returned
+        let page = api.get_range("Sheet1".into(), "A990:C5000".into(), 0, None, None, None).await.unwrap();
+        assert_eq!((page.range.as_str(), page.continuation), ("A990:C1000", None));
+        let result = api.get_range("Sheet1".into(), "A1:C10".into(), 0, None, None, Some("x".into())).await;
+        assert!(matches!(result, Err(ApiError::InvalidRequest(_))));
+    }
+    
+    #[tokio::test]
+    async fn import_and_export_paths_stay_in_the_base_directory() {
+        let base = std::env::temp_dir().join(format!("ssengine-base-dir-{}", std::process::id()));
+        std::fs::create_dir_all(base.join("models")).unwrap();
+        let api = tall_sheet(3).with_base_dir(&base);
+        
+        // A relative path inside the directory is allowed, including a new file
+        api.export_xlsx(PathBuf::from("models/out.xlsx")).await.unwrap();
+        assert!(base.join("models/out.xlsx").exists());
+        api.import_xlsx(PathBuf::from("./models/out.xlsx")).await.unwrap();
+        assert_eq!(api.get_cell("Sheet1".into(), 2, 2).await.unwrap().value, serde_json::json!(22.0));
+        
+        // Escapes are refused before touching the filesystem
+        let outside = std::env::temp_dir().join("ssengine-escape.xlsx");
+        for path in [PathBuf::from("../ssengine-escape.xlsx"), PathBuf::from("models/../../x.xlsx"), outside.clone()] {
+            assert!(matches!(api.export_xlsx(path.clone()).await, Err(ApiError::Forbidden(_))), "{}", path.display());
+            assert!(matches!(api.import_xlsx(path).await, Err(ApiError::Forbidden(_))));
+        }
+        assert!(!outside.exists());
+        assert!(matches!(api.export_xlsx(PathBuf::new()).await, Err(ApiError::InvalidRequest(_))));
+        
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+    
+    #[tokio::test]
+    async fn notes_and_links_are_part_of_the_cell_response() {
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Sheet1".into()).unwrap();
+        let sheet = workbook.get_sheet_mut("Sheet1").unwrap();
+        sheet.set_cell(0, 0, CellValue::Number(0.08)).unwrap();
+        sheet.set_cell_note(0, 0, "Assumed WACC");
+        let api = WorkbookApi::from_workbook(workbook);
+        
+        let json = serde_json::to_value(api.get_cell("Sheet1".into(), 0, 0).await.unwrap()).unwrap();
+        assert_eq!(json["note"], "Assumed WACC");
+        assert_eq!(json["value"], serde_json::json!(0.08));
+        let json = serde_json::to_value(api.get_cell("Sheet1".into(), 1, 0).await.unwrap()).unwrap();
+        assert_eq!(json["note"], serde_json::Value::Null);
+        assert_eq!(json["hyperlink"], serde_json::Value::Null);
+        
+        api.write(|wb| Ok(wb.get_sheet_mut("Sheet1").unwrap().set_cell_hyperlink(2, 0, "https://example.com", Some("Docs"))?)).await.unwrap();
+        let json = serde_json::to_value(api.get_cell("Sheet1".into(), 2, 0).await.unwrap()).unwrap();
+        assert_eq!((&json["value"], &json["hyperlink"]), (&serde_json::json!("Docs"), &serde_json::json!("https://example.com")));
+    }
+}
@@ -5,7 +5,7 @@ pub mod server;
 pub mod schemas;
 
 // Re-export key functionality for easier access
-pub use server::run_server;
+pub use server::{run_server, ServerConfig};
 pub use api::WorkbookApi;
 
 #[cfg(test)]
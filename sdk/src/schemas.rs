@@ -1,20 +1,22 @@
 // ssengine-sdk/src/schemas.rs
 // Schema definitions for API requests and responses
 
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::JsonSchema;
 use serde::{Serialize, Deserialize};
-use serde_json::Value;
+use serde_json::{json, Value};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct AddSheetRequest {
     pub name: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct AddSheetResponse {
     pub sheet_id: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct SetCellRequest {
     pub sheet: String,
     pub row: u32,
@@ -22,50 +24,202 @@ pub struct SetCellRequest {
     pub value: String, // Can be formula (starts with '=') or raw value
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct SetCellResponse {
     pub success: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct EvaluateRequest {
+    pub sheet: String,
+    pub formula: String, // Evaluated as if entered in A1 of the sheet, e.g. "=SUM(A1:A10)"
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct EvaluateResponse {
+    pub value: Value, // JSON value representation
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct DeleteCellRequest {
+    pub sheet: String,
+    pub row: u32,
+    pub col: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct DeleteCellResponse {
+    pub deleted: bool, // False when there was no cell to delete
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct GetCellRequest {
     pub sheet: String,
     pub row: u32,
     pub col: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct GetCellResponse {
     pub value: Value,       // JSON value representation
     pub formula: Option<String>,
     pub formatted: String,  // Formatted string representation
+    pub note: Option<String>, // Comment attached to the cell
+    pub hyperlink: Option<String>, // URL the cell links to
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct GetRangeRequest {
+    pub sheet: String,
+    pub range: String, // A1 notation, e.g. "A1:D100000"
+    #[serde(default)]
+    pub offset: u32, // Rows to skip from the top of the range
+    pub limit: Option<u32>, // Most rows to return
+    pub max_cells: Option<usize>, // Most cells to return (defaults to DEFAULT_MAX_CELLS)
+    pub continuation: Option<String>, // Token from the previous page; replaces offset
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct GetRangeResponse {
+    pub range: String, // The rows returned, in A1 notation
+    pub values: Vec<Vec<Value>>,
+    pub continuation: Option<String>, // Present while more rows remain
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct TraceRequest {
+    pub sheet: String,
+    pub row: u32,
+    pub col: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct TraceResponse {
+    pub precedents: Vec<String>, // Cells the cell depends on, e.g. "Sheet1!A1"
+    pub dependents: Vec<String>, // Formula cells that depend on the cell
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ListFunctionsResponse {
+    pub functions: Vec<crate::api::FunctionInfo>, // Sorted by name
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ExportXlsxRequest {
     pub path: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ExportXlsxResponse {
     pub success: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ImportXlsxRequest {
     pub path: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ImportXlsxResponse {
     pub success: bool,
 }
 
+// Body of every error response
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ErrorResponse {
+    pub error: String, // Human-readable message
+    pub cell: Option<String>, // The cell whose formula failed, e.g. "Projections!G10"
+    pub code: String, // e.g. "INVALID_REQUEST", "ENGINE_ERROR", "UNAUTHORIZED"
+}
+
 // OpenAPI schema generation function
 pub fn generate_openapi_schema() -> schemars::schema::RootSchema {
-    let mut gen = schemars::gen::SchemaGenerator::default();
-    let schema = gen.into_root_schema_for::<AddSheetRequest>();
-    schema
+    let gen = schemars::gen::SchemaGenerator::default();
+    gen.into_root_schema_for::<AddSheetRequest>()
+}
+
+const XLSX_CONTENT_TYPE: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+
+// A JSON request or response body of type T
+fn json_content<T: JsonSchema>(gen: &mut SchemaGenerator) -> Value {
+    json!({ "content": { "application/json": { "schema": gen.subschema_for::<T>() } } })
+}
+
+// An XLSX file as a request or response body
+fn xlsx_content() -> Value {
+    json!({ "content": { XLSX_CONTENT_TYPE: { "schema": { "type": "string", "format": "binary" } } } })
+}
+
+// A documented route: (method, path, summary, request body, success response, needs
+// the bearer token)
+type Route = (&'static str, &'static str, &'static str, Option<Value>, Value, bool);
+
+// Complete OpenAPI 3 document for the HTTP server: every route with its method,
+// request and response bodies and error responses. Served at GET /openapi.json.
+pub fn generate_full_openapi() -> Value {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+    let any_json = json!({ "content": { "application/json": { "schema": { "type": "object" } } } });
+    
+    let routes: Vec<Route> = vec![
+        ("get", "/health", "Check that the server is up", None,
+            json!({ "content": { "application/json": { "schema": { "type": "object", "properties": { "status": { "type": "string" } } } } } }), false),
+        ("get", "/openapi.json", "This document", None, any_json, false),
+        ("post", "/add_sheet", "Add a sheet", Some(json_content::<AddSheetRequest>(&mut gen)), json_content::<AddSheetResponse>(&mut gen), true),
+        ("post", "/set_cell", "Set a cell to a value or formula", Some(json_content::<SetCellRequest>(&mut gen)), json_content::<SetCellResponse>(&mut gen), true),
+        ("post", "/delete_cell", "Remove a cell and recalculate the formulas that read it", Some(json_content::<DeleteCellRequest>(&mut gen)), json_content::<DeleteCellResponse>(&mut gen), true),
+        ("post", "/evaluate", "Evaluate a formula against the current data without storing it", Some(json_content::<EvaluateRequest>(&mut gen)), json_content::<EvaluateResponse>(&mut gen), false),
+        ("post", "/get_cell", "Read a cell", Some(json_content::<GetCellRequest>(&mut gen)), json_content::<GetCellResponse>(&mut gen), false),
+        ("post", "/get_range", "Read a page of a range's rows", Some(json_content::<GetRangeRequest>(&mut gen)), json_content::<GetRangeResponse>(&mut gen), false),
+        ("post", "/trace", "List a cell's precedents and dependents", Some(json_content::<TraceRequest>(&mut gen)), json_content::<TraceResponse>(&mut gen), false),
+        ("get", "/functions", "List the supported spreadsheet functions", None, json_content::<ListFunctionsResponse>(&mut gen), false),
+        ("post", "/export_xlsx", "Save the workbook to a server-side XLSX file", Some(json_content::<ExportXlsxRequest>(&mut gen)), json_content::<ExportXlsxResponse>(&mut gen), true),
+        ("post", "/import_xlsx", "Replace the workbook with a server-side XLSX file", Some(json_content::<ImportXlsxRequest>(&mut gen)), json_content::<ImportXlsxResponse>(&mut gen), true),
+        ("get", "/export.xlsx", "Download the workbook as an XLSX file", None, xlsx_content(), false),
+        ("post", "/import.xlsx", "Replace the workbook with an uploaded XLSX file", Some(xlsx_content()), json_content::<ImportXlsxResponse>(&mut gen), true),
+    ];
+    let error = json_content::<ErrorResponse>(&mut gen);
+    let error_response = |description: &str| {
+        let mut response = error.clone();
+        response["description"] = json!(description);
+        response
+    };
+    
+    let mut paths = serde_json::Map::new();
+    for (method, path, summary, request, mut success, authenticated) in routes {
+        success["description"] = json!("Success");
+        let mut responses = json!({
+            "200": success,
+            "400": error_response("Invalid request"),
+            "500": error_response("Engine or server failure"),
+        });
+        let mut operation = json!({ "summary": summary });
+        if let Some(mut request) = request {
+            request["required"] = json!(true);
+            operation["requestBody"] = request;
+        }
+        if authenticated {
+            responses["401"] = error_response("Missing or invalid bearer token");
+            operation["security"] = json!([{ "bearerAuth": [] }]);
+        }
+        operation["responses"] = responses;
+        
+        let entry = paths.entry(path.to_string()).or_insert_with(|| json!({}));
+        entry[method] = operation;
+    }
+    
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "ssengine",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "HTTP/JSON API for AI agents to interact with the ssengine spreadsheet engine",
+        },
+        "paths": paths,
+        "components": {
+            "schemas": gen.definitions(),
+            "securitySchemes": { "bearerAuth": { "type": "http", "scheme": "bearer" } },
+        },
+    })
 }
 
 // Generate function-calling schema for LLM AI agents
@@ -78,7 +232,7 @@ pub fn generate_function_schema() -> serde_json::Value {
             "properties": {
                 "operation": {
                     "type": "string",
-                    "enum": ["add_sheet", "set_cell", "get_cell", "export_xlsx", "import_xlsx"],
+                    "enum": ["add_sheet", "set_cell", "delete_cell", "get_cell", "evaluate", "get_range", "trace", "export_xlsx", "import_xlsx"],
                     "description": "The operation to perform on the spreadsheet engine"
                 },
                 "sheet_name": {
@@ -97,6 +251,26 @@ pub fn generate_function_schema() -> serde_json::Value {
                     "type": "string",
                     "description": "The value to set in the cell. Can be a formula (starts with '=') or raw value"
                 },
+                "formula": {
+                    "type": "string",
+                    "description": "The formula to evaluate without storing it, e.g. =SUM(A1:A10)"
+                },
+                "range": {
+                    "type": "string",
+                    "description": "A range in A1 notation for get_range, e.g. A1:D100"
+                },
+                "offset": {
+                    "type": "integer",
+                    "description": "Rows of the range to skip for get_range (0-based)"
+                },
+                "limit": {
+                    "type": "integer",
+                    "description": "Most rows get_range returns"
+                },
+                "continuation": {
+                    "type": "string",
+                    "description": "Token returned by get_range to read the following rows"
+                },
                 "path": {
                     "type": "string",
                     "description": "The file path for import/export operations"
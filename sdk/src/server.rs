@@ -1,21 +1,32 @@
 // ssengine-sdk/src/server.rs
 // HTTP server implementation for the spreadsheet API
 
-use crate::api::{WorkbookApi, ApiError, error_to_json};
+use crate::api::{WorkbookApi, ApiError, error_to_json, value_to_json};
 use crate::schemas::*;
+use axum::body::Bytes;
 use axum::routing::{get, post};
 use axum::{Json, Router, Extension};
-use axum::http::StatusCode;
-use axum::extract::Path;
+use axum::http::{header, StatusCode};
+use axum::extract::{Request, State};
+use axum::middleware::{self, Next};
 use axum::response::{IntoResponse, Response};
-use serde_json::{json, Value};
+use serde_json::json;
+use ssengine_core::{MAX_COLUMNS, MAX_ROWS};
 use std::sync::Arc;
 use std::net::SocketAddr;
 use tower_http::cors::{Any, CorsLayer};
 
+// Server settings fixed at startup
+#[derive(Debug, Clone, Default)]
+pub struct ServerConfig {
+    // Bearer token required on every mutating route; None leaves them open
+    pub auth_token: Option<String>,
+}
+
 // Initialize the API router
-pub fn create_router(api: WorkbookApi) -> Router {
+pub fn create_router(api: WorkbookApi, config: ServerConfig) -> Router {
     let api = Arc::new(api);
+    let config = Arc::new(config);
     
     // Define CORS policy
     let cors = CorsLayer::new()
@@ -23,16 +34,29 @@ pub fn create_router(api: WorkbookApi) -> Router {
         .allow_methods(Any)
         .allow_headers(Any);
     
+    // Operations that change the workbook or touch the filesystem need the token
+    let mutating = Router::new()
+        .route("/add_sheet", post(add_sheet))
+        .route("/set_cell", post(set_cell))
+        .route("/delete_cell", post(delete_cell))
+        .route("/export_xlsx", post(export_xlsx))
+        .route("/import_xlsx", post(import_xlsx))
+        .route("/import.xlsx", post(import_xlsx_bytes))
+        .route_layer(middleware::from_fn_with_state(config, require_token));
+    
     Router::new()
         // Healthcheck endpoint
         .route("/health", get(health_check))
         
-        // Workbook operations
-        .route("/add_sheet", post(add_sheet))
-        .route("/set_cell", post(set_cell))
+        // Read-only workbook operations
         .route("/get_cell", post(get_cell))
-        .route("/export_xlsx", post(export_xlsx))
-        .route("/import_xlsx", post(import_xlsx))
+        .route("/get_range", post(get_range))
+        .route("/evaluate", post(evaluate))
+        .route("/trace", post(trace))
+        .route("/functions", get(list_functions))
+        .route("/openapi.json", get(openapi))
+        .route("/export.xlsx", get(export_xlsx_bytes))
+        .merge(mutating)
         
         // Attach shared state and middleware
         .layer(Extension(api))
@@ -40,15 +64,69 @@ pub fn create_router(api: WorkbookApi) -> Router {
 }
 
 // Run the HTTP server
-pub async fn run_server(api: WorkbookApi, addr: SocketAddr) {
-    let app = create_router(api);
+pub async fn run_server(api: WorkbookApi, addr: SocketAddr, config: ServerConfig) {
+    let app = create_router(api, config);
     
     println!("Starting ssengine SDK server on {}", addr);
     
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await
-        .unwrap();
+    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+    axum::serve(listener, app).await.unwrap();
+}
+
+// Reject requests without the configured bearer token
+async fn require_token(State(config): State<Arc<ServerConfig>>, request: Request, next: Next) -> Response {
+    if let Some(expected) = &config.auth_token {
+        let provided = request.headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        if !provided.is_some_and(|token| tokens_match(token, expected)) {
+            return ApiErrorResponse(ApiError::Unauthorized).into_response();
+        }
+    }
+    next.run(request).await
+}
+
+// Compare tokens in time independent of where they first differ
+fn tokens_match(provided: &str, expected: &str) -> bool {
+    provided.len() == expected.len()
+        && provided.bytes().zip(expected.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+// Request validation
+
+// Reject a blank sheet name
+fn validate_sheet_name(sheet: &str) -> Result<(), ApiErrorResponse> {
+    if sheet.trim().is_empty() {
+        return Err(ApiErrorResponse(ApiError::InvalidRequest("Sheet name must not be blank".into())));
+    }
+    Ok(())
+}
+
+// Reject coordinates past the last row or column of a sheet (both are 0-based)
+fn validate_coordinates(row: u32, col: u32) -> Result<(), ApiErrorResponse> {
+    if row >= MAX_ROWS {
+        return Err(ApiErrorResponse(ApiError::InvalidRequest(format!("Row {} is out of range (0 to {})", row, MAX_ROWS - 1))));
+    }
+    if col >= MAX_COLUMNS {
+        return Err(ApiErrorResponse(ApiError::InvalidRequest(format!("Column {} is out of range (0 to {})", col, MAX_COLUMNS - 1))));
+    }
+    Ok(())
+}
+
+// Reject a blank sheet name or one the workbook doesn't have
+async fn validate_sheet(api: &WorkbookApi, sheet: &str) -> Result<(), ApiErrorResponse> {
+    validate_sheet_name(sheet)?;
+    if !api.has_sheet(sheet.to_string()).await.map_err(ApiErrorResponse)? {
+        return Err(ApiErrorResponse(ApiError::InvalidRequest(format!("Sheet '{}' does not exist", sheet))));
+    }
+    Ok(())
+}
+
+// Reject a request for a cell outside the sheet or on a missing sheet
+async fn validate_cell(api: &WorkbookApi, sheet: &str, row: u32, col: u32) -> Result<(), ApiErrorResponse> {
+    validate_coordinates(row, col)?;
+    validate_sheet(api, sheet).await
 }
 
 // Route handlers
@@ -61,8 +139,9 @@ async fn add_sheet(
     Extension(api): Extension<Arc<WorkbookApi>>,
     Json(payload): Json<AddSheetRequest>,
 ) -> Result<Json<AddSheetResponse>, ApiErrorResponse> {
-    let sheet_id = api.add_sheet(payload.name)
-        .map_err(ApiErrorResponse)?
+    validate_sheet_name(&payload.name)?;
+    let sheet_id = api.add_sheet(payload.name).await
+        .map_err(ApiErrorResponse)?;
     
     Ok(Json(AddSheetResponse { sheet_id }))
 }
@@ -71,31 +150,102 @@ async fn set_cell(
     Extension(api): Extension<Arc<WorkbookApi>>,
     Json(payload): Json<SetCellRequest>,
 ) -> Result<Json<SetCellResponse>, ApiErrorResponse> {
-    api.set_cell(payload.sheet, payload.row, payload.col, payload.value)
+    validate_cell(&api, &payload.sheet, payload.row, payload.col).await?;
+    api.set_cell(payload.sheet, payload.row, payload.col, payload.value).await
         .map_err(ApiErrorResponse)?;
     
     Ok(Json(SetCellResponse { success: true }))
 }
 
+async fn delete_cell(
+    Extension(api): Extension<Arc<WorkbookApi>>,
+    Json(payload): Json<DeleteCellRequest>,
+) -> Result<Json<DeleteCellResponse>, ApiErrorResponse> {
+    validate_cell(&api, &payload.sheet, payload.row, payload.col).await?;
+    let deleted = api.delete_cell(payload.sheet, payload.row, payload.col).await
+        .map_err(ApiErrorResponse)?;
+    
+    Ok(Json(DeleteCellResponse { deleted }))
+}
+
 async fn get_cell(
     Extension(api): Extension<Arc<WorkbookApi>>,
     Json(payload): Json<GetCellRequest>,
 ) -> Result<Json<GetCellResponse>, ApiErrorResponse> {
-    let cell = api.get_cell(payload.sheet, payload.row, payload.col)
+    validate_cell(&api, &payload.sheet, payload.row, payload.col).await?;
+    let cell = api.get_cell(payload.sheet, payload.row, payload.col).await
         .map_err(ApiErrorResponse)?;
     
     Ok(Json(GetCellResponse { 
         value: cell.value,
         formula: cell.formula,
         formatted: cell.formatted,
+        note: cell.note,
+        hyperlink: cell.hyperlink,
+    }))
+}
+
+async fn get_range(
+    Extension(api): Extension<Arc<WorkbookApi>>,
+    Json(payload): Json<GetRangeRequest>,
+) -> Result<Json<GetRangeResponse>, ApiErrorResponse> {
+    validate_sheet(&api, &payload.sheet).await?;
+    let page = api.get_range(payload.sheet, payload.range, payload.offset, payload.limit, payload.max_cells, payload.continuation).await
+        .map_err(ApiErrorResponse)?;
+    
+    Ok(Json(GetRangeResponse {
+        range: page.range,
+        values: page.values,
+        continuation: page.continuation,
     }))
 }
 
+// Evaluate a formula without storing it; the workbook is only read
+async fn evaluate(
+    Extension(api): Extension<Arc<WorkbookApi>>,
+    Json(payload): Json<EvaluateRequest>,
+) -> Result<Json<EvaluateResponse>, ApiErrorResponse> {
+    validate_sheet(&api, &payload.sheet).await?;
+    let value = api.evaluate(payload.sheet, payload.formula).await
+        .map_err(ApiErrorResponse)?;
+    
+    Ok(Json(EvaluateResponse { value: value_to_json(&value) }))
+}
+
+async fn trace(
+    Extension(api): Extension<Arc<WorkbookApi>>,
+    Json(payload): Json<TraceRequest>,
+) -> Result<Json<TraceResponse>, ApiErrorResponse> {
+    validate_cell(&api, &payload.sheet, payload.row, payload.col).await?;
+    let trace = api.trace(payload.sheet, payload.row, payload.col).await
+        .map_err(ApiErrorResponse)?;
+    
+    Ok(Json(TraceResponse {
+        precedents: trace.precedents,
+        dependents: trace.dependents,
+    }))
+}
+
+// OpenAPI 3 description of every route
+async fn openapi() -> impl IntoResponse {
+    Json(generate_full_openapi())
+}
+
+// Every supported spreadsheet function with its argument counts, for autocomplete
+async fn list_functions(
+    Extension(api): Extension<Arc<WorkbookApi>>,
+) -> Result<Json<ListFunctionsResponse>, ApiErrorResponse> {
+    let functions = api.list_functions().await
+        .map_err(ApiErrorResponse)?;
+    
+    Ok(Json(ListFunctionsResponse { functions }))
+}
+
 async fn export_xlsx(
     Extension(api): Extension<Arc<WorkbookApi>>,
     Json(payload): Json<ExportXlsxRequest>,
 ) -> Result<Json<ExportXlsxResponse>, ApiErrorResponse> {
-    api.export_xlsx(payload.path.into())
+    api.export_xlsx(payload.path.into()).await
         .map_err(ApiErrorResponse)?;
     
     Ok(Json(ExportXlsxResponse { success: true }))
@@ -105,7 +255,34 @@ async fn import_xlsx(
     Extension(api): Extension<Arc<WorkbookApi>>,
     Json(payload): Json<ImportXlsxRequest>,
 ) -> Result<Json<ImportXlsxResponse>, ApiErrorResponse> {
-    api.import_xlsx(payload.path.into())
+    api.import_xlsx(payload.path.into()).await
+        .map_err(ApiErrorResponse)?;
+    
+    Ok(Json(ImportXlsxResponse { success: true }))
+}
+
+// Download the workbook as an XLSX file
+async fn export_xlsx_bytes(
+    Extension(api): Extension<Arc<WorkbookApi>>,
+) -> Result<Response, ApiErrorResponse> {
+    let bytes = api.export_xlsx_bytes().await
+        .map_err(ApiErrorResponse)?;
+    
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"workbook.xlsx\""),
+        ],
+        bytes,
+    ).into_response())
+}
+
+// Replace the workbook with an uploaded XLSX file (the raw request body)
+async fn import_xlsx_bytes(
+    Extension(api): Extension<Arc<WorkbookApi>>,
+    body: Bytes,
+) -> Result<Json<ImportXlsxResponse>, ApiErrorResponse> {
+    api.import_xlsx_bytes(&body).await
         .map_err(ApiErrorResponse)?;
     
     Ok(Json(ImportXlsxResponse { success: true }))
@@ -116,8 +293,251 @@ pub struct ApiErrorResponse(ApiError);
 
 impl IntoResponse for ApiErrorResponse {
     fn into_response(self) -> Response {
-        let status = StatusCode::from(self.0);
+        let status = StatusCode::from(&self.0);
         let body = Json(error_to_json(self.0));
         (status, body).into_response()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use ssengine_core::{CellValue, Workbook};
+    use tower::ServiceExt;
+    
+    // A sheet of formulas that all read A1, so editing A1 recalculates every one
+    fn large_model(formulas: u32) -> Workbook {
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Sheet1".into()).unwrap();
+        let mut cells = vec![(0, 0, CellValue::Number(1.0))];
+        cells.extend((0..formulas).map(|row| (row, 1, CellValue::Formula(format!("=A1*2+{}", row)))));
+        workbook.set_cells("Sheet1", cells).unwrap();
+        workbook
+    }
+    
+    fn post_json(uri: &str, body: serde_json::Value) -> Request {
+        Request::post(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+    
+    async fn json_body(response: Response) -> serde_json::Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+    
+    // The test runtime has a single thread, so a recalculation running on it would
+    // hold up the health check until it finished
+    #[tokio::test]
+    async fn requests_are_served_during_a_long_recalculation() {
+        let app = create_router(WorkbookApi::from_workbook(large_model(10_000)), ServerConfig::default());
+        
+        let edit = post_json("/set_cell", json!({ "sheet": "Sheet1", "row": 0, "col": 0, "value": "2" }));
+        let recalc = tokio::spawn(app.clone().oneshot(edit));
+        tokio::task::yield_now().await;
+        
+        let health = app.clone().oneshot(Request::get("/health").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(health.status(), StatusCode::OK);
+        assert!(!recalc.is_finished(), "the health check waited for the recalculation");
+        
+        let edited = recalc.await.unwrap().unwrap();
+        assert_eq!(edited.status(), StatusCode::OK);
+        let cell = app.oneshot(post_json("/get_cell", json!({ "sheet": "Sheet1", "row": 9_999, "col": 1 }))).await.unwrap();
+        assert_eq!(json_body(cell).await["value"], json!(10_003.0));
+    }
+    
+    fn secured() -> ServerConfig {
+        ServerConfig { auth_token: Some("s3cret".into()) }
+    }
+    
+    #[tokio::test]
+    async fn mutating_routes_require_the_token() {
+        let app = create_router(WorkbookApi::new(), secured());
+        
+        let anonymous = app.clone().oneshot(post_json("/add_sheet", json!({ "name": "Sheet1" }))).await.unwrap();
+        assert_eq!(anonymous.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(json_body(anonymous).await["code"], "UNAUTHORIZED");
+        
+        let mut wrong = post_json("/add_sheet", json!({ "name": "Sheet1" }));
+        wrong.headers_mut().insert(header::AUTHORIZATION, "Bearer guess".parse().unwrap());
+        assert_eq!(app.clone().oneshot(wrong).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+        
+        let mut authorized = post_json("/add_sheet", json!({ "name": "Sheet1" }));
+        authorized.headers_mut().insert(header::AUTHORIZATION, "Bearer s3cret".parse().unwrap());
+        assert_eq!(app.clone().oneshot(authorized).await.unwrap().status(), StatusCode::OK);
+        
+        // Reads stay open
+        let health = app.oneshot(Request::get("/health").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(health.status(), StatusCode::OK);
+    }
+    
+    #[tokio::test]
+    async fn paths_escaping_the_sandbox_are_forbidden() {
+        let sandbox = std::env::temp_dir().join(format!("ssengine-sandbox-{}", std::process::id()));
+        std::fs::create_dir_all(&sandbox).unwrap();
+        let app = create_router(WorkbookApi::new().with_base_dir(&sandbox), secured());
+        
+        for path in ["../escape.xlsx", "nested/../../escape.xlsx", "/tmp/escape.xlsx"] {
+            let mut request = post_json("/export_xlsx", json!({ "path": path }));
+            request.headers_mut().insert(header::AUTHORIZATION, "Bearer s3cret".parse().unwrap());
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN, "{}", path);
+            assert_eq!(json_body(response).await["code"], "FORBIDDEN");
+        }
+        assert!(!sandbox.join("../escape.xlsx").exists());
+        
+        std::fs::remove_dir_all(&sandbox).unwrap();
+    }
+    
+    #[tokio::test]
+    async fn exported_bytes_import_into_another_workbook() {
+        let source = create_router(WorkbookApi::from_workbook(large_model(3)), ServerConfig::default());
+        let export = source.oneshot(Request::get("/export.xlsx").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(export.status(), StatusCode::OK);
+        assert_eq!(export.headers()[header::CONTENT_TYPE], "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet");
+        assert_eq!(export.headers()[header::CONTENT_DISPOSITION], "attachment; filename=\"workbook.xlsx\"");
+        let bytes = axum::body::to_bytes(export.into_body(), usize::MAX).await.unwrap();
+        assert!(bytes.starts_with(b"PK"));
+        
+        let target = create_router(WorkbookApi::new(), ServerConfig::default());
+        let import = target.clone().oneshot(Request::post("/import.xlsx").body(Body::from(bytes)).unwrap()).await.unwrap();
+        assert_eq!(import.status(), StatusCode::OK);
+        for (row, col, expected) in [(0, 0, json!(1.0)), (2, 1, json!(4.0))] {
+            let cell = target.clone().oneshot(post_json("/get_cell", json!({ "sheet": "Sheet1", "row": row, "col": col }))).await.unwrap();
+            assert_eq!(json_body(cell).await["value"], expected);
+        }
+        
+        let garbage = target.oneshot(Request::post("/import.xlsx").body(Body::from("not a zip")).unwrap()).await.unwrap();
+        assert_eq!(garbage.status(), StatusCode::BAD_REQUEST);
+    }
+    
+    #[tokio::test]
+    async fn trace_lists_transitive_precedents_and_dependents() {
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Sheet1".into()).unwrap();
+        workbook.set_cell_value("Sheet1", 0, 0, 1.0).unwrap();
+        workbook.set_cell_value("Sheet1", 0, 1, "=A1*2").unwrap();
+        workbook.set_cell_value("Sheet1", 0, 2, "=B1+1").unwrap();
+        workbook.set_cell_value("Sheet1", 0, 3, "=C1-A1").unwrap();
+        let app = create_router(WorkbookApi::from_workbook(workbook), ServerConfig::default());
+        
+        let response = app.clone().oneshot(post_json("/trace", json!({ "sheet": "Sheet1", "row": 0, "col": 2 }))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(json_body(response).await, json!({
+            "precedents": ["Sheet1!A1", "Sheet1!B1"],
+            "dependents": ["Sheet1!D1"],
+        }));
+        
+        let missing = app.oneshot(post_json("/trace", json!({ "sheet": "Nope", "row": 0, "col": 0 }))).await.unwrap();
+        assert_eq!(missing.status(), StatusCode::BAD_REQUEST);
+    }
+    
+    #[tokio::test]
+    async fn functions_are_listed_with_their_arity() {
+        let app = create_router(WorkbookApi::new(), ServerConfig::default());
+        let response = app.oneshot(Request::get("/functions").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        
+        let body = json_body(response).await;
+        let functions = body["functions"].as_array().unwrap();
+        let sum = functions.iter().find(|function| function["name"] == "SUM").unwrap();
+        assert_eq!(sum["min_args"], json!(1));
+        assert_eq!(sum["max_args"], json!(null));
+        assert_eq!(sum["category"], json!("Math"));
+        let if_function = functions.iter().find(|function| function["name"] == "IF").unwrap();
+        assert_eq!((&if_function["min_args"], &if_function["max_args"]), (&json!(3), &json!(3)));
+    }
+    
+    #[tokio::test]
+    async fn openapi_document_covers_every_route() {
+        let app = create_router(WorkbookApi::new(), ServerConfig::default());
+        let response = app.oneshot(Request::get("/openapi.json").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let document = json_body(response).await;
+        assert_eq!(document["openapi"], json!("3.0.3"));
+        
+        let routes = [
+            ("post", "/add_sheet"), ("post", "/set_cell"), ("post", "/delete_cell"),
+            ("post", "/export_xlsx"), ("post", "/import_xlsx"), ("post", "/import.xlsx"),
+            ("get", "/health"), ("post", "/get_cell"), ("post", "/get_range"), ("post", "/evaluate"),
+            ("post", "/trace"), ("get", "/functions"), ("get", "/openapi.json"), ("get", "/export.xlsx"),
+        ];
+        let paths = document["paths"].as_object().unwrap();
+        assert_eq!(paths.len(), routes.len());
+        for (method, path) in routes {
+            let operation = &paths[path][method];
+            assert!(operation["responses"]["200"].is_object(), "{} {} is not documented", method, path);
+            assert!(operation["responses"]["400"].is_object(), "{} {} has no error response", method, path);
+        }
+        
+        // Mutating routes carry the bearer token requirement; the schemas they name exist
+        assert_eq!(paths["/set_cell"]["post"]["security"], json!([{ "bearerAuth": [] }]));
+        assert!(paths["/get_cell"]["post"].get("security").is_none());
+        assert!(document["components"]["schemas"]["SetCellRequest"].is_object());
+        assert!(document["components"]["schemas"]["ErrorResponse"].is_object());
+    }
+    
+    #[tokio::test]
+    async fn invalid_requests_are_rejected_with_a_reason() {
+        let app = create_router(WorkbookApi::from_workbook(large_model(0)), ServerConfig::default());
+        let rejections = [
+            ("/add_sheet", json!({ "name": "  " }), "Sheet name must not be blank"),
+            ("/set_cell", json!({ "sheet": "", "row": 0, "col": 0, "value": "1" }), "Sheet name must not be blank"),
+            ("/set_cell", json!({ "sheet": "Sheet1", "row": MAX_ROWS, "col": 0, "value": "1" }), "Row 1048576 is out of range (0 to 1048575)"),
+            ("/get_cell", json!({ "sheet": "Sheet1", "row": 0, "col": MAX_COLUMNS }), "Column 16384 is out of range (0 to 16383)"),
+            ("/get_cell", json!({ "sheet": "Missing", "row": 0, "col": 0 }), "Sheet 'Missing' does not exist"),
+            ("/delete_cell", json!({ "sheet": "Missing", "row": 0, "col": 0 }), "Sheet 'Missing' does not exist"),
+        ];
+        for (uri, body, reason) in rejections {
+            let response = app.clone().oneshot(post_json(uri, body.clone())).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{} {}", uri, body);
+            let error = json_body(response).await;
+            assert_eq!(error["code"], json!("INVALID_REQUEST"));
+            assert!(error["error"].as_str().unwrap().contains(reason), "{} {}: {}", uri, body, error);
+        }
+        
+        let valid = app.oneshot(post_json("/get_cell", json!({ "sheet": "Sheet1", "row": MAX_ROWS - 1, "col": MAX_COLUMNS - 1 }))).await.unwrap();
+        assert_eq!(valid.status(), StatusCode::OK);
+    }
+    
+    #[tokio::test]
+    async fn deleting_a_cell_updates_the_sum_reading_it() {
+        let mut workbook = large_model(0);
+        workbook.set_cell_value("Sheet1", 1, 0, 2.0).unwrap();
+        workbook.set_cell_value("Sheet1", 0, 1, "=SUM(A1:A2)").unwrap();
+        let app = create_router(WorkbookApi::from_workbook(workbook), ServerConfig::default());
+        
+        let sum = || post_json("/get_cell", json!({ "sheet": "Sheet1", "row": 0, "col": 1 }));
+        assert_eq!(json_body(app.clone().oneshot(sum()).await.unwrap()).await["value"], json!(3.0));
+        
+        let delete = || post_json("/delete_cell", json!({ "sheet": "Sheet1", "row": 0, "col": 0 }));
+        let deleted = app.clone().oneshot(delete()).await.unwrap();
+        assert_eq!(deleted.status(), StatusCode::OK);
+        assert_eq!(json_body(deleted).await["deleted"], json!(true));
+        assert_eq!(json_body(app.clone().oneshot(sum()).await.unwrap()).await["value"], json!(2.0));
+        
+        // The cell is gone, not blank: deleting it again finds nothing
+        assert_eq!(json_body(app.oneshot(delete()).await.unwrap()).await["deleted"], json!(false));
+    }
+    
+    #[tokio::test]
+    async fn evaluate_leaves_the_workbook_unchanged() {
+        let mut workbook = large_model(0);
+        workbook.set_cell_value("Sheet1", 1, 0, 2.0).unwrap();
+        let app = create_router(WorkbookApi::from_workbook(workbook), ServerConfig::default());
+        
+        let evaluated = app.clone().oneshot(post_json("/evaluate", json!({ "sheet": "Sheet1", "formula": "=A1+A2" }))).await.unwrap();
+        assert_eq!(evaluated.status(), StatusCode::OK);
+        assert_eq!(json_body(evaluated).await["value"], json!(3.0));
+        
+        // The formula is evaluated as if in A1, but A1 keeps its value
+        let cell = json_body(app.clone().oneshot(post_json("/get_cell", json!({ "sheet": "Sheet1", "row": 0, "col": 0 }))).await.unwrap()).await;
+        assert_eq!(cell["value"], json!(1.0));
+        assert_eq!(cell["formula"], json!(null));
+        
+        let missing = app.oneshot(post_json("/evaluate", json!({ "sheet": "Missing", "formula": "=1" }))).await.unwrap();
+        assert_eq!(missing.status(), StatusCode::BAD_REQUEST);
+    }
+}